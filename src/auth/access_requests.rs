@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use crate::pubkey::PubKey;
+
+/// Maximum number of pending requests kept, so a flood of random keys
+/// can't grow the list without bound
+const MAX_ACCESS_REQUESTS: usize = 50;
+
+/// Time after which a request is forgotten if the key doesn't try again
+const ACCESS_REQUEST_TTL: Duration = Duration::from_secs(24 * 3600);
+
+#[derive(Debug, Clone)]
+pub struct AccessRequest {
+    pub key: PubKey,
+    pub username: String,
+    pub addr: Option<SocketAddr>,
+    pub requested_at: Instant,
+}
+
+/// Keys rejected by the whitelist, kept for operators to review and
+/// approve. Repeated attempts from the same key only refresh its request
+#[derive(Debug, Clone, Default)]
+pub struct AccessRequests {
+    requests: HashMap<String, AccessRequest>,
+}
+
+impl AccessRequests {
+    pub fn record(&mut self, key: PubKey, username: &str, addr: Option<SocketAddr>) {
+        self.expire();
+        let fingerprint = key.fingerprint();
+        if !self.requests.contains_key(&fingerprint) && self.requests.len() >= MAX_ACCESS_REQUESTS {
+            let oldest = self
+                .requests
+                .iter()
+                .min_by_key(|(_, request)| request.requested_at)
+                .map(|(fingerprint, _)| fingerprint.clone());
+            if let Some(oldest) = oldest {
+                self.requests.remove(&oldest);
+            }
+        }
+        self.requests.insert(
+            fingerprint,
+            AccessRequest {
+                key,
+                username: username.to_string(),
+                addr,
+                requested_at: Instant::now(),
+            },
+        );
+    }
+
+    pub fn remove(&mut self, key: &PubKey) {
+        self.requests.remove(&key.fingerprint());
+    }
+
+    /// Returns the live requests, newest first
+    pub fn pending(&self) -> Vec<&AccessRequest> {
+        let mut pending = self
+            .requests
+            .values()
+            .filter(|request| request.requested_at.elapsed() < ACCESS_REQUEST_TTL)
+            .collect::<Vec<&AccessRequest>>();
+        pending.sort_by_key(|request| std::cmp::Reverse(request.requested_at));
+        pending
+    }
+
+    fn expire(&mut self) {
+        self.requests
+            .retain(|_, request| request.requested_at.elapsed() < ACCESS_REQUEST_TTL);
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use russh_keys::key::KeyPair;
+
+    fn create_test_pubkey() -> PubKey {
+        let key_pair = KeyPair::generate_ed25519().unwrap();
+        PubKey::from(key_pair.clone_public_key().unwrap())
+    }
+
+    #[test]
+    fn refresh_repeated_requests_from_same_key() {
+        let mut requests = AccessRequests::default();
+        let key = create_test_pubkey();
+        requests.record(key.clone(), "alice", None);
+        requests.record(key.clone(), "alice2", "10.0.0.1:50000".parse().ok());
+
+        let pending = requests.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].username, "alice2");
+        assert_eq!(pending[0].addr, "10.0.0.1:50000".parse().ok());
+
+        requests.remove(&key);
+        assert!(requests.pending().is_empty());
+    }
+
+    #[test]
+    fn drop_oldest_request_when_full() {
+        let mut requests = AccessRequests::default();
+        let first = create_test_pubkey();
+        requests.record(first.clone(), "first", None);
+        for _ in 0..MAX_ACCESS_REQUESTS {
+            requests.record(create_test_pubkey(), "other", None);
+        }
+
+        let pending = requests.pending();
+        assert_eq!(pending.len(), MAX_ACCESS_REQUESTS);
+        assert!(pending.iter().all(|request| request.key != first));
+    }
+}
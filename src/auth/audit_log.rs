@@ -0,0 +1,112 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+use chrono::{DateTime, Utc};
+use log::error;
+use serde_json::json;
+use tokio::sync::mpsc;
+
+use crate::chat::User;
+
+/// A single operator action: who did what to whom and when
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub time: DateTime<Utc>,
+    pub op: String,
+    pub fingerprint: String,
+    pub action: String,
+    pub target: String,
+}
+
+impl AuditEntry {
+    pub fn new(op: &User, action: &str, target: &str) -> Self {
+        Self {
+            time: Utc::now(),
+            op: op.username().to_string(),
+            fingerprint: op.public_key().fingerprint(),
+            action: action.to_string(),
+            target: target.to_string(),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        json!({
+            "timestamp": self.time.to_rfc3339(),
+            "op": self.op,
+            "fingerprint": self.fingerprint,
+            "action": self.action,
+            "target": self.target,
+        })
+        .to_string()
+    }
+}
+
+/// Moderation trail appended to a file, one JSON object per line. The
+/// entries are written on a thread of their own so neither commands nor
+/// the runtime wait on the disk
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    tx: mpsc::UnboundedSender<AuditEntry>,
+}
+
+impl AuditLog {
+    /// Opens the file for appending, creating it if missing, and starts
+    /// the writer thread
+    pub fn open(path: &str) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        std::thread::spawn(move || write_entries(file, rx));
+        Ok(Self { tx })
+    }
+
+    pub fn record(&self, entry: AuditEntry) {
+        if self.tx.send(entry).is_err() {
+            error!("Audit log writer has stopped, dropping the entry");
+        }
+    }
+}
+
+fn write_entries(mut file: File, mut rx: mpsc::UnboundedReceiver<AuditEntry>) {
+    while let Some(entry) = rx.blocking_recv() {
+        if let Err(err) = writeln!(file, "{}", entry.to_json()) {
+            error!("Failed to write to the audit log: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn append_entries_as_json_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("audit.log");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "").unwrap();
+
+        let op = User::default();
+        let log = AuditLog::open(path).unwrap();
+        log.record(AuditEntry::new(&op, "kick", "bob"));
+        log.record(AuditEntry::new(&op, "ban", "name=eve 1h"));
+        drop(log);
+
+        let mut content = String::new();
+        for _ in 0..100 {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            content = std::fs::read_to_string(path).unwrap();
+            if content.lines().count() == 2 {
+                break;
+            }
+        }
+        let lines = content.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 2);
+        let entry: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(entry["op"], op.username().to_string());
+        assert_eq!(entry["fingerprint"], op.public_key().fingerprint());
+        assert_eq!(entry["action"], "kick");
+        assert_eq!(entry["target"], "bob");
+        assert!(entry["timestamp"].is_string());
+    }
+}
@@ -1,10 +1,17 @@
-use std::collections::HashSet;
+use chrono::{DateTime, Utc};
+use log::warn;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
 use std::time::Duration;
 
+use crate::chat::User;
 use crate::pubkey::PubKey;
 
+use super::access_requests::{AccessRequest, AccessRequests};
+use super::audit_log::{AuditEntry, AuditLog};
+use super::permission::Permission;
 use super::set::TimedHashSet;
-use super::{pubkey_file_manager, PubKeyFileManager};
+use super::{pubkey_file_manager, IpRange, PubKeyFileManager};
 
 #[derive(Debug)]
 pub enum AuthError {
@@ -33,9 +40,17 @@ pub struct Auth {
     oplist_file_manager: Option<PubKeyFileManager>,
     whitelist_file_manager: Option<PubKeyFileManager>,
     operators: HashSet<PubKey>,
+    op_permissions: HashMap<PubKey, HashSet<Permission>>,
     trusted_keys: HashSet<PubKey>,
+    trusted_key_expiries: HashMap<PubKey, DateTime<Utc>>,
+    trusted_key_names: HashMap<PubKey, String>,
     banned_usernames: TimedHashSet<String>,
     banned_fingerprints: TimedHashSet<String>,
+    banned_ips: TimedHashSet<IpRange>,
+    name_reservation: Option<Duration>,
+    reserved_names: TimedHashSet<(String, String)>,
+    access_requests: AccessRequests,
+    audit_log: Option<AuditLog>,
 }
 
 impl Auth {
@@ -65,13 +80,25 @@ impl Auth {
 
     pub fn clear_trusted_keys(&mut self) {
         self.trusted_keys.clear();
+        self.trusted_key_expiries.clear();
     }
 
-    pub fn add_trusted_key(&mut self, key: PubKey) {
-        self.trusted_keys.insert(key.into());
+    /// Trusts the key with no expiry. Returns `false` if the key was
+    /// already trusted
+    pub fn add_trusted_key(&mut self, key: PubKey) -> bool {
+        self.trusted_key_expiries.remove(&key);
+        self.access_requests.remove(&key);
+        self.trusted_keys.insert(key)
+    }
+
+    /// Names the trusted key for `trusted_key_by_name`, as a comment in
+    /// the whitelist file would
+    pub fn set_trusted_key_name(&mut self, key: PubKey, name: &str) {
+        self.trusted_key_names.insert(key, name.to_string());
     }
 
     pub fn remove_trusted_key(&mut self, key: PubKey) {
+        self.trusted_key_expiries.remove(&key);
         self.trusted_keys.remove(&key.into());
     }
 
@@ -81,22 +108,45 @@ impl Auth {
 
     pub fn clear_operators(&mut self) {
         self.operators.clear();
+        self.op_permissions.clear();
     }
 
-    pub fn add_operator(&mut self, key: PubKey) {
-        self.operators.insert(key.into());
+    /// Returns `false` if the key was already an operator
+    pub fn add_operator(&mut self, key: PubKey) -> bool {
+        self.operators.insert(key)
     }
 
     pub fn remove_operator(&mut self, key: PubKey) {
+        self.op_permissions.remove(&key);
         self.operators.remove(&key.into());
     }
 
+    /// Restricts the operator to the permissions. Operators without
+    /// restrictions have all of them
+    pub fn set_op_permissions(&mut self, key: PubKey, permissions: HashSet<Permission>) {
+        self.op_permissions.insert(key, permissions);
+    }
+
+    /// Returns the permissions the operator is restricted to, if any
+    pub fn op_permissions(&self, key: &PubKey) -> Option<&HashSet<Permission>> {
+        self.op_permissions.get(key)
+    }
+
     pub fn load_trusted_keys(&mut self) -> Result<(), AuthError> {
         if let Some(loader) = &self.whitelist_file_manager {
+            if let Ok(names) = loader.load_key_comments() {
+                self.trusted_key_names = names;
+            }
             return loader
-                .load_keys()
+                .load_keys_with_expiry()
                 .map(|keys| {
-                    self.trusted_keys.extend(keys);
+                    for (key, expiry) in keys {
+                        match expiry {
+                            Some(expiry) => self.trusted_key_expiries.insert(key.clone(), expiry),
+                            None => self.trusted_key_expiries.remove(&key),
+                        };
+                        self.trusted_keys.insert(key);
+                    }
                 })
                 .map_err(AuthError::LoadKeysError);
         }
@@ -105,12 +155,30 @@ impl Auth {
 
     pub fn load_operators(&mut self) -> Result<(), AuthError> {
         if let Some(loader) = &self.oplist_file_manager {
-            return loader
-                .load_keys()
-                .map(|keys| {
-                    self.operators.extend(keys);
-                })
-                .map_err(AuthError::LoadKeysError);
+            let keys = loader.load_keys().map_err(AuthError::LoadKeysError)?;
+            let permissions = loader.load_key_option("permissions").unwrap_or_default();
+            for key in keys {
+                match permissions
+                    .get(&key)
+                    .map(|list| Permission::parse_list(list))
+                {
+                    Some(Ok(permissions)) => self.set_op_permissions(key.clone(), permissions),
+                    // A malformed list must not grant every permission
+                    Some(Err(err)) => {
+                        warn!(
+                            "Skipping operator {}: malformed permissions: {}",
+                            key.fingerprint(),
+                            err
+                        );
+                        continue;
+                    }
+                    None => {
+                        self.op_permissions.remove(&key);
+                    }
+                }
+                self.operators.insert(key);
+            }
+            return Ok(());
         }
         Err(AuthError::NoOplist)
     }
@@ -118,16 +186,37 @@ impl Auth {
     pub fn save_trusted_keys(&mut self) -> Result<(), AuthError> {
         if let Some(loader) = &self.whitelist_file_manager {
             return loader
-                .save_keys(&self.trusted_keys)
+                .save_keys_with_expiry(
+                    &self.trusted_keys,
+                    &self.trusted_key_expiries,
+                    &self.trusted_key_names,
+                )
                 .map_err(AuthError::SaveKeysError);
         }
         Err(AuthError::NoWhitelist)
     }
 
+    /// Writes the trusted keys to a new file at the path and returns the
+    /// number of keys written
+    pub fn export_trusted_keys(&self, path: &str) -> Result<usize, AuthError> {
+        PubKeyFileManager::export_keys(
+            path,
+            &self.trusted_keys,
+            &self.trusted_key_expiries,
+            &self.trusted_key_names,
+        )
+        .map_err(AuthError::SaveKeysError)
+    }
+
     pub fn save_operators(&mut self) -> Result<(), AuthError> {
         if let Some(loader) = &self.oplist_file_manager {
+            let permissions = self
+                .op_permissions
+                .iter()
+                .map(|(key, permissions)| (key.clone(), Permission::format_list(permissions)))
+                .collect();
             return loader
-                .save_keys(&self.operators)
+                .save_keys_with_permissions(&self.operators, &permissions)
                 .map_err(AuthError::SaveKeysError);
         }
         Err(AuthError::NoOplist)
@@ -137,8 +226,34 @@ impl Auth {
         matches!(&self.operators, list if list.iter().find(|k| *k == key).is_some())
     }
 
+    /// Checks that the key is an operator allowed to do what the
+    /// permission covers
+    pub fn has_permission(&self, key: &PubKey, permission: Permission) -> bool {
+        self.is_op(key)
+            && self
+                .op_permissions
+                .get(key)
+                .is_none_or(|permissions| permissions.contains(&permission))
+    }
+
+    /// Checks that the key is in the whitelist and its `expiry-time`,
+    /// if any, has not passed yet
     pub fn is_trusted(&self, key: &PubKey) -> bool {
-        matches!(&self.trusted_keys, list if list.iter().find(|k| *k == key).is_some())
+        let is_expired =
+            matches!(self.trusted_key_expiries.get(key), Some(expiry) if *expiry <= Utc::now());
+        !is_expired
+            && matches!(&self.trusted_keys, list if list.iter().find(|k| *k == key).is_some())
+    }
+
+    /// Finds the trusted key whose whitelist comment starts with the
+    /// name, e.g. `ssh-ed25519 AAAA... alice`
+    pub fn trusted_key_by_name(&self, name: &str) -> Option<&PubKey> {
+        self.trusted_key_names
+            .iter()
+            .find(|(key, comment)| {
+                comment.split_whitespace().next() == Some(name) && self.is_trusted(key)
+            })
+            .map(|(key, _)| key)
     }
 
     pub fn check_bans(&mut self, user: &str, key: &PubKey) -> bool {
@@ -164,6 +279,83 @@ impl Auth {
             .insert(fingerprint.to_string(), duration)
     }
 
+    pub fn ban_ip(&mut self, range: IpRange, duration: Duration) {
+        self.banned_ips.insert(range, duration)
+    }
+
+    /// Checks if the address falls within any of the banned ranges
+    pub fn is_ip_banned(&self, addr: &IpAddr) -> bool {
+        self.banned_ips.iter().any(|range| range.contains(addr))
+    }
+
+    /// Sets how long a name given up by an operator stays reserved for
+    /// their key. `None` turns the reservation off
+    pub fn set_name_reservation(&mut self, window: Option<Duration>) {
+        self.name_reservation = window;
+    }
+
+    /// Reserves the name for the key if it belongs to an operator, so
+    /// no one else can take it right after the operator gives it up
+    pub fn reserve_op_name(&mut self, name: &str, key: &PubKey) {
+        if let Some(window) = self.name_reservation.filter(|_| self.is_op(key)) {
+            self.reserved_names
+                .insert((name.to_string(), key.fingerprint()), window);
+        }
+    }
+
+    /// Checks if the name was recently given up by an operator with a
+    /// different key
+    pub fn is_name_reserved(&self, name: &str, key: &PubKey) -> bool {
+        let fingerprint = key.fingerprint();
+        self.reserved_names
+            .iter()
+            .any(|(reserved, owner)| reserved == name && *owner != fingerprint)
+    }
+
+    /// Records a key rejected by the whitelist so operators can review
+    /// it and let it in
+    pub fn record_access_request(&mut self, key: PubKey, username: &str, addr: Option<SocketAddr>) {
+        self.access_requests.record(key, username, addr);
+    }
+
+    /// Keys rejected by the whitelist that weren't trusted since, newest
+    /// first
+    pub fn access_requests(&self) -> Vec<&AccessRequest> {
+        self.access_requests.pending()
+    }
+
+    /// Keeps a trail of operator actions in the audit log
+    pub fn set_audit_log(&mut self, audit_log: AuditLog) {
+        self.audit_log = Some(audit_log);
+    }
+
+    /// Records the operator action in the audit log, if there is one
+    pub fn audit(&self, op: &User, action: &str, target: &str) {
+        if let Some(audit_log) = &self.audit_log {
+            audit_log.record(AuditEntry::new(op, action, target));
+        }
+    }
+
+    /// Lifts the username ban and returns `true` if there was one
+    pub fn unban_username(&mut self, username: &str) -> bool {
+        self.banned_usernames.remove(&username.to_string())
+    }
+
+    /// Lifts the fingerprint ban and returns `true` if there was one
+    pub fn unban_fingerprint(&mut self, fingerprint: &str) -> bool {
+        self.banned_fingerprints.remove(&fingerprint.to_string())
+    }
+
+    /// Lifts the ban on exactly this range and returns `true` if there
+    /// was one
+    pub fn unban_ip(&mut self, range: &IpRange) -> bool {
+        self.banned_ips.remove(range)
+    }
+
+    pub fn banned_ips(&self) -> Vec<String> {
+        self.banned_ips.iter().map(|r| r.to_string()).collect()
+    }
+
     pub fn banned(&self) -> (Vec<String>, Vec<String>) {
         let names = self
             .banned_usernames
@@ -226,6 +418,17 @@ mod tests {
         assert_eq!(auth.whitelist_file_manager, Some(manager));
     }
 
+    #[test]
+    fn test_add_keys_tells_if_already_present() {
+        let mut auth = Auth::default();
+        let pubkey = create_test_pubkey();
+
+        assert!(auth.add_trusted_key(pubkey.clone()));
+        assert!(!auth.add_trusted_key(pubkey.clone()));
+        assert!(auth.add_operator(pubkey.clone()));
+        assert!(!auth.add_operator(pubkey));
+    }
+
     #[test]
     fn test_enable_disable_whitelist_mode() {
         let mut auth = Auth::default();
@@ -251,6 +454,22 @@ mod tests {
         assert!(auth.is_trusted(&pubkey.into()));
     }
 
+    #[test]
+    fn test_find_trusted_key_by_comment_name() {
+        let pubkey = create_test_pubkey();
+        let content = format!("{} alice laptop\n", pubkey.long());
+        let (_dir, full_path) = setup_test_file("test_trusted_key_names.txt", &content);
+
+        let mut auth = Auth::default();
+        auth.set_whitelist(PubKeyFileManager::new(&full_path));
+        auth.load_trusted_keys().unwrap();
+        assert_eq!(auth.trusted_key_by_name("alice"), Some(&pubkey));
+        assert_eq!(auth.trusted_key_by_name("laptop"), None);
+
+        auth.remove_trusted_key(pubkey);
+        assert_eq!(auth.trusted_key_by_name("alice"), None);
+    }
+
     #[test]
     fn test_load_trusted_keys_no_whitelist() {
         let mut auth = Auth::default();
@@ -298,6 +517,25 @@ mod tests {
         assert!(saved_content.contains(&pubkey.long()));
     }
 
+    #[test]
+    fn test_save_trusted_keys_keeps_names() {
+        let file_path = "test_trusted_keys_save_names.txt";
+        let (_dir, full_path) = setup_empty_test_file(file_path);
+
+        let mut auth = Auth::default();
+        auth.set_whitelist(PubKeyFileManager::new(&full_path));
+
+        let pubkey = create_test_pubkey();
+        auth.add_trusted_key(pubkey.clone());
+        auth.set_trusted_key_name(pubkey.clone(), "alice");
+        auth.save_trusted_keys().unwrap();
+
+        let mut reloaded = Auth::default();
+        reloaded.set_whitelist(PubKeyFileManager::new(&full_path));
+        reloaded.load_trusted_keys().unwrap();
+        assert_eq!(reloaded.trusted_key_by_name("alice"), Some(&pubkey));
+    }
+
     #[test]
     fn test_save_trusted_keys_no_whitelist() {
         let mut auth = Auth::default();
@@ -340,6 +578,43 @@ mod tests {
         assert!(auth.is_op(&pubkey.into()));
     }
 
+    #[test]
+    fn test_load_operators_with_permissions() {
+        let moderator = create_test_pubkey();
+        let admin = create_test_pubkey();
+        let invalid = create_test_pubkey();
+        let content = format!(
+            "permissions=\"kick,mute\" {}\n{}\npermissions=\"kick,fly\" {}\n",
+            moderator.long(),
+            admin.long(),
+            invalid.long()
+        );
+        let (_dir, full_path) = setup_test_file("test_operators_permissions.txt", &content);
+
+        let mut auth = Auth::default();
+        auth.set_oplist(PubKeyFileManager::new(&full_path));
+        auth.load_operators().unwrap();
+
+        assert!(auth.has_permission(&moderator, Permission::Kick));
+        assert!(auth.has_permission(&moderator, Permission::Mute));
+        assert!(!auth.has_permission(&moderator, Permission::Ban));
+        assert!(!auth.has_permission(&moderator, Permission::Oplist));
+        assert!(auth.has_permission(&admin, Permission::Ban));
+        assert!(auth.has_permission(&admin, Permission::Oplist));
+        assert!(!auth.is_op(&invalid));
+        assert!(!auth.has_permission(&create_test_pubkey(), Permission::Kick));
+
+        auth.save_operators().unwrap();
+        let mut reloaded = Auth::default();
+        reloaded.set_oplist(PubKeyFileManager::new(&full_path));
+        reloaded.load_operators().unwrap();
+        assert_eq!(
+            reloaded.op_permissions(&moderator),
+            Some(&HashSet::from([Permission::Kick, Permission::Mute]))
+        );
+        assert_eq!(reloaded.op_permissions(&admin), None);
+    }
+
     #[test]
     fn test_is_trusted() {
         let mut auth = Auth::default();
@@ -349,6 +624,28 @@ mod tests {
         assert!(auth.is_trusted(&pubkey.into()));
     }
 
+    #[test]
+    fn test_is_trusted_respects_expiry_time() {
+        let expired_key = create_test_pubkey();
+        let valid_key = create_test_pubkey();
+        let content = format!(
+            "expiry-time=\"20000101\" {}\nexpiry-time=\"99990101\" {}",
+            expired_key.long(),
+            valid_key.long()
+        );
+        let (_dir, full_path) = setup_test_file("test_whitelist_expiry.txt", &content);
+
+        let mut auth = Auth::default();
+        auth.set_whitelist(PubKeyFileManager::new(&full_path));
+        auth.load_trusted_keys().unwrap();
+
+        assert!(!auth.is_trusted(&expired_key));
+        assert!(auth.is_trusted(&valid_key));
+
+        auth.add_trusted_key(expired_key.clone());
+        assert!(auth.is_trusted(&expired_key));
+    }
+
     #[test]
     fn test_check_bans() {
         let mut auth = Auth::default();
@@ -363,6 +660,22 @@ mod tests {
         assert!(auth.check_bans("", &pubkey.into())); // Check if fingerprint banning works
     }
 
+    #[test]
+    fn test_unban() {
+        let mut auth = Auth::default();
+        let username = "alice";
+        let pubkey = create_test_pubkey();
+        let fingerprint = pubkey.fingerprint();
+
+        auth.ban_username(username, Duration::from_secs(60));
+        auth.ban_fingerprint(&fingerprint, Duration::from_secs(60));
+
+        assert!(auth.unban_username(username));
+        assert!(auth.unban_fingerprint(&fingerprint));
+        assert!(!auth.check_bans(username, &pubkey));
+        assert!(!auth.unban_username(username));
+    }
+
     #[test]
     fn test_banned() {
         let mut auth = Auth::default();
@@ -379,6 +692,39 @@ mod tests {
         assert_eq!(banned_fingerprints, vec![fingerprint.to_string()]);
     }
 
+    #[test]
+    fn test_ban_ip_range() {
+        let mut auth = Auth::default();
+        let range = "10.0.0.0/24".parse::<IpRange>().unwrap();
+
+        auth.ban_ip(range, Duration::from_secs(60));
+        assert!(auth.is_ip_banned(&"10.0.0.42".parse().unwrap()));
+        assert!(!auth.is_ip_banned(&"10.0.1.42".parse().unwrap()));
+        assert_eq!(auth.banned_ips(), vec!["10.0.0.0/24".to_string()]);
+
+        assert!(auth.unban_ip(&range));
+        assert!(!auth.is_ip_banned(&"10.0.0.42".parse().unwrap()));
+        assert!(!auth.unban_ip(&range));
+    }
+
+    #[test]
+    fn test_reserve_op_name() {
+        let mut auth = Auth::default();
+        let op = create_test_pubkey();
+        let user = create_test_pubkey();
+        auth.add_operator(op.clone());
+
+        auth.reserve_op_name("boss", &op);
+        assert!(!auth.is_name_reserved("boss", &user));
+
+        auth.set_name_reservation(Some(Duration::from_secs(60)));
+        auth.reserve_op_name("boss", &op);
+        auth.reserve_op_name("alice", &user);
+        assert!(auth.is_name_reserved("boss", &user));
+        assert!(!auth.is_name_reserved("boss", &op));
+        assert!(!auth.is_name_reserved("alice", &op));
+    }
+
     #[test]
     fn test_add_remove_operator() {
         let mut auth = Auth::default();
@@ -446,6 +792,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_trusting_key_clears_its_access_request() {
+        let mut auth = Auth::default();
+        let key = create_test_pubkey();
+        auth.record_access_request(key.clone(), "alice", None);
+        auth.record_access_request(create_test_pubkey(), "bob", None);
+        assert_eq!(auth.access_requests().len(), 2);
+
+        auth.add_trusted_key(key);
+        let pending = auth.access_requests();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].username, "bob");
+    }
+
     #[test]
     fn test_display_no_oplist() {
         let error = AuthError::NoOplist;
@@ -2,6 +2,8 @@ use humantime;
 use std::str::FromStr;
 use std::time::Duration;
 
+use crate::pubkey;
+
 #[derive(Debug, PartialEq)]
 struct BanDuration(Duration);
 
@@ -67,6 +69,12 @@ impl FromStr for BanQuery {
             let name = next_part.to_string();
             let duration_str = parts.nth(1).ok_or("missing duration")?;
             let duration = duration_str.parse::<BanDuration>()?;
+            if let Some(fingerprint) = pubkey::parse_fingerprint(next_part) {
+                return Ok(BanQuery::Multiple(vec![BanItem {
+                    attribute: Attribute::Fingerprint(fingerprint.to_string()),
+                    duration: duration.0,
+                }]));
+            }
             return Ok(BanQuery::Single {
                 name,
                 duration: duration.0,
@@ -89,6 +97,40 @@ impl FromStr for BanQuery {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub enum UnbanQuery {
+    Single(String),
+    Multiple(Vec<Attribute>),
+}
+
+impl FromStr for UnbanQuery {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split_whitespace();
+
+        // Peek the next part to decide the type of command
+        let next_part = parts.clone().next().ok_or("missing arguments")?;
+
+        // Single unban command
+        if !next_part.contains('=') {
+            if let Some(fingerprint) = pubkey::parse_fingerprint(next_part) {
+                return Ok(UnbanQuery::Multiple(vec![Attribute::Fingerprint(
+                    fingerprint.to_string(),
+                )]));
+            }
+            return Ok(UnbanQuery::Single(next_part.to_string()));
+        }
+
+        // Multiple unban command
+        let attributes = parts
+            .map(|part| part.parse::<Attribute>())
+            .collect::<Result<Vec<Attribute>, Self::Err>>()?;
+
+        Ok(UnbanQuery::Multiple(attributes))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -233,4 +275,47 @@ mod tests {
         assert!(parsed.is_err());
         assert_eq!(parsed.unwrap_err(), "unknown attribute");
     }
+
+    #[test]
+    fn test_unban_query_single() {
+        let input = "alice";
+        let expected = UnbanQuery::Single("alice".to_string());
+        assert_eq!(UnbanQuery::from_str(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_unban_query_multiple() {
+        let input = "name=alice fingerprint=abc123";
+        let expected = UnbanQuery::Multiple(vec![
+            Attribute::Name("alice".to_string()),
+            Attribute::Fingerprint("abc123".to_string()),
+        ]);
+        assert_eq!(UnbanQuery::from_str(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_unban_query_missing_arguments() {
+        let parsed = UnbanQuery::from_str("");
+        assert_eq!(parsed.unwrap_err(), "missing arguments");
+    }
+
+    #[test]
+    fn test_unban_query_unknown_attribute_format() {
+        let parsed = UnbanQuery::from_str("name=alice unknown=1");
+        assert_eq!(parsed.unwrap_err(), "unknown attribute");
+    }
+
+    #[test]
+    fn test_ban_query_bare_fingerprint() {
+        let fingerprint = "a".repeat(43);
+        let input = format!("SHA256:{} 1h", fingerprint);
+        let expected = BanQuery::Multiple(vec![BanItem {
+            attribute: Attribute::Fingerprint(fingerprint.clone()),
+            duration: Duration::from_secs(3600),
+        }]);
+        assert_eq!(BanQuery::from_str(&input).unwrap(), expected);
+
+        let expected = UnbanQuery::Multiple(vec![Attribute::Fingerprint(fingerprint.clone())]);
+        assert_eq!(UnbanQuery::from_str(&fingerprint).unwrap(), expected);
+    }
 }
@@ -0,0 +1,134 @@
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// Range of IP addresses in CIDR notation, e.g. `10.0.0.0/24`. A bare
+/// address is a range of one, i.e. `/32` for IPv4 or `/128` for IPv6
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpRange {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpRange {
+    /// Checks if the address is within the range. IPv4-mapped IPv6
+    /// addresses are matched as IPv4
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        let addr = addr.to_canonical();
+        addr.is_ipv4() == self.network.is_ipv4() && truncate(addr, self.prefix_len) == self.network
+    }
+}
+
+/// Clears all but the first `prefix_len` bits of the address
+fn truncate(addr: IpAddr, prefix_len: u8) -> IpAddr {
+    match addr {
+        IpAddr::V4(addr) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(addr) & mask))
+        }
+        IpAddr::V6(addr) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(addr) & mask))
+        }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+
+        let network = addr
+            .parse::<IpAddr>()
+            .map_err(|_| "invalid ip address")?
+            .to_canonical();
+        let max_len = match network {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix_len = match prefix_len {
+            Some(len) => len.parse::<u8>().map_err(|_| "invalid ip prefix length")?,
+            None => max_len,
+        };
+        if prefix_len > max_len {
+            return Err("invalid ip prefix length");
+        }
+
+        Ok(IpRange {
+            network: truncate(network, prefix_len),
+            prefix_len,
+        })
+    }
+}
+
+impl Display for IpRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_ip_range_parse_bare_address() {
+        let range = "192.168.1.1".parse::<IpRange>().unwrap();
+        assert_eq!(range.to_string(), "192.168.1.1/32");
+
+        let range = "::1".parse::<IpRange>().unwrap();
+        assert_eq!(range.to_string(), "::1/128");
+    }
+
+    #[test]
+    fn test_ip_range_parse_clears_host_bits() {
+        let range = "10.0.0.7/24".parse::<IpRange>().unwrap();
+        assert_eq!(range.to_string(), "10.0.0.0/24");
+        assert_eq!(range, "10.0.0.0/24".parse::<IpRange>().unwrap());
+    }
+
+    #[test]
+    fn test_ip_range_parse_invalid() {
+        assert_eq!("10.0.0/24".parse::<IpRange>(), Err("invalid ip address"));
+        assert_eq!(
+            "10.0.0.0/33".parse::<IpRange>(),
+            Err("invalid ip prefix length")
+        );
+        assert_eq!(
+            "10.0.0.0/x".parse::<IpRange>(),
+            Err("invalid ip prefix length")
+        );
+    }
+
+    #[test]
+    fn test_ip_range_contains_ipv4() {
+        let range = "10.0.0.0/24".parse::<IpRange>().unwrap();
+        assert!(range.contains(&ip("10.0.0.1")));
+        assert!(range.contains(&ip("10.0.0.255")));
+        assert!(!range.contains(&ip("10.0.1.1")));
+        assert!(range.contains(&ip("::ffff:10.0.0.7")));
+        assert!(!range.contains(&ip("::1")));
+
+        let range = "0.0.0.0/0".parse::<IpRange>().unwrap();
+        assert!(range.contains(&ip("8.8.8.8")));
+    }
+
+    #[test]
+    fn test_ip_range_contains_ipv6() {
+        let range = "2001:db8::/32".parse::<IpRange>().unwrap();
+        assert!(range.contains(&ip("2001:db8:1::1")));
+        assert!(!range.contains(&ip("2001:db9::1")));
+
+        let range = "2001:db8::1".parse::<IpRange>().unwrap();
+        assert!(range.contains(&ip("2001:db8::1")));
+        assert!(!range.contains(&ip("2001:db8::2")));
+    }
+}
@@ -1,8 +1,15 @@
+mod access_requests;
+mod audit_log;
 mod auth;
 mod ban;
+mod ip_range;
+mod permission;
 mod pubkey_file_manager;
 mod set;
 
+pub use audit_log::AuditLog;
 pub use auth::Auth;
-pub use ban::{Attribute as BanAttribute, BanQuery};
+pub use ban::{Attribute as BanAttribute, BanQuery, UnbanQuery};
+pub use ip_range::IpRange;
+pub use permission::Permission;
 pub use pubkey_file_manager::PubKeyFileManager;
@@ -0,0 +1,68 @@
+use std::collections::HashSet;
+
+use strum::{Display, EnumIter, EnumString, IntoEnumIterator};
+
+/// What an operator is allowed to do. Operators listed without the
+/// `permissions` option in the oplist have all of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumIter, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Permission {
+    /// Mute users, one at a time or all at once
+    Mute,
+    /// Kick users, summon them and veto votes to kick
+    Kick,
+    /// Ban and unban users, names, keys and addresses
+    Ban,
+    /// See the addresses and sessions of users
+    Inspect,
+    /// Pin messages, color names, and edit the MOTD and blocklist
+    Room,
+    /// Shut down the server
+    Shutdown,
+    /// Modify the whitelist and see pending access requests
+    Whitelist,
+    /// Modify the oplist
+    Oplist,
+}
+
+impl Permission {
+    /// Parses a comma-separated list, e.g. `kick,mute`. Fails on the
+    /// first unknown name
+    pub fn parse_list(list: &str) -> Result<HashSet<Permission>, String> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                name.parse::<Permission>()
+                    .map_err(|_| format!("unknown permission `{}`", name))
+            })
+            .collect()
+    }
+
+    /// Joins the permissions in their declaration order, the way the
+    /// oplist expects them
+    pub fn format_list(permissions: &HashSet<Permission>) -> String {
+        Permission::iter()
+            .filter(|permission| permissions.contains(permission))
+            .map(|permission| permission.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn parse_and_format_permission_lists() {
+        let permissions = Permission::parse_list("mute, kick").unwrap();
+        assert_eq!(
+            permissions,
+            HashSet::from([Permission::Kick, Permission::Mute])
+        );
+        assert_eq!(Permission::format_list(&permissions), "mute,kick");
+        assert!(Permission::parse_list("").unwrap().is_empty());
+        assert!(Permission::parse_list("kick,fly").is_err());
+    }
+}
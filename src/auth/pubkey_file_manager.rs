@@ -1,6 +1,8 @@
-use std::collections::HashSet;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use std::collections::{HashMap, HashSet};
 use std::fs::OpenOptions;
-use std::io;
+use std::io::{self, Write};
+use std::path::{Component, Path};
 
 use crate::pubkey::PubKey;
 use crate::utils;
@@ -26,11 +28,16 @@ impl std::fmt::Display for LoadError {
     }
 }
 
+impl std::error::Error for LoadError {}
+
 #[derive(Debug)]
 pub enum SaveError {
     IoError(io::Error),
     EncodeError(russh_keys::Error),
     NoKeysError,
+    NoFileError,
+    FileExistsError,
+    UnsafePathError,
 }
 
 impl From<io::Error> for SaveError {
@@ -51,30 +58,54 @@ impl std::fmt::Display for SaveError {
             SaveError::IoError(err) => write!(f, "I/O error: {}", err),
             SaveError::EncodeError(err) => write!(f, "failed to encode key to base64: {}", err),
             SaveError::NoKeysError => write!(f, "file has no keys"),
+            SaveError::NoFileError => write!(f, "keys are not backed by a file"),
+            SaveError::FileExistsError => {
+                write!(f, "file already exists, refusing to overwrite it")
+            }
+            SaveError::UnsafePathError => write!(f, "path must not contain `..` components"),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Loads and saves public keys in the authorized_keys format.
+///
+/// Keys are read from a file, from an in-memory list of lines (e.g.
+/// passed via an environment variable), or from both merged
+/// together. Saving is only possible when a file is configured.
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct PubKeyFileManager {
-    file_path: String,
+    file_path: Option<String>,
+    inline_keys: Option<String>,
 }
 
 impl PubKeyFileManager {
     pub fn new(file_path: &str) -> Self {
         Self {
-            file_path: file_path.into(),
+            file_path: Some(file_path.into()),
+            inline_keys: None,
         }
     }
 
+    /// Sets newline-separated authorized_keys lines to load in
+    /// addition to the file content.
+    pub fn set_inline_keys(&mut self, keys: &str) {
+        self.inline_keys = Some(keys.into());
+    }
+
     pub fn load_keys(&self) -> Result<HashSet<PubKey>, LoadError> {
-        let lines = utils::fs::read_file_lines(&self.file_path)?;
+        self.load_keys_with_expiry()
+            .map(|keys| keys.into_keys().collect())
+    }
 
-        let keys: HashSet<PubKey> = lines
+    /// Loads keys along with the `expiry-time` option of their lines.
+    /// Keys with a malformed expiry are skipped.
+    pub fn load_keys_with_expiry(
+        &self,
+    ) -> Result<HashMap<PubKey, Option<DateTime<Utc>>>, LoadError> {
+        let keys: HashMap<PubKey, Option<DateTime<Utc>>> = self
+            .read_lines()?
             .iter()
-            .filter_map(|line| utils::ssh::split_ssh_key(line))
-            .filter_map(|(_, key)| russh_keys::parse_public_key_base64(&key).ok())
-            .map(|key| key.into())
+            .filter_map(|line| parse_key_line(line))
             .collect();
 
         if keys.is_empty() {
@@ -84,25 +115,194 @@ impl PubKeyFileManager {
         }
     }
 
+    /// Loads the comments written after the keys, e.g. the names of
+    /// their owners. Keys without a comment are left out
+    pub fn load_key_comments(&self) -> Result<HashMap<PubKey, String>, LoadError> {
+        let comments = self
+            .read_lines()?
+            .iter()
+            .filter_map(|line| {
+                let (key, _) = parse_key_line(line)?;
+                Some((key, utils::ssh::authorized_key_comment(line)?))
+            })
+            .collect();
+        Ok(comments)
+    }
+
+    /// Loads the value of an option of the key lines, e.g. the
+    /// `permissions` of operators. Keys without the option are left out
+    pub fn load_key_option(&self, name: &str) -> Result<HashMap<PubKey, String>, LoadError> {
+        let values = self
+            .read_lines()?
+            .iter()
+            .filter_map(|line| {
+                let (key, _) = parse_key_line(line)?;
+                let (options, _, _) = utils::ssh::split_authorized_key(line)?;
+                Some((key, utils::ssh::find_key_option(&options?, name)?))
+            })
+            .collect();
+        Ok(values)
+    }
+
+    /// Lists the lines skipped on load that aren't blank or comments,
+    /// e.g. truncated keys, as `(line number, line)` pairs. Inline keys
+    /// are numbered after the file lines
+    pub fn invalid_lines(&self) -> Result<Vec<(usize, String)>, LoadError> {
+        let invalid = self
+            .read_lines()?
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| {
+                let text = String::from_utf8_lossy(line);
+                let text = text.trim();
+                !text.is_empty() && !text.starts_with('#') && parse_key_line(line).is_none()
+            })
+            .map(|(i, line)| (i + 1, String::from_utf8_lossy(line).trim().to_string()))
+            .collect();
+        Ok(invalid)
+    }
+
+    fn read_lines(&self) -> Result<Vec<Vec<u8>>, LoadError> {
+        let mut lines = vec![];
+        if let Some(path) = &self.file_path {
+            lines.extend(utils::fs::read_file_lines(path)?);
+        }
+        if let Some(keys) = &self.inline_keys {
+            lines.extend(keys.lines().map(|line| line.as_bytes().to_vec()));
+        }
+        Ok(lines)
+    }
+
+    #[cfg(test)]
     pub fn save_keys(&self, keys: &HashSet<PubKey>) -> Result<(), SaveError> {
+        self.save_keys_with_expiry(keys, &HashMap::new(), &HashMap::new())
+    }
+
+    /// Saves keys, writing a `permissions` option in front of the ones
+    /// found in `permissions`
+    pub fn save_keys_with_permissions(
+        &self,
+        keys: &HashSet<PubKey>,
+        permissions: &HashMap<PubKey, String>,
+    ) -> Result<(), SaveError> {
+        if keys.is_empty() {
+            return Err(SaveError::NoKeysError);
+        }
+
+        let path = self.file_path.as_ref().ok_or(SaveError::NoFileError)?;
+        let mut file = OpenOptions::new().write(true).truncate(true).open(path)?;
+        write_keys(
+            &mut file,
+            keys,
+            &HashMap::new(),
+            permissions,
+            &HashMap::new(),
+        )
+    }
+
+    /// Saves keys, writing an `expiry-time` option in front of the
+    /// ones found in `expiries` and the ones found in `comments` after
+    /// them
+    pub fn save_keys_with_expiry(
+        &self,
+        keys: &HashSet<PubKey>,
+        expiries: &HashMap<PubKey, DateTime<Utc>>,
+        comments: &HashMap<PubKey, String>,
+    ) -> Result<(), SaveError> {
         if keys.is_empty() {
             return Err(SaveError::NoKeysError);
         }
 
-        let mut file = OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open(&self.file_path)?;
+        let path = self.file_path.as_ref().ok_or(SaveError::NoFileError)?;
+        let mut file = OpenOptions::new().write(true).truncate(true).open(path)?;
+        write_keys(&mut file, keys, expiries, &HashMap::new(), comments)
+    }
 
-        for key in keys {
-            let key = key.clone();
-            russh_keys::write_public_key_base64(&mut file, &key.into())?;
+    /// Writes keys to a new file outside of the managed one and returns
+    /// the number of keys written. Existing files are never overwritten
+    /// and paths with `..` components are refused.
+    pub fn export_keys(
+        path: &str,
+        keys: &HashSet<PubKey>,
+        expiries: &HashMap<PubKey, DateTime<Utc>>,
+        comments: &HashMap<PubKey, String>,
+    ) -> Result<usize, SaveError> {
+        if keys.is_empty() {
+            return Err(SaveError::NoKeysError);
+        }
+        if Path::new(path)
+            .components()
+            .any(|c| matches!(c, Component::ParentDir))
+        {
+            return Err(SaveError::UnsafePathError);
         }
 
-        Ok(())
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                return Err(SaveError::FileExistsError)
+            }
+            Err(err) => return Err(err.into()),
+        };
+        write_keys(&mut file, keys, expiries, &HashMap::new(), comments)?;
+        Ok(keys.len())
     }
 }
 
+/// Parses an authorized_keys line into its key and `expiry-time`
+/// option. Returns `None` for lines that don't hold a valid key or
+/// have a malformed expiry
+fn parse_key_line(line: &[u8]) -> Option<(PubKey, Option<DateTime<Utc>>)> {
+    let (options, _, key) = utils::ssh::split_authorized_key(line)?;
+    let expiry = match options.and_then(|opts| utils::ssh::find_key_option(&opts, "expiry-time")) {
+        Some(value) => Some(parse_expiry_time(&value)?),
+        None => None,
+    };
+    let key = russh_keys::parse_public_key_base64(&key).ok()?;
+    Some((key.into(), expiry))
+}
+
+fn write_keys<W: Write>(
+    w: &mut W,
+    keys: &HashSet<PubKey>,
+    expiries: &HashMap<PubKey, DateTime<Utc>>,
+    permissions: &HashMap<PubKey, String>,
+    comments: &HashMap<PubKey, String>,
+) -> Result<(), SaveError> {
+    for key in keys {
+        let mut options = vec![];
+        if let Some(expiry) = expiries.get(key) {
+            options.push(format!("expiry-time=\"{}\"", expiry.format("%Y%m%d%H%M%S")));
+        }
+        if let Some(permissions) = permissions.get(key) {
+            options.push(format!("permissions=\"{}\"", permissions));
+        }
+        if !options.is_empty() {
+            write!(w, "{} ", options.join(","))?;
+        }
+        match comments.get(key) {
+            Some(comment) => writeln!(w, "{} {}", key.long(), comment)?,
+            None => russh_keys::write_public_key_base64(&mut *w, &key.clone().into())?,
+        }
+    }
+    Ok(())
+}
+
+/// Parses an `expiry-time` option value in the `YYYYMMDD[HHMM[SS]]`
+/// form. Times are interpreted as UTC.
+fn parse_expiry_time(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim_end_matches('Z');
+    let datetime = match value.len() {
+        8 => NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()?
+            .and_hms_opt(0, 0, 0)?,
+        12 => NaiveDateTime::parse_from_str(value, "%Y%m%d%H%M").ok()?,
+        14 => NaiveDateTime::parse_from_str(value, "%Y%m%d%H%M%S").ok()?,
+        _ => return None,
+    };
+    Some(datetime.and_utc())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -139,6 +339,27 @@ mod tests {
         assert!(keys.contains(&pubkey));
     }
 
+    #[test]
+    fn test_invalid_lines() {
+        let file_path = "test_keys_invalid_lines.txt";
+        let pubkey = create_test_pubkey();
+        let content = format!(
+            "# operators\n{}\n\nssh-ed25519 AAAAtruncated\nnot a key\n",
+            pubkey.long()
+        );
+        let (_dir, full_path) = setup_test_file(file_path, &content);
+
+        let manager = PubKeyFileManager::new(&full_path);
+        assert_eq!(manager.load_keys().unwrap().len(), 1);
+        assert_eq!(
+            manager.invalid_lines().unwrap(),
+            vec![
+                (4, "ssh-ed25519 AAAAtruncated".to_string()),
+                (5, "not a key".to_string())
+            ]
+        );
+    }
+
     #[test]
     fn test_load_keys_no_keys_error() {
         let file_path = "test_keys_no_keys.txt";
@@ -157,6 +378,139 @@ mod tests {
         assert!(matches!(result, Err(LoadError::IoError(_))));
     }
 
+    #[test]
+    fn test_load_inline_keys_success() {
+        let pubkey = create_test_pubkey();
+
+        let mut manager = PubKeyFileManager::default();
+        manager.set_inline_keys(&format!("# comment\n{}\n", pubkey.long()));
+        let keys = manager.load_keys().unwrap();
+        assert_eq!(keys, HashSet::from([pubkey]));
+    }
+
+    #[test]
+    fn test_load_keys_merges_file_and_inline_keys() {
+        let file_path = "test_keys_load_merge.txt";
+        let file_key = create_test_pubkey();
+        let inline_key = create_test_pubkey();
+        let (_dir, full_path) = setup_test_file(file_path, &file_key.long());
+
+        let mut manager = PubKeyFileManager::new(&full_path);
+        manager.set_inline_keys(&inline_key.long());
+        let keys = manager.load_keys().unwrap();
+        assert!(keys.contains(&file_key));
+        assert!(keys.contains(&inline_key));
+    }
+
+    #[test]
+    fn test_load_keys_with_expiry() {
+        let expiring_key = create_test_pubkey();
+        let plain_key = create_test_pubkey();
+        let invalid_key = create_test_pubkey();
+
+        let mut manager = PubKeyFileManager::default();
+        manager.set_inline_keys(&format!(
+            "expiry-time=\"20250101\",no-pty {}\n{}\nexpiry-time=\"tomorrow\" {}",
+            expiring_key.long(),
+            plain_key.long(),
+            invalid_key.long()
+        ));
+        let keys = manager.load_keys_with_expiry().unwrap();
+
+        let expiry = "2025-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(keys.get(&expiring_key), Some(&Some(expiry)));
+        assert_eq!(keys.get(&plain_key), Some(&None));
+        assert!(!keys.contains_key(&invalid_key));
+    }
+
+    #[test]
+    fn test_load_key_comments() {
+        let pubkey = create_test_pubkey();
+        let other = create_test_pubkey();
+        let content = format!("{} alice\n{}\n", pubkey.long(), other.long());
+        let (_temp_dir, file_path) = setup_test_file("keys.txt", &content);
+        let manager = PubKeyFileManager::new(&file_path);
+
+        let comments = manager.load_key_comments().unwrap();
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments.get(&pubkey).map(String::as_str), Some("alice"));
+    }
+
+    #[test]
+    fn test_save_and_load_key_option() {
+        let file_path = "test_keys_save_permissions.txt";
+        let restricted = create_test_pubkey();
+        let plain = create_test_pubkey();
+        let (_dir, full_path) = setup_test_file(file_path, "");
+
+        let manager = PubKeyFileManager::new(&full_path);
+        manager
+            .save_keys_with_permissions(
+                &HashSet::from([restricted.clone(), plain.clone()]),
+                &HashMap::from([(restricted.clone(), "kick,mute".to_string())]),
+            )
+            .unwrap();
+
+        assert_eq!(manager.load_keys().unwrap().len(), 2);
+        let permissions = manager.load_key_option("permissions").unwrap();
+        assert_eq!(permissions.len(), 1);
+        assert_eq!(
+            permissions.get(&restricted).map(String::as_str),
+            Some("kick,mute")
+        );
+    }
+
+    #[test]
+    fn test_parse_expiry_time() {
+        let expected = "2025-01-02T03:04:05Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(parse_expiry_time("20250102030405"), Some(expected));
+        assert_eq!(parse_expiry_time("20250102030405Z"), Some(expected));
+        assert!(parse_expiry_time("202501020304").is_some());
+        assert!(parse_expiry_time("2025").is_none());
+    }
+
+    #[test]
+    fn test_save_keys_with_expiry() {
+        let file_path = "test_keys_save_expiry.txt";
+        let pubkey = create_test_pubkey();
+        let (_dir, full_path) = setup_test_file(file_path, "");
+
+        let expiry = "2025-01-02T03:04:05Z".parse::<DateTime<Utc>>().unwrap();
+        let manager = PubKeyFileManager::new(&full_path);
+        manager
+            .save_keys_with_expiry(
+                &HashSet::from([pubkey.clone()]),
+                &HashMap::from([(pubkey.clone(), expiry)]),
+                &HashMap::new(),
+            )
+            .unwrap();
+
+        let keys = manager.load_keys_with_expiry().unwrap();
+        assert_eq!(keys.get(&pubkey), Some(&Some(expiry)));
+    }
+
+    #[test]
+    fn test_save_keys_with_comments() {
+        let file_path = "test_keys_save_comments.txt";
+        let named = create_test_pubkey();
+        let unnamed = create_test_pubkey();
+        let (_dir, full_path) = setup_test_file(file_path, "");
+
+        let manager = PubKeyFileManager::new(&full_path);
+        manager
+            .save_keys_with_expiry(
+                &HashSet::from([named.clone(), unnamed.clone()]),
+                &HashMap::new(),
+                &HashMap::from([(named.clone(), "alice".to_string())]),
+            )
+            .unwrap();
+
+        assert_eq!(manager.load_keys().unwrap().len(), 2);
+        let comments = manager.load_key_comments().unwrap();
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments.get(&named).map(String::as_str), Some("alice"));
+    }
+
     #[test]
     fn test_save_keys_success() {
         let file_path = "test_keys_save_success.txt";
@@ -214,6 +568,18 @@ mod tests {
         assert!(matches!(result, Err(SaveError::IoError(_))));
     }
 
+    #[test]
+    fn test_save_keys_no_file_error() {
+        let pubkey = create_test_pubkey();
+        let keys = HashSet::from([pubkey.clone()]);
+
+        let mut manager = PubKeyFileManager::default();
+        manager.set_inline_keys(&pubkey.long());
+        let result = manager.save_keys(&keys);
+
+        assert!(matches!(result, Err(SaveError::NoFileError)));
+    }
+
     #[test]
     fn test_save_keys_encode_error() {
         // russh_keys::write_public_key_base64 can only fail with IO
@@ -245,6 +611,12 @@ mod tests {
         assert_eq!(format!("{}", save_error), "file has no keys");
     }
 
+    #[test]
+    fn test_save_error_display_no_file_error() {
+        let save_error = SaveError::NoFileError;
+        assert_eq!(format!("{}", save_error), "keys are not backed by a file");
+    }
+
     #[test]
     fn test_load_error_display_io_error() {
         let io_error = io::Error::from(io::ErrorKind::NotFound);
@@ -257,4 +629,41 @@ mod tests {
         let load_error = LoadError::NoKeysError;
         assert_eq!(format!("{}", load_error), "file has no keys");
     }
+
+    #[test]
+    fn test_export_keys_to_new_file() {
+        let temp = TempDir::new().unwrap();
+        let path = format!("{}/export.txt", temp.path().display());
+        let pubkey = create_test_pubkey();
+        let keys = HashSet::from([pubkey.clone()]);
+
+        let comments = HashMap::from([(pubkey.clone(), "alice".to_string())]);
+
+        let count =
+            PubKeyFileManager::export_keys(&path, &keys, &HashMap::new(), &comments).unwrap();
+        assert_eq!(count, 1);
+
+        let manager = PubKeyFileManager::new(&path);
+        assert_eq!(manager.load_keys().unwrap(), keys);
+        assert_eq!(manager.load_key_comments().unwrap(), comments);
+    }
+
+    #[test]
+    fn test_export_keys_refuses_to_overwrite() {
+        let (_dir, full_path) = setup_test_file("existing.txt", "content");
+        let keys = HashSet::from([create_test_pubkey()]);
+
+        let result =
+            PubKeyFileManager::export_keys(&full_path, &keys, &HashMap::new(), &HashMap::new());
+        assert!(matches!(result, Err(SaveError::FileExistsError)));
+        assert_eq!(fs::read_to_string(&full_path).unwrap(), "content");
+    }
+
+    #[test]
+    fn test_export_keys_refuses_parent_dir_components() {
+        let keys = HashSet::from([create_test_pubkey()]);
+        let result =
+            PubKeyFileManager::export_keys("../keys.txt", &keys, &HashMap::new(), &HashMap::new());
+        assert!(matches!(result, Err(SaveError::UnsafePathError)));
+    }
 }
@@ -1,12 +1,21 @@
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone)]
 pub struct TimedHashSet<T> {
     items: HashMap<T, Instant>,
     expiration_times: HashMap<T, Duration>,
 }
 
+impl<T> Default for TimedHashSet<T> {
+    fn default() -> Self {
+        Self {
+            items: HashMap::new(),
+            expiration_times: HashMap::new(),
+        }
+    }
+}
+
 impl<T> TimedHashSet<T>
 where
     T: Eq + std::hash::Hash + Clone,
@@ -32,6 +41,15 @@ where
         }
     }
 
+    /// Removes the item and returns `true` if it was present and not
+    /// expired yet
+    pub fn remove(&mut self, item: &T) -> bool {
+        let is_present = self.contains(item);
+        self.items.remove(item);
+        self.expiration_times.remove(item);
+        is_present
+    }
+
     pub fn iter(&self) -> TimedHashSetIter<T> {
         TimedHashSetIter {
             items_iter: self.items.iter(),
@@ -155,4 +173,15 @@ mod tests {
         assert!(!items.contains(&item1));
         assert!(items.contains(&item2));
     }
+
+    #[test]
+    fn test_remove() {
+        let mut set = TimedHashSet::default();
+        let item = "item1".to_string();
+
+        set.insert(item.clone(), Duration::from_secs(2));
+        assert!(set.remove(&item));
+        assert!(!set.contains(&item));
+        assert!(!set.remove(&item));
+    }
 }
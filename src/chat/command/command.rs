@@ -1,294 +1,721 @@
 use std::str::FromStr;
 use strum::{EnumCount, EnumIter, EnumProperty};
 
-use crate::chat::user::{Theme, TimestampMode};
+use crate::chat::user::{PromptTemplate, Theme, ThemeChoice, TimestampMode};
 
 use super::command_props::CommandProps;
+use super::motd_command::MotdCommand;
 use super::parse_error::CommandParseError;
+use super::poll_command::PollCommand;
+use super::prefix::command_prefix;
 use super::whitelist_command::WhitelistCommand;
 use super::OplistCommand;
 
 #[derive(Debug, Clone, PartialEq, EnumProperty, EnumIter, EnumCount)]
 pub enum Command {
-    #[strum(props(Cmd = "/exit", Help = "Exit the chat application"))]
+    #[strum(props(Cmd = "exit", Help = "Exit the chat application"))]
     Exit,
 
     #[strum(props(
-        Cmd = "/away",
-        Args = "<reason>",
-        Help = "Let the room know you can't make it and why"
+        Cmd = "away",
+        Args = "[reason]",
+        Help = "Let the room know you can't make it and why. Reuses the last reason if none is given. End it with e.g. \"back in 30m\" to tell PMs when you return"
     ))]
-    Away(String),
+    Away(Option<String>),
 
-    #[strum(props(Cmd = "/back", Help = "Clear away status"))]
+    #[strum(props(Cmd = "back", Help = "Clear away status"))]
     Back,
 
-    #[strum(props(Cmd = "/name", Args = "<name>", Help = "Rename yourself"))]
+    #[strum(props(Cmd = "afk", Help = "List users who are away and why"))]
+    Afk,
+
+    #[strum(props(Cmd = "name", Args = "<name>", Help = "Rename yourself"))]
     Name(String),
 
     #[strum(props(
-        Cmd = "/msg",
-        Args = "<user> <message>",
-        Help = "Send a private message to a user"
+        Cmd = "msg",
+        Args = "<user[,user...]> <message>",
+        Help = "Send a private message to a user, or to each of a comma-separated list of users"
     ))]
-    Msg(String, String),
+    Msg(Vec<String>, String),
 
     #[strum(props(
-        Cmd = "/reply",
+        Cmd = "reply",
         Args = "<message>",
         Help = "Reply to the previous private message"
     ))]
     Reply(String),
 
-    #[strum(props(Cmd = "/ignore", Args = "[user]", Help = "Hide messages from a user"))]
+    #[strum(props(
+        Cmd = "quote",
+        Args = "<user> <message>",
+        Help = "Send a message under the latest message of a user, quoted"
+    ))]
+    Quote(String, String),
+
+    #[strum(props(
+        Cmd = "paste",
+        Help = "Post the next lines as one block, up to a line holding only \".\". The cancel command discards them"
+    ))]
+    Paste,
+
+    #[strum(props(
+        Cmd = "remind",
+        Args = "<duration> <text>",
+        Help = "Send yourself the text after the duration, e.g. 10m"
+    ))]
+    Remind(String, String),
+
+    #[strum(props(Cmd = "ignore", Args = "[user]", Help = "Hide messages from a user"))]
     Ignore(Option<String>),
 
     #[strum(props(
-        Cmd = "/unignore",
+        Cmd = "unignore",
         Args = "<user>",
         Help = "Stop hidding messages from a user"
     ))]
     Unignore(String),
 
     #[strum(props(
-        Cmd = "/focus",
+        Cmd = "focus",
         Args = "[user]",
-        Help = "Only show messages from focused users. $ to reset"
+        Help = "Only show messages from focused users, or hide -user. $ to reset"
     ))]
     Focus(Option<String>),
 
-    #[strum(props(Cmd = "/users", Help = "List users who are connected"))]
+    #[strum(props(Cmd = "users", Help = "List users who are connected"))]
     Users,
 
-    #[strum(props(Cmd = "/whois", Args = "<user>", Help = "Information about a user"))]
-    Whois(String),
+    #[strum(props(Cmd = "ops", Help = "List operators who are connected"))]
+    Ops,
 
     #[strum(props(
-        Cmd = "/timestamp",
-        Args = "<time|datetime|off>",
-        Help = "Prefix messages with a UTC timestamp"
+        Cmd = "whois",
+        Args = "[user]",
+        Help = "Information about a user, or yourself if none is given"
+    ))]
+    Whois(Option<String>),
+
+    #[strum(props(Cmd = "stats", Help = "Show statistics of your current session"))]
+    Stats,
+
+    #[strum(props(Cmd = "settings", Help = "Show all your current preferences"))]
+    Settings,
+
+    #[strum(props(
+        Cmd = "timestamp",
+        Args = "<time|datetime|relative|off>",
+        Help = "Prefix messages with a UTC timestamp, or with the time since you joined"
     ))]
     Timestamp(TimestampMode),
 
-    #[strum(props(Cmd = "/theme", Args = "<theme>", Help = "Set your color theme"))]
-    Theme(Theme),
+    #[strum(props(
+        Cmd = "theme",
+        Args = "<theme|random|next>",
+        Help = "Set your color theme, a random one or the next one in the list"
+    ))]
+    Theme(ThemeChoice),
 
-    #[strum(props(Cmd = "/themes", Help = "List supported color themes"))]
-    Themes,
+    #[strum(props(
+        Cmd = "themes",
+        Args = "[preview]",
+        Help = "List supported color themes, or preview each of them"
+    ))]
+    Themes(bool),
 
-    #[strum(props(Cmd = "/quiet", Help = "Silence room announcements"))]
+    #[strum(props(Cmd = "quiet", Help = "Silence room announcements"))]
     Quiet,
 
-    /// Operator commands
+    #[strum(props(
+        Cmd = "emoji",
+        Help = "Toggle expanding :shortcode: emoji in your messages"
+    ))]
+    Emoji,
 
     #[strum(props(
-        Cmd = "/mute",
+        Cmd = "bell",
+        Args = "<on|off>",
+        Help = "Turn the terminal bell on highlights, PMs and summons on or off"
+    ))]
+    Bell(bool),
+
+    #[strum(props(
+        Cmd = "receipts",
+        Help = "Toggle read receipts: see when your PMs are read and let others see when you read theirs"
+    ))]
+    Receipts,
+
+    #[strum(props(
+        Cmd = "prompt",
+        Args = "[template]",
+        Help = "Set your prompt with {name}, {status} and {users} placeholders. Resets it if no template is given"
+    ))]
+    Prompt(Option<PromptTemplate>),
+
+    #[strum(props(
+        Cmd = "redraw",
+        Help = "Clear your screen and show the recent history again, e.g. after the terminal got garbled"
+    ))]
+    Redraw,
+
+    #[strum(props(Cmd = "pinned", Help = "List the pinned messages"))]
+    Pinned,
+
+    #[strum(props(
+        Cmd = "sessions",
+        Args = "[close <n>]",
+        Help = "List your sessions connected with the same key, or close one of them"
+    ))]
+    Sessions(Option<usize>),
+
+    #[strum(props(
+        Cmd = "votekick",
         Args = "<user>",
-        Help = "Toggle muting user, preventing messages from broadcasting",
+        Help = "Vote to kick a user, who is kicked once enough users voted"
+    ))]
+    Votekick(String),
+
+    #[strum(props(
+        Cmd = "poll",
+        Args = "[\"question\" <options...> | close]",
+        Help = "Start a poll for the room to vote on, close it, or show the votes so far"
+    ))]
+    Poll(PollCommand),
+
+    #[strum(props(
+        Cmd = "vote",
+        Args = "<n>",
+        Help = "Vote for the n-th option of the poll"
+    ))]
+    Vote(usize),
+
+    /// Operator commands
+
+    #[strum(props(
+        Cmd = "mute",
+        Args = "<user|fingerprint>",
+        Help = "Toggle muting user, preventing messages from broadcasting. A key fingerprint mutes the key whatever name it joins with",
         Op = "true"
     ))]
     Mute(String),
 
     #[strum(props(
-        Cmd = "/kick",
+        Cmd = "mute-all",
+        Help = "Mute everyone but the operators, e.g. during a raid",
+        Op = "true"
+    ))]
+    MuteAll,
+
+    #[strum(props(Cmd = "unmute-all", Help = "Unmute everyone muted", Op = "true"))]
+    UnmuteAll,
+
+    #[strum(props(
+        Cmd = "kick",
+        Args = "<user> [reason]",
+        Help = "Kick user from the server, telling them and the room why",
+        Op = "true"
+    ))]
+    Kick(String, Option<String>),
+
+    #[strum(props(
+        Cmd = "summon",
         Args = "<user>",
-        Help = "Kick user from the server",
+        Help = "Ring the user's terminal to get their attention",
         Op = "true"
     ))]
-    Kick(String),
+    Summon(String),
 
     #[strum(props(
-        Cmd = "/ban",
-        Args = "<query>",
-        Help = "Ban user from the server",
+        Cmd = "veto",
+        Args = "<user>",
+        Help = "Cancel the vote to kick a user",
+        Op = "true"
+    ))]
+    Veto(String),
+
+    #[strum(props(
+        Cmd = "ban",
+        Args = "[--dry-run] <query>",
+        Help = "Ban user from the server, or list who would be banned with --dry-run",
         Op = "true"
     ))]
-    Ban(String),
+    Ban(String, bool),
 
-    #[strum(props(Cmd = "/banned", Help = "List the current ban conditions", Op = "true"))]
+    #[strum(props(Cmd = "banned", Help = "List the current ban conditions", Op = "true"))]
     Banned,
 
     #[strum(props(
-        Cmd = "/motd",
-        Args = "[message]",
-        Help = "Set a new message of the day, or print the motd if no message",
+        Cmd = "requests",
+        Help = "List keys turned away by the whitelist",
+        Op = "true"
+    ))]
+    Requests,
+
+    #[strum(props(
+        Cmd = "who-ip",
+        Args = "<ip>",
+        Help = "List users connected from the IP address",
+        Op = "true"
+    ))]
+    WhoIp(String),
+
+    #[strum(props(
+        Cmd = "sessions-of",
+        Args = "<user>",
+        Help = "List the sessions connected with the same key as the user",
+        Op = "true"
+    ))]
+    SessionsOf(String),
+
+    #[strum(props(
+        Cmd = "shutdown",
+        Args = "<delay | cancel>",
+        Help = "Shut down the server after the delay, e.g. 5m, or cancel a scheduled shutdown",
+        Op = "true"
+    ))]
+    Shutdown(String),
+
+    #[strum(props(
+        Cmd = "unban",
+        Args = "<query>",
+        Help = "Lift bans matching the query",
+        Op = "true"
+    ))]
+    Unban(String),
+
+    #[strum(props(
+        Cmd = "blocklist",
+        Args = "reload",
+        Help = "Reload the blocked words from the blocklist file",
+        Op = "true"
+    ))]
+    ReloadBlocklist,
+
+    #[strum(props(
+        Cmd = "color",
+        Args = "<user> <hex | reset>",
+        Help = "Pin a color to the user's name, or reset it to the default",
+        Op = "true"
+    ))]
+    Color(String, String),
+
+    #[strum(props(
+        Cmd = "pin",
+        Args = "<message | ^n>",
+        Help = "Pin a message shown to everyone who joins, or the n-th latest message with ^n",
         Op = "true"
     ))]
-    Motd(Option<String>),
+    Pin(String),
 
     #[strum(props(
-        Cmd = "/whitelist",
+        Cmd = "unpin",
+        Args = "<n>",
+        Help = "Unpin the n-th pinned message",
+        Op = "true"
+    ))]
+    Unpin(usize),
+
+    #[strum(props(
+        Cmd = "clearhistory",
+        Help = "Wipe the room history, so users who join don't see the messages sent so far",
+        Op = "true"
+    ))]
+    ClearHistory,
+
+    #[strum(props(
+        Cmd = "motd",
+        Args = "[message | append <line> | prepend <line> | reload | on | off]",
+        Help = "Set, extend or reload from the MOTD file the message of the day, toggle it on join, or print the motd if no message",
+        Op = "true"
+    ))]
+    Motd(MotdCommand),
+
+    #[strum(props(
+        Cmd = "whitelist",
         Args = "<command> [args...]",
-        Help = "Modify the whitelist or whitelist state. See /whitelist help for subcommands",
+        Help = "Modify the whitelist or whitelist state. Run it with \"help\" for subcommands",
         Op = "true"
     ))]
     Whitelist(WhitelistCommand),
 
     #[strum(props(
-        Cmd = "/oplist",
+        Cmd = "oplist",
         Args = "<command> [args...]",
-        Help = "Modify the oplist or oplist state. See /oplist help for subcommands",
+        Help = "Modify the oplist or oplist state. Run it with \"help\" for subcommands",
         Op = "true"
     ))]
     Oplist(OplistCommand),
 
     /// Secret commands (just hidden or easter eggs)
 
-    #[strum(props(Cmd = "/me", Args = "[action]"))]
+    #[strum(props(Cmd = "me", Args = "[action]"))]
     Me(Option<String>),
 
-    #[strum(props(Cmd = "/slap", Args = "[user]"))]
+    #[strum(props(Cmd = "slap", Args = "[user]"))]
     Slap(Option<String>),
 
-    #[strum(props(Cmd = "/shrug",))]
+    #[strum(props(Cmd = "shrug",))]
     Shrug,
 
-    #[strum(props(Cmd = "/help"))]
-    Help,
+    #[strum(props(Cmd = "help", Args = "[command]"))]
+    Help(Option<String>),
 
-    #[strum(props(Cmd = "/version"))]
+    #[strum(props(Cmd = "version"))]
     Version,
 
-    #[strum(props(Cmd = "/uptime"))]
+    #[strum(props(Cmd = "uptime"))]
     Uptime,
+
+    #[strum(props(Cmd = "ping"))]
+    Ping,
 }
 
 impl FromStr for Command {
     type Err = CommandParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_with_prefix(s, command_prefix())
+    }
+}
+
+impl Command {
+    /// Parses the input as a command if it starts with the prefix. The
+    /// prefix is stripped before matching the command name
+    pub fn parse_with_prefix(s: &str, prefix: &str) -> Result<Self, CommandParseError> {
         let (cmd, args) = if let Some((cmd, args)) = s.split_once(' ') {
             (cmd, args.trim())
         } else {
             (s, "")
         };
 
-        if !cmd.starts_with("/") {
-            return Err(Self::Err::NotRecognizedAsCommand);
-        }
+        let name = match cmd.strip_prefix(prefix) {
+            Some(name) => name,
+            None => return Err(CommandParseError::NotRecognizedAsCommand),
+        };
 
-        match cmd.as_bytes() {
-            b"/exit" => Ok(Command::Exit),
-            b"/help" => Ok(Command::Help),
-            b"/version" => Ok(Command::Version),
-            b"/uptime" => Ok(Command::Uptime),
-            b"/back" => Ok(Command::Back),
-            b"/users" => Ok(Command::Users),
-            b"/shrug" => Ok(Command::Shrug),
-            b"/quiet" => Ok(Command::Quiet),
-            b"/themes" => Ok(Command::Themes),
-            b"/banned" => Ok(Command::Banned),
-            b"/away" => match args.is_empty() {
-                true => Err(Self::Err::ArgumentExpected(format!("away reason"))),
-                false => Ok(Command::Away(args.to_string())),
-            },
-            b"/name" => match args.splitn(2, ' ').nth(0) {
+        match name.as_bytes() {
+            b"exit" => Ok(Command::Exit),
+            b"version" => Ok(Command::Version),
+            b"uptime" => Ok(Command::Uptime),
+            b"ping" => Ok(Command::Ping),
+            b"back" => Ok(Command::Back),
+            b"afk" => Ok(Command::Afk),
+            b"users" => Ok(Command::Users),
+            b"ops" => Ok(Command::Ops),
+            b"stats" => Ok(Command::Stats),
+            b"settings" => Ok(Command::Settings),
+            b"paste" => Ok(Command::Paste),
+            b"shrug" => Ok(Command::Shrug),
+            b"quiet" => Ok(Command::Quiet),
+            b"emoji" => Ok(Command::Emoji),
+            b"bell" => match args {
+                "on" => Ok(Command::Bell(true)),
+                "off" => Ok(Command::Bell(false)),
+                "" => Err(CommandParseError::ArgumentExpected("on|off".to_string())),
+                _ => Err(CommandParseError::Other(
+                    "bell argument must be: on|off".to_string(),
+                )),
+            },
+            b"receipts" => Ok(Command::Receipts),
+            b"redraw" => Ok(Command::Redraw),
+            b"pinned" => Ok(Command::Pinned),
+            b"sessions" => match args.split_once(' ') {
+                _ if args.is_empty() => Ok(Command::Sessions(None)),
+                Some(("close", number)) => match number.trim().parse::<usize>() {
+                    Ok(number) => Ok(Command::Sessions(Some(number))),
+                    Err(_) => Err(CommandParseError::Other(format!(
+                        "session number must be a number from {}",
+                        Command::Sessions(None).display_cmd()
+                    ))),
+                },
+                _ if args == "close" => Err(CommandParseError::ArgumentExpected(
+                    "session number".to_string(),
+                )),
+                _ => Err(CommandParseError::Other(
+                    "sessions argument must be: close <n>".to_string(),
+                )),
+            },
+            b"votekick" => match args.split(' ').next() {
+                Some("") | None => {
+                    Err(CommandParseError::ArgumentExpected("user name".to_string()))
+                }
+                Some(user) => Ok(Command::Votekick(user.to_string())),
+            },
+            b"poll" => match args.parse::<PollCommand>() {
+                Ok(sub_cmd) => Ok(Command::Poll(sub_cmd)),
+                Err(err) => Err(err),
+            },
+            b"vote" => match args {
+                "" => Err(CommandParseError::ArgumentExpected(
+                    "option number".to_string(),
+                )),
+                _ => match args.parse::<usize>() {
+                    Ok(option) => Ok(Command::Vote(option)),
+                    Err(_) => Err(CommandParseError::Other(
+                        "option must be a number from the poll".to_string(),
+                    )),
+                },
+            },
+            b"sessions-of" => match args.split_whitespace().next() {
+                Some(user) => Ok(Command::SessionsOf(user.to_string())),
+                None => Err(CommandParseError::ArgumentExpected("user name".to_string())),
+            },
+            b"pin" => match args.is_empty() {
+                true => Err(CommandParseError::ArgumentExpected(
+                    "message to pin".to_string(),
+                )),
+                false => Ok(Command::Pin(args.to_string())),
+            },
+            b"unpin" => match args {
+                "" => Err(CommandParseError::ArgumentExpected(
+                    "pin number".to_string(),
+                )),
+                _ => match args.parse::<usize>() {
+                    Ok(number) => Ok(Command::Unpin(number)),
+                    Err(_) => Err(CommandParseError::Other(format!(
+                        "pin number must be a number from {}",
+                        Command::Pinned.display_cmd()
+                    ))),
+                },
+            },
+            b"prompt" => match args.is_empty() {
+                true => Ok(Command::Prompt(None)),
+                false => match args.parse::<PromptTemplate>() {
+                    Ok(template) => Ok(Command::Prompt(Some(template))),
+                    Err(err) => Err(CommandParseError::Other(err)),
+                },
+            },
+            b"themes" => match args {
+                "" => Ok(Command::Themes(false)),
+                "preview" => Ok(Command::Themes(true)),
+                _ => Err(CommandParseError::Other(
+                    "themes argument must be: preview".to_string(),
+                )),
+            },
+            b"banned" => Ok(Command::Banned),
+            b"requests" => Ok(Command::Requests),
+            b"clearhistory" => Ok(Command::ClearHistory),
+            b"mute-all" => Ok(Command::MuteAll),
+            b"unmute-all" => Ok(Command::UnmuteAll),
+            b"shutdown" => match args.is_empty() {
+                true => Err(CommandParseError::ArgumentExpected(
+                    "shutdown delay".to_string(),
+                )),
+                false => Ok(Command::Shutdown(args.to_string())),
+            },
+            b"away" => match args.is_empty() {
+                true => Ok(Command::Away(None)),
+                false => Ok(Command::Away(Some(args.to_string()))),
+            },
+            b"name" => match args.split(' ').next() {
                 Some(new_name) if !new_name.is_empty() => Ok(Command::Name(new_name.to_string())),
-                _ => Err(Self::Err::ArgumentExpected(format!("new name"))),
+                _ => Err(CommandParseError::ArgumentExpected("new name".to_string())),
+            },
+            b"ban" => {
+                let (query, dry_run) = match args.strip_prefix("--dry-run") {
+                    Some(query) if query.is_empty() || query.starts_with(' ') => {
+                        (query.trim_start(), true)
+                    }
+                    _ => (args, false),
+                };
+                match query.is_empty() {
+                    true => Err(CommandParseError::ArgumentExpected("ban query".to_string())),
+                    false => Ok(Command::Ban(query.to_string(), dry_run)),
+                }
+            }
+            b"unban" => match args.is_empty() {
+                true => Err(CommandParseError::ArgumentExpected(
+                    "unban query".to_string(),
+                )),
+                false => Ok(Command::Unban(args.to_string())),
             },
-            b"/ban" => match args.is_empty() {
-                true => Err(Self::Err::ArgumentExpected(format!("ban query"))),
-                false => Ok(Command::Ban(args.to_string())),
+            b"blocklist" => match args {
+                "reload" => Ok(Command::ReloadBlocklist),
+                "" => Err(CommandParseError::ArgumentExpected(
+                    "blocklist subcommand".to_string(),
+                )),
+                _ => Err(CommandParseError::Other(format!(
+                    "unknown blocklist subcommand: {}",
+                    args
+                ))),
             },
-            b"/whitelist" => match args.parse::<WhitelistCommand>() {
+            b"whitelist" => match args.parse::<WhitelistCommand>() {
                 Ok(sub_cmd) => Ok(Command::Whitelist(sub_cmd)),
                 Err(err) => Err(err),
             },
-            b"/oplist" => match args.parse::<OplistCommand>() {
+            b"oplist" => match args.parse::<OplistCommand>() {
                 Ok(sub_cmd) => Ok(Command::Oplist(sub_cmd)),
                 Err(err) => Err(err),
             },
-            b"/motd" => Ok(match args.is_empty() {
-                true => Command::Motd(None),
-                false => Command::Motd(Some(args.to_string())),
-            }),
-            b"/me" => match args.is_empty() {
+            b"motd" => match args.parse::<MotdCommand>() {
+                Ok(sub_cmd) => Ok(Command::Motd(sub_cmd)),
+                Err(err) => Err(err),
+            },
+            b"me" => match args.is_empty() {
                 true => Ok(Command::Me(None)),
                 false => Ok(Command::Me(Some(args.to_string()))),
             },
-            b"/reply" => match args.is_empty() {
-                true => Err(Self::Err::ArgumentExpected(format!("message body"))),
+            b"reply" => match args.is_empty() {
+                true => Err(CommandParseError::ArgumentExpected(
+                    "message body".to_string(),
+                )),
                 false => Ok(Command::Reply(args.to_string())),
             },
-            b"/whois" => match args.splitn(2, ' ').nth(0) {
-                Some(user) if user.is_empty() => {
-                    Err(Self::Err::ArgumentExpected(format!("user name")))
+            b"quote" => {
+                let mut iter = args.splitn(2, ' ');
+                let user = match iter.next() {
+                    Some(user) if !user.is_empty() => user.to_string(),
+                    _ => return Err(CommandParseError::ArgumentExpected("user name".to_string())),
+                };
+                match iter.next().map(str::trim_start) {
+                    Some(body) if !body.is_empty() => Ok(Command::Quote(user, body.to_string())),
+                    _ => Err(CommandParseError::ArgumentExpected(
+                        "message body".to_string(),
+                    )),
                 }
-                Some(user) => Ok(Command::Whois(user.to_string())),
-                None => unreachable!(), // splitn returns [""] for an empty input
+            }
+            b"whois" => match args.split(' ').next() {
+                Some("") => Ok(Command::Whois(None)),
+                Some(user) => Ok(Command::Whois(Some(user.to_string()))),
+                None => unreachable!(), // split returns [""] for an empty input
+            },
+            b"help" => match args.split(' ').next() {
+                Some("") => Ok(Command::Help(None)),
+                Some(cmd) => Ok(Command::Help(Some(cmd.to_string()))),
+                None => unreachable!(), // split returns [""] for an empty input
             },
-            b"/slap" => match args.splitn(2, ' ').nth(0) {
-                Some(user) if user.is_empty() => Ok(Command::Slap(None)),
+            b"slap" => match args.split(' ').next() {
+                Some("") => Ok(Command::Slap(None)),
                 Some(user) => Ok(Command::Slap(Some(user.to_string()))),
-                None => unreachable!(), // splitn returns [""] for an empty input
+                None => unreachable!(), // split returns [""] for an empty input
             },
-            b"/timestamp" => match args.splitn(2, ' ').nth(0) {
+            b"timestamp" => match args.split(' ').next() {
                 Some(mode) => match mode.parse::<TimestampMode>() {
                     Ok(parsed_mode) => Ok(Command::Timestamp(parsed_mode)),
-                    Err(_) => Err(Self::Err::Other(format!(
+                    Err(_) => Err(CommandParseError::Other(format!(
                         "timestamp mode value must be one of: {}",
                         TimestampMode::values().join(", ")
                     ))),
                 },
-                None => unreachable!(), // splitn returns [""] for an empty input
+                None => unreachable!(), // split returns [""] for an empty input
             },
-            b"/theme" => match args.splitn(2, ' ').nth(0) {
-                Some(theme) => match theme.parse::<Theme>() {
+            b"theme" => match args.split(' ').next() {
+                Some("preview") => Ok(Command::Themes(true)),
+                Some(theme) => match theme.parse::<ThemeChoice>() {
                     Ok(parsed_theme) => Ok(Command::Theme(parsed_theme)),
-                    Err(_) => Err(Self::Err::Other(format!(
+                    Err(_) => Err(CommandParseError::Other(format!(
                         "theme value must be one of: {}",
                         Theme::values().join(", ")
                     ))),
                 },
-                None => unreachable!(), // splitn returns [""] for an empty input
+                None => unreachable!(), // split returns [""] for an empty input
             },
-            b"/ignore" => match args.splitn(2, ' ').nth(0) {
-                Some(user) if user.is_empty() => Ok(Command::Ignore(None)),
+            b"ignore" => match args.split(' ').next() {
+                Some("") => Ok(Command::Ignore(None)),
                 Some(user) => Ok(Command::Ignore(Some(user.to_string()))),
-                None => unreachable!(), // splitn returns [""] for an empty input
+                None => unreachable!(), // split returns [""] for an empty input
             },
-            b"/unignore" => match args.splitn(2, ' ').nth(0) {
-                Some(user) if user.is_empty() => {
-                    Err(Self::Err::ArgumentExpected(format!("user name")))
-                }
+            b"unignore" => match args.split(' ').next() {
+                Some("") => Err(CommandParseError::ArgumentExpected("user name".to_string())),
                 Some(user) => Ok(Command::Unignore(user.to_string())),
-                None => unreachable!(), // splitn returns [""] for an empty input
+                None => unreachable!(), // split returns [""] for an empty input
             },
-            b"/focus" => match args.splitn(2, ' ').nth(0) {
-                Some(users) if users.is_empty() => Ok(Command::Focus(None)),
+            b"focus" => match args.split(' ').next() {
+                Some("") => Ok(Command::Focus(None)),
                 Some(users) => Ok(Command::Focus(Some(users.to_string()))),
-                None => unreachable!(), // splitn returns [""] for an empty input
+                None => unreachable!(), // split returns [""] for an empty input
             },
-            b"/mute" => match args.splitn(2, ' ').nth(0) {
-                Some(user) if user.is_empty() => {
-                    Err(Self::Err::ArgumentExpected(format!("user name")))
-                }
+            b"mute" => match args.split(' ').next() {
+                Some("") => Err(CommandParseError::ArgumentExpected("user name".to_string())),
                 Some(user) => Ok(Command::Mute(user.to_string())),
-                None => unreachable!(), // splitn returns [""] for an empty input
+                None => unreachable!(), // split returns [""] for an empty input
             },
-            b"/kick" => match args.splitn(2, ' ').nth(0) {
-                Some(user) if user.is_empty() => {
-                    Err(Self::Err::ArgumentExpected(format!("user name")))
-                }
-                Some(user) => Ok(Command::Kick(user.to_string())),
-                None => unreachable!(), // splitn returns [""] for an empty input
+            b"who-ip" => match args.split(' ').next() {
+                Some("") => Err(CommandParseError::ArgumentExpected(
+                    "IP address".to_string(),
+                )),
+                Some(ip) => Ok(Command::WhoIp(ip.to_string())),
+                None => unreachable!(), // split returns [""] for an empty input
             },
-            b"/msg" => {
+            b"kick" => {
                 let mut iter = args.splitn(2, ' ');
                 let user = match iter.next() {
                     Some(user) if !user.is_empty() => user.to_string(),
-                    _ => return Err(Self::Err::ArgumentExpected(format!("user name"))),
+                    _ => return Err(CommandParseError::ArgumentExpected("user name".to_string())),
+                };
+                let reason = iter
+                    .next()
+                    .map(str::trim)
+                    .filter(|reason| !reason.is_empty())
+                    .map(str::to_string);
+                Ok(Command::Kick(user, reason))
+            }
+            b"summon" => match args.split(' ').next() {
+                Some("") | None => {
+                    Err(CommandParseError::ArgumentExpected("user name".to_string()))
+                }
+                Some(user) => Ok(Command::Summon(user.to_string())),
+            },
+            b"veto" => match args.split(' ').next() {
+                Some("") | None => {
+                    Err(CommandParseError::ArgumentExpected("user name".to_string()))
+                }
+                Some(user) => Ok(Command::Veto(user.to_string())),
+            },
+            b"color" => {
+                let mut iter = args.split_whitespace();
+                let user = match iter.next() {
+                    Some(user) => user.to_string(),
+                    None => {
+                        return Err(CommandParseError::ArgumentExpected("user name".to_string()))
+                    }
                 };
+                let color = match iter.next() {
+                    Some(color) => color.to_string(),
+                    None => {
+                        return Err(CommandParseError::ArgumentExpected("hex color".to_string()))
+                    }
+                };
+                Ok(Command::Color(user, color))
+            }
+            b"msg" => {
+                let mut iter = args.splitn(2, ' ');
+                let mut users: Vec<String> = vec![];
+                for user in iter.next().unwrap_or_default().split(',') {
+                    if !user.is_empty() && !users.iter().any(|u| u == user) {
+                        users.push(user.to_string());
+                    }
+                }
+                if users.is_empty() {
+                    return Err(CommandParseError::ArgumentExpected("user name".to_string()));
+                }
                 let body = match iter.next() {
                     Some(body) if !body.is_empty() => body.trim_start().to_string(),
-                    _ => return Err(Self::Err::ArgumentExpected(format!("message body"))),
+                    _ => {
+                        return Err(CommandParseError::ArgumentExpected(
+                            "message body".to_string(),
+                        ))
+                    }
+                };
+                Ok(Command::Msg(users, body.trim_start().to_string()))
+            }
+            b"remind" => {
+                let mut iter = args.splitn(2, ' ');
+                let delay = match iter.next() {
+                    Some(delay) if !delay.is_empty() => delay.to_string(),
+                    _ => return Err(CommandParseError::ArgumentExpected("duration".to_string())),
+                };
+                let text = match iter.next().map(str::trim_start) {
+                    Some(text) if !text.is_empty() => text.to_string(),
+                    _ => {
+                        return Err(CommandParseError::ArgumentExpected(
+                            "reminder text".to_string(),
+                        ))
+                    }
                 };
-                Ok(Command::Msg(
-                    user.to_string(),
-                    body.trim_start().to_string(),
-                ))
+                Ok(Command::Remind(delay, text))
             }
-            _ => Err(Self::Err::UnknownCommand),
+            _ => Err(CommandParseError::UnknownCommand),
         }
     }
 }
@@ -309,6 +736,10 @@ impl CommandProps for Command {
     fn is_op(&self) -> bool {
         self.get_str("Op").unwrap_or_default() == "true"
     }
+
+    fn display_cmd(&self) -> String {
+        format!("{}{}", command_prefix(), self.cmd())
+    }
 }
 
 #[cfg(test)]
@@ -322,7 +753,15 @@ mod should {
 
     #[test]
     fn parse_help_command() {
-        assert_eq!("/help".parse::<Command>().unwrap(), Command::Help);
+        assert_eq!("/help".parse::<Command>().unwrap(), Command::Help(None));
+    }
+
+    #[test]
+    fn parse_help_command_with_args() {
+        assert_eq!(
+            "/help ban".parse::<Command>().unwrap(),
+            Command::Help(Some("ban".to_string()))
+        );
     }
 
     #[test]
@@ -335,14 +774,25 @@ mod should {
         assert_eq!("/uptime".parse::<Command>().unwrap(), Command::Uptime);
     }
 
+    #[test]
+    fn parse_ping_command() {
+        assert_eq!("/ping".parse::<Command>().unwrap(), Command::Ping);
+    }
+
     #[test]
     fn parse_back_command() {
         assert_eq!("/back".parse::<Command>().unwrap(), Command::Back);
     }
 
+    #[test]
+    fn parse_afk_command() {
+        assert_eq!("/afk".parse::<Command>().unwrap(), Command::Afk);
+    }
+
     #[test]
     fn parse_users_command() {
         assert_eq!("/users".parse::<Command>().unwrap(), Command::Users);
+        assert_eq!("/ops".parse::<Command>().unwrap(), Command::Ops);
     }
 
     #[test]
@@ -357,32 +807,54 @@ mod should {
 
     #[test]
     fn parse_themes_command() {
-        assert_eq!("/themes".parse::<Command>().unwrap(), Command::Themes);
+        assert_eq!(
+            "/themes".parse::<Command>().unwrap(),
+            Command::Themes(false)
+        );
+        assert_eq!(
+            "/themes preview".parse::<Command>().unwrap(),
+            Command::Themes(true)
+        );
+        assert_eq!(
+            "/theme preview".parse::<Command>().unwrap(),
+            Command::Themes(true)
+        );
     }
 
     #[test]
     fn parse_banned_command() {
         assert_eq!("/banned".parse::<Command>().unwrap(), Command::Banned);
+        assert_eq!("/requests".parse::<Command>().unwrap(), Command::Requests);
     }
 
     #[test]
-    fn parse_away_command_with_args() {
+    fn parse_blocklist_command() {
         assert_eq!(
-            "/away Out for lunch".parse::<Command>().unwrap(),
-            Command::Away("Out for lunch".to_string())
+            "/blocklist reload".parse::<Command>().unwrap(),
+            Command::ReloadBlocklist
         );
+        assert_eq!(
+            "/blocklist".parse::<Command>(),
+            Err(CommandParseError::ArgumentExpected(
+                "blocklist subcommand".to_string()
+            ))
+        );
+        assert!("/blocklist clear".parse::<Command>().is_err());
     }
 
     #[test]
-    fn fail_to_parse_away_command_without_args() {
+    fn parse_away_command_with_args() {
         assert_eq!(
-            "/away".parse::<Command>(),
-            Err(CommandParseError::ArgumentExpected(
-                "away reason".to_string()
-            ))
+            "/away Out for lunch".parse::<Command>().unwrap(),
+            Command::Away(Some("Out for lunch".to_string()))
         );
     }
 
+    #[test]
+    fn parse_away_command_without_args() {
+        assert_eq!("/away".parse::<Command>().unwrap(), Command::Away(None));
+    }
+
     #[test]
     fn parse_name_command() {
         assert_eq!(
@@ -403,7 +875,19 @@ mod should {
     fn parse_ban_command() {
         assert_eq!(
             "/ban spammer".parse::<Command>().unwrap(),
-            Command::Ban("spammer".to_string())
+            Command::Ban("spammer".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn parse_ban_command_in_dry_run() {
+        assert_eq!(
+            "/ban --dry-run name=spammer 1h".parse::<Command>().unwrap(),
+            Command::Ban("name=spammer 1h".to_string(), true)
+        );
+        assert_eq!(
+            "/ban --dry-run".parse::<Command>(),
+            Err(CommandParseError::ArgumentExpected("ban query".to_string()))
         );
     }
 
@@ -435,13 +919,24 @@ mod should {
     fn parse_motd_command_with_args() {
         assert_eq!(
             "/motd Welcome!".parse::<Command>().unwrap(),
-            Command::Motd(Some("Welcome!".to_string()))
+            Command::Motd(MotdCommand::Set("Welcome!".to_string()))
         );
     }
 
     #[test]
     fn parse_motd_command_without_args() {
-        assert_eq!("/motd".parse::<Command>().unwrap(), Command::Motd(None));
+        assert_eq!(
+            "/motd".parse::<Command>().unwrap(),
+            Command::Motd(MotdCommand::View)
+        );
+    }
+
+    #[test]
+    fn parse_motd_append_command() {
+        assert_eq!(
+            "/motd append Be nice".parse::<Command>().unwrap(),
+            Command::Motd(MotdCommand::Append("Be nice".to_string()))
+        );
     }
 
     #[test]
@@ -465,6 +960,24 @@ mod should {
         );
     }
 
+    #[test]
+    fn parse_quote_command() {
+        assert_eq!(
+            "/quote bob  me too".parse::<Command>().unwrap(),
+            Command::Quote("bob".to_string(), "me too".to_string())
+        );
+        assert_eq!(
+            "/quote".parse::<Command>(),
+            Err(CommandParseError::ArgumentExpected("user name".to_string()))
+        );
+        assert_eq!(
+            "/quote bob".parse::<Command>(),
+            Err(CommandParseError::ArgumentExpected(
+                "message body".to_string()
+            ))
+        );
+    }
+
     #[test]
     fn fail_to_parse_reply_command_without_args() {
         assert_eq!(
@@ -479,16 +992,134 @@ mod should {
     fn parse_whois_command() {
         assert_eq!(
             "/whois username".parse::<Command>().unwrap(),
-            Command::Whois("username".to_string())
+            Command::Whois(Some("username".to_string()))
         );
     }
 
     #[test]
-    fn fail_to_parse_whois_command_without_args() {
+    fn parse_whois_command_without_args() {
+        assert_eq!("/whois".parse::<Command>().unwrap(), Command::Whois(None));
+    }
+
+    #[test]
+    fn parse_command_with_custom_prefix() {
         assert_eq!(
-            "/whois".parse::<Command>(),
-            Err(CommandParseError::ArgumentExpected("user name".to_string()))
+            Command::parse_with_prefix("!me dances", "!"),
+            Ok(Command::Me(Some("dances".to_string())))
         );
+        assert_eq!(
+            Command::parse_with_prefix("!!exit", "!!"),
+            Ok(Command::Exit)
+        );
+        assert_eq!(
+            Command::parse_with_prefix("/exit", "!"),
+            Err(CommandParseError::NotRecognizedAsCommand)
+        );
+    }
+
+    #[test]
+    fn parse_stats_command() {
+        assert_eq!("/stats".parse::<Command>().unwrap(), Command::Stats);
+        assert_eq!("/settings".parse::<Command>().unwrap(), Command::Settings);
+        assert_eq!("/paste".parse::<Command>().unwrap(), Command::Paste);
+    }
+
+    #[test]
+    fn parse_who_ip_command() {
+        assert_eq!(
+            "/who-ip 10.0.0.1".parse::<Command>().unwrap(),
+            Command::WhoIp("10.0.0.1".to_string())
+        );
+        assert_eq!(
+            "/who-ip".parse::<Command>(),
+            Err(CommandParseError::ArgumentExpected(
+                "IP address".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_clearhistory_command() {
+        assert_eq!(
+            "/clearhistory".parse::<Command>().unwrap(),
+            Command::ClearHistory
+        );
+    }
+
+    #[test]
+    fn parse_mute_all_commands() {
+        assert_eq!("/mute-all".parse::<Command>().unwrap(), Command::MuteAll);
+        assert_eq!(
+            "/unmute-all".parse::<Command>().unwrap(),
+            Command::UnmuteAll
+        );
+    }
+
+    #[test]
+    fn parse_emoji_command() {
+        assert_eq!("/emoji".parse::<Command>().unwrap(), Command::Emoji);
+        assert_eq!("/receipts".parse::<Command>().unwrap(), Command::Receipts);
+    }
+
+    #[test]
+    fn parse_redraw_command() {
+        assert_eq!("/redraw".parse::<Command>().unwrap(), Command::Redraw);
+    }
+
+    #[test]
+    fn parse_pin_commands() {
+        assert_eq!("/pinned".parse::<Command>().unwrap(), Command::Pinned);
+        assert_eq!(
+            "/pin read the rules".parse::<Command>().unwrap(),
+            Command::Pin("read the rules".to_string())
+        );
+        assert_eq!(
+            "/pin".parse::<Command>(),
+            Err(CommandParseError::ArgumentExpected(
+                "message to pin".to_string()
+            ))
+        );
+        assert_eq!("/unpin 2".parse::<Command>().unwrap(), Command::Unpin(2));
+        assert!(matches!(
+            "/unpin two".parse::<Command>(),
+            Err(CommandParseError::Other(_))
+        ));
+    }
+
+    #[test]
+    fn parse_sessions_commands() {
+        assert_eq!(
+            "/sessions".parse::<Command>().unwrap(),
+            Command::Sessions(None)
+        );
+        assert_eq!(
+            "/sessions close 2".parse::<Command>().unwrap(),
+            Command::Sessions(Some(2))
+        );
+        assert_eq!(
+            "/sessions close".parse::<Command>(),
+            Err(CommandParseError::ArgumentExpected(
+                "session number".to_string()
+            ))
+        );
+        assert!(matches!(
+            "/sessions kill 2".parse::<Command>(),
+            Err(CommandParseError::Other(_))
+        ));
+        assert_eq!(
+            "/sessions-of alice".parse::<Command>().unwrap(),
+            Command::SessionsOf("alice".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_prompt_command() {
+        assert_eq!("/prompt".parse::<Command>().unwrap(), Command::Prompt(None));
+        assert_eq!(
+            "/prompt {name} ({users})".parse::<Command>().unwrap(),
+            Command::Prompt(Some("{name} ({users})".parse().unwrap()))
+        );
+        assert!("/prompt {nick}".parse::<Command>().is_err());
     }
 
     #[test]
@@ -510,6 +1141,10 @@ mod should {
             "/timestamp time".parse::<Command>().unwrap(),
             Command::Timestamp(TimestampMode::Time)
         );
+        assert_eq!(
+            "/timestamp relative".parse::<Command>().unwrap(),
+            Command::Timestamp(TimestampMode::Relative)
+        );
     }
 
     #[test]
@@ -517,7 +1152,7 @@ mod should {
         assert_eq!(
             "/timestamp invalid_mode".parse::<Command>(),
             Err(CommandParseError::Other(
-                "timestamp mode value must be one of: time, datetime, off".to_string()
+                "timestamp mode value must be one of: time, datetime, relative, off".to_string()
             ))
         );
     }
@@ -526,7 +1161,11 @@ mod should {
     fn parse_theme_command_with_valid_theme() {
         assert_eq!(
             "/theme colors".parse::<Command>().unwrap(),
-            Command::Theme(Theme::Colors)
+            Command::Theme(ThemeChoice::Named(Theme::Colors))
+        );
+        assert_eq!(
+            "/theme next".parse::<Command>().unwrap(),
+            Command::Theme(ThemeChoice::Next)
         );
     }
 
@@ -602,7 +1241,79 @@ mod should {
     fn parse_kick_command() {
         assert_eq!(
             "/kick user".parse::<Command>().unwrap(),
-            Command::Kick("user".to_string())
+            Command::Kick("user".to_string(), None)
+        );
+        assert_eq!(
+            "/kick user  stop spamming ".parse::<Command>().unwrap(),
+            Command::Kick("user".to_string(), Some("stop spamming".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_bell_command() {
+        assert_eq!("/bell on".parse::<Command>().unwrap(), Command::Bell(true));
+        assert_eq!(
+            "/bell off".parse::<Command>().unwrap(),
+            Command::Bell(false)
+        );
+        assert_eq!(
+            "/bell".parse::<Command>(),
+            Err(CommandParseError::ArgumentExpected("on|off".to_string()))
+        );
+        assert!("/bell loud".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn parse_poll_and_vote_commands() {
+        assert_eq!(
+            "/poll \"Lunch?\" pizza pasta".parse::<Command>().unwrap(),
+            Command::Poll(PollCommand::Start(
+                "Lunch?".to_string(),
+                vec!["pizza".to_string(), "pasta".to_string()]
+            ))
+        );
+        assert_eq!(
+            "/poll close".parse::<Command>().unwrap(),
+            Command::Poll(PollCommand::Close)
+        );
+        assert_eq!("/vote 2".parse::<Command>().unwrap(), Command::Vote(2));
+        assert_eq!(
+            "/vote".parse::<Command>(),
+            Err(CommandParseError::ArgumentExpected(
+                "option number".to_string()
+            ))
+        );
+        assert!(matches!(
+            "/vote pizza".parse::<Command>(),
+            Err(CommandParseError::Other(_))
+        ));
+    }
+
+    #[test]
+    fn parse_votekick_and_veto_commands() {
+        assert_eq!(
+            "/votekick user".parse::<Command>().unwrap(),
+            Command::Votekick("user".to_string())
+        );
+        assert_eq!(
+            "/veto user".parse::<Command>().unwrap(),
+            Command::Veto("user".to_string())
+        );
+        assert_eq!(
+            "/votekick".parse::<Command>(),
+            Err(CommandParseError::ArgumentExpected("user name".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_summon_command() {
+        assert_eq!(
+            "/summon user".parse::<Command>().unwrap(),
+            Command::Summon("user".to_string())
+        );
+        assert_eq!(
+            "/summon".parse::<Command>(),
+            Err(CommandParseError::ArgumentExpected("user name".to_string()))
         );
     }
 
@@ -618,7 +1329,38 @@ mod should {
     fn parse_msg_command_with_args() {
         assert_eq!(
             "/msg user Hello!".parse::<Command>().unwrap(),
-            Command::Msg("user".to_string(), "Hello!".to_string())
+            Command::Msg(vec!["user".to_string()], "Hello!".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_msg_command_with_many_users() {
+        assert_eq!(
+            "/msg alice,bob,,alice,carol, Hello!"
+                .parse::<Command>()
+                .unwrap(),
+            Command::Msg(
+                vec!["alice".to_string(), "bob".to_string(), "carol".to_string()],
+                "Hello!".to_string()
+            )
+        );
+        assert_eq!(
+            "/msg ,, Hello!".parse::<Command>(),
+            Err(CommandParseError::ArgumentExpected("user name".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_remind_command() {
+        assert_eq!(
+            "/remind 10m stand up".parse::<Command>().unwrap(),
+            Command::Remind("10m".to_string(), "stand up".to_string())
+        );
+        assert_eq!(
+            "/remind 10m".parse::<Command>(),
+            Err(CommandParseError::ArgumentExpected(
+                "reminder text".to_string()
+            ))
         );
     }
 
@@ -640,6 +1382,62 @@ mod should {
         );
     }
 
+    #[test]
+    fn parse_shutdown_command() {
+        assert_eq!(
+            "/shutdown 5m".parse::<Command>().unwrap(),
+            Command::Shutdown("5m".to_string())
+        );
+        assert_eq!(
+            "/shutdown".parse::<Command>(),
+            Err(CommandParseError::ArgumentExpected(
+                "shutdown delay".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_unban_command() {
+        assert_eq!(
+            "/unban name=alice".parse::<Command>().unwrap(),
+            Command::Unban("name=alice".to_string())
+        );
+    }
+
+    #[test]
+    fn fail_to_parse_unban_command_without_args() {
+        assert_eq!(
+            "/unban".parse::<Command>(),
+            Err(CommandParseError::ArgumentExpected(
+                "unban query".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_color_command_with_args() {
+        assert_eq!(
+            "/color bob #ff8800".parse::<Command>().unwrap(),
+            Command::Color("bob".to_string(), "#ff8800".to_string())
+        );
+    }
+
+    #[test]
+    fn fail_to_parse_color_command_without_color() {
+        assert_eq!(
+            "/color bob".parse::<Command>(),
+            Err(CommandParseError::ArgumentExpected("hex color".to_string()))
+        );
+    }
+
+    #[test]
+    fn fail_to_parse_color_command_without_args() {
+        assert_eq!(
+            "/color".parse::<Command>(),
+            Err(CommandParseError::ArgumentExpected("user name".to_string()))
+        );
+    }
+
     #[test]
     fn fail_to_parse_invalid_command() {
         assert_eq!(
@@ -6,6 +6,12 @@ pub trait CommandProps: Debug {
     fn help(&self) -> &str;
     fn is_op(&self) -> bool;
 
+    /// The command as typed by users. Top-level commands include the
+    /// command prefix
+    fn display_cmd(&self) -> String {
+        self.cmd().to_string()
+    }
+
     fn is_visible(&self) -> bool {
         !self.help().is_empty()
     }
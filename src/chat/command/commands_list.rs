@@ -78,10 +78,44 @@ pub fn format_commands<C: CommandProps>(commands: &Vec<C>) -> String {
     result_vec.join("")
 }
 
+/// Formats the usage line of a single command followed by its full
+/// help text
+pub fn format_command_details<C: CommandProps>(cmd: &C) -> String {
+    let usage = format!("{} {}", cmd.display_cmd(), cmd.args());
+    format!("{}{}{}", usage.trim_end(), utils::NEWLINE, cmd.help())
+}
+
+/// Finds the command closest to the mistyped name if it is at most two
+/// edits away, or one for very short names
+pub fn suggest_command<'a, C: CommandProps>(name: &str, commands: &'a [C]) -> Option<&'a C> {
+    let max_distance = if name.chars().count() <= 3 { 1 } else { 2 };
+    commands
+        .iter()
+        .map(|cmd| (edit_distance(name, cmd.cmd()), cmd))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, cmd)| cmd)
+}
+
+/// Levenshtein distance between two strings, counted in chars
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut prev = (0..=b.len()).collect::<Vec<usize>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[b.len()]
+}
+
 fn format_command<C: CommandProps>(cmd: &C, is_last: bool) -> String {
     format!(
         "{:<10} {:<20} {}{}",
-        cmd.cmd(),
+        cmd.display_cmd(),
         cmd.args(),
         cmd.help(),
         if is_last { "" } else { utils::NEWLINE }
@@ -131,6 +165,57 @@ mod should {
         assert_eq!(format_commands(&commands), expected);
     }
 
+    #[test]
+    fn format_command_details_correctly() {
+        let command = MockCommand {
+            cmd: "cmd1",
+            args: "args1",
+            help: "help1",
+            is_visible: true,
+            is_op: false,
+        };
+        assert_eq!(format_command_details(&command), "cmd1 args1\n\rhelp1");
+    }
+
+    #[test]
+    fn format_command_details_without_args() {
+        let command = MockCommand {
+            cmd: "cmd1",
+            args: "",
+            help: "help1",
+            is_visible: true,
+            is_op: false,
+        };
+        assert_eq!(format_command_details(&command), "cmd1\n\rhelp1");
+    }
+
+    #[test]
+    fn measure_edit_distance() {
+        assert_eq!(edit_distance("whois", "whois"), 0);
+        assert_eq!(edit_distance("whoi", "whois"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "msg"), 3);
+    }
+
+    #[test]
+    fn suggest_closest_command() {
+        let commands = ["whois", "whitelist", "ban", "banned"]
+            .into_iter()
+            .map(|cmd| MockCommand {
+                cmd,
+                args: "",
+                help: "",
+                is_visible: true,
+                is_op: false,
+            })
+            .collect::<Vec<MockCommand>>();
+        assert_eq!(suggest_command("whoi", &commands).unwrap().cmd, "whois");
+        assert_eq!(suggest_command("bnaned", &commands).unwrap().cmd, "banned");
+        assert_eq!(suggest_command("bam", &commands).unwrap().cmd, "ban");
+        assert!(suggest_command("xyz", &commands).is_none());
+        assert!(suggest_command("whitelisting", &commands).is_none());
+    }
+
     #[test]
     fn sort_by_command_length() {
         let mut commands = vec![
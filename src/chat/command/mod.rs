@@ -1,13 +1,19 @@
 mod command;
 mod command_props;
 mod commands_list;
+mod motd_command;
 mod oplist_command;
 mod parse_error;
+mod poll_command;
+mod prefix;
 mod whitelist_command;
 
 pub use command::*;
 pub use command_props::CommandProps;
 pub use commands_list::*;
+pub use motd_command::MotdCommand;
 pub use oplist_command::*;
 pub use parse_error::CommandParseError;
+pub use poll_command::PollCommand;
+pub use prefix::{command_prefix, set_command_prefix};
 pub use whitelist_command::*;
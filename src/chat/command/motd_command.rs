@@ -0,0 +1,107 @@
+use std::str::FromStr;
+
+use super::parse_error::CommandParseError;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum MotdCommand {
+    /// Print the current message of the day
+    #[default]
+    View,
+    /// Replace the message of the day with the text
+    Set(String),
+    /// Add a line after the existing message of the day
+    Append(String),
+    /// Add a line before the existing message of the day
+    Prepend(String),
+    /// Re-read the message of the day from the MOTD file
+    Reload,
+    /// Show the message of the day to users on join
+    On,
+    /// Stop showing the message of the day to users on join
+    Off,
+}
+
+impl FromStr for MotdCommand {
+    type Err = CommandParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Ok(Self::View);
+        }
+
+        let (cmd, args) = if let Some((cmd, args)) = s.split_once(' ') {
+            (cmd, args.trim())
+        } else {
+            (s, "")
+        };
+        match cmd.as_bytes() {
+            b"reload" if args.is_empty() => Ok(Self::Reload),
+            b"on" if args.is_empty() => Ok(Self::On),
+            b"off" if args.is_empty() => Ok(Self::Off),
+            b"append" => match args.is_empty() {
+                true => Err(Self::Err::ArgumentExpected("line to append".to_string())),
+                false => Ok(Self::Append(args.to_string())),
+            },
+            b"prepend" => match args.is_empty() {
+                true => Err(Self::Err::ArgumentExpected("line to prepend".to_string())),
+                false => Ok(Self::Prepend(args.to_string())),
+            },
+            _ => Ok(Self::Set(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn parse_view_command() {
+        assert_eq!("".parse::<MotdCommand>(), Ok(MotdCommand::View));
+    }
+
+    #[test]
+    fn parse_set_command() {
+        assert_eq!(
+            "Welcome to the chat!".parse::<MotdCommand>(),
+            Ok(MotdCommand::Set("Welcome to the chat!".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_append_and_prepend_commands() {
+        assert_eq!(
+            "append Be nice".parse::<MotdCommand>(),
+            Ok(MotdCommand::Append("Be nice".to_string()))
+        );
+        assert_eq!(
+            "prepend Hello!".parse::<MotdCommand>(),
+            Ok(MotdCommand::Prepend("Hello!".to_string()))
+        );
+    }
+
+    #[test]
+    fn fail_to_parse_append_without_line() {
+        assert_eq!(
+            "append".parse::<MotdCommand>(),
+            Err(CommandParseError::ArgumentExpected(
+                "line to append".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_reload_command() {
+        assert_eq!("reload".parse::<MotdCommand>(), Ok(MotdCommand::Reload));
+    }
+
+    #[test]
+    fn parse_on_off_commands() {
+        assert_eq!("on".parse::<MotdCommand>(), Ok(MotdCommand::On));
+        assert_eq!("off".parse::<MotdCommand>(), Ok(MotdCommand::Off));
+        assert_eq!(
+            "off duty".parse::<MotdCommand>(),
+            Ok(MotdCommand::Set("off duty".to_string()))
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use std::str::FromStr;
+
+use super::parse_error::CommandParseError;
+
+/// Maximum number of options a poll may offer
+const MAX_POLL_OPTIONS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum PollCommand {
+    /// Print the open poll with the votes so far
+    #[default]
+    View,
+    /// Open a poll with the question and the options to vote for
+    Start(String, Vec<String>),
+    /// Close the open poll and announce the results
+    Close,
+}
+
+impl FromStr for PollCommand {
+    type Err = CommandParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => return Ok(Self::View),
+            "close" => return Ok(Self::Close),
+            _ => {}
+        }
+
+        let mut words = split_quoted(s)?.into_iter();
+        let question = words.next().unwrap_or_default();
+        let options = words.collect::<Vec<String>>();
+        match options.len() {
+            0 | 1 => Err(Self::Err::ArgumentExpected(
+                "question and at least two options".to_string(),
+            )),
+            n if n > MAX_POLL_OPTIONS => Err(Self::Err::Other(format!(
+                "a poll may have at most {} options",
+                MAX_POLL_OPTIONS
+            ))),
+            _ => Ok(Self::Start(question, options)),
+        }
+    }
+}
+
+/// Splits the text into words, keeping the words in double quotes
+/// together, e.g. `"Lunch where?" "the usual" pizza`
+fn split_quoted(s: &str) -> Result<Vec<String>, CommandParseError> {
+    let mut words = vec![];
+    let mut rest = s.trim_start();
+    while !rest.is_empty() {
+        let (word, tail) = match rest.strip_prefix('"') {
+            Some(quoted) => match quoted.split_once('"') {
+                Some((word, tail)) => (word, tail),
+                None => return Err(CommandParseError::Other("unclosed quote".to_string())),
+            },
+            None => rest.split_once(' ').unwrap_or((rest, "")),
+        };
+        if !word.trim().is_empty() {
+            words.push(word.trim().to_string());
+        }
+        rest = tail.trim_start();
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn parse_view_and_close_commands() {
+        assert_eq!("".parse::<PollCommand>(), Ok(PollCommand::View));
+        assert_eq!("close".parse::<PollCommand>(), Ok(PollCommand::Close));
+    }
+
+    #[test]
+    fn parse_start_command_with_quoted_words() {
+        assert_eq!(
+            "\"Lunch where?\" \"the usual\" pizza".parse::<PollCommand>(),
+            Ok(PollCommand::Start(
+                "Lunch where?".to_string(),
+                vec!["the usual".to_string(), "pizza".to_string()]
+            ))
+        );
+        assert_eq!(
+            "Tabs? yes no".parse::<PollCommand>(),
+            Ok(PollCommand::Start(
+                "Tabs?".to_string(),
+                vec!["yes".to_string(), "no".to_string()]
+            ))
+        );
+    }
+
+    #[test]
+    fn fail_to_parse_start_command_without_options() {
+        assert!(matches!(
+            "\"Lunch where?\" pizza".parse::<PollCommand>(),
+            Err(CommandParseError::ArgumentExpected(_))
+        ));
+        assert!(matches!(
+            "\"Lunch where? pizza pasta".parse::<PollCommand>(),
+            Err(CommandParseError::Other(_))
+        ));
+        assert!(matches!(
+            format!("Count? {}", "n ".repeat(11)).parse::<PollCommand>(),
+            Err(CommandParseError::Other(_))
+        ));
+    }
+}
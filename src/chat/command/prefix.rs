@@ -0,0 +1,20 @@
+use std::sync::OnceLock;
+
+const DEFAULT_COMMAND_PREFIX: &str = "/";
+
+static COMMAND_PREFIX: OnceLock<String> = OnceLock::new();
+
+/// Sets the sigil that input must start with to be read as a command.
+/// It can only be set once, at startup, before any input is parsed
+pub fn set_command_prefix(prefix: &str) {
+    if COMMAND_PREFIX.set(prefix.to_string()).is_err() {
+        panic!("command prefix is already set");
+    }
+}
+
+pub fn command_prefix() -> &'static str {
+    COMMAND_PREFIX
+        .get()
+        .map(String::as_str)
+        .unwrap_or(DEFAULT_COMMAND_PREFIX)
+}
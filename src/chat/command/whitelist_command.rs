@@ -82,6 +82,13 @@ pub enum WhitelistCommand {
     ))]
     Save,
 
+    #[strum(props(
+        Cmd = "export",
+        Args = "<path>",
+        Help = "Write public keys to a new file at the path for backup"
+    ))]
+    Export(String),
+
     #[strum(props(Cmd = "reverify", Help = "Kick all users not in the whitelist"))]
     Reverify,
 
@@ -124,6 +131,10 @@ impl FromStr for WhitelistCommand {
                 ))),
                 false => Ok(Self::Remove(args.to_string())),
             },
+            b"export" => match args.is_empty() {
+                true => Err(Self::Err::ArgumentExpected("file path".to_string())),
+                false => Ok(Self::Export(args.to_string())),
+            },
             b"load" => match args.parse::<WhitelistLoadMode>() {
                 Ok(mode) => Ok(Self::Load(mode)),
                 Err(_) => Err(Self::Err::Other(format!(
@@ -255,6 +266,15 @@ mod whitelist_command_should {
         );
     }
 
+    #[test]
+    fn parse_export_command() {
+        let command = "export /tmp/keys.txt";
+        assert_eq!(
+            command.parse::<WhitelistCommand>(),
+            Ok(WhitelistCommand::Export("/tmp/keys.txt".to_string()))
+        );
+    }
+
     #[test]
     fn parse_status_command() {
         let command = "status";
@@ -5,6 +5,7 @@ pub struct Author {
     id: usize,
     username: UserName,
     is_muted: bool,
+    is_newcomer: bool,
 }
 
 pub type Recipient = Author;
@@ -21,6 +22,10 @@ impl Author {
     pub fn is_muted(&self) -> bool {
         self.is_muted
     }
+
+    pub fn is_newcomer(&self) -> bool {
+        self.is_newcomer
+    }
 }
 
 impl From<User> for Author {
@@ -29,6 +34,7 @@ impl From<User> for Author {
             id: user.id(),
             username: user.username().clone(),
             is_muted: user.is_muted(),
+            is_newcomer: user.is_newcomer(),
         }
     }
 }
@@ -39,6 +45,7 @@ impl From<&User> for Author {
             id: user.id(),
             username: user.username().clone(),
             is_muted: user.is_muted(),
+            is_newcomer: user.is_newcomer(),
         }
     }
 }
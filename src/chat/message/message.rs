@@ -2,7 +2,7 @@ use chrono::{DateTime, Utc};
 use enum_dispatch::enum_dispatch;
 
 use crate::chat::UserConfig;
-use crate::utils::{BEL, NULL};
+use crate::utils::{BEL, NEWLINE, NULL};
 
 use super::{Author, Recipient};
 
@@ -38,6 +38,53 @@ pub trait MessageFormatter: Clone + MessageBaseOps {
             self.format(cfg)
         )
     }
+
+    /// Prefixes a message replayed from the history with its age as of
+    /// `now`, e.g. `[5m ago]`. Ages over a minute are rounded down to
+    /// whole minutes
+    fn format_replayed(&self, cfg: &UserConfig, now: DateTime<Utc>) -> String {
+        let secs = now
+            .signed_duration_since(self.message_created_at())
+            .num_seconds()
+            .max(0) as u64;
+        let secs = match secs {
+            secs if secs >= 60 => secs - secs % 60,
+            secs => secs,
+        };
+        let age = format!(
+            "[{} ago]",
+            humantime::format_duration(std::time::Duration::from_secs(secs))
+        );
+        format!(
+            "{} {}",
+            cfg.theme().style_system_text(&age),
+            self.format(cfg)
+        )
+    }
+
+    /// Prefixes the message with the time elapsed between `since` and its
+    /// creation as `+HH:MM:SS`, or `-HH:MM:SS` for messages created before
+    fn format_with_elapsed(&self, cfg: &UserConfig, since: DateTime<Utc>) -> String {
+        let elapsed = self.message_created_at().signed_duration_since(since);
+        let sign = if elapsed < chrono::Duration::zero() {
+            '-'
+        } else {
+            '+'
+        };
+        let secs = elapsed.num_seconds().abs();
+        let timestamp = format!(
+            "{}{:02}:{:02}:{:02}",
+            sign,
+            secs / 3600,
+            secs % 3600 / 60,
+            secs % 60
+        );
+        format!(
+            "{} {}",
+            cfg.theme().style_system_text(&timestamp),
+            self.format(cfg)
+        )
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -62,16 +109,42 @@ impl MessageBaseOps for MessageBase {
     }
 }
 
+/// Longest quoted text in characters, longer ones are cut off
+const QUOTE_MAX_CHARS: usize = 60;
+
+/// Message of another user shown above a public message sent with
+/// `/quote`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quote {
+    author: String,
+    text: String,
+}
+
+impl Quote {
+    pub fn new(author: String, text: String) -> Self {
+        let text = match text.chars().count() > QUOTE_MAX_CHARS {
+            true => format!(
+                "{}…",
+                text.chars().take(QUOTE_MAX_CHARS - 1).collect::<String>()
+            ),
+            false => text,
+        };
+        Self { author, text }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Public {
     base: MessageBase,
     from: Author,
+    quote: Option<Quote>,
 }
 
 impl Public {
     pub fn new(from: Author, body: String) -> Self {
         Self {
             from,
+            quote: None,
             base: MessageBase {
                 body,
                 created_at: Utc::now(),
@@ -79,6 +152,14 @@ impl Public {
         }
     }
 
+    /// Creates a message shown under the quoted one
+    pub fn new_quoting(from: Author, quote: Quote, body: String) -> Self {
+        Self {
+            quote: Some(quote),
+            ..Self::new(from, body)
+        }
+    }
+
     pub fn from(&self) -> &Author {
         &self.from
     }
@@ -105,8 +186,32 @@ impl MessageFormatter for Public {
             }
         }
 
+        let quote = match &self.quote {
+            Some(quote) => format!(
+                "{} {}: {}{}",
+                cfg.theme().style_system_text(">"),
+                cfg.theme().style_username(&quote.author),
+                cfg.theme().style_system_text(&quote.text),
+                NEWLINE
+            ),
+            None => String::new(),
+        };
         let username = cfg.theme().style_username(self.from.username().as_ref());
-        format!("{}: {}", username, message)
+        format!(
+            "{}{}{}: {}",
+            quote,
+            username,
+            newcomer_marker(&self.from, cfg),
+            message
+        )
+    }
+}
+
+/// Marks the author as a newcomer after their name, if they are one
+fn newcomer_marker(author: &Author, cfg: &UserConfig) -> String {
+    match author.is_newcomer() {
+        true => format!(" {}", cfg.theme().style_newcomer_marker()),
+        false => String::new(),
     }
 }
 
@@ -151,8 +256,9 @@ impl MessageBaseOps for Private {
 impl MessageFormatter for Private {
     fn format(&self, cfg: &UserConfig) -> String {
         format!(
-            "[PM from {}] {}{}",
+            "[PM from {}{}] {}{}",
             cfg.theme().style_username(self.from.username().as_ref()),
+            newcomer_marker(&self.from, cfg),
             cfg.theme().style_text(&self.message_body()),
             if cfg.bell() {
                 BEL // emit bell sound in recipient's terminal
@@ -167,12 +273,16 @@ impl MessageFormatter for Private {
 pub struct Emote {
     base: MessageBase,
     from: Author,
+    target: Option<usize>,
+    highlighted: bool,
 }
 
 impl Emote {
     pub fn new(from: Author, body: String) -> Self {
         Self {
             from,
+            target: None,
+            highlighted: false,
             base: MessageBase {
                 body,
                 created_at: Utc::now(),
@@ -180,9 +290,30 @@ impl Emote {
         }
     }
 
+    /// Creates an emote aimed at another user, e.g. `/slap`. The target
+    /// gets a highlighted copy so they notice it
+    pub fn new_targeted(from: Author, target: usize, body: String) -> Self {
+        Self {
+            target: Some(target),
+            ..Self::new(from, body)
+        }
+    }
+
     pub fn from(&self) -> &Author {
         &self.from
     }
+
+    pub fn target(&self) -> Option<usize> {
+        self.target
+    }
+
+    /// Returns the copy of the emote rendered for its target
+    pub fn highlighted(&self) -> Self {
+        Self {
+            highlighted: true,
+            ..self.clone()
+        }
+    }
 }
 
 impl MessageBaseOps for Emote {
@@ -197,8 +328,28 @@ impl MessageBaseOps for Emote {
 
 impl MessageFormatter for Emote {
     fn format(&self, cfg: &UserConfig) -> String {
-        let text = format!(" ** {} {}", self.from.username(), &self.message_body());
-        cfg.theme().style_text(&text).to_string()
+        let marker = match self.from.is_newcomer() {
+            true => " (new)",
+            false => "",
+        };
+        let text = format!(
+            " ** {}{} {}",
+            self.from.username(),
+            marker,
+            &self.message_body()
+        );
+        if !self.highlighted {
+            return cfg.theme().style_text(&text).to_string();
+        }
+        format!(
+            "{}{}",
+            cfg.theme().style_tagged_username(&text),
+            if cfg.bell() {
+                BEL // emit bell sound in target's terminal
+            } else {
+                NULL
+            }
+        )
     }
 }
 
@@ -236,8 +387,12 @@ impl MessageBaseOps for Announce {
 
 impl MessageFormatter for Announce {
     fn format(&self, cfg: &UserConfig) -> String {
-        let text = format!(" * {} {}", self.from.username(), &self.message_body());
-        cfg.theme().style_system_text(&text).to_string()
+        let text = format!(" {} {}", self.from.username(), &self.message_body());
+        format!(
+            " {}{}",
+            cfg.theme().style_announce("*"),
+            cfg.theme().style_system_text(&text)
+        )
     }
 }
 
@@ -275,8 +430,12 @@ impl MessageBaseOps for System {
 
 impl MessageFormatter for System {
     fn format(&self, cfg: &UserConfig) -> String {
-        let text = format!("-> {}", &self.message_body());
-        cfg.theme().style_system_text(&text).to_string()
+        let text = format!(" {}", &self.message_body());
+        format!(
+            "{}{}",
+            cfg.theme().style_system("->"),
+            cfg.theme().style_system_text(&text)
+        )
     }
 }
 
@@ -314,8 +473,12 @@ impl MessageBaseOps for Error {
 
 impl MessageFormatter for Error {
     fn format(&self, cfg: &UserConfig) -> String {
-        let text = format!("-> Error: {}", &self.message_body());
-        cfg.theme().style_system_text(&text).to_string()
+        let text = format!(" {}", &self.message_body());
+        format!(
+            "{}{}",
+            cfg.theme().style_error("-> Error:"),
+            cfg.theme().style_system_text(&text)
+        )
     }
 }
 
@@ -415,6 +578,17 @@ mod should {
         );
     }
 
+    #[test]
+    fn format_quoting_public_message_correctly() {
+        let quote = Quote::new("bob".to_string(), "x".repeat(100));
+        let msg = Public::new_quoting(mock_author(), quote, "me too".to_string());
+        let formatted_msg = msg.format(&mock_user_config());
+        let (quoted, message) = formatted_msg.split_once(NEWLINE).unwrap();
+        assert!(quoted.contains("bob"));
+        assert!(quoted.contains(&format!("{}…", "x".repeat(QUOTE_MAX_CHARS - 1))));
+        assert_eq!(message, "\u{1b}[38;2;104;128;66malice\u{1b}[39m: me too");
+    }
+
     #[test]
     fn format_private_message_correctly() {
         let author = mock_author();
@@ -437,6 +611,22 @@ mod should {
         assert_eq!(formatted_msg, "\u{1b}[38;5;15m ** alice waves\u{1b}[39m");
     }
 
+    #[test]
+    fn format_targeted_emote_message_for_target_correctly() {
+        let author = mock_author();
+        let msg = Emote::new_targeted(author.clone(), 1, "slaps bob".to_string());
+        let cfg = mock_user_config();
+        assert_eq!(msg.target(), Some(1));
+        assert_eq!(
+            msg.format(&cfg),
+            "\u{1b}[38;5;15m ** alice slaps bob\u{1b}[39m"
+        );
+        assert_eq!(
+            msg.highlighted().format(&cfg),
+            "\u{1b}[48;5;3m\u{1b}[38;5;0m\u{1b}[1m ** alice slaps bob\u{1b}[0m\u{7}"
+        );
+    }
+
     #[test]
     fn format_announce_message_correctly() {
         let author = mock_author();
@@ -445,7 +635,7 @@ mod should {
         let formatted_msg = msg.format(&cfg);
         assert_eq!(
             formatted_msg,
-            "\u{1b}[38;5;8m * alice announcement\u{1b}[39m"
+            " \u{1b}[38;5;3m*\u{1b}[39m\u{1b}[38;5;8m alice announcement\u{1b}[39m"
         );
     }
 
@@ -455,7 +645,10 @@ mod should {
         let msg = System::new(author.clone(), "system message".to_string());
         let cfg = mock_user_config();
         let formatted_msg = msg.format(&cfg);
-        assert_eq!(formatted_msg, "\u{1b}[38;5;8m-> system message\u{1b}[39m");
+        assert_eq!(
+            formatted_msg,
+            "\u{1b}[38;5;14m->\u{1b}[39m\u{1b}[38;5;8m system message\u{1b}[39m"
+        );
     }
 
     #[test]
@@ -466,7 +659,7 @@ mod should {
         let formatted_msg = msg.format(&cfg);
         assert_eq!(
             formatted_msg,
-            "\u{1b}[38;5;8m-> Error: error occurred\u{1b}[39m"
+            "\u{1b}[38;5;9m-> Error:\u{1b}[39m\u{1b}[38;5;8m error occurred\u{1b}[39m"
         );
     }
 
@@ -494,4 +687,41 @@ mod should {
         let formatted_msg = msg.format_with_timestamp(&cfg, "%Y-%m-%d %H:%M:%S");
         assert_eq!(formatted_msg, "\u{1b}[38;5;8m2024-07-19 12:34:56\u{1b}[39m \u{1b}[38;2;104;128;66malice\u{1b}[39m: hello world");
     }
+
+    #[test]
+    fn format_message_with_elapsed_time() {
+        let author = mock_author();
+        let joined_at = Utc.with_ymd_and_hms(2024, 7, 19, 12, 0, 0).unwrap();
+
+        let mut msg = Public::new(author.clone(), "hello world".to_string());
+        msg.base.created_at = Utc.with_ymd_and_hms(2024, 7, 19, 13, 3, 12).unwrap();
+
+        let cfg = mock_user_config();
+        let formatted_msg = msg.format_with_elapsed(&cfg, joined_at);
+        assert_eq!(
+            formatted_msg,
+            "\u{1b}[38;5;8m+01:03:12\u{1b}[39m \u{1b}[38;2;104;128;66malice\u{1b}[39m: hello world"
+        );
+
+        msg.base.created_at = Utc.with_ymd_and_hms(2024, 7, 19, 11, 59, 30).unwrap();
+        let formatted_msg = msg.format_with_elapsed(&cfg, joined_at);
+        assert!(formatted_msg.starts_with("\u{1b}[38;5;8m-00:00:30\u{1b}[39m "));
+    }
+
+    #[test]
+    fn format_replayed_message_with_its_age() {
+        let author = mock_author();
+        let mut msg = Public::new(author.clone(), "hello world".to_string());
+        msg.base.created_at = Utc.with_ymd_and_hms(2024, 7, 19, 12, 0, 0).unwrap();
+
+        let cfg = mock_user_config();
+        let now = Utc.with_ymd_and_hms(2024, 7, 19, 12, 5, 42).unwrap();
+        assert!(msg
+            .format_replayed(&cfg, now)
+            .starts_with("\u{1b}[38;5;8m[5m ago]\u{1b}[39m "));
+        let now = Utc.with_ymd_and_hms(2024, 7, 19, 12, 0, 42).unwrap();
+        assert!(msg
+            .format_replayed(&cfg, now)
+            .starts_with("\u{1b}[38;5;8m[42s ago]\u{1b}[39m "));
+    }
 }
@@ -1,6 +1,6 @@
 use circular_buffer::CircularBuffer;
 
-use super::message::Message;
+use super::message::{Message, Public};
 
 #[derive(Clone)]
 pub struct MessageHistory<const SIZE: usize> {
@@ -18,6 +18,18 @@ impl<const SIZE: usize> MessageHistory<SIZE> {
         self.buf.push_back(message)
     }
 
+    pub fn clear(&mut self) {
+        self.buf.clear()
+    }
+
+    /// Finds the latest public message sent by the user
+    pub fn last_public_from(&self, username: &str) -> Option<&Public> {
+        self.buf.iter().rev().find_map(|message| match message {
+            Message::Public(msg) if msg.from().username().as_ref() == username => Some(msg),
+            _ => None,
+        })
+    }
+
     pub fn iter(&self) -> circular_buffer::Iter<Message> {
         self.buf.iter()
     }
@@ -102,6 +114,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_clear() {
+        let mut history = MessageHistory::<5>::new();
+        history.push(get_test_system_message("Spam").into());
+        history.clear();
+        assert_eq!(
+            history.iter().count(),
+            0,
+            "Buffer should be empty after clear"
+        );
+    }
+
     #[test]
     fn test_iterate_empty() {
         let history = MessageHistory::<1>::new();
@@ -6,5 +6,9 @@ pub mod message;
 pub mod ratelimit;
 
 pub use command::*;
-pub use room::ChatRoom;
+pub use room::{
+    countdown_step, ChatRoom, FirstJoinWelcome, LeaveReason, LookalikeNameMode, MotdThrottle, Pin,
+    PrivateDelivery, ScheduledShutdown, SingleSessionMode, VoteKickTally, VoteKickThreshold,
+    DEFAULT_MOTD_MAX_LEN,
+};
 pub use user::*;
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+
+use crate::utils;
+
+type UserId = usize;
+
+const DEFAULT_MAX_STRIKES: usize = 3;
+const DEFAULT_STRIKE_WINDOW: Duration = Duration::from_secs(600);
+
+lazy_static::lazy_static! {
+    static ref RE_INVISIBLE: Regex = Regex::new(r"[\p{M}\p{Cf}]").unwrap();
+}
+
+#[derive(Debug)]
+struct StrikeEntry {
+    count: usize,
+    first_at: Instant,
+}
+
+/// Outcome of a message checked against the blocklist
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlocklistVerdict {
+    /// The message has no blocked words
+    Clean,
+    /// The message is dropped and the sender got a strike out of the
+    /// allowed number
+    Strike(usize, usize),
+    /// The sender ran out of strikes and should be kicked
+    Kick,
+}
+
+/// Drops messages containing blocked words and counts strikes against
+/// the senders within a window
+#[derive(Debug)]
+pub struct Blocklist {
+    words: Vec<String>,
+    path: Option<String>,
+    max_strikes: usize,
+    window: Duration,
+    strikes: HashMap<UserId, StrikeEntry>,
+}
+
+impl Default for Blocklist {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_STRIKES, DEFAULT_STRIKE_WINDOW)
+    }
+}
+
+impl Blocklist {
+    pub fn new(max_strikes: usize, window: Duration) -> Self {
+        Self {
+            words: vec![],
+            path: None,
+            max_strikes,
+            window,
+            strikes: HashMap::new(),
+        }
+    }
+
+    pub fn set_max_strikes(&mut self, max_strikes: usize) {
+        self.max_strikes = max_strikes;
+    }
+
+    pub fn set_words(&mut self, words: &[&str]) {
+        self.words = words
+            .iter()
+            .map(|word| normalize(word))
+            .filter(|word| !word.is_empty())
+            .collect();
+    }
+
+    /// Loads blocked words from the file, one per line, and remembers
+    /// its path for later reloads. Returns the number of words loaded
+    pub fn load_file(&mut self, path: &str) -> std::io::Result<usize> {
+        let content = utils::fs::read_file_to_string(path)?;
+        self.set_words(&content.lines().collect::<Vec<_>>());
+        self.path = Some(path.to_string());
+        Ok(self.words.len())
+    }
+
+    /// Re-reads the file the words were loaded from. Returns `None` if
+    /// the blocklist did not come from a file
+    pub fn reload(&mut self) -> Option<std::io::Result<usize>> {
+        let path = self.path.clone()?;
+        Some(self.load_file(&path))
+    }
+
+    pub fn contains_blocked(&self, body: &str) -> bool {
+        if self.words.is_empty() {
+            return false;
+        }
+        let body = normalize(body);
+        self.words.iter().any(|word| body.contains(word.as_str()))
+    }
+
+    /// Checks the message body sent by the user and counts a strike if
+    /// it contains a blocked word. A threshold of zero never kicks
+    pub fn check(&mut self, user_id: UserId, body: &str) -> BlocklistVerdict {
+        if !self.contains_blocked(body) {
+            return BlocklistVerdict::Clean;
+        }
+
+        let now = Instant::now();
+        let entry = self.strikes.entry(user_id).or_insert(StrikeEntry {
+            count: 0,
+            first_at: now,
+        });
+        if now - entry.first_at > self.window {
+            entry.count = 0;
+            entry.first_at = now;
+        }
+        entry.count += 1;
+
+        match entry.count {
+            count if self.max_strikes > 0 && count >= self.max_strikes => {
+                self.strikes.remove(&user_id);
+                BlocklistVerdict::Kick
+            }
+            count => BlocklistVerdict::Strike(count, self.max_strikes),
+        }
+    }
+
+    pub fn forget(&mut self, user_id: &UserId) {
+        self.strikes.remove(user_id);
+    }
+
+    /// Number of users with strikes
+    pub fn tracked_users(&self) -> usize {
+        self.strikes.len()
+    }
+}
+
+/// Folds the text so that case, fullwidth forms, combining marks and
+/// invisible format characters can't be used to slip past the list
+fn normalize(text: &str) -> String {
+    let folded = text
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            c => c,
+        })
+        .collect::<String>()
+        .to_lowercase();
+    RE_INVISIBLE.replace_all(&folded, "").to_string()
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    fn blocklist(max_strikes: usize) -> Blocklist {
+        let mut blocklist = Blocklist::new(max_strikes, Duration::from_secs(60));
+        blocklist.set_words(&["spam", "Scam", ""]);
+        blocklist
+    }
+
+    #[test]
+    fn match_case_insensitively() {
+        let blocklist = blocklist(3);
+        assert!(blocklist.contains_blocked("buy SPAM now"));
+        assert!(blocklist.contains_blocked("it's a scam"));
+        assert!(!blocklist.contains_blocked("hello there"));
+    }
+
+    #[test]
+    fn match_through_unicode_tricks() {
+        let blocklist = blocklist(3);
+        assert!(blocklist.contains_blocked("ｓｐａｍ"));
+        assert!(blocklist.contains_blocked("sp\u{200b}am"));
+        assert!(blocklist.contains_blocked("s\u{0301}pam"));
+    }
+
+    #[test]
+    fn kick_after_max_strikes() {
+        let mut blocklist = blocklist(2);
+        assert_eq!(blocklist.check(1, "hi"), BlocklistVerdict::Clean);
+        assert_eq!(blocklist.check(1, "spam"), BlocklistVerdict::Strike(1, 2));
+        assert_eq!(blocklist.check(2, "spam"), BlocklistVerdict::Strike(1, 2));
+        assert_eq!(blocklist.check(1, "spam"), BlocklistVerdict::Kick);
+    }
+
+    #[test]
+    fn reset_strikes_after_window_passes() {
+        let mut blocklist = Blocklist::new(2, Duration::from_millis(10));
+        blocklist.set_words(&["spam"]);
+        assert_eq!(blocklist.check(1, "spam"), BlocklistVerdict::Strike(1, 2));
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(blocklist.check(1, "spam"), BlocklistVerdict::Strike(1, 2));
+    }
+
+    #[test]
+    fn never_kick_when_threshold_is_zero() {
+        let mut blocklist = blocklist(0);
+        for count in 1..=5 {
+            assert_eq!(
+                blocklist.check(1, "spam"),
+                BlocklistVerdict::Strike(count, 0)
+            );
+        }
+    }
+
+    #[test]
+    fn reload_words_from_file() {
+        use assert_fs::fixture::{FileWriteStr, PathChild};
+
+        let mut blocklist = Blocklist::default();
+        assert!(blocklist.reload().is_none());
+
+        let temp = assert_fs::TempDir::new().unwrap();
+        let file = temp.child("blocklist.txt");
+        file.write_str("spam\n\nscam\n").unwrap();
+        let path = file.path().to_str().unwrap();
+        assert_eq!(blocklist.load_file(path).unwrap(), 2);
+
+        file.write_str("phishing").unwrap();
+        assert_eq!(blocklist.reload().unwrap().unwrap(), 1);
+        assert!(!blocklist.contains_blocked("spam"));
+        assert!(blocklist.contains_blocked("phishing link"));
+    }
+}
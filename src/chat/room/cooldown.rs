@@ -0,0 +1,124 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+type UserId = usize;
+
+/// Noisy commands that get a cooldown unless configured otherwise
+const DEFAULT_COOLDOWNS: [(&str, Duration); 3] = [
+    ("slap", Duration::from_secs(5)),
+    ("me", Duration::from_secs(2)),
+    ("shrug", Duration::from_secs(2)),
+];
+
+/// Minimum time between two uses of a command by the same user, so
+/// noisy commands can't be spammed within the message rate limit
+#[derive(Debug)]
+pub struct CommandCooldowns {
+    cooldowns: HashMap<String, Duration>,
+    last_used: HashMap<(UserId, String), Instant>,
+}
+
+impl Default for CommandCooldowns {
+    fn default() -> Self {
+        Self {
+            cooldowns: DEFAULT_COOLDOWNS
+                .iter()
+                .map(|(cmd, cooldown)| (cmd.to_string(), *cooldown))
+                .collect(),
+            last_used: HashMap::new(),
+        }
+    }
+}
+
+impl CommandCooldowns {
+    /// Sets the cooldown of the command. Zero removes it
+    pub fn set_cooldown(&mut self, cmd: &str, cooldown: Duration) {
+        match cooldown.is_zero() {
+            true => self.cooldowns.remove(cmd),
+            false => self.cooldowns.insert(cmd.to_string(), cooldown),
+        };
+    }
+
+    /// Registers a use of the command by the user and returns the time
+    /// left if the command is still cooling down from the previous use.
+    /// Uses during the cooldown don't restart it
+    pub fn check(&mut self, user_id: UserId, cmd: &str) -> Option<Duration> {
+        self.check_at(user_id, cmd, Instant::now())
+    }
+
+    fn check_at(&mut self, user_id: UserId, cmd: &str, now: Instant) -> Option<Duration> {
+        let cooldown = *self.cooldowns.get(cmd)?;
+        let key = (user_id, cmd.to_string());
+        if let Some(used_at) = self.last_used.get(&key) {
+            let elapsed = now.saturating_duration_since(*used_at);
+            if elapsed < cooldown {
+                return Some(cooldown - elapsed);
+            }
+        }
+        self.last_used.insert(key, now);
+        None
+    }
+
+    pub fn forget(&mut self, user_id: &UserId) {
+        self.last_used.retain(|(id, _), _| id != user_id);
+    }
+
+    /// Number of users with a command still on cooldown record
+    pub fn tracked_users(&self) -> usize {
+        self.last_used
+            .keys()
+            .map(|(id, _)| id)
+            .collect::<HashSet<&UserId>>()
+            .len()
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn allow_command_again_once_cooldown_passes() {
+        let mut cooldowns = CommandCooldowns::default();
+        let now = Instant::now();
+        assert_eq!(cooldowns.check_at(1, "slap", now), None);
+
+        let almost = now + Duration::from_millis(4999);
+        assert_eq!(
+            cooldowns.check_at(1, "slap", almost),
+            Some(Duration::from_millis(1))
+        );
+        assert_eq!(cooldowns.check_at(2, "slap", almost), None);
+
+        let after = now + Duration::from_secs(5);
+        assert_eq!(cooldowns.check_at(1, "slap", after), None);
+        assert!(cooldowns.check_at(1, "slap", after).is_some());
+    }
+
+    #[test]
+    fn skip_commands_without_cooldown() {
+        let mut cooldowns = CommandCooldowns::default();
+        let now = Instant::now();
+        assert_eq!(cooldowns.check_at(1, "users", now), None);
+        assert_eq!(cooldowns.check_at(1, "users", now), None);
+
+        cooldowns.set_cooldown("slap", Duration::ZERO);
+        assert_eq!(cooldowns.check_at(1, "slap", now), None);
+        assert_eq!(cooldowns.check_at(1, "slap", now), None);
+
+        cooldowns.set_cooldown("users", Duration::from_secs(1));
+        assert_eq!(cooldowns.check_at(1, "users", now), None);
+        assert!(cooldowns.check_at(1, "users", now).is_some());
+    }
+
+    #[test]
+    fn forget_user_cooldowns() {
+        let mut cooldowns = CommandCooldowns::default();
+        let now = Instant::now();
+        cooldowns.check_at(1, "slap", now);
+        cooldowns.check_at(2, "slap", now);
+        cooldowns.forget(&1);
+        assert_eq!(cooldowns.check_at(1, "slap", now), None);
+        assert!(cooldowns.check_at(2, "slap", now).is_some());
+    }
+}
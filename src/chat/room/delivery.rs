@@ -0,0 +1,10 @@
+/// Outcome of sending a private message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PrivateDelivery {
+    /// The recipient got the message
+    Delivered,
+    /// The recipient ignores the author, so the message was dropped
+    Ignored,
+    /// The author is muted, so the message was not sent
+    Muted,
+}
@@ -0,0 +1,50 @@
+use std::fmt::Display;
+
+use crate::chat::user::UserName;
+
+/// Why a user left the room. Carried from wherever the session is closed
+/// to the departure announce
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum LeaveReason {
+    /// The user left on their own or their connection dropped
+    #[default]
+    Quit,
+    /// An operator, if named, or a room policy kicked the user
+    Kicked(Option<UserName>),
+    /// The operator banned the user
+    Banned(UserName),
+    /// The user was inactive for too long
+    Idle,
+    /// The server is shutting down
+    ServerShutdown,
+}
+
+impl LeaveReason {
+    /// Text announced to the room after the user name
+    pub fn announce(&self) -> String {
+        match self {
+            LeaveReason::Quit => "left".to_string(),
+            LeaveReason::Kicked(None) => "was kicked from the server".to_string(),
+            LeaveReason::Kicked(Some(by)) => format!("was kicked from the server by {}", by),
+            LeaveReason::Banned(by) => format!("was banned from the server by {}", by),
+            LeaveReason::Idle => "was disconnected for being idle".to_string(),
+            LeaveReason::ServerShutdown => "left as the server shut down".to_string(),
+        }
+    }
+}
+
+impl Display for LeaveReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                LeaveReason::Quit => "quit",
+                LeaveReason::Kicked(_) => "kicked",
+                LeaveReason::Banned(_) => "banned",
+                LeaveReason::Idle => "idle",
+                LeaveReason::ServerShutdown => "server shutdown",
+            }
+        )
+    }
+}
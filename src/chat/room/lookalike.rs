@@ -0,0 +1,27 @@
+use std::fmt::Display;
+
+use strum::EnumString;
+
+/// Describes how the room handles a name that looks like the name of
+/// another member, e.g. with a Cyrillic letter in place of a Latin one
+#[derive(Debug, Clone, Copy, PartialEq, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum LookalikeNameMode {
+    /// Allow the name and warn the room about the resemblance
+    Warn,
+    /// Refuse the name
+    Reject,
+}
+
+impl Display for LookalikeNameMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                LookalikeNameMode::Warn => "warn",
+                LookalikeNameMode::Reject => "reject",
+            }
+        )
+    }
+}
@@ -1,24 +1,66 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 use chrono::{DateTime, Utc};
+use log::debug;
+use tokio::sync::mpsc::error::{SendError, TrySendError};
 use tokio::sync::{mpsc, watch};
 
+use super::leave_reason::LeaveReason;
+
 use crate::chat::message::{self, Message, MessageFormatter};
-use crate::chat::user::User;
+use crate::chat::user::{TimestampMode, User};
+
+/// Maximum number of reminders a member can have pending at once
+const MAX_REMINDERS: usize = 5;
+
+/// Maximum number of unseen PMs tracked for read receipts
+const MAX_UNSEEN_PMS: usize = 20;
+
+/// Minimum time between two summons of the same member
+const SUMMON_INTERVAL: Duration = Duration::from_secs(30);
 
 #[derive(Clone)]
 pub struct RoomMember {
     pub user: User,
     message_tx: mpsc::Sender<String>,
-    exit_tx: watch::Sender<()>,
+    exit_tx: watch::Sender<LeaveReason>,
+    unread_tx: watch::Sender<usize>,
+    prompt_tx: watch::Sender<String>,
+    dropped: Arc<AtomicUsize>,
+    reminders: Arc<AtomicUsize>,
     last_sent_at: Option<DateTime<Utc>>,
+    last_input_at: Instant,
+    sent_count: usize,
+    leave_note: Option<String>,
+    summoned_at: Option<Instant>,
+    unseen_pms: VecDeque<usize>,
+    dead: bool,
 }
 
 impl RoomMember {
-    pub fn new(user: User, message_tx: mpsc::Sender<String>, exit_tx: watch::Sender<()>) -> Self {
+    pub fn new(
+        user: User,
+        message_tx: mpsc::Sender<String>,
+        exit_tx: watch::Sender<LeaveReason>,
+    ) -> Self {
         Self {
             user,
             message_tx,
             exit_tx,
+            unread_tx: watch::channel(0).0,
+            prompt_tx: watch::channel(String::new()).0,
+            dropped: Arc::new(AtomicUsize::new(0)),
+            reminders: Arc::new(AtomicUsize::new(0)),
             last_sent_at: None,
+            last_input_at: Instant::now(),
+            sent_count: 0,
+            leave_note: None,
+            summoned_at: None,
+            unseen_pms: VecDeque::new(),
+            dead: false,
         }
     }
 
@@ -30,16 +72,218 @@ impl RoomMember {
         self.last_sent_at = Some(time);
     }
 
-    pub fn exit(&self) -> Result<(), watch::error::SendError<()>> {
-        self.exit_tx.send(())
+    /// Number of messages sent by the member this session
+    pub fn sent_count(&self) -> usize {
+        self.sent_count
+    }
+
+    pub fn count_sent_message(&mut self) {
+        self.sent_count += 1;
+    }
+
+    /// Time since the member last sent a message, or since they joined
+    /// if they haven't sent any
+    pub fn idle_duration(&self) -> Duration {
+        match self.last_sent_at {
+            Some(time) => {
+                let secs = Utc::now().signed_duration_since(time).num_seconds() as u64;
+                Duration::from_secs(secs)
+            }
+            None => self.user.joined_duration(),
+        }
+    }
+
+    /// Notes that the member typed something, a message or not
+    pub fn register_input(&mut self) {
+        self.last_input_at = Instant::now();
+    }
+
+    /// Time since the member last typed anything, or since they joined
+    pub fn input_idle_duration(&self) -> Duration {
+        self.last_input_at.elapsed()
+    }
+
+    /// Number of private messages received since the member last sent a
+    /// message
+    #[cfg(test)]
+    pub fn unread_count(&self) -> usize {
+        *self.unread_tx.borrow()
+    }
+
+    pub fn subscribe_unread(&self) -> watch::Receiver<usize> {
+        self.unread_tx.subscribe()
+    }
+
+    pub fn subscribe_prompt(&self) -> watch::Receiver<String> {
+        self.prompt_tx.subscribe()
+    }
+
+    /// Updates the prompt the member's session shows, waking it only
+    /// if the prompt changed
+    pub fn set_prompt(&self, prompt: String) {
+        self.prompt_tx.send_if_modified(|current| {
+            let changed = *current != prompt;
+            *current = prompt;
+            changed
+        });
+    }
+
+    pub fn add_unread(&self) {
+        self.unread_tx.send_modify(|count| *count += 1);
     }
 
-    pub async fn send_message(&self, msg: Message) -> Result<(), mpsc::error::SendError<String>> {
-        let message = match self.user.config().timestamp_mode().format() {
-            Some(fmt) => msg.format_with_timestamp(&self.user.config(), fmt),
-            None => msg.format(&self.user.config()),
-        };
-        self.message_tx.send(message).await
+    pub fn clear_unread(&self) {
+        self.unread_tx.send_if_modified(|count| {
+            let changed = *count != 0;
+            *count = 0;
+            changed
+        });
+    }
+
+    /// Text added to the departure announce, e.g. why an operator
+    /// kicked the member
+    pub fn leave_note(&self) -> Option<&String> {
+        self.leave_note.as_ref()
+    }
+
+    pub fn set_leave_note(&mut self, note: String) {
+        self.leave_note = Some(note);
+    }
+
+    /// Remembers a PM from the sender waiting to be seen. The oldest
+    /// one is forgotten once too many are waiting
+    pub fn track_unseen_pm(&mut self, sender_id: usize) {
+        if self.unseen_pms.len() >= MAX_UNSEEN_PMS {
+            self.unseen_pms.pop_front();
+        }
+        self.unseen_pms.push_back(sender_id);
+    }
+
+    /// Returns the senders of the PMs waiting to be seen, oldest first,
+    /// with the number of PMs from each
+    pub fn take_unseen_pms(&mut self) -> Vec<(usize, usize)> {
+        let mut senders: Vec<(usize, usize)> = vec![];
+        for sender_id in self.unseen_pms.drain(..) {
+            match senders.iter_mut().find(|(id, _)| *id == sender_id) {
+                Some((_, count)) => *count += 1,
+                None => senders.push((sender_id, 1)),
+            }
+        }
+        senders
+    }
+
+    /// Registers a summon of the member and returns the time left if
+    /// they were summoned too recently
+    pub fn register_summon(&mut self) -> Option<Duration> {
+        let now = Instant::now();
+        if let Some(left) = self
+            .summoned_at
+            .and_then(|at| SUMMON_INTERVAL.checked_sub(now.duration_since(at)))
+        {
+            return Some(left);
+        }
+        self.summoned_at = Some(now);
+        None
+    }
+
+    /// Whether the member's session was found gone and is being removed
+    pub fn is_dead(&self) -> bool {
+        self.dead
+    }
+
+    pub fn mark_dead(&mut self) {
+        self.dead = true;
+    }
+
+    /// Closes the member's session, which leaves the room for the reason
+    pub fn exit(&self, reason: LeaveReason) -> Result<(), watch::error::SendError<LeaveReason>> {
+        self.exit_tx.send(reason)
+    }
+
+    /// Queues the message to the member without waiting, so a slow
+    /// client can't hold up the room. Messages that don't fit into a full
+    /// buffer are dropped, and the member is told how many once there is
+    /// room again
+    pub async fn send_message(&self, msg: Message) -> Result<(), SendError<String>> {
+        let message = self.format(&msg);
+
+        if self.dropped.load(Ordering::Relaxed) > 0 && self.message_tx.capacity() > 1 {
+            let dropped = self.dropped.swap(0, Ordering::Relaxed);
+            let warning = message::Error::new(
+                self.user.clone().into(),
+                match dropped {
+                    1 => "1 message was dropped because your connection is slow".to_string(),
+                    n => format!(
+                        "{} messages were dropped because your connection is slow",
+                        n
+                    ),
+                },
+            );
+            self.try_send(self.format(&warning.into()))?;
+        }
+
+        self.try_send(message)
+    }
+
+    /// Queues a message replayed from the history, prefixed with its age
+    /// instead of the member's timestamp
+    pub fn send_replayed_message(
+        &self,
+        msg: &Message,
+        now: DateTime<Utc>,
+    ) -> Result<(), SendError<String>> {
+        self.try_send(msg.format_replayed(self.user.config(), now))
+    }
+
+    fn format(&self, msg: &Message) -> String {
+        let cfg = self.user.config();
+        match cfg.timestamp_mode() {
+            TimestampMode::Relative => msg.format_with_elapsed(cfg, self.user.joined_at()),
+            mode => match mode.format() {
+                Some(fmt) => msg.format_with_timestamp(cfg, fmt),
+                None => msg.format(cfg),
+            },
+        }
+    }
+
+    fn try_send(&self, message: String) -> Result<(), SendError<String>> {
+        match self.message_tx.try_send(message) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                debug!(
+                    "Dropped a message to user {} with a full buffer ({} so far)",
+                    self.user.id(),
+                    dropped
+                );
+                Ok(())
+            }
+            Err(TrySendError::Closed(message)) => Err(SendError(message)),
+        }
+    }
+
+    /// Delivers the message to the member after the delay from a
+    /// background task. The reminder is dropped if the session closes
+    /// first. Returns false if too many reminders are already pending
+    pub fn schedule_reminder(&self, delay: Duration, msg: Message) -> bool {
+        if self.reminders.fetch_add(1, Ordering::Relaxed) >= MAX_REMINDERS {
+            self.reminders.fetch_sub(1, Ordering::Relaxed);
+            return false;
+        }
+
+        let member = self.clone();
+        let mut exit_rx = self.exit_tx.subscribe();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {
+                    let _ = member.send_message(msg).await;
+                }
+                _ = exit_rx.changed() => {}
+                _ = member.message_tx.closed() => {}
+            }
+            member.reminders.fetch_sub(1, Ordering::Relaxed);
+        });
+        true
     }
 
     pub async fn send_user_is_muted_message(&self) -> Result<(), mpsc::error::SendError<String>> {
@@ -64,7 +308,7 @@ mod should {
     #[tokio::test]
     async fn create_room_member() {
         let (message_tx, _message_rx) = mpsc::channel(1);
-        let (_exit_tx, _exit_rx) = watch::channel(());
+        let (_exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let user = User::default();
 
         let room_member = RoomMember::new(user.clone(), message_tx, _exit_tx);
@@ -76,7 +320,7 @@ mod should {
     #[tokio::test]
     async fn update_last_sent_time() {
         let (message_tx, _message_rx) = mpsc::channel(1);
-        let (_exit_tx, _exit_rx) = watch::channel(());
+        let (_exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let user = User::default();
         let mut room_member = RoomMember::new(user.clone(), message_tx, _exit_tx);
 
@@ -86,10 +330,24 @@ mod should {
         assert_eq!(*room_member.last_sent_time(), Some(now));
     }
 
+    #[tokio::test]
+    async fn count_sent_messages_and_idle_time() {
+        let (message_tx, _message_rx) = mpsc::channel(1);
+        let (_exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut room_member = RoomMember::new(User::default(), message_tx, _exit_tx);
+        assert_eq!(room_member.sent_count(), 0);
+
+        room_member.update_last_sent_time(Utc::now() - chrono::Duration::seconds(90));
+        room_member.count_sent_message();
+        room_member.count_sent_message();
+        assert_eq!(room_member.sent_count(), 2);
+        assert_eq!(room_member.idle_duration(), Duration::from_secs(90));
+    }
+
     #[tokio::test]
     async fn send_message() {
         let (message_tx, mut message_rx) = mpsc::channel(1);
-        let (_exit_tx, _exit_rx) = watch::channel(());
+        let (_exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let user = User::default();
         let room_member = RoomMember::new(user.clone(), message_tx, _exit_tx);
 
@@ -104,7 +362,7 @@ mod should {
     #[tokio::test]
     async fn send_message_with_timestamp() {
         let (message_tx, mut message_rx) = mpsc::channel(1);
-        let (_exit_tx, _exit_rx) = watch::channel(());
+        let (_exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut user = User::default();
         user.config_mut().set_timestamp_mode(TimestampMode::Time);
         let room_member = RoomMember::new(user.clone(), message_tx, _exit_tx);
@@ -123,7 +381,7 @@ mod should {
     #[tokio::test]
     async fn send_user_is_muted_message() {
         let (message_tx, mut message_rx) = mpsc::channel(1);
-        let (_exit_tx, _exit_rx) = watch::channel(());
+        let (_exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let user = User::default();
         let room_member = RoomMember::new(user.clone(), message_tx, _exit_tx);
 
@@ -134,16 +392,114 @@ mod should {
         assert!(received_message.contains("You are muted and cannot send messages."));
     }
 
+    #[tokio::test]
+    async fn drop_messages_when_buffer_is_full() {
+        let (message_tx, mut message_rx) = mpsc::channel(2);
+        let (_exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let room_member = RoomMember::new(User::default(), message_tx, _exit_tx);
+
+        for body in ["one", "two", "three", "four"] {
+            let msg = message::System::new(User::default().into(), body.to_string());
+            assert!(room_member.send_message(msg.into()).await.is_ok());
+        }
+        assert!(message_rx.recv().await.unwrap().contains("one"));
+        assert!(message_rx.recv().await.unwrap().contains("two"));
+
+        let msg = message::System::new(User::default().into(), "five".to_string());
+        assert!(room_member.send_message(msg.into()).await.is_ok());
+        assert!(message_rx
+            .recv()
+            .await
+            .unwrap()
+            .contains("2 messages were dropped"));
+        assert!(message_rx.recv().await.unwrap().contains("five"));
+    }
+
+    #[tokio::test]
+    async fn deliver_reminder_after_delay() {
+        let (message_tx, mut message_rx) = mpsc::channel(10);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let room_member = RoomMember::new(User::default(), message_tx, exit_tx);
+
+        let msg = message::System::new(User::default().into(), "Reminder: tea".to_string());
+        assert!(room_member.schedule_reminder(Duration::from_millis(10), msg.into()));
+        assert!(message_rx.try_recv().is_err());
+
+        let received_message = tokio::time::timeout(Duration::from_secs(1), message_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(received_message.contains("Reminder: tea"));
+    }
+
+    #[tokio::test]
+    async fn drop_reminders_when_session_exits() {
+        let (message_tx, mut message_rx) = mpsc::channel(10);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let room_member = RoomMember::new(User::default(), message_tx, exit_tx);
+
+        let msg: Message = message::System::new(User::default().into(), "tea".to_string()).into();
+        for _ in 0..MAX_REMINDERS {
+            assert!(room_member.schedule_reminder(Duration::from_millis(50), msg.clone()));
+        }
+        assert!(!room_member.schedule_reminder(Duration::from_millis(50), msg.clone()));
+
+        room_member.exit(LeaveReason::Quit).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(message_rx.try_recv().is_err());
+        assert!(room_member.schedule_reminder(Duration::from_millis(50), msg));
+    }
+
     #[tokio::test]
     async fn exit() {
         let (_message_tx, _message_rx) = mpsc::channel(1);
-        let (exit_tx, exit_rx) = watch::channel(());
+        let (exit_tx, exit_rx) = watch::channel(LeaveReason::default());
         let user = User::default();
         let room_member = RoomMember::new(user.clone(), _message_tx, exit_tx);
 
-        let result = room_member.exit();
+        let result = room_member.exit(LeaveReason::Kicked(None));
 
         assert!(result.is_ok());
-        assert_eq!(*exit_rx.borrow(), ());
+        assert_eq!(*exit_rx.borrow(), LeaveReason::Kicked(None));
+    }
+
+    #[tokio::test]
+    async fn count_and_clear_unread() {
+        let (message_tx, _message_rx) = mpsc::channel(1);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let room_member = RoomMember::new(User::default(), message_tx, exit_tx);
+        let mut unread_rx = room_member.subscribe_unread();
+
+        room_member.add_unread();
+        room_member.add_unread();
+        assert_eq!(room_member.unread_count(), 2);
+        assert!(unread_rx.has_changed().unwrap());
+        assert_eq!(*unread_rx.borrow_and_update(), 2);
+
+        room_member.clear_unread();
+        assert_eq!(room_member.unread_count(), 0);
+        assert!(unread_rx.has_changed().unwrap());
+        unread_rx.borrow_and_update();
+
+        room_member.clear_unread();
+        assert!(!unread_rx.has_changed().unwrap());
+    }
+
+    #[test]
+    fn group_unseen_pms_by_sender_within_bound() {
+        let (message_tx, _message_rx) = mpsc::channel(1);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut room_member = RoomMember::new(User::default(), message_tx, exit_tx);
+
+        room_member.track_unseen_pm(1);
+        for _ in 0..MAX_UNSEEN_PMS {
+            room_member.track_unseen_pm(2);
+        }
+        room_member.track_unseen_pm(3);
+        assert_eq!(
+            room_member.take_unseen_pms(),
+            vec![(2, MAX_UNSEEN_PMS - 1), (3, 1)]
+        );
+        assert!(room_member.take_unseen_pms().is_empty());
     }
 }
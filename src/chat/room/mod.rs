@@ -1,4 +1,32 @@
+mod blocklist;
+mod cooldown;
+mod delivery;
+mod leave_reason;
+mod lookalike;
 mod member;
+mod motd_throttle;
+mod newcomers;
+mod offline;
+mod pins;
+mod poll;
+mod reconnect;
+mod repeat_filter;
 mod room;
+mod seen_file;
+mod session_mode;
+mod shutdown;
+mod sizes;
+mod slow_mode;
+mod votekick;
+mod welcome;
 
-pub use room::ChatRoom;
+pub use delivery::PrivateDelivery;
+pub use leave_reason::LeaveReason;
+pub use lookalike::LookalikeNameMode;
+pub use motd_throttle::MotdThrottle;
+pub use pins::Pin;
+pub use room::{ChatRoom, DEFAULT_MOTD_MAX_LEN};
+pub use session_mode::SingleSessionMode;
+pub use shutdown::{countdown_step, ScheduledShutdown};
+pub use votekick::{VoteKickTally, VoteKickThreshold};
+pub use welcome::FirstJoinWelcome;
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use super::seen_file::SeenFile;
+
+/// Remembers when the message of the day was last shown to each key so
+/// returning users only see it once per interval. When a seen file is
+/// set, the times are saved to it so they survive restarts
+#[derive(Debug)]
+pub struct MotdThrottle {
+    interval: Duration,
+    last_shown: HashMap<String, DateTime<Utc>>,
+    seen_file: Option<SeenFile>,
+}
+
+impl MotdThrottle {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_shown: HashMap::new(),
+            seen_file: None,
+        }
+    }
+
+    /// Loads the last shown times from the file, one `<fingerprint>
+    /// <rfc3339 time>` pair per line, and keeps saving them to it.
+    /// Malformed lines are skipped
+    pub fn load_file(&mut self, path: &str) -> std::io::Result<()> {
+        let (file, content) = SeenFile::open(path)?;
+        for line in content.lines() {
+            let entry = line.trim().split_once(' ').and_then(|(fp, time)| {
+                let time = DateTime::parse_from_rfc3339(time.trim()).ok()?;
+                Some((fp.to_string(), time.with_timezone(&Utc)))
+            });
+            self.last_shown.extend(entry);
+        }
+        self.seen_file = Some(file);
+        Ok(())
+    }
+
+    /// Checks if the MOTD is due for the key and, if so, records it as
+    /// shown now
+    pub fn register(&mut self, fingerprint: &str) -> std::io::Result<bool> {
+        self.register_at(fingerprint, Utc::now())
+    }
+
+    fn register_at(&mut self, fingerprint: &str, now: DateTime<Utc>) -> std::io::Result<bool> {
+        if let Some(shown_at) = self.last_shown.get(fingerprint) {
+            let elapsed = now
+                .signed_duration_since(*shown_at)
+                .to_std()
+                .unwrap_or_default();
+            if elapsed < self.interval {
+                return Ok(false);
+            }
+        }
+        self.last_shown.insert(fingerprint.to_string(), now);
+        self.save()?;
+        Ok(true)
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let file = match &self.seen_file {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        let content = self
+            .last_shown
+            .iter()
+            .map(|(fp, time)| format!("{} {}\n", fp, time.to_rfc3339()))
+            .collect::<String>();
+        file.overwrite(&content)
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::chat::room::seen_file::temp_seen_file;
+
+    #[test]
+    fn show_motd_once_per_interval() {
+        let mut throttle = MotdThrottle::new(Duration::from_secs(3600));
+        let now = Utc::now();
+        assert!(throttle.register_at("SHA256:abc", now).unwrap());
+        assert!(!throttle.register_at("SHA256:abc", now).unwrap());
+        assert!(throttle.register_at("SHA256:def", now).unwrap());
+
+        let later = now + chrono::Duration::hours(2);
+        assert!(throttle.register_at("SHA256:abc", later).unwrap());
+    }
+
+    #[test]
+    fn remember_last_shown_across_restarts() {
+        let (_temp_dir, path) = temp_seen_file();
+        let open = || {
+            let mut throttle = MotdThrottle::new(Duration::from_secs(3600));
+            throttle.load_file(&path).unwrap();
+            throttle
+        };
+
+        let mut throttle = open();
+        assert!(throttle.register("SHA256:abc").unwrap());
+
+        let mut throttle = open();
+        assert!(!throttle.register("SHA256:abc").unwrap());
+        assert!(throttle.register("SHA256:def").unwrap());
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::seen_file::SeenFile;
+
+/// Remembers when each key first joined, so that users who joined for
+/// the first time within the window can be told apart from regulars.
+/// Keys past the window only keep their fingerprint. When a seen file
+/// is set, first joins are appended to it so they survive restarts
+#[derive(Debug)]
+pub struct Newcomers {
+    window: Duration,
+    first_seen: HashMap<String, SystemTime>,
+    regulars: HashSet<String>,
+    seen_file: Option<SeenFile>,
+}
+
+impl Newcomers {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            first_seen: HashMap::new(),
+            regulars: HashSet::new(),
+            seen_file: None,
+        }
+    }
+
+    /// Number of keys still within the window of their first join
+    pub fn tracked_keys(&self) -> usize {
+        self.first_seen.len()
+    }
+
+    /// Loads first joins from the file, one `<fingerprint> <unix seconds>`
+    /// per line, and keeps appending new ones to it
+    pub fn load_seen_file(&mut self, path: &str) -> std::io::Result<()> {
+        let (file, content) = SeenFile::open(path)?;
+        self.load_seen(&content, SystemTime::now());
+        self.seen_file = Some(file);
+        Ok(())
+    }
+
+    /// Registers a join of the key and tells if it is a newcomer. Keys
+    /// already known from before, e.g. from the welcome seen file, are
+    /// never newcomers
+    pub fn register(&mut self, fingerprint: &str, is_known: bool) -> std::io::Result<bool> {
+        let now = SystemTime::now();
+        let is_first_join = !is_known && !self.has_seen(fingerprint);
+        let is_newcomer = self.register_at(fingerprint, is_known, now);
+        if let (true, Some(file)) = (is_first_join, &self.seen_file) {
+            let secs = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            file.append(&format!("{} {}", fingerprint, secs))?;
+        }
+        Ok(is_newcomer)
+    }
+
+    fn has_seen(&self, fingerprint: &str) -> bool {
+        self.first_seen.contains_key(fingerprint) || self.regulars.contains(fingerprint)
+    }
+
+    fn register_at(&mut self, fingerprint: &str, is_known: bool, now: SystemTime) -> bool {
+        self.prune(now);
+        if self.regulars.contains(fingerprint) {
+            return false;
+        }
+        match self.first_seen.contains_key(fingerprint) {
+            true => true,
+            false if is_known => false,
+            false => {
+                self.first_seen.insert(fingerprint.to_string(), now);
+                true
+            }
+        }
+    }
+
+    fn load_seen(&mut self, content: &str, now: SystemTime) {
+        for line in content.lines() {
+            let mut words = line.split_whitespace();
+            let (Some(fingerprint), Some(secs)) = (words.next(), words.next()) else {
+                continue;
+            };
+            let Ok(secs) = secs.parse::<u64>() else {
+                continue;
+            };
+            let first_seen = UNIX_EPOCH + Duration::from_secs(secs);
+            self.first_seen.insert(fingerprint.to_string(), first_seen);
+        }
+        self.prune(now);
+    }
+
+    /// Moves the keys whose first join is older than the window to the
+    /// regulars
+    fn prune(&mut self, now: SystemTime) {
+        let window = self.window;
+        let is_expired =
+            |first_seen: &SystemTime| now.duration_since(*first_seen).unwrap_or_default() >= window;
+        let expired = self
+            .first_seen
+            .iter()
+            .filter(|(_, first_seen)| is_expired(first_seen))
+            .map(|(fingerprint, _)| fingerprint.clone())
+            .collect::<Vec<String>>();
+        for fingerprint in expired {
+            self.first_seen.remove(&fingerprint);
+            self.regulars.insert(fingerprint);
+        }
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::chat::room::seen_file::temp_seen_file;
+    use crate::utils;
+
+    #[test]
+    fn flag_keys_within_window_of_first_join() {
+        let mut newcomers = Newcomers::new(Duration::from_secs(3600));
+        let now = SystemTime::now();
+        assert!(newcomers.register_at("key", false, now));
+        assert!(newcomers.register_at("key", false, now + Duration::from_secs(60)));
+        assert!(!newcomers.register_at("key", false, now + Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn never_flag_known_keys() {
+        let mut newcomers = Newcomers::new(Duration::from_secs(3600));
+        let now = SystemTime::now();
+        assert!(!newcomers.register_at("key", true, now));
+        assert!(!newcomers.register_at("key", true, now));
+    }
+
+    #[test]
+    fn prune_keys_past_the_window() {
+        let mut newcomers = Newcomers::new(Duration::from_secs(3600));
+        let now = SystemTime::now();
+        newcomers.register_at("old", false, now);
+        newcomers.register_at("new", false, now + Duration::from_secs(1800));
+        assert_eq!(newcomers.tracked_keys(), 2);
+
+        assert!(newcomers.register_at("new", false, now + Duration::from_secs(3600)));
+        assert_eq!(newcomers.tracked_keys(), 1);
+        assert!(!newcomers.register_at("old", false, now + Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn remember_first_joins_across_restarts() {
+        let (_temp_dir, path) = temp_seen_file();
+        let open = || {
+            let mut newcomers = Newcomers::new(Duration::from_secs(3600));
+            newcomers.load_seen_file(&path).unwrap();
+            newcomers
+        };
+
+        let mut newcomers = open();
+        assert!(newcomers.register("SHA256:abc", false).unwrap());
+
+        let mut newcomers = open();
+        assert_eq!(newcomers.tracked_keys(), 1);
+        assert!(newcomers.register("SHA256:abc", false).unwrap());
+
+        let mut newcomers = Newcomers::new(Duration::from_secs(60));
+        let later = SystemTime::now() + Duration::from_secs(60);
+        newcomers.load_seen(&utils::fs::read_file_to_string(&path).unwrap(), later);
+        assert_eq!(newcomers.tracked_keys(), 0);
+        assert!(!newcomers.register_at("SHA256:abc", false, later));
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Maximum number of messages queued for a single key
+const MAX_QUEUED_PER_KEY: usize = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OfflineMessage {
+    pub from: String,
+    pub text: String,
+    pub queued_at: Instant,
+}
+
+/// Private messages to trusted users who are offline, kept by the key
+/// fingerprint until they join again or the messages expire
+#[derive(Debug)]
+pub struct OfflineMessages {
+    ttl: Duration,
+    queues: HashMap<String, VecDeque<OfflineMessage>>,
+}
+
+impl OfflineMessages {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            queues: HashMap::new(),
+        }
+    }
+
+    pub fn queue(&mut self, fingerprint: &str, from: &str, text: &str) -> Result<(), String> {
+        self.queue_at(fingerprint, from, text, Instant::now())
+    }
+
+    /// Removes and returns the live messages queued for the key, oldest
+    /// first
+    pub fn take(&mut self, fingerprint: &str) -> Vec<OfflineMessage> {
+        self.take_at(fingerprint, Instant::now())
+    }
+
+    fn queue_at(
+        &mut self,
+        fingerprint: &str,
+        from: &str,
+        text: &str,
+        now: Instant,
+    ) -> Result<(), String> {
+        self.expire(now);
+        let queue = self.queues.entry(fingerprint.to_string()).or_default();
+        if queue.len() >= MAX_QUEUED_PER_KEY {
+            return Err(format!(
+                "there are already {} messages waiting for them",
+                MAX_QUEUED_PER_KEY
+            ));
+        }
+        queue.push_back(OfflineMessage {
+            from: from.to_string(),
+            text: text.to_string(),
+            queued_at: now,
+        });
+        Ok(())
+    }
+
+    fn take_at(&mut self, fingerprint: &str, now: Instant) -> Vec<OfflineMessage> {
+        self.expire(now);
+        self.queues
+            .remove(fingerprint)
+            .map(Vec::from)
+            .unwrap_or_default()
+    }
+
+    fn expire(&mut self, now: Instant) {
+        let ttl = self.ttl;
+        for queue in self.queues.values_mut() {
+            queue.retain(|message| now.duration_since(message.queued_at) < ttl);
+        }
+        self.queues.retain(|_, queue| !queue.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn deliver_queued_messages_once() {
+        let mut offline = OfflineMessages::new(Duration::from_secs(60));
+        let now = Instant::now();
+        offline.queue_at("key", "alice", "hi", now).unwrap();
+        offline.queue_at("key", "bob", "hey", now).unwrap();
+
+        let messages = offline.take_at("key", now);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].from, "alice");
+        assert_eq!(messages[1].text, "hey");
+        assert!(offline.take_at("key", now).is_empty());
+    }
+
+    #[test]
+    fn refuse_messages_over_the_limit() {
+        let mut offline = OfflineMessages::new(Duration::from_secs(60));
+        let now = Instant::now();
+        for _ in 0..MAX_QUEUED_PER_KEY {
+            offline.queue_at("key", "alice", "hi", now).unwrap();
+        }
+        assert!(offline.queue_at("key", "alice", "hi", now).is_err());
+        assert!(offline.queue_at("other", "alice", "hi", now).is_ok());
+    }
+
+    #[test]
+    fn drop_expired_messages() {
+        let mut offline = OfflineMessages::new(Duration::from_secs(60));
+        let now = Instant::now();
+        offline.queue_at("key", "alice", "old", now).unwrap();
+        let later = now + Duration::from_secs(61);
+        offline.queue_at("key", "alice", "new", later).unwrap();
+
+        let messages = offline.take_at("key", later);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].text, "new");
+    }
+}
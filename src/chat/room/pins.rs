@@ -0,0 +1,104 @@
+use crate::utils;
+
+/// Maximum number of messages pinned at once
+const MAX_PINS: usize = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pin {
+    pub author: String,
+    pub text: String,
+}
+
+/// Messages pinned by the operators, shown to everyone who joins right
+/// after the message of the day
+#[derive(Debug, Clone, Default)]
+pub struct PinnedMessages {
+    pins: Vec<Pin>,
+}
+
+impl PinnedMessages {
+    /// Adds the pin at the end of the list and returns its number
+    pub fn pin(&mut self, pin: Pin) -> Result<usize, String> {
+        if self.pins.len() >= MAX_PINS {
+            return Err(format!(
+                "there are already {} pinned messages, unpin one first",
+                MAX_PINS
+            ));
+        }
+        self.pins.push(pin);
+        Ok(self.pins.len())
+    }
+
+    /// Removes the pin by its number as listed, starting from 1
+    pub fn unpin(&mut self, number: usize) -> Option<Pin> {
+        match number {
+            n if n >= 1 && n <= self.pins.len() => Some(self.pins.remove(n - 1)),
+            _ => None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pins.is_empty()
+    }
+
+    /// Lists the pins under a header, numbered as `/unpin` expects
+    pub fn render(&self) -> String {
+        self.pins
+            .iter()
+            .enumerate()
+            .fold("Pinned:".to_string(), |list, (i, pin)| {
+                format!(
+                    "{}{} {}. {}: {}",
+                    list,
+                    utils::NEWLINE,
+                    i + 1,
+                    pin.author,
+                    pin.text
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    fn create_pin(text: &str) -> Pin {
+        Pin {
+            author: "alice".to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn number_pins_in_order() {
+        let mut pins = PinnedMessages::default();
+        assert!(pins.is_empty());
+        assert_eq!(pins.pin(create_pin("first")), Ok(1));
+        assert_eq!(pins.pin(create_pin("second")), Ok(2));
+        assert_eq!(
+            pins.render(),
+            format!(
+                "Pinned:{nl} 1. alice: first{nl} 2. alice: second",
+                nl = utils::NEWLINE
+            )
+        );
+
+        assert_eq!(pins.unpin(0), None);
+        assert_eq!(pins.unpin(3), None);
+        assert_eq!(pins.unpin(1), Some(create_pin("first")));
+        assert_eq!(
+            pins.render(),
+            format!("Pinned:{} 1. alice: second", utils::NEWLINE)
+        );
+    }
+
+    #[test]
+    fn refuse_pins_over_the_limit() {
+        let mut pins = PinnedMessages::default();
+        for _ in 0..MAX_PINS {
+            pins.pin(create_pin("pin")).unwrap();
+        }
+        assert!(pins.pin(create_pin("pin")).is_err());
+    }
+}
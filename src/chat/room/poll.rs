@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::chat::message::Author;
+use crate::utils;
+
+/// Time a poll stays open unless the server is told otherwise
+const DEFAULT_POLL_WINDOW: Duration = Duration::from_secs(600);
+
+/// Question put to the room with numbered options, each key voting
+/// for one of them once whatever its sessions
+#[derive(Debug, Clone)]
+pub struct Poll {
+    pub by: Author,
+    pub question: String,
+    pub options: Vec<String>,
+    votes: HashMap<String, usize>,
+    started_at: Instant,
+}
+
+impl Poll {
+    /// Number of votes for each option, in the order of the options
+    pub fn tally(&self) -> Vec<usize> {
+        let mut tally = vec![0; self.options.len()];
+        for option in self.votes.values() {
+            tally[*option] += 1;
+        }
+        tally
+    }
+
+    pub fn voters(&self) -> usize {
+        self.votes.len()
+    }
+
+    /// Lists the options under the question with their votes
+    pub fn results(&self) -> String {
+        let mut results = self.question.clone();
+        for (i, (option, votes)) in self.options.iter().zip(self.tally()).enumerate() {
+            results += &format!(
+                "{} {}. {}: {} {}",
+                utils::NEWLINE,
+                i + 1,
+                option,
+                votes,
+                match votes {
+                    1 => "vote",
+                    _ => "votes",
+                }
+            );
+        }
+        results
+    }
+}
+
+/// The poll open in the room, if any. Only one can be open at a time
+#[derive(Debug)]
+pub struct Polls {
+    window: Duration,
+    ops_only: bool,
+    active: Option<Poll>,
+}
+
+impl Default for Polls {
+    fn default() -> Self {
+        Self {
+            window: DEFAULT_POLL_WINDOW,
+            ops_only: false,
+            active: None,
+        }
+    }
+}
+
+impl Polls {
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    pub fn set_ops_only(&mut self, ops_only: bool) {
+        self.ops_only = ops_only;
+    }
+
+    pub fn ops_only(&self) -> bool {
+        self.ops_only
+    }
+
+    pub fn active(&self) -> Option<&Poll> {
+        self.active.as_ref()
+    }
+
+    pub fn start(
+        &mut self,
+        by: Author,
+        question: String,
+        options: Vec<String>,
+    ) -> Result<(), String> {
+        self.start_at(by, question, options, Instant::now())
+    }
+
+    /// Counts the vote of the key for the option, numbered from 1 as
+    /// announced
+    pub fn vote(&mut self, voter_key: &str, option: usize) -> Result<&str, String> {
+        let poll = self.active.as_mut().ok_or("there is no open poll")?;
+        if option == 0 || option > poll.options.len() {
+            return Err(format!(
+                "option must be a number from 1 to {}",
+                poll.options.len()
+            ));
+        }
+        if poll.votes.contains_key(voter_key) {
+            return Err("you already voted in this poll".to_string());
+        }
+        poll.votes.insert(voter_key.to_string(), option - 1);
+        Ok(&poll.options[option - 1])
+    }
+
+    /// Closes the open poll and returns it with the final votes
+    pub fn close(&mut self) -> Option<Poll> {
+        self.active.take()
+    }
+
+    /// Closes the poll if it was open for longer than the window and
+    /// returns it
+    pub fn expire(&mut self) -> Option<Poll> {
+        self.expire_at(Instant::now())
+    }
+
+    fn start_at(
+        &mut self,
+        by: Author,
+        question: String,
+        options: Vec<String>,
+        now: Instant,
+    ) -> Result<(), String> {
+        if self.active.is_some() {
+            return Err("a poll is already open, close it first".to_string());
+        }
+        self.active = Some(Poll {
+            by,
+            question,
+            options,
+            votes: HashMap::new(),
+            started_at: now,
+        });
+        Ok(())
+    }
+
+    fn expire_at(&mut self, now: Instant) -> Option<Poll> {
+        let poll = self.active.as_ref()?;
+        match now.duration_since(poll.started_at) >= self.window {
+            true => self.active.take(),
+            false => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    fn start(polls: &mut Polls, now: Instant) -> Result<(), String> {
+        let options = vec!["tea".to_string(), "coffee".to_string()];
+        polls.start_at(Author::default(), "Drinks?".to_string(), options, now)
+    }
+
+    #[test]
+    fn count_one_vote_per_key() {
+        let mut polls = Polls::default();
+        start(&mut polls, Instant::now()).unwrap();
+        assert!(start(&mut polls, Instant::now()).is_err());
+
+        assert_eq!(polls.vote("alice", 2), Ok("coffee"));
+        assert!(polls.vote("alice", 1).is_err());
+        assert!(polls.vote("bob", 0).is_err());
+        assert!(polls.vote("bob", 3).is_err());
+        assert_eq!(polls.vote("bob", 2), Ok("coffee"));
+        assert_eq!(polls.vote("carol", 1), Ok("tea"));
+
+        let poll = polls.close().unwrap();
+        assert_eq!(poll.tally(), vec![1, 2]);
+        assert_eq!(poll.voters(), 3);
+        assert!(poll.results().contains("2. coffee: 2 votes"));
+        assert!(polls.vote("dave", 1).is_err());
+    }
+
+    #[test]
+    fn expire_the_poll_after_the_window() {
+        let mut polls = Polls::default();
+        polls.set_window(Duration::from_secs(60));
+        let now = Instant::now();
+        start(&mut polls, now).unwrap();
+
+        assert!(polls.expire_at(now + Duration::from_secs(30)).is_none());
+        assert!(polls.expire_at(now + Duration::from_secs(60)).is_some());
+        assert!(polls.active().is_none());
+        assert!(start(&mut polls, now).is_ok());
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::chat::UserName;
+
+/// Names of users who dropped unexpectedly, held for their keys for a
+/// while so a reconnect gets the same name back
+#[derive(Debug)]
+pub struct ReconnectHolds {
+    window: Duration,
+    held: HashMap<String, (UserName, Instant)>,
+}
+
+impl ReconnectHolds {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            held: HashMap::new(),
+        }
+    }
+
+    /// Holds the name for the key fingerprint until the window passes
+    pub fn hold(&mut self, fingerprint: String, name: UserName) {
+        self.expire();
+        self.held.insert(fingerprint, (name, Instant::now()));
+    }
+
+    /// Releases and returns the name held for the fingerprint, if the
+    /// hold is still live
+    pub fn take(&mut self, fingerprint: &str) -> Option<UserName> {
+        self.expire();
+        self.held.remove(fingerprint).map(|(name, _)| name)
+    }
+
+    pub fn is_held(&self, name: &UserName) -> bool {
+        self.held
+            .values()
+            .any(|(held, at)| held == name && at.elapsed() < self.window)
+    }
+
+    fn expire(&mut self) {
+        let window = self.window;
+        self.held.retain(|_, (_, at)| at.elapsed() < window);
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn release_held_name_only_to_its_key() {
+        let mut holds = ReconnectHolds::new(Duration::from_secs(60));
+        holds.hold("SHA256:abc".into(), "alice".into());
+        assert!(holds.is_held(&"alice".into()));
+        assert_eq!(holds.take("SHA256:def"), None);
+        assert_eq!(holds.take("SHA256:abc"), Some("alice".into()));
+        assert!(!holds.is_held(&"alice".into()));
+    }
+
+    #[test]
+    fn expire_holds_after_window_passes() {
+        let mut holds = ReconnectHolds::new(Duration::from_millis(10));
+        holds.hold("SHA256:abc".into(), "alice".into());
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!holds.is_held(&"alice".into()));
+        assert_eq!(holds.take("SHA256:abc"), None);
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+type UserId = usize;
+
+const DEFAULT_REPEAT_WINDOW: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+struct RepeatEntry {
+    body: String,
+    count: usize,
+    last_sent_at: Instant,
+}
+
+/// Tracks identical messages sent by a user in a row and tells when
+/// the user goes over the allowed number of repeats within a window.
+/// It is off until a limit is set
+#[derive(Debug)]
+pub struct RepeatFilter {
+    max_repeats: usize,
+    window: Duration,
+    entries: HashMap<UserId, RepeatEntry>,
+}
+
+impl Default for RepeatFilter {
+    fn default() -> Self {
+        Self::new(0, DEFAULT_REPEAT_WINDOW)
+    }
+}
+
+impl RepeatFilter {
+    pub fn new(max_repeats: usize, window: Duration) -> Self {
+        Self {
+            max_repeats,
+            window,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn set_max_repeats(&mut self, max_repeats: usize) {
+        self.max_repeats = max_repeats;
+    }
+
+    /// Registers the message body sent by the user and returns `true`
+    /// if it is a repeat over the limit that should be dropped. A
+    /// limit of zero disables the filter.
+    pub fn is_flood(&mut self, user_id: UserId, body: &str) -> bool {
+        if self.max_repeats == 0 {
+            return false;
+        }
+
+        let now = Instant::now();
+        match self.entries.get_mut(&user_id) {
+            Some(entry) if entry.body == body && now - entry.last_sent_at <= self.window => {
+                entry.count += 1;
+                entry.last_sent_at = now;
+                entry.count > self.max_repeats
+            }
+            _ => {
+                let entry = RepeatEntry {
+                    body: body.to_string(),
+                    count: 1,
+                    last_sent_at: now,
+                };
+                self.entries.insert(user_id, entry);
+                false
+            }
+        }
+    }
+
+    pub fn forget(&mut self, user_id: &UserId) {
+        self.entries.remove(user_id);
+    }
+
+    /// Number of users with a tracked message
+    pub fn tracked_users(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn allow_repeats_up_to_limit() {
+        let mut filter = RepeatFilter::new(2, Duration::from_secs(30));
+        assert!(!filter.is_flood(1, "hi"));
+        assert!(!filter.is_flood(1, "hi"));
+        assert!(filter.is_flood(1, "hi"));
+        assert!(filter.is_flood(1, "hi"));
+    }
+
+    #[test]
+    fn reset_when_body_changes() {
+        let mut filter = RepeatFilter::new(2, Duration::from_secs(30));
+        assert!(!filter.is_flood(1, "hi"));
+        assert!(!filter.is_flood(1, "hi"));
+        assert!(!filter.is_flood(1, "hello"));
+        assert!(!filter.is_flood(1, "hi"));
+    }
+
+    #[test]
+    fn track_users_separately() {
+        let mut filter = RepeatFilter::new(1, Duration::from_secs(30));
+        assert!(!filter.is_flood(1, "hi"));
+        assert!(!filter.is_flood(2, "hi"));
+        assert!(filter.is_flood(1, "hi"));
+    }
+
+    #[test]
+    fn reset_after_window_passes() {
+        let mut filter = RepeatFilter::new(1, Duration::from_millis(10));
+        assert!(!filter.is_flood(1, "hi"));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!filter.is_flood(1, "hi"));
+    }
+
+    #[test]
+    fn not_filter_when_disabled() {
+        let mut filter = RepeatFilter::new(0, Duration::from_secs(30));
+        for _ in 0..10 {
+            assert!(!filter.is_flood(1, "hi"));
+        }
+    }
+}
@@ -1,17 +1,38 @@
-use std::collections::hash_map::{Iter, IterMut};
-use std::collections::HashMap;
+use std::collections::hash_map::Iter;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
 use std::time::Duration;
 
 use chrono::{DateTime, Utc};
+use crossterm::style::Color;
 use governor::Quota;
+use log::warn;
 use nonzero_ext::nonzero;
 use tokio::sync::{mpsc, watch};
 
+use super::blocklist::{Blocklist, BlocklistVerdict};
+use super::cooldown::CommandCooldowns;
+use super::delivery::PrivateDelivery;
+use super::leave_reason::LeaveReason;
+use super::lookalike::LookalikeNameMode;
 use super::member::RoomMember;
-
-use crate::chat::message::{self, Message, MessageHistory};
+use super::motd_throttle::MotdThrottle;
+use super::newcomers::Newcomers;
+use super::offline::OfflineMessages;
+use super::pins::{Pin, PinnedMessages};
+use super::poll::{Poll, Polls};
+use super::reconnect::ReconnectHolds;
+use super::repeat_filter::RepeatFilter;
+use super::session_mode::SingleSessionMode;
+use super::shutdown::ScheduledShutdown;
+use super::sizes::RoomSizes;
+use super::slow_mode::{SlowMode, SlowModeChange};
+use super::votekick::{VoteKickTally, VoteKickThreshold, VoteKicks};
+use super::welcome::FirstJoinWelcome;
+
+use crate::chat::message::{self, Message, MessageBaseOps, MessageHistory};
 use crate::chat::ratelimit::RateLimit;
-use crate::chat::user::{User, UserName};
+use crate::chat::user::{PromptTemplate, Theme, TimestampMode, User, UserName, UserStatus};
 use crate::pubkey::PubKey;
 use crate::utils::{self, sanitize};
 
@@ -20,13 +41,51 @@ type UserId = usize;
 const MESSAGE_MAX_BURST: std::num::NonZeroU32 = nonzero!(10u32);
 const MESSAGE_RATE_QUOTA: Quota = Quota::per_second(MESSAGE_MAX_BURST);
 
+/// Longest MOTD in bytes operators may set from the chat
+pub const DEFAULT_MOTD_MAX_LEN: usize = 4096;
+
+/// Lines around the history replayed on join, so the old messages
+/// can be told apart from the live ones
+const HISTORY_REPLAY_MARKER: &str = "--- replay ---";
+const HISTORY_LIVE_MARKER: &str = "--- live ---";
+
 pub struct ChatRoom {
     names: HashMap<UserId, UserName>,
     members: HashMap<UserName, RoomMember>,
     ratelims: HashMap<UserId, RateLimit>,
+    repeats: RepeatFilter,
+    cooldowns: CommandCooldowns,
+    slow_mode: SlowMode,
+    votekicks: Option<VoteKicks>,
+    polls: Polls,
+    blocklist: Blocklist,
     history: MessageHistory<20>,
+    pinned_colors: HashMap<String, Color>,
+    saved_focus: HashMap<String, (BTreeSet<UserId>, BTreeSet<UserId>)>,
+    muted_keys: HashSet<String>,
+    single_session: Option<SingleSessionMode>,
+    max_per_key: Option<usize>,
+    lookalike_names: Option<LookalikeNameMode>,
+    unread_badge: bool,
+    prompt_template: Option<PromptTemplate>,
+    default_theme: Theme,
+    default_timestamp_mode: TimestampMode,
     motd: String,
+    motd_path: Option<String>,
+    motd_enabled: bool,
+    motd_max_len: usize,
+    motd_banners: Vec<String>,
+    next_banner: usize,
+    motd_throttle: Option<MotdThrottle>,
+    goodbye: Option<String>,
+    welcome: Option<FirstJoinWelcome>,
+    newcomers: Option<Newcomers>,
+    pins: PinnedMessages,
+    offline: Option<OfflineMessages>,
+    reconnect: Option<ReconnectHolds>,
+    shutdown_tx: watch::Sender<Option<ScheduledShutdown>>,
     created_at: DateTime<Utc>,
+    peak_members: usize,
 }
 
 impl ChatRoom {
@@ -35,9 +94,39 @@ impl ChatRoom {
             names: HashMap::new(),
             members: HashMap::new(),
             ratelims: HashMap::new(),
+            repeats: RepeatFilter::default(),
+            cooldowns: CommandCooldowns::default(),
+            slow_mode: SlowMode::default(),
+            votekicks: None,
+            polls: Polls::default(),
+            blocklist: Blocklist::default(),
             history: MessageHistory::new(),
+            pinned_colors: HashMap::new(),
+            saved_focus: HashMap::new(),
+            muted_keys: HashSet::new(),
+            single_session: None,
+            max_per_key: None,
+            lookalike_names: None,
+            unread_badge: false,
+            prompt_template: None,
+            default_theme: Theme::default(),
+            default_timestamp_mode: TimestampMode::default(),
             motd: motd.to_string(),
+            motd_path: None,
+            motd_enabled: true,
+            motd_max_len: DEFAULT_MOTD_MAX_LEN,
+            motd_banners: vec![],
+            next_banner: 0,
+            motd_throttle: None,
+            goodbye: None,
+            welcome: None,
+            newcomers: None,
+            pins: PinnedMessages::default(),
+            offline: None,
+            reconnect: None,
+            shutdown_tx: watch::channel(None).0,
             created_at: Utc::now(),
+            peak_members: 0,
         }
     }
 
@@ -45,8 +134,318 @@ impl ChatRoom {
         &self.motd
     }
 
-    pub fn set_motd(&mut self, motd: String) {
-        self.motd = motd;
+    /// Replaces the MOTD, dropping any banners loaded from a directory.
+    /// Terminal escapes are stripped from the text and a MOTD over the
+    /// length limit is refused, leaving the current one in place
+    pub fn set_motd(&mut self, motd: &str) -> Result<(), String> {
+        self.motd = self.check_motd(sanitize::message(motd))?;
+        self.motd_banners.clear();
+        Ok(())
+    }
+
+    /// Turns sending the MOTD to joining users on or off
+    pub fn set_motd_enabled(&mut self, enabled: bool) {
+        self.motd_enabled = enabled;
+    }
+
+    /// Sets the longest MOTD in bytes that can be set, appended or
+    /// prepended from the chat
+    pub fn set_motd_max_len(&mut self, max_len: usize) {
+        self.motd_max_len = max_len;
+    }
+
+    /// Adds the line after the existing MOTD
+    pub fn append_motd(&mut self, line: &str) -> Result<(), String> {
+        let line = sanitize::message(line);
+        self.motd = self.check_motd(match self.motd.is_empty() {
+            true => line,
+            false => format!("{}{}{}", self.motd, utils::NEWLINE, line),
+        })?;
+        Ok(())
+    }
+
+    /// Adds the line before the existing MOTD
+    pub fn prepend_motd(&mut self, line: &str) -> Result<(), String> {
+        let line = sanitize::message(line);
+        self.motd = self.check_motd(match self.motd.is_empty() {
+            true => line,
+            false => format!("{}{}{}", line, utils::NEWLINE, self.motd),
+        })?;
+        Ok(())
+    }
+
+    fn check_motd(&self, motd: String) -> Result<String, String> {
+        match motd.len() > self.motd_max_len {
+            true => Err(format!(
+                "MOTD would be {} bytes, over the limit of {}",
+                motd.len(),
+                self.motd_max_len
+            )),
+            false => Ok(motd),
+        }
+    }
+
+    /// Sets the MOTD from the file and remembers its path for later
+    /// reloads
+    pub fn load_motd_file(&mut self, path: &str) -> std::io::Result<()> {
+        let motd = utils::fs::read_file_to_string(path)?;
+        self.motd = motd.replace("\n", utils::NEWLINE);
+        self.motd_path = Some(path.to_string());
+        Ok(())
+    }
+
+    /// Re-reads the MOTD from the file it was loaded from. Returns
+    /// `None` if the MOTD did not come from a file.
+    pub fn reload_motd(&mut self) -> Option<std::io::Result<()>> {
+        let path = self.motd_path.clone()?;
+        Some(self.load_motd_file(&path))
+    }
+
+    /// Loads the banner written to users right before they leave with
+    /// `/exit` or are kicked. An empty file means no banner
+    pub fn load_goodbye_file(&mut self, path: &str) -> std::io::Result<()> {
+        let goodbye = utils::fs::read_file_to_string(path)?;
+        self.goodbye = match goodbye.trim().is_empty() {
+            true => None,
+            false => Some(goodbye.replace("\n", utils::NEWLINE)),
+        };
+        Ok(())
+    }
+
+    /// Loads every `.ans` file in the directory as a banner that
+    /// joining users get in turn instead of the MOTD. Returns the number
+    /// of banners found; with none, the MOTD is sent as usual
+    pub fn load_motd_dir(&mut self, path: &str) -> std::io::Result<usize> {
+        let mut banners = vec![];
+        for file in utils::fs::list_files_with_extension(path, "ans")? {
+            let banner = utils::fs::read_file_to_string(&file.to_string_lossy())?;
+            banners.push(banner.replace("\n", utils::NEWLINE));
+        }
+        self.motd_banners = banners;
+        self.next_banner = 0;
+        Ok(self.motd_banners.len())
+    }
+
+    /// Shows the message of the day to a key only if it wasn't shown to
+    /// it within the throttle interval, instead of on every join
+    pub fn set_motd_throttle(&mut self, throttle: MotdThrottle) {
+        self.motd_throttle = Some(throttle);
+    }
+
+    /// Sets the private greeting sent to keys on their first-ever join
+    pub fn set_welcome(&mut self, welcome: FirstJoinWelcome) {
+        self.welcome = Some(welcome);
+    }
+
+    pub fn pins(&self) -> &PinnedMessages {
+        &self.pins
+    }
+
+    pub fn pin(&mut self, pin: Pin) -> Result<usize, String> {
+        self.pins.pin(pin)
+    }
+
+    pub fn unpin(&mut self, number: usize) -> Option<Pin> {
+        self.pins.unpin(number)
+    }
+
+    /// Forgets the messages sent so far, so they are no longer replayed
+    /// to the users who join
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    /// Finds the latest public message of the user still in the history
+    pub fn last_public_message(&self, username: &str) -> Option<&message::Public> {
+        self.history.last_public_from(username)
+    }
+
+    /// Finds the public message or emote `nth` places back in the
+    /// history, starting from 1 for the latest one
+    pub fn find_recent_message(&self, nth: usize) -> Option<Pin> {
+        self.history
+            .iter()
+            .rev()
+            .filter_map(|msg| match msg {
+                Message::Public(m) => Some(Pin {
+                    author: m.from().username().to_string(),
+                    text: m.message_body().to_string(),
+                }),
+                Message::Emote(m) => Some(Pin {
+                    author: m.from().username().to_string(),
+                    text: format!("** {}", m.message_body()),
+                }),
+                _ => None,
+            })
+            .nth(nth.checked_sub(1)?)
+    }
+
+    /// Holds the names of users who drop unexpectedly for the window so
+    /// they get it back when reconnecting with the same key
+    pub fn set_reconnect_window(&mut self, window: Duration) {
+        self.reconnect = Some(ReconnectHolds::new(window));
+    }
+
+    /// Holds the name of the user for a reconnect. Called when the
+    /// session drops without the user leaving on purpose
+    pub fn hold_name_for_reconnect(&mut self, user_id: &UserId) {
+        let (reconnect, name) = match (&mut self.reconnect, self.names.get(user_id)) {
+            (Some(reconnect), Some(name)) => (reconnect, name),
+            _ => return,
+        };
+        if let Some(member) = self.members.get(name) {
+            reconnect.hold(member.user.public_key().fingerprint(), name.clone());
+        }
+    }
+
+    /// Enables counting of unread private messages, which sessions show
+    /// next to the prompt
+    pub fn set_unread_badge(&mut self, enabled: bool) {
+        self.unread_badge = enabled;
+    }
+
+    /// Sets the prompt layout for users who haven't picked their own
+    pub fn set_prompt_template(&mut self, template: PromptTemplate) {
+        self.prompt_template = Some(template);
+    }
+
+    /// Sets the theme users start with before they pick their own
+    pub fn set_default_theme(&mut self, theme: Theme) {
+        self.default_theme = theme;
+    }
+
+    /// Sets the timestamp mode users start with before they pick their own
+    pub fn set_default_timestamp_mode(&mut self, mode: TimestampMode) {
+        self.default_timestamp_mode = mode;
+    }
+
+    /// Builds the user's prompt from their own template or the room's,
+    /// falling back to the themed display name
+    pub fn prompt(&self, user: &User) -> String {
+        let template = user
+            .config()
+            .prompt_template()
+            .or(self.prompt_template.as_ref());
+        match template {
+            Some(template) => template.expand(user, self.members.len()),
+            None => user.config().display_name().to_string(),
+        }
+    }
+
+    /// Hands every member's session its prompt again, so prompts that
+    /// show the number of users online stay current as users come and go
+    fn refresh_prompts(&self) {
+        for member in self.members.values() {
+            member.set_prompt(self.prompt(&member.user));
+        }
+    }
+
+    /// Asks the server to shut down after the delay, replacing any
+    /// shutdown scheduled before
+    pub fn schedule_shutdown(&self, delay: Duration, by: &User) {
+        self.shutdown_tx
+            .send_replace(Some(ScheduledShutdown::new(delay, by.into())));
+    }
+
+    /// Aborts the scheduled shutdown. Returns `false` if there was none.
+    pub fn cancel_shutdown(&self) -> bool {
+        self.shutdown_tx.send_replace(None).is_some()
+    }
+
+    pub fn subscribe_shutdown(&self) -> watch::Receiver<Option<ScheduledShutdown>> {
+        self.shutdown_tx.subscribe()
+    }
+
+    /// Tells everyone the server is going down and closes all sessions
+    pub async fn close_all(&mut self, by: &message::Author) -> anyhow::Result<()> {
+        let message = message::Announce::new(by.clone(), "shut down the server".to_string());
+        self.send_message(message.into()).await?;
+        for member in self.members.values() {
+            let _ = member.exit(LeaveReason::ServerShutdown);
+        }
+        Ok(())
+    }
+
+    /// Allows only one session per public key, resolving duplicate
+    /// logins according to the given mode
+    pub fn set_single_session_mode(&mut self, mode: SingleSessionMode) {
+        self.single_session = Some(mode);
+    }
+
+    /// Caps the number of sessions connected with the same key. Zero
+    /// lifts the cap
+    pub fn set_max_per_key(&mut self, max: usize) {
+        self.max_per_key = Some(max).filter(|max| *max > 0);
+    }
+
+    /// Refuses another session with the key once it has as many as
+    /// allowed. Operators are exempt
+    pub fn check_max_per_key(&self, key: &PubKey, is_op: bool) -> anyhow::Result<()> {
+        let max = match self.max_per_key {
+            Some(max) if !is_op => max,
+            _ => return Ok(()),
+        };
+        if self.sessions_of_key(&key.fingerprint()).len() >= max {
+            anyhow::bail!(
+                "too many sessions with this key, at most {} may be connected at once",
+                max
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks new names against the names of other members for
+    /// lookalikes, handling them according to the given mode
+    pub fn set_lookalike_name_mode(&mut self, mode: LookalikeNameMode) {
+        self.lookalike_names = Some(mode);
+    }
+
+    pub fn lookalike_name_mode(&self) -> Option<LookalikeNameMode> {
+        self.lookalike_names
+    }
+
+    /// Returns the name of another member that looks like the given name
+    /// without being the same, e.g. with a Cyrillic letter swapped in
+    pub fn find_lookalike(&self, username: &str, except: Option<UserId>) -> Option<&UserName> {
+        let skeleton = sanitize::name_skeleton(username);
+        self.members
+            .iter()
+            .filter(|(_, member)| Some(member.user.id()) != except)
+            .map(|(name, _)| name)
+            .find(|name| name.as_ref() != username && sanitize::name_skeleton(name) == skeleton)
+    }
+
+    /// Pins a color to the member's key, so every current and future
+    /// member sees it on the names of that key only, even after a
+    /// rename. `None` clears the override. Returns `false` if no member
+    /// has the name
+    pub fn set_username_color(&mut self, username: &UserName, color: Option<Color>) -> bool {
+        let fingerprint = match self.try_find_member(username) {
+            Some(member) => member.user.public_key().fingerprint(),
+            None => return false,
+        };
+        match color {
+            Some(color) => self.pinned_colors.insert(fingerprint, color),
+            None => self.pinned_colors.remove(&fingerprint),
+        };
+        self.refresh_username_colors();
+        true
+    }
+
+    /// Gives every member the pinned colors of the names currently
+    /// held by the pinned keys
+    fn refresh_username_colors(&mut self) {
+        let colors: HashMap<String, Color> = self
+            .members
+            .iter()
+            .filter_map(|(name, member)| {
+                let fingerprint = member.user.public_key().fingerprint();
+                Some((name.to_string(), *self.pinned_colors.get(&fingerprint)?))
+            })
+            .collect();
+        for member in self.members.values_mut() {
+            member.user.set_username_colors(colors.clone());
+        }
     }
 
     pub fn uptime(&self) -> String {
@@ -55,26 +454,445 @@ impl ChatRoom {
         humantime::format_duration(Duration::from_secs(since_created)).to_string()
     }
 
+    /// Highest number of members connected at once since the start
+    pub fn peak_members(&self) -> usize {
+        self.peak_members
+    }
+
     pub fn get_ratelimit(&self, user_id: UserId) -> Option<&RateLimit> {
         self.ratelims.get(&user_id)
     }
 
+    /// Sets how many identical messages in a row a user may send
+    /// before the next ones are dropped. Zero disables the check.
+    pub fn set_max_repeats(&mut self, max_repeats: usize) {
+        self.repeats.set_max_repeats(max_repeats);
+    }
+
+    /// Sets the minimum time between two uses of the command by the
+    /// same user. Zero lifts the cooldown
+    pub fn set_command_cooldown(&mut self, cmd: &str, cooldown: Duration) {
+        self.cooldowns.set_cooldown(cmd, cooldown);
+    }
+
+    /// Registers a use of the command by the user and returns the time
+    /// left if it is still cooling down
+    pub fn check_command_cooldown(&mut self, user_id: UserId, cmd: &str) -> Option<Duration> {
+        self.cooldowns.check(user_id, cmd)
+    }
+
+    /// Marks users whose key first joined within the window as
+    /// newcomers next to their name
+    pub fn set_newcomer_window(&mut self, window: Duration) {
+        self.newcomers = Some(Newcomers::new(window));
+    }
+
+    /// Keeps first joins of keys in the file so that newcomers stay
+    /// marked across restarts. Only applies once the window is set
+    pub fn load_newcomers_seen_file(&mut self, path: &str) -> std::io::Result<()> {
+        match self.newcomers.as_mut() {
+            Some(newcomers) => newcomers.load_seen_file(path),
+            None => Ok(()),
+        }
+    }
+
+    /// Keeps private messages to offline trusted users for this long
+    /// and delivers them when they join again
+    pub fn set_offline_messages(&mut self, ttl: Duration) {
+        self.offline = Some(OfflineMessages::new(ttl));
+    }
+
+    pub fn has_offline_messages(&self) -> bool {
+        self.offline.is_some()
+    }
+
+    /// Queues the private message for the offline owner of the key
+    pub fn queue_offline_message(
+        &mut self,
+        fingerprint: &str,
+        from: &UserName,
+        text: &str,
+    ) -> Result<(), String> {
+        match self.offline.as_mut() {
+            Some(offline) => offline.queue(fingerprint, from.as_ref(), text),
+            None => Err("offline messages are disabled".to_string()),
+        }
+    }
+
+    /// Turns slow mode on whenever more than the threshold of public
+    /// messages are sent within 10 seconds, holding every user to one
+    /// message per cooldown until the room calms down. Zero disables it
+    pub fn set_slow_mode(&mut self, threshold: usize, cooldown: Duration) {
+        self.slow_mode = SlowMode::new(threshold, cooldown);
+    }
+
+    /// Registers a public message sent by the user towards the room
+    /// rate and returns the time left if slow mode holds it back.
+    /// Everyone is told when slow mode goes on or off
+    pub async fn check_slow_mode(&mut self, user: &User) -> anyhow::Result<Option<Duration>> {
+        if let Some(change) = self.slow_mode.refresh() {
+            self.announce_slow_mode(change).await?;
+        }
+        if let Some(remaining) = self.slow_mode.check(user.id()) {
+            return Ok(Some(remaining));
+        }
+        if let Some(change) = self.slow_mode.register(user.id()) {
+            self.announce_slow_mode(change).await?;
+        }
+        Ok(None)
+    }
+
+    /// Lifts slow mode once the room calmed down and tells everyone,
+    /// even if nobody sends a message
+    pub async fn announce_slow_mode_end(&mut self) -> anyhow::Result<()> {
+        if let Some(change) = self.slow_mode.refresh() {
+            self.announce_slow_mode(change).await?;
+        }
+        Ok(())
+    }
+
+    async fn announce_slow_mode(&self, change: SlowModeChange) -> anyhow::Result<()> {
+        let text = match change {
+            SlowModeChange::Entered => format!(
+                "Slow mode is on as the room is flooded, one message every {} each",
+                humantime::format_duration(self.slow_mode.cooldown())
+            ),
+            SlowModeChange::Left => "Slow mode is off".to_string(),
+        };
+        for (_, member) in self.members.iter() {
+            let message = message::System::new(member.user.clone().into(), text.clone());
+            member.send_message(message.into()).await?;
+        }
+        Ok(())
+    }
+
+    /// Lets users kick each other once enough of them voted to within
+    /// the window
+    pub fn set_votekick(&mut self, threshold: VoteKickThreshold, window: Duration) {
+        self.votekicks = Some(VoteKicks::new(threshold, window));
+    }
+
+    pub fn votekick_window(&self) -> Option<Duration> {
+        self.votekicks.as_ref().map(|votekicks| votekicks.window())
+    }
+
+    /// Counts the user's vote to kick the target. Every key online
+    /// besides the target's may vote once, whatever its sessions
+    pub fn vote_kick(&mut self, voter: UserId, target: UserId) -> Result<VoteKickTally, String> {
+        let key_of = |id: UserId| {
+            self.try_find_member_by_id(id)
+                .map(|member| member.user.public_key().fingerprint())
+                .ok_or("user not found".to_string())
+        };
+        let (voter_key, target_key) = (key_of(voter)?, key_of(target)?);
+        let electorate = self
+            .members
+            .values()
+            .map(|member| member.user.public_key().fingerprint())
+            .filter(|key| *key != target_key)
+            .collect::<HashSet<String>>()
+            .len();
+        match self.votekicks.as_mut() {
+            Some(votekicks) => votekicks.vote(target, &target_key, &voter_key, electorate),
+            None => Err("vote kicks are disabled".to_string()),
+        }
+    }
+
+    /// Closes the vote to kick the target, telling if there was one
+    pub fn cancel_vote_kick(&mut self, target: UserId) -> bool {
+        self.votekicks
+            .as_mut()
+            .is_some_and(|votekicks| votekicks.cancel(&target))
+    }
+
+    /// Closes the vote kicks open for longer than the window and tells
+    /// the room their targets stay
+    pub async fn announce_expired_vote_kicks(&mut self) -> anyhow::Result<()> {
+        let expired = match self.votekicks.as_mut() {
+            Some(votekicks) => votekicks.expire(),
+            None => return Ok(()),
+        };
+        for target in expired {
+            let Some(member) = self.try_find_member_by_id(target) else {
+                continue;
+            };
+            let message = message::Announce::new(
+                member.user.clone().into(),
+                "stays, the vote to kick them expired".to_string(),
+            );
+            self.send_message(message.into()).await?;
+        }
+        Ok(())
+    }
+
+    /// Closes polls that were open for this long
+    pub fn set_poll_window(&mut self, window: Duration) {
+        self.polls.set_window(window);
+    }
+
+    pub fn poll_window(&self) -> Duration {
+        self.polls.window()
+    }
+
+    /// Lets only operators start polls
+    pub fn set_polls_ops_only(&mut self, ops_only: bool) {
+        self.polls.set_ops_only(ops_only);
+    }
+
+    pub fn polls_ops_only(&self) -> bool {
+        self.polls.ops_only()
+    }
+
+    pub fn poll(&self) -> Option<&Poll> {
+        self.polls.active()
+    }
+
+    /// Opens a poll, unless one is open already
+    pub fn start_poll(
+        &mut self,
+        by: &User,
+        question: String,
+        options: Vec<String>,
+    ) -> Result<(), String> {
+        self.polls.start(by.clone().into(), question, options)
+    }
+
+    /// Counts the user's vote for the option numbered from 1 and
+    /// returns the option voted for. Each key votes once, whatever its
+    /// sessions
+    pub fn vote_in_poll(&mut self, voter: UserId, option: usize) -> Result<String, String> {
+        let voter_key = self
+            .try_find_member_by_id(voter)
+            .map(|member| member.user.public_key().fingerprint())
+            .ok_or("user not found".to_string())?;
+        self.polls.vote(&voter_key, option).map(str::to_string)
+    }
+
+    pub fn close_poll(&mut self) -> Option<Poll> {
+        self.polls.close()
+    }
+
+    /// Closes the poll if it was open for longer than the window and
+    /// tells the room its results
+    pub async fn announce_expired_poll(&mut self) -> anyhow::Result<()> {
+        if let Some(poll) = self.polls.expire() {
+            let message = message::Announce::new(
+                poll.by.clone(),
+                format!("closed the poll as time ran out: {}", poll.results()),
+            );
+            self.send_message(message.into()).await?;
+        }
+        Ok(())
+    }
+
+    /// Registers a public message body sent by the user and tells if
+    /// it repeats the previous ones more times than allowed
+    pub fn is_flood(&mut self, user_id: UserId, body: &str) -> bool {
+        self.repeats.is_flood(user_id, body)
+    }
+
+    /// Loads blocked words from the file, one per line. Returns the
+    /// number of words loaded
+    pub fn load_blocklist_file(&mut self, path: &str) -> std::io::Result<usize> {
+        self.blocklist.load_file(path)
+    }
+
+    /// Re-reads the blocklist file. Returns `None` if the blocklist did
+    /// not come from a file
+    pub fn reload_blocklist(&mut self) -> Option<std::io::Result<usize>> {
+        self.blocklist.reload()
+    }
+
+    /// Sets how many blocked messages within the window get a user
+    /// kicked. Zero only drops the messages.
+    pub fn set_max_strikes(&mut self, max_strikes: usize) {
+        self.blocklist.set_max_strikes(max_strikes);
+    }
+
+    /// Checks the message body against the blocklist and returns `true`
+    /// if it was dropped. The sender is warned about the strike, or
+    /// kicked once out of strikes
+    pub async fn check_blocklist(&mut self, user: &User, body: &str) -> anyhow::Result<bool> {
+        let message: Message = match self.blocklist.check(user.id(), body) {
+            BlocklistVerdict::Clean => return Ok(false),
+            BlocklistVerdict::Strike(count, 0) => message::Error::new(
+                user.clone().into(),
+                format!(
+                    "message dropped. It contains a blocked word (strike {})",
+                    count
+                ),
+            )
+            .into(),
+            BlocklistVerdict::Strike(count, max) => message::Error::new(
+                user.clone().into(),
+                format!(
+                    "message dropped. It contains a blocked word (strike {} of {})",
+                    count, max
+                ),
+            )
+            .into(),
+            BlocklistVerdict::Kick => {
+                self.find_member(user.username())
+                    .exit(LeaveReason::Kicked(None))?;
+                message::Announce::new(
+                    user.clone().into(),
+                    "was kicked for using blocked words".to_string(),
+                )
+                .into()
+            }
+        };
+        self.send_message(message).await?;
+        Ok(true)
+    }
+
+    /// Runs the checks a public message body of a non-operator goes
+    /// through, repeats, blocked words and slow mode, and returns `true`
+    /// if it was dropped. The sender is told why
+    pub async fn check_public_message(&mut self, user: &User, body: &str) -> anyhow::Result<bool> {
+        if self.is_flood(user.id(), body) {
+            let message = message::Error::new(
+                user.clone().into(),
+                "repeated message dropped. Try saying something new".to_string(),
+            );
+            self.send_message(message.into()).await?;
+            return Ok(true);
+        }
+        if self.check_blocklist(user, body).await? {
+            return Ok(true);
+        }
+        if let Some(remaining) = self.check_slow_mode(user).await? {
+            let remaining = Duration::from_secs(remaining.as_secs_f64().ceil() as u64);
+            let message = message::Error::new(
+                user.clone().into(),
+                format!(
+                    "slow mode is on. Message dropped. Next allowed in {}",
+                    humantime::format_duration(remaining)
+                ),
+            );
+            self.send_message(message.into()).await?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Mutes or unmutes the key by its fingerprint and returns whether it
+    /// is muted now. Unlike a member's mute, it holds for whoever uses
+    /// the key, whatever name they go by
+    pub fn switch_key_mute(&mut self, fingerprint: &str) -> bool {
+        match self.muted_keys.remove(fingerprint) {
+            true => false,
+            false => self.muted_keys.insert(fingerprint.to_string()),
+        }
+    }
+
+    /// Checks whether the author is muted, either directly or through
+    /// the key they joined with
+    pub fn is_author_muted(&self, author: &message::Author) -> bool {
+        if author.is_muted() {
+            return true;
+        }
+        self.names
+            .get(&author.id())
+            .and_then(|name| self.members.get(name))
+            .is_some_and(|member| {
+                self.muted_keys
+                    .contains(&member.user.public_key().fingerprint())
+            })
+    }
+
+    /// Mutes or unmutes every member that isn't exempt and returns how
+    /// many were switched
+    pub fn switch_mute_all<F>(&mut self, muted: bool, is_exempt: F) -> usize
+    where
+        F: Fn(&User) -> bool,
+    {
+        let mut count = 0;
+        for member in self.members.values_mut() {
+            if member.user.is_muted() != muted && !is_exempt(&member.user) {
+                member.user.switch_mute_mode();
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Returns the names of members connected from the address, sorted.
+    /// IPv4-mapped IPv6 addresses are matched as IPv4
+    pub fn members_from_ip(&self, ip: &IpAddr) -> Vec<&UserName> {
+        let ip = ip.to_canonical();
+        let mut names = self
+            .members
+            .iter()
+            .filter(|(_, member)| {
+                member.user.peer_addr().map(|addr| addr.ip().to_canonical()) == Some(ip)
+            })
+            .map(|(name, _)| name)
+            .collect::<Vec<&UserName>>();
+        names.sort_by_key(|name| name.to_lowercase());
+        names
+    }
+
+    /// Returns the users connected with the key, oldest session first
+    pub fn sessions_of_key(&self, fingerprint: &str) -> Vec<&User> {
+        let mut sessions = self
+            .members
+            .values()
+            .map(|member| &member.user)
+            .filter(|user| user.public_key().fingerprint() == fingerprint)
+            .collect::<Vec<&User>>();
+        sessions.sort_by_key(|user| (user.joined_at(), user.id()));
+        sessions
+    }
+
+    /// Counts the entries of the per-user maps and the references to
+    /// users who already left
+    pub fn sizes(&self) -> RoomSizes {
+        let is_stale = |id: &UserId| !self.names.contains_key(id);
+        let member_refs = self.members.values().map(|member| {
+            let user = &member.user;
+            let ids = user
+                .ignored()
+                .iter()
+                .chain(user.focused())
+                .chain(user.excluded());
+            ids.filter(|id| is_stale(id)).count()
+        });
+        let saved_refs = self.saved_focus.values().map(|(focused, excluded)| {
+            focused
+                .iter()
+                .chain(excluded)
+                .filter(|id| is_stale(id))
+                .count()
+        });
+        RoomSizes {
+            members: self.members.len(),
+            names: self.names.len(),
+            ratelims: self.ratelims.len(),
+            repeats: self.repeats.tracked_users(),
+            cooldowns: self.cooldowns.tracked_users(),
+            slow_mode: self.slow_mode.tracked_users(),
+            strikes: self.blocklist.tracked_users(),
+            saved_focus: self.saved_focus.len(),
+            votekicks: self.votekicks.as_ref().map_or(0, VoteKicks::open_polls),
+            newcomers: self.newcomers.as_ref().map_or(0, Newcomers::tracked_keys),
+            stale_refs: member_refs.chain(saved_refs).sum(),
+        }
+    }
+
     pub fn add_member(&mut self, name: UserName, member: RoomMember) {
         self.members.insert(name, member);
+        self.refresh_username_colors();
     }
 
     pub fn remove_member(&mut self, username: &UserName) {
         self.members.remove(username);
+        self.refresh_username_colors();
     }
 
     pub fn add_name(&mut self, id: UserId, name: UserName) {
         self.names.insert(id, name);
     }
 
-    pub fn members_iter_mut(&mut self) -> IterMut<UserName, RoomMember> {
-        self.members.iter_mut()
-    }
-
     pub fn members_iter(&self) -> Iter<UserName, RoomMember> {
         self.members.iter()
     }
@@ -83,31 +901,108 @@ impl ChatRoom {
         &self.names
     }
 
+    /// Returns the name, the away reason and the time spent away for
+    /// every away member, sorted by name
+    pub fn away_members(&self) -> Vec<(UserName, String, Duration)> {
+        let now = Utc::now();
+        let mut away = self
+            .members
+            .values()
+            .filter_map(|member| match member.user.status() {
+                UserStatus::Away { reason, since, .. } => {
+                    let secs = now.signed_duration_since(since).num_seconds() as u64;
+                    let duration = Duration::from_secs(secs);
+                    Some((member.user.username().clone(), reason.clone(), duration))
+                }
+                UserStatus::Active => None,
+            })
+            .collect::<Vec<_>>();
+        away.sort_by_key(|(name, _, _)| name.to_lowercase());
+        away
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub async fn join(
         &mut self,
         user_id: UserId,
         username: String,
         key: PubKey,
         ssh_id: String,
+        peer_addr: Option<SocketAddr>,
         message_tx: mpsc::Sender<String>,
-        exit_tx: watch::Sender<()>,
+        exit_tx: watch::Sender<LeaveReason>,
     ) -> anyhow::Result<User> {
-        let username = match self.is_room_member(&username) {
-            true => rand::random::<UserName>(),
-            false if username.trim().is_empty() => rand::random::<UserName>(),
-            false => sanitize::name(&username).into(),
+        if let Some(mode) = self.single_session {
+            self.resolve_duplicate_session(&key, mode).await?;
+        }
+
+        let fingerprint = key.fingerprint();
+        let held_name = match &mut self.reconnect {
+            Some(reconnect) => reconnect.take(&fingerprint),
+            None => None,
+        };
+        let mut username = match held_name.filter(|name| !self.is_room_member(name)) {
+            Some(name) => name,
+            None => match sanitize::name(&username) {
+                name if name.trim().is_empty() => rand::random::<UserName>(),
+                name => self.suggest_available_name(&name),
+            },
+        };
+        let lookalike = match self.lookalike_names {
+            Some(_) => self.find_lookalike(&username, None).cloned(),
+            None => None,
+        };
+        let rejected_name = match (self.lookalike_names, &lookalike) {
+            (Some(LookalikeNameMode::Reject), Some(_)) => {
+                let name = username.clone();
+                username = rand::random::<UserName>();
+                Some(name)
+            }
+            _ => None,
         };
 
-        let user = User::new(user_id, username.clone(), ssh_id, key);
+        let mut user = User::new(user_id, username.clone(), ssh_id, key);
+        user.set_theme(self.default_theme.into());
+        user.config_mut()
+            .set_timestamp_mode(self.default_timestamp_mode);
+        user.set_peer_addr(peer_addr);
+        if let Some((focused, excluded)) = self.saved_focus.get(&fingerprint) {
+            user.restore_focus(focused.clone(), excluded.clone());
+        }
+        let is_known = self
+            .welcome
+            .as_ref()
+            .is_some_and(|welcome| welcome.has_seen(&fingerprint));
+        if let Some(newcomers) = self.newcomers.as_mut() {
+            match newcomers.register(&fingerprint, is_known) {
+                Ok(true) => user.mark_newcomer(),
+                Ok(false) => {}
+                Err(err) => {
+                    warn!("Failed to save the first join of {fingerprint}: {err}");
+                    user.mark_newcomer();
+                }
+            }
+        }
         let member = RoomMember::new(user.clone(), message_tx, exit_tx);
 
+        for (_, member) in self.members.iter_mut() {
+            member.user.resolve_pending(&username, user_id);
+        }
         self.members.insert(username.clone(), member);
         self.names.insert(user_id, username.clone());
+        self.peak_members = self.peak_members.max(self.members.len());
+        self.refresh_prompts();
+        self.refresh_username_colors();
         self.ratelims
             .insert(user_id, RateLimit::direct(MESSAGE_RATE_QUOTA));
 
-        self.send_motd(&username).await;
+        if self.motd_enabled && self.is_motd_due(&fingerprint) {
+            self.send_motd(&username).await;
+        }
+        self.send_welcome(&username, &fingerprint).await;
+        self.send_pins(&username).await;
         self.feed_history(&username).await;
+        self.send_offline_messages(&username, &fingerprint).await;
 
         let message = message::Announce::new(
             user.clone().into(),
@@ -115,12 +1010,108 @@ impl ChatRoom {
         );
         self.send_message(message.into()).await?;
 
+        match (rejected_name, lookalike) {
+            (Some(name), Some(other)) => {
+                let message = message::Error::new(
+                    user.clone().into(),
+                    format!(
+                        "\"{}\" name looks too much like {}, joined as {}",
+                        name,
+                        other,
+                        user.username()
+                    ),
+                );
+                self.send_message(message.into()).await?;
+            }
+            (None, Some(other)) => {
+                let message = message::Announce::new(
+                    user.clone().into(),
+                    format!("looks a lot like {}, beware of impersonation", other),
+                );
+                self.send_message(message.into()).await?;
+            }
+            _ => {}
+        }
+
         Ok(user)
     }
 
+    /// Ignores or focuses on the named users for the member, right away
+    /// for those online and as soon as they join for the others. Returns
+    /// the names found online and the ones left pending
+    pub fn ignore_or_focus_by_name(
+        &mut self,
+        username: &UserName,
+        names: &[String],
+        focus: bool,
+    ) -> (Vec<UserName>, Vec<UserName>) {
+        let targets = names
+            .iter()
+            .map(UserName::from)
+            .filter(|name| name != username)
+            .map(|name| (self.try_find_member(&name).map(|m| m.user.id()), name))
+            .collect::<Vec<(Option<UserId>, UserName)>>();
+        let user = &mut self.find_member_mut(username).user;
+        let (mut found, mut pending) = (vec![], vec![]);
+        for (id, name) in targets {
+            match id {
+                Some(id) if focus => user.focus(id),
+                Some(id) => user.ignore(id),
+                None if focus => user.focus_later(name.clone()),
+                None => user.ignore_later(name.clone()),
+            }
+            match id {
+                Some(_) => found.push(name),
+                None => pending.push(name),
+            }
+        }
+        (found, pending)
+    }
+
+    async fn resolve_duplicate_session(
+        &mut self,
+        key: &PubKey,
+        mode: SingleSessionMode,
+    ) -> anyhow::Result<()> {
+        let fingerprint = key.fingerprint();
+        let duplicate = self
+            .members
+            .values()
+            .find(|member| member.user.public_key().fingerprint() == fingerprint);
+
+        let member = match duplicate {
+            Some(member) => member,
+            None => return Ok(()),
+        };
+
+        match mode {
+            SingleSessionMode::Reject => {
+                anyhow::bail!("another session with this key is already connected")
+            }
+            SingleSessionMode::Evict => {
+                // The old session leaves the room on its own once signalled
+                let message = message::Error::new(
+                    member.user.clone().into(),
+                    "disconnected: logged in from another session".to_string(),
+                );
+                member.send_message(message.into()).await?;
+                member.exit(LeaveReason::Quit)?;
+                Ok(())
+            }
+        }
+    }
+
     pub async fn send_motd(&mut self, username: &UserName) {
-        let motd = self.motd.clone();
+        let motd = match self.motd_banners.is_empty() {
+            true => self.motd.clone(),
+            false => {
+                let banner = self.next_banner % self.motd_banners.len();
+                self.next_banner = banner + 1;
+                self.motd_banners[banner].clone()
+            }
+        };
         let member = self.find_member(username);
+        let motd = member.user.config().theme().render_markup(&motd);
         let message = message::System::new(
             member.user.clone().into(),
             format!("{}{}", motd, utils::NEWLINE),
@@ -128,16 +1119,109 @@ impl ChatRoom {
         let _ = member.send_message(message.into()).await;
     }
 
-    pub async fn feed_history(&mut self, username: &UserName) {
-        let member = self.find_member(username);
-        for msg in self.history.iter() {
-            if let Err(_) = member.send_message(msg.to_owned()).await {
-                continue;
+    fn is_motd_due(&mut self, fingerprint: &str) -> bool {
+        let throttle = match self.motd_throttle.as_mut() {
+            Some(throttle) => throttle,
+            None => return true,
+        };
+        match throttle.register(fingerprint) {
+            Ok(is_due) => is_due,
+            Err(err) => {
+                warn!("Failed to save the MOTD time of {fingerprint}: {err}");
+                true
             }
         }
     }
 
-    pub async fn leave(&mut self, user_id: &UserId) -> anyhow::Result<()> {
+    async fn send_welcome(&mut self, username: &UserName, fingerprint: &str) {
+        let welcome = match self.welcome.as_mut() {
+            Some(welcome) => welcome,
+            None => return,
+        };
+        match welcome.register(fingerprint) {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(err) => warn!("Failed to save the seen key {fingerprint}: {err}"),
+        }
+
+        let text = welcome.message().to_string();
+        let member = self.find_member(username);
+        let message = message::System::new(
+            member.user.clone().into(),
+            format!("{}{}", text, utils::NEWLINE),
+        );
+        let _ = member.send_message(message.into()).await;
+    }
+
+    async fn send_offline_messages(&mut self, username: &UserName, fingerprint: &str) {
+        let messages = match self.offline.as_mut() {
+            Some(offline) => offline.take(fingerprint),
+            None => return,
+        };
+        let member = self.find_member(username);
+        for queued in messages {
+            let message = message::System::new(
+                member.user.clone().into(),
+                format!(
+                    "[offline message] {} ({} ago): {}",
+                    queued.from,
+                    humantime::format_duration(Duration::from_secs(
+                        queued.queued_at.elapsed().as_secs()
+                    )),
+                    queued.text
+                ),
+            );
+            let _ = member.send_message(message.into()).await;
+        }
+    }
+
+    /// Sends the goodbye banner, if any, to the member about to leave
+    pub async fn send_goodbye(&self, username: &UserName) {
+        let goodbye = match &self.goodbye {
+            Some(goodbye) => goodbye,
+            None => return,
+        };
+        let member = self.find_member(username);
+        let goodbye = member.user.config().theme().render_markup(goodbye);
+        let message = message::System::new(member.user.clone().into(), goodbye);
+        let _ = member.send_message(message.into()).await;
+    }
+
+    pub async fn send_pins(&mut self, username: &UserName) {
+        if self.pins.is_empty() {
+            return;
+        }
+        let member = self.find_member(username);
+        let message = message::System::new(
+            member.user.clone().into(),
+            format!("{}{}", self.pins.render(), utils::NEWLINE),
+        );
+        let _ = member.send_message(message.into()).await;
+    }
+
+    /// Replays the history to the member with the age of each message,
+    /// between markers that set it apart from the live messages
+    pub async fn feed_history(&mut self, username: &UserName) {
+        let member = self.find_member(username);
+        if self.history.iter().next().is_none() {
+            return;
+        }
+        let marker = |text: &str| message::System::new(member.user.clone().into(), text.into());
+        let _ = member
+            .send_message(marker(HISTORY_REPLAY_MARKER).into())
+            .await;
+        let now = Utc::now();
+        for msg in self.history.iter() {
+            if member.send_replayed_message(msg, now).is_err() {
+                continue;
+            }
+        }
+        let _ = member
+            .send_message(marker(HISTORY_LIVE_MARKER).into())
+            .await;
+    }
+
+    pub async fn leave(&mut self, user_id: &UserId, reason: LeaveReason) -> anyhow::Result<()> {
         let username = match self.try_get_name(user_id) {
             Some(name) => name.clone(),
             None => return Ok(()),
@@ -145,24 +1229,58 @@ impl ChatRoom {
 
         let member = self.find_member(&username);
         let user = &member.user;
+        let fingerprint = user.public_key().fingerprint();
+        let focus = (user.focused().clone(), user.excluded().clone());
         let duration = humantime::format_duration(user.joined_duration());
-        let message =
-            message::Announce::new(user.clone().into(), format!("left: (After {})", duration));
+        let message = message::Announce::new(
+            user.clone().into(),
+            match member.leave_note() {
+                Some(note) => format!("{}: {} (After {})", reason.announce(), note, duration),
+                None => format!("{}: (After {})", reason.announce(), duration),
+            },
+        );
         self.send_message(message.into()).await?;
 
         self.members.remove(&username);
         self.names.remove(user_id);
         self.ratelims.remove(user_id);
+        self.refresh_prompts();
+        self.refresh_username_colors();
+        self.repeats.forget(user_id);
+        self.cooldowns.forget(user_id);
+        self.slow_mode.forget(user_id);
+        self.blocklist.forget(user_id);
+        let key_left = self.sessions_of_key(&fingerprint).is_empty();
+        if let Some(votekicks) = self.votekicks.as_mut() {
+            votekicks.forget(user_id);
+            if key_left {
+                votekicks.forget_voter(&fingerprint);
+            }
+        }
 
         for (_, member) in &mut self.members {
             member.user.unignore(user_id);
             member.user.unfocus(user_id);
         }
 
+        // Keep the focus filter for a reconnect with the same key
+        for (focused, excluded) in self.saved_focus.values_mut() {
+            focused.remove(user_id);
+            excluded.remove(user_id);
+        }
+        // Filters left with nobody to focus on would pile up with churn
+        self.saved_focus
+            .retain(|_, (focused, excluded)| !focused.is_empty() || !excluded.is_empty());
+        match focus.0.is_empty() && focus.1.is_empty() {
+            true => self.saved_focus.remove(&fingerprint),
+            false => self.saved_focus.insert(fingerprint, focus),
+        };
+
         Ok(())
     }
 
     pub async fn send_message(&mut self, msg: Message) -> anyhow::Result<()> {
+        let mut dead = vec![];
         match msg {
             Message::System(ref m) => {
                 let member = self.find_member(&m.from().username());
@@ -177,51 +1295,71 @@ impl ChatRoom {
                 member.send_message(msg).await?;
             }
             Message::Public(ref m) => {
+                if let Some(member) = self.try_find_member(m.from().username()) {
+                    member.clear_unread();
+                }
                 self.history.push(msg.clone());
+                let is_muted = self.is_author_muted(m.from());
                 for (_, member) in self.members.iter() {
-                    if m.from().is_muted() && member.user.id() == m.from().id() {
+                    if member.is_dead() {
+                        continue;
+                    }
+                    if is_muted && member.user.id() == m.from().id() {
                         member.send_user_is_muted_message().await?;
                     }
-                    if m.from().is_muted() {
+                    if is_muted {
                         continue;
                     }
                     if member.user.ignored().contains(&m.from().id()) {
                         continue;
                     }
-                    if !member.user.focused().is_empty()
-                        && !member.user.focused().contains(&m.from().id())
-                    {
+                    if !member.user.config().is_in_focus(m.from().id()) {
                         continue;
                     }
                     if let Err(_) = member.send_message(msg.clone()).await {
-                        continue;
+                        dead.push(member.user.id());
                     }
                 }
             }
             Message::Emote(ref m) => {
+                if let Some(member) = self.try_find_member(m.from().username()) {
+                    member.clear_unread();
+                }
                 self.history.push(msg.clone());
+                let is_muted = self.is_author_muted(m.from());
                 for (_, member) in self.members.iter() {
-                    if m.from().is_muted() && member.user.id() == m.from().id() {
+                    if member.is_dead() {
+                        continue;
+                    }
+                    if is_muted && member.user.id() == m.from().id() {
                         member.send_user_is_muted_message().await?;
                     }
-                    if m.from().is_muted() {
+                    if is_muted {
                         continue;
                     }
                     if member.user.ignored().contains(&m.from().id()) {
                         continue;
                     }
-                    if let Err(_) = member.send_message(msg.clone()).await {
-                        continue;
+                    let msg = match m.target() {
+                        Some(target) if target == member.user.id() => m.highlighted().into(),
+                        _ => msg.clone(),
+                    };
+                    if member.send_message(msg).await.is_err() {
+                        dead.push(member.user.id());
                     }
                 }
             }
             Message::Announce(ref m) => {
                 self.history.push(msg.clone());
+                let is_muted = self.is_author_muted(m.from());
                 for (_, member) in self.members.iter() {
-                    if m.from().is_muted() && member.user.id() == m.from().id() {
+                    if member.is_dead() {
+                        continue;
+                    }
+                    if is_muted && member.user.id() == m.from().id() {
                         member.send_user_is_muted_message().await?;
                     }
-                    if m.from().is_muted() {
+                    if is_muted {
                         continue;
                     }
                     if member.user.config().quiet() {
@@ -231,28 +1369,103 @@ impl ChatRoom {
                         continue;
                     }
                     if let Err(_) = member.send_message(msg.clone()).await {
-                        continue;
+                        dead.push(member.user.id());
                     }
                 }
             }
-            Message::Private(ref m) => {
-                let from = self.find_member(&m.from().username());
-
-                if m.from().is_muted() {
-                    from.send_user_is_muted_message().await?;
-                    return Ok(());
-                }
-
-                let to = self.find_member(&m.to().username());
-                if !to.user.ignored().contains(&m.from().id()) {
-                    to.send_message(msg).await?;
-                }
+            Message::Private(m) => {
+                self.send_private_message(m).await?;
             }
         }
 
+        self.close_dead_members(dead);
         Ok(())
     }
 
+    /// Stops sending to the members whose session went away and tells
+    /// their session to leave, which removes them from the room
+    fn close_dead_members(&mut self, dead: Vec<UserId>) {
+        for user_id in dead {
+            let username = match self.try_get_name(&user_id) {
+                Some(name) => name.clone(),
+                None => continue,
+            };
+            warn!(
+                "Closing session of user {} which stopped receiving",
+                username
+            );
+            let member = self.find_member_mut(&username);
+            member.mark_dead();
+            let _ = member.exit(LeaveReason::Quit);
+        }
+    }
+
+    /// Sends the private message and tells whether the recipient got it
+    pub async fn send_private_message(
+        &mut self,
+        msg: message::Private,
+    ) -> anyhow::Result<PrivateDelivery> {
+        let from = self.find_member(msg.from().username());
+
+        if self.is_author_muted(msg.from()) {
+            from.send_user_is_muted_message().await?;
+            return Ok(PrivateDelivery::Muted);
+        }
+
+        from.clear_unread();
+
+        let to = self.find_member(msg.to().username());
+        if to.user.ignored().contains(&msg.from().id()) {
+            return Ok(PrivateDelivery::Ignored);
+        }
+
+        if self.unread_badge && to.user.id() != msg.from().id() {
+            to.add_unread();
+        }
+        let wants_receipt = from.user.config().read_receipts() && to.user.config().read_receipts();
+        let (to_username, from_id) = (to.user.username().clone(), msg.from().id());
+        to.send_message(msg.into()).await?;
+        if wants_receipt {
+            self.find_member_mut(&to_username).track_unseen_pm(from_id);
+        }
+        Ok(PrivateDelivery::Delivered)
+    }
+
+    /// Tells the senders of the PMs the user hasn't seen yet that the
+    /// user saw them, on any input the user submits. Nothing is sent
+    /// unless both sides still have read receipts on. A sender that
+    /// can't be told doesn't keep the others from being told
+    pub async fn notify_seen(&mut self, username: &UserName) {
+        let member = match self.members.get_mut(username) {
+            Some(member) => member,
+            None => return,
+        };
+        let senders = member.take_unseen_pms();
+        if senders.is_empty() || !member.user.config().read_receipts() {
+            return;
+        }
+        let reader = member.user.clone();
+        for (sender_id, count) in senders {
+            let sender = match self.names.get(&sender_id).and_then(|n| self.members.get(n)) {
+                Some(sender) if sender.user.config().read_receipts() => sender,
+                _ => continue,
+            };
+            let text = match count {
+                1 => format!("{} saw your message", reader.username()),
+                n => format!("{} saw your {} messages", reader.username(), n),
+            };
+            let message = message::System::new(sender.user.clone().into(), text);
+            if let Err(err) = sender.send_message(message.into()).await {
+                warn!(
+                    "Failed to tell {} that {} saw their messages: {}",
+                    sender.user.username(),
+                    reader.username(),
+                    err
+                );
+            }
+        }
+    }
+
     pub fn find_name_by_prefix(&self, prefix: &str, skip: &str) -> Option<String> {
         if prefix.is_empty() {
             return None;
@@ -279,10 +1492,37 @@ impl ChatRoom {
         None
     }
 
+    /// Returns `base` if no member uses it yet, otherwise the first
+    /// free name made by appending a numeric suffix: `alice2`,
+    /// `alice3` and so on.
+    pub fn suggest_available_name(&self, base: &str) -> UserName {
+        if !self.is_name_taken(base) {
+            return base.into();
+        }
+
+        let mut suffix = 2;
+        loop {
+            let name = sanitize::suffixed_name(base, &suffix.to_string());
+            if !self.is_name_taken(&name) {
+                return name.into();
+            }
+            suffix += 1;
+        }
+    }
+
     pub fn is_room_member(&self, username: &str) -> bool {
         self.members.contains_key(&username.into())
     }
 
+    /// Checks if the name is used by a member or held for a reconnect
+    pub fn is_name_taken(&self, username: &str) -> bool {
+        let held = match &self.reconnect {
+            Some(reconnect) => reconnect.is_held(&username.into()),
+            None => false,
+        };
+        held || self.is_room_member(username)
+    }
+
     pub fn find_member(&self, username: &UserName) -> &RoomMember {
         self.members
             .get(&username)
@@ -296,11 +1536,27 @@ impl ChatRoom {
     }
 
     pub fn find_member_by_id(&mut self, user_id: UserId) -> &RoomMember {
-        self.try_get_name(&user_id)
-            .and_then(|name| self.try_find_member(&name))
+        self.try_find_member_by_id(user_id)
             .expect(format!("User {user_id} should be a member of the server room").as_str())
     }
 
+    /// Notes that the user typed something, which keeps them from being
+    /// disconnected as idle
+    pub fn register_input(&mut self, user_id: UserId) {
+        let member = self
+            .names
+            .get(&user_id)
+            .and_then(|name| self.members.get_mut(name));
+        if let Some(member) = member {
+            member.register_input();
+        }
+    }
+
+    pub fn try_find_member_by_id(&self, user_id: UserId) -> Option<&RoomMember> {
+        self.try_get_name(&user_id)
+            .and_then(|name| self.try_find_member(name))
+    }
+
     pub fn try_find_member(&self, username: &UserName) -> Option<&RoomMember> {
         self.members.get(&username)
     }
@@ -318,110 +1574,1483 @@ impl ChatRoom {
 mod should {
     use std::usize;
 
-    use super::*;
-    use crate::chat::user::{User, UserName};
-    use crate::pubkey::PubKey;
-    use message::Author;
-    use tokio::sync::{mpsc, watch};
+    use super::*;
+    use crate::chat::user::{User, UserName};
+    use crate::pubkey::PubKey;
+    use crate::utils::BEL;
+    use message::Author;
+    use tokio::sync::{mpsc, watch};
+
+    fn create_test_pubkey() -> PubKey {
+        let key_pair = russh_keys::key::KeyPair::generate_ed25519().unwrap();
+        PubKey::from(key_pair.clone_public_key().unwrap())
+    }
+
+    pub struct MockChannel {
+        tx: mpsc::Sender<String>,
+        rx: mpsc::Receiver<String>,
+        messages: Vec<String>,
+    }
+
+    impl MockChannel {
+        pub fn new(buffer: usize) -> Self {
+            let (tx, rx) = mpsc::channel(buffer);
+            let messages = Vec::new();
+            Self { tx, rx, messages }
+        }
+    }
+
+    #[tokio::test]
+    async fn create_chat_room() {
+        let chat_room = ChatRoom::new("Welcome to the chat room!");
+
+        assert_eq!(chat_room.motd(), "Welcome to the chat room!");
+        assert_eq!(chat_room.uptime(), "0s");
+        assert_eq!(chat_room.peak_members(), 0);
+        assert!(chat_room.names().is_empty());
+        assert!(chat_room.members_iter().count() == 0);
+    }
+
+    #[tokio::test]
+    async fn track_peak_members_on_join_only() {
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        let channel = MockChannel::new(20);
+
+        for (id, name) in [(1, "alice"), (2, "bob")] {
+            chat_room
+                .join(
+                    id,
+                    name.to_string(),
+                    PubKey::default(),
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+        }
+        assert_eq!(chat_room.peak_members(), 2);
+
+        chat_room.leave(&1, LeaveReason::Quit).await.unwrap();
+        assert_eq!(chat_room.names().len(), 1);
+        assert_eq!(chat_room.peak_members(), 2);
+
+        chat_room
+            .join(
+                3,
+                "carol".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(chat_room.peak_members(), 2);
+    }
+
+    #[tokio::test]
+    async fn set_and_get_motd() {
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_motd("New MOTD").unwrap();
+
+        assert_eq!(chat_room.motd(), "New MOTD");
+    }
+
+    #[tokio::test]
+    async fn refuse_motd_over_the_limit() {
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_motd_max_len(16);
+        assert!(chat_room.set_motd(&"x".repeat(16)).is_ok());
+        assert!(chat_room.set_motd(&"y".repeat(17)).is_err());
+        assert_eq!(chat_room.motd(), &"x".repeat(16));
+
+        assert!(chat_room.append_motd("more").is_err());
+        assert!(chat_room.prepend_motd("more").is_err());
+        assert_eq!(chat_room.motd(), &"x".repeat(16));
+    }
+
+    #[tokio::test]
+    async fn strip_escapes_from_submitted_motd() {
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_motd("\x1b[2JHello\x1b]0;pwned\x07").unwrap();
+        assert_eq!(chat_room.motd(), "Hello");
+
+        chat_room.append_motd("\x1b[31mBe nice").unwrap();
+        assert_eq!(chat_room.motd(), &format!("Hello{}Be nice", utils::NEWLINE));
+    }
+
+    #[tokio::test]
+    async fn append_and_prepend_motd_lines() {
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.append_motd("Be nice").unwrap();
+        chat_room.prepend_motd("Hello").unwrap();
+        assert_eq!(
+            chat_room.motd(),
+            &format!("Hello{0}Welcome!{0}Be nice", utils::NEWLINE)
+        );
+
+        let mut chat_room = ChatRoom::new("");
+        chat_room.append_motd("Be nice").unwrap();
+        assert_eq!(chat_room.motd(), "Be nice");
+    }
+
+    #[tokio::test]
+    async fn reload_motd_from_file() {
+        use assert_fs::fixture::{FileWriteStr, PathChild};
+
+        let mut chat_room = ChatRoom::new("Welcome!");
+        assert!(chat_room.reload_motd().is_none());
+
+        let temp = assert_fs::TempDir::new().unwrap();
+        let file = temp.child("motd.txt");
+        file.write_str("line 1\nline 2").unwrap();
+        let path = file.path().to_str().unwrap();
+
+        chat_room.load_motd_file(path).unwrap();
+        assert_eq!(chat_room.motd(), "line 1\n\rline 2");
+
+        file.write_str("updated").unwrap();
+        assert!(chat_room.reload_motd().unwrap().is_ok());
+        assert_eq!(chat_room.motd(), "updated");
+    }
+
+    #[tokio::test]
+    async fn rotate_motd_banners_from_dir() {
+        use assert_fs::fixture::{FileWriteStr, PathChild};
+
+        let temp = assert_fs::TempDir::new().unwrap();
+        temp.child("1.ans").write_str("first\nart").unwrap();
+        temp.child("2.ans").write_str("second art").unwrap();
+        temp.child("readme.txt").write_str("not a banner").unwrap();
+
+        let mut channel = MockChannel::new(20);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        let path = temp.path().to_str().unwrap();
+        assert_eq!(chat_room.load_motd_dir(path).unwrap(), 2);
+
+        for id in 1..=3 {
+            chat_room
+                .join(
+                    id,
+                    "alice".to_string(),
+                    PubKey::default(),
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+            chat_room.leave(&id, LeaveReason::Quit).await.unwrap();
+        }
+
+        while let Ok(msg) = channel.rx.try_recv() {
+            channel.messages.push(msg);
+        }
+        let banners = channel
+            .messages
+            .iter()
+            .filter_map(|msg| match msg {
+                msg if msg.contains("first\n\rart") => Some(1),
+                msg if msg.contains("second art") => Some(2),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(banners, vec![1, 2, 1]);
+        assert!(!channel.messages.iter().any(|msg| msg.contains("Welcome!")));
+    }
+
+    #[tokio::test]
+    async fn send_goodbye_from_file_unless_empty() {
+        use assert_fs::fixture::{FileWriteStr, PathChild};
+
+        let temp = assert_fs::TempDir::new().unwrap();
+        let file = temp.child("goodbye.txt");
+        file.write_str("See you\nsoon").unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let mut channel = MockChannel::new(20);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        let user = chat_room
+            .join(
+                1,
+                "alice".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                channel.tx.clone(),
+                exit_tx,
+            )
+            .await
+            .unwrap();
+        while channel.rx.try_recv().is_ok() {}
+
+        chat_room.send_goodbye(user.username()).await;
+        assert!(channel.rx.try_recv().is_err());
+
+        chat_room.load_goodbye_file(path).unwrap();
+        chat_room.send_goodbye(user.username()).await;
+        file.write_str("  \n").unwrap();
+        chat_room.load_goodbye_file(path).unwrap();
+        chat_room.send_goodbye(user.username()).await;
+
+        while let Ok(msg) = channel.rx.try_recv() {
+            channel.messages.push(msg);
+        }
+        assert_eq!(channel.messages.len(), 1);
+        assert!(channel.messages[0].contains("See you\n\rsoon"));
+    }
+
+    #[tokio::test]
+    async fn return_to_baseline_sizes_after_join_leave_cycles() {
+        let channel = MockChannel::new(1000);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        let alice = chat_room
+            .join(
+                1,
+                "alice".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+        let baseline = chat_room.sizes();
+
+        let key = PubKey::default();
+        for id in 2..=50 {
+            let bob = chat_room
+                .join(
+                    id,
+                    "bob".to_string(),
+                    key.clone(),
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+            let member = chat_room.find_member_mut(alice.username());
+            member.user.ignore(id);
+            member.user.focus(id);
+            chat_room.find_member_mut(bob.username()).user.focus(1);
+            chat_room.check_command_cooldown(id, "slap");
+            chat_room.is_flood(id, "hello");
+            chat_room.check_slow_mode(&bob).await.unwrap();
+            chat_room.leave(&id, LeaveReason::Quit).await.unwrap();
+        }
+
+        let sizes = chat_room.sizes();
+        assert_eq!(
+            sizes,
+            RoomSizes {
+                saved_focus: 1,
+                ..baseline
+            }
+        );
+        assert!(!sizes.has_leaks());
+
+        chat_room.leave(&1, LeaveReason::Quit).await.unwrap();
+        assert_eq!(chat_room.sizes(), RoomSizes::default());
+    }
+
+    #[tokio::test]
+    async fn tell_sender_when_pm_is_seen_if_both_opted_in() {
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        let mut channels = vec![];
+        let mut users = vec![];
+        for (id, name) in [(1, "alice"), (2, "bob")] {
+            let channel = MockChannel::new(20);
+            let user = chat_room
+                .join(
+                    id,
+                    name.to_string(),
+                    PubKey::default(),
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+            users.push(user);
+            channels.push(channel);
+        }
+        let (alice, bob) = (users[0].clone(), users[1].clone());
+        let send_pm = |text: &str| {
+            message::Private::new(alice.clone().into(), bob.clone().into(), text.to_string())
+        };
+
+        chat_room.send_private_message(send_pm("hi")).await.unwrap();
+        chat_room.notify_seen(bob.username()).await;
+
+        for user in [&alice, &bob] {
+            let member = chat_room.find_member_mut(user.username());
+            member.user.config_mut().switch_read_receipts();
+        }
+        while channels[0].rx.try_recv().is_ok() {}
+        chat_room
+            .send_private_message(send_pm("one"))
+            .await
+            .unwrap();
+        chat_room
+            .send_private_message(send_pm("two"))
+            .await
+            .unwrap();
+        chat_room.notify_seen(alice.username()).await;
+        chat_room.notify_seen(bob.username()).await;
+        chat_room.notify_seen(bob.username()).await;
+
+        while let Ok(msg) = channels[0].rx.try_recv() {
+            channels[0].messages.push(msg);
+        }
+        assert_eq!(channels[0].messages.len(), 1);
+        assert!(channels[0].messages[0].contains("bob saw your 2 messages"));
+    }
+
+    #[tokio::test]
+    async fn keep_telling_senders_when_one_is_gone() {
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        let mut channels = vec![];
+        let mut users = vec![];
+        for (id, name) in [(1, "alice"), (2, "bob"), (3, "carol")] {
+            let channel = MockChannel::new(20);
+            let user = chat_room
+                .join(
+                    id,
+                    name.to_string(),
+                    PubKey::default(),
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+            let member = chat_room.find_member_mut(user.username());
+            member.user.config_mut().switch_read_receipts();
+            users.push(user);
+            channels.push(channel);
+        }
+        let carol = users[2].clone();
+        for sender in &users[..2] {
+            let message =
+                message::Private::new(sender.clone().into(), carol.clone().into(), "hi".into());
+            chat_room.send_private_message(message).await.unwrap();
+        }
+
+        let alice_channel = channels.remove(0);
+        drop(alice_channel);
+        chat_room.notify_seen(carol.username()).await;
+
+        while let Ok(msg) = channels[0].rx.try_recv() {
+            channels[0].messages.push(msg);
+        }
+        assert!(channels[0]
+            .messages
+            .iter()
+            .any(|msg| msg.contains("carol saw your message")));
+    }
+
+    #[tokio::test]
+    async fn mark_first_time_keys_as_newcomers() {
+        let channel = MockChannel::new(20);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_newcomer_window(Duration::from_secs(3600));
+        let mut welcome = FirstJoinWelcome::new("Hi!");
+        let regular = PubKey::default();
+        welcome.register(&regular.fingerprint()).unwrap();
+        chat_room.set_welcome(welcome);
+
+        let mut users = vec![];
+        for (id, name, key) in [(1, "alice", PubKey::default()), (2, "bob", regular)] {
+            let user = chat_room
+                .join(
+                    id,
+                    name.to_string(),
+                    key,
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+            users.push(user);
+        }
+        assert!(users[0].is_newcomer());
+        assert!(users[0].to_string().starts_with("name: alice (new)"));
+        assert!(!users[1].is_newcomer());
+        assert_eq!(chat_room.sizes().newcomers, 1);
+    }
+
+    #[tokio::test]
+    async fn add_and_remove_member() {
+        let (message_tx, _message_rx) = mpsc::channel(1);
+        let (_exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let user = User::default();
+        let username = UserName::from("alice");
+        let mut chat_room = ChatRoom::new("Welcome!");
+
+        let member = RoomMember::new(user.clone(), message_tx, _exit_tx);
+        chat_room.add_member(username.clone(), member);
+
+        assert!(chat_room.is_room_member(&username));
+        chat_room.remove_member(&username);
+        assert!(!chat_room.is_room_member(&username));
+    }
+
+    #[tokio::test]
+    async fn join_chat_room() {
+        let mut channel = MockChannel::new(5);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+
+        // Populate chat history
+        let history_message =
+            message::Public::new(Author::from(User::default()), "Hi all!".to_string());
+        assert!(chat_room.send_message(history_message.into()).await.is_ok());
+
+        // Join user
+        let user = chat_room
+            .join(
+                1,
+                "alice".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                channel.tx.clone(),
+                exit_tx,
+            )
+            .await;
+
+        assert!(user.is_ok());
+        assert!(chat_room.is_room_member("alice"));
+        assert_eq!(chat_room.names().get(&1).unwrap(), "alice");
+
+        // Receive exactly 5 messages
+        for _ in 0..5 {
+            match channel.rx.try_recv() {
+                Ok(msg) => channel.messages.push(msg),
+                Err(_) => panic!("Expected 5 messages but received less"),
+            }
+        }
+
+        // Check if there are more than 5 messages
+        match channel.rx.try_recv() {
+            Ok(_) => panic!("More than 5 messages were received"),
+            Err(_) => {}
+        }
+
+        assert!(channel.messages[0].contains("Welcome!"),);
+        assert!(channel.messages[1].contains(HISTORY_REPLAY_MARKER),);
+        assert!(channel.messages[2].contains("Hi all!"),);
+        assert!(channel.messages[3].contains(HISTORY_LIVE_MARKER),);
+        assert!(channel.messages[4].contains("alice joined. (Connected: 1)"),);
+    }
+
+    #[tokio::test]
+    async fn apply_default_theme_and_timestamp_mode_on_join() {
+        let channel = MockChannel::new(5);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_default_theme(Theme::Hacker);
+        chat_room.set_default_timestamp_mode(TimestampMode::DateTime);
+
+        let user = chat_room
+            .join(
+                1,
+                "alice".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                channel.tx.clone(),
+                exit_tx,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(user.config().theme().theme(), Theme::Hacker);
+        assert_eq!(*user.config().timestamp_mode(), TimestampMode::DateTime);
+    }
+
+    #[tokio::test]
+    async fn replay_nothing_after_clearing_history() {
+        let mut channel = MockChannel::new(5);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        let spam = message::Public::new(Author::from(User::default()), "Spam!".to_string());
+        chat_room.send_message(spam.into()).await.unwrap();
+        chat_room.clear_history();
+
+        chat_room
+            .join(
+                1,
+                "alice".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                channel.tx.clone(),
+                exit_tx,
+            )
+            .await
+            .unwrap();
+        while let Ok(msg) = channel.rx.try_recv() {
+            channel.messages.push(msg);
+        }
+
+        assert_eq!(channel.messages.len(), 2);
+        assert!(channel.messages[0].contains("Welcome!"));
+        assert!(channel.messages[1].contains("alice joined. (Connected: 1)"));
+    }
+
+    #[tokio::test]
+    async fn replay_history_with_ages_between_markers() {
+        let mut channel = MockChannel::new(10);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_motd_enabled(false);
+        for text in ["first", "second"] {
+            let message = message::Public::new(Author::from(User::default()), text.to_string());
+            chat_room.send_message(message.into()).await.unwrap();
+        }
+
+        chat_room
+            .join(
+                1,
+                "alice".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                channel.tx.clone(),
+                exit_tx,
+            )
+            .await
+            .unwrap();
+        while let Ok(msg) = channel.rx.try_recv() {
+            channel.messages.push(msg);
+        }
+
+        assert_eq!(channel.messages.len(), 5);
+        assert!(channel.messages[0].contains(HISTORY_REPLAY_MARKER));
+        assert!(channel.messages[1].contains("[0s ago]"));
+        assert!(channel.messages[1].contains("first"));
+        assert!(channel.messages[2].contains("second"));
+        assert!(channel.messages[3].contains(HISTORY_LIVE_MARKER));
+        assert!(channel.messages[4].contains("alice joined"));
+    }
+
+    #[tokio::test]
+    async fn close_member_with_closed_channel_on_broadcast() {
+        let mut channel = MockChannel::new(20);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        let alice = chat_room
+            .join(
+                1,
+                "alice".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+        let (bob_tx, bob_rx) = mpsc::channel(20);
+        let (bob_exit_tx, mut bob_exit_rx) = watch::channel(LeaveReason::default());
+        chat_room
+            .join(
+                2,
+                "bob".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                bob_tx,
+                bob_exit_tx,
+            )
+            .await
+            .unwrap();
+        drop(bob_rx);
+
+        let message = message::Public::new(alice.clone().into(), "anyone here?".to_string());
+        chat_room.send_message(message.into()).await.unwrap();
+        assert!(bob_exit_rx.has_changed().unwrap());
+        bob_exit_rx.mark_unchanged();
+        assert!(chat_room.find_member(&"bob".into()).is_dead());
+
+        let message = message::Public::new(alice.into(), "hello?".to_string());
+        chat_room.send_message(message.into()).await.unwrap();
+        assert!(!bob_exit_rx.has_changed().unwrap());
+
+        // The session of bob leaves the room once told to
+        chat_room.leave(&2, LeaveReason::Quit).await.unwrap();
+        assert!(!chat_room.is_room_member("bob"));
+
+        while let Ok(msg) = channel.rx.try_recv() {
+            channel.messages.push(msg);
+        }
+        let last = channel.messages.last().unwrap();
+        assert!(last.contains("bob left"));
+        assert_eq!(
+            channel
+                .messages
+                .iter()
+                .filter(|msg| msg.contains("bob left"))
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn refuse_sessions_over_the_limit_per_key() {
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let channel = MockChannel::new(100);
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_max_per_key(2);
+        let key = PubKey::default();
+
+        for id in 1..=2 {
+            assert!(chat_room.check_max_per_key(&key, false).is_ok());
+            chat_room
+                .join(
+                    id,
+                    "alice".to_string(),
+                    key.clone(),
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+        }
+        let err = chat_room.check_max_per_key(&key, false).unwrap_err();
+        assert!(err.to_string().contains("at most 2"));
+        assert!(chat_room.check_max_per_key(&key, true).is_ok());
+        assert!(chat_room
+            .check_max_per_key(&PubKey::default(), false)
+            .is_ok());
+
+        chat_room.leave(&1, LeaveReason::Quit).await.unwrap();
+        assert!(chat_room.check_max_per_key(&key, false).is_ok());
+    }
+
+    #[tokio::test]
+    async fn join_without_motd_when_disabled() {
+        let mut channel = MockChannel::new(5);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_motd_enabled(false);
+
+        chat_room
+            .join(
+                1,
+                "alice".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                channel.tx.clone(),
+                exit_tx,
+            )
+            .await
+            .unwrap();
+
+        while let Ok(msg) = channel.rx.try_recv() {
+            channel.messages.push(msg);
+        }
+        assert!(!channel.messages.iter().any(|msg| msg.contains("Welcome!")));
+        assert!(channel.messages.iter().any(|msg| msg.contains("joined")));
+    }
+
+    #[tokio::test]
+    async fn kick_user_out_of_blocklist_strikes() {
+        use assert_fs::fixture::{FileWriteStr, PathChild};
+
+        let temp = assert_fs::TempDir::new().unwrap();
+        let file = temp.child("blocklist.txt");
+        file.write_str("spam").unwrap();
+
+        let mut channel = MockChannel::new(20);
+        let (exit_tx, exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room
+            .load_blocklist_file(file.path().to_str().unwrap())
+            .unwrap();
+        chat_room.set_max_strikes(2);
+
+        let user = chat_room
+            .join(
+                1,
+                "alice".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                channel.tx.clone(),
+                exit_tx,
+            )
+            .await
+            .unwrap();
+
+        assert!(!chat_room.check_blocklist(&user, "hello").await.unwrap());
+        assert!(chat_room.check_blocklist(&user, "SPAM").await.unwrap());
+        assert!(!exit_rx.has_changed().unwrap());
+        assert!(chat_room.check_blocklist(&user, "spam").await.unwrap());
+        assert!(exit_rx.has_changed().unwrap());
+
+        while let Ok(msg) = channel.rx.try_recv() {
+            channel.messages.push(msg);
+        }
+        assert!(channel
+            .messages
+            .iter()
+            .any(|msg| msg.contains("strike 1 of 2")));
+        assert!(channel
+            .messages
+            .iter()
+            .any(|msg| msg.contains("was kicked for using blocked words")));
+    }
+
+    #[tokio::test]
+    async fn send_motd_once_per_interval_when_throttled() {
+        let mut channel = MockChannel::new(20);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_motd_throttle(MotdThrottle::new(Duration::from_secs(3600)));
+        let key = PubKey::default();
+
+        for id in 1..=2 {
+            chat_room
+                .join(
+                    id,
+                    "alice".to_string(),
+                    key.clone(),
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+            chat_room.leave(&id, LeaveReason::Quit).await.unwrap();
+        }
+
+        while let Ok(msg) = channel.rx.try_recv() {
+            channel.messages.push(msg);
+        }
+        let motds = channel
+            .messages
+            .iter()
+            .filter(|msg| msg.contains("Welcome!"))
+            .count();
+        assert_eq!(motds, 1);
+    }
+
+    #[tokio::test]
+    async fn send_welcome_on_first_join_only() {
+        let mut channel = MockChannel::new(20);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_welcome(FirstJoinWelcome::new("Nice to meet you"));
+        let key = PubKey::default();
+
+        for id in 1..=2 {
+            chat_room
+                .join(
+                    id,
+                    "alice".to_string(),
+                    key.clone(),
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+            chat_room.leave(&id, LeaveReason::Quit).await.unwrap();
+        }
+
+        while let Ok(msg) = channel.rx.try_recv() {
+            channel.messages.push(msg);
+        }
+        let welcomes = channel
+            .messages
+            .iter()
+            .filter(|msg| msg.contains("Nice to meet you"))
+            .count();
+        assert_eq!(welcomes, 1);
+    }
+
+    #[tokio::test]
+    async fn count_one_vote_kick_per_key() {
+        let channel = MockChannel::new(40);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_votekick(VoteKickThreshold::Count(2), Duration::from_secs(60));
+        let key = PubKey::default();
+        let members = [
+            (1, "alice", key.clone()),
+            (2, "alice2", key),
+            (3, "bob", PubKey::default()),
+        ];
+        for (id, name, key) in members {
+            chat_room
+                .join(
+                    id,
+                    name.to_string(),
+                    key,
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            chat_room.vote_kick(1, 3),
+            Ok(VoteKickTally::Started {
+                votes: 1,
+                needed: 2
+            })
+        );
+        assert!(chat_room.vote_kick(2, 3).is_err());
+        assert!(chat_room.vote_kick(3, 1).is_ok());
+        assert!(chat_room.vote_kick(2, 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn announce_expired_vote_kicks() {
+        let mut channel = MockChannel::new(20);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_votekick(VoteKickThreshold::Count(2), Duration::ZERO);
+        for (id, name) in [(1, "alice"), (2, "bob"), (3, "carol")] {
+            chat_room
+                .join(
+                    id,
+                    name.to_string(),
+                    PubKey::default(),
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+        }
+        chat_room.vote_kick(1, 2).unwrap();
+        chat_room.announce_expired_vote_kicks().await.unwrap();
+
+        while let Ok(msg) = channel.rx.try_recv() {
+            channel.messages.push(msg);
+        }
+        assert!(channel
+            .messages
+            .iter()
+            .any(|msg| msg.contains("bob stays, the vote to kick them expired")));
+        assert_eq!(chat_room.sizes().votekicks, 0);
+    }
+
+    #[tokio::test]
+    async fn announce_results_of_expired_poll() {
+        let mut channel = MockChannel::new(20);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_poll_window(Duration::ZERO);
+        let user = chat_room
+            .join(
+                1,
+                "alice".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                channel.tx.clone(),
+                exit_tx,
+            )
+            .await
+            .unwrap();
+        let options = vec!["tea".to_string(), "coffee".to_string()];
+        chat_room
+            .start_poll(&user, "Drinks?".to_string(), options)
+            .unwrap();
+        chat_room.vote_in_poll(1, 2).unwrap();
+        chat_room.announce_expired_poll().await.unwrap();
+
+        while let Ok(msg) = channel.rx.try_recv() {
+            channel.messages.push(msg);
+        }
+        assert!(channel
+            .messages
+            .iter()
+            .any(|msg| msg.contains("alice closed the poll as time ran out: Drinks?")));
+        assert!(chat_room.poll().is_none());
+    }
+
+    #[tokio::test]
+    async fn refresh_prompts_as_users_come_and_go() {
+        let channel = MockChannel::new(40);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_prompt_template("{users} online".parse().unwrap());
+        let mut prompt_rx = None;
+        for (id, name) in [(1, "alice"), (2, "bob")] {
+            chat_room
+                .join(
+                    id,
+                    name.to_string(),
+                    PubKey::default(),
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+            prompt_rx.get_or_insert_with(|| chat_room.find_member_by_id(1).subscribe_prompt());
+        }
+        let mut prompt_rx = prompt_rx.unwrap();
+        assert!(prompt_rx.has_changed().unwrap());
+        assert_eq!(*prompt_rx.borrow_and_update(), "2 online");
+
+        chat_room.leave(&2, LeaveReason::Quit).await.unwrap();
+        assert_eq!(*prompt_rx.borrow_and_update(), "1 online");
+    }
+
+    #[tokio::test]
+    async fn restore_held_name_on_reconnect_with_same_key() {
+        let channel = MockChannel::new(30);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_reconnect_window(Duration::from_secs(60));
+
+        let key = PubKey::default();
+        let alice = chat_room
+            .join(
+                1,
+                "alice".to_string(),
+                key.clone(),
+                "ssh".to_string(),
+                None,
+                channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+        chat_room.hold_name_for_reconnect(&alice.id());
+        chat_room
+            .leave(&alice.id(), LeaveReason::Quit)
+            .await
+            .unwrap();
+
+        let other = chat_room
+            .join(
+                2,
+                "alice".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(other.username(), &UserName::from("alice2"));
+
+        let alice = chat_room
+            .join(
+                3,
+                "random".to_string(),
+                key,
+                "ssh".to_string(),
+                None,
+                channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(alice.username(), &UserName::from("alice"));
+    }
+
+    #[tokio::test]
+    async fn warn_about_lookalike_names_on_join() {
+        let mut channel = MockChannel::new(20);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_lookalike_name_mode(LookalikeNameMode::Warn);
+
+        for (id, name) in [(1, "alice"), (2, "\u{430}lic\u{435}")] {
+            chat_room
+                .join(
+                    id,
+                    name.to_string(),
+                    PubKey::default(),
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+        }
+
+        assert!(chat_room.is_room_member("\u{430}lic\u{435}"));
+        while let Ok(msg) = channel.rx.try_recv() {
+            channel.messages.push(msg);
+        }
+        assert!(channel
+            .messages
+            .iter()
+            .any(|msg| msg.contains("looks a lot like alice, beware of impersonation")));
+    }
+
+    #[tokio::test]
+    async fn reject_lookalike_names_on_join() {
+        let channel = MockChannel::new(20);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_lookalike_name_mode(LookalikeNameMode::Reject);
+
+        let mut names = vec![];
+        for (id, name) in [(1, "alice"), (2, "\u{430}lice"), (3, "bob")] {
+            let user = chat_room
+                .join(
+                    id,
+                    name.to_string(),
+                    PubKey::default(),
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+            names.push(user.username().to_string());
+        }
+
+        assert_eq!(names[0], "alice");
+        assert_ne!(names[1], "\u{430}lice");
+        assert_eq!(names[2], "bob");
+        assert_eq!(
+            chat_room.find_lookalike("ALICE", None),
+            Some(&UserName::from("alice"))
+        );
+        assert_eq!(chat_room.find_lookalike("ALICE", Some(1)), None);
+    }
+
+    #[tokio::test]
+    async fn join_with_taken_name_gets_numeric_suffix() {
+        let channel = MockChannel::new(20);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+
+        let mut names = vec![];
+        for id in 1..=3 {
+            let user = chat_room
+                .join(
+                    id,
+                    "alice".to_string(),
+                    PubKey::default(),
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+            names.push(user.username().to_string());
+        }
 
-    pub struct MockChannel {
-        tx: mpsc::Sender<String>,
-        rx: mpsc::Receiver<String>,
-        messages: Vec<String>,
+        assert_eq!(names, vec!["alice", "alice2", "alice3"]);
+        assert_eq!(
+            chat_room.suggest_available_name("alice"),
+            UserName::from("alice4")
+        );
+        assert_eq!(
+            chat_room.suggest_available_name("bob"),
+            UserName::from("bob")
+        );
     }
 
-    impl MockChannel {
-        pub fn new(buffer: usize) -> Self {
-            let (tx, rx) = mpsc::channel(buffer);
-            let messages = Vec::new();
-            Self { tx, rx, messages }
+    #[tokio::test]
+    async fn keep_suffixed_name_within_max_length() {
+        let channel = MockChannel::new(20);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+
+        let mut names = vec![];
+        for id in 1..=2 {
+            let user = chat_room
+                .join(
+                    id,
+                    "ExactlySixteen..".to_string(),
+                    PubKey::default(),
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+            names.push(user.username().to_string());
         }
+
+        assert_eq!(names, vec!["ExactlySixteen..", "ExactlySixteen.2"]);
+        assert_eq!(
+            chat_room.suggest_available_name("ExactlySixteen.."),
+            UserName::from("ExactlySixteen.3")
+        );
     }
 
     #[tokio::test]
-    async fn create_chat_room() {
-        let chat_room = ChatRoom::new("Welcome to the chat room!");
+    async fn evict_old_session_with_same_key_in_single_session_mode() {
+        let mut old_channel = MockChannel::new(100);
+        let new_channel = MockChannel::new(100);
+        let (old_exit_tx, mut old_exit_rx) = watch::channel(LeaveReason::default());
+        let (new_exit_tx, _new_exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_single_session_mode(SingleSessionMode::Evict);
 
-        assert_eq!(chat_room.motd(), "Welcome to the chat room!");
-        assert_eq!(chat_room.uptime(), "0s");
-        assert!(chat_room.names().is_empty());
-        assert!(chat_room.members_iter().count() == 0);
+        let key = PubKey::default();
+        chat_room
+            .join(
+                1,
+                "alice".into(),
+                key.clone(),
+                "ssh".into(),
+                None,
+                old_channel.tx.clone(),
+                old_exit_tx,
+            )
+            .await
+            .unwrap();
+        let user = chat_room
+            .join(
+                2,
+                "alice".into(),
+                key,
+                "ssh".into(),
+                None,
+                new_channel.tx.clone(),
+                new_exit_tx,
+            )
+            .await
+            .unwrap();
+
+        while let Ok(msg) = old_channel.rx.try_recv() {
+            old_channel.messages.push(msg);
+        }
+        assert!(old_channel
+            .messages
+            .iter()
+            .any(|msg| msg.contains("logged in from another session")));
+        assert!(old_exit_rx.changed().await.is_ok());
+        assert_eq!(*old_exit_rx.borrow(), LeaveReason::Quit);
+        assert_eq!(user.username(), &UserName::from("alice2"));
+        assert!(chat_room.try_get_name(&1).is_some());
+
+        chat_room.leave(&1, LeaveReason::Quit).await.unwrap();
+        assert_eq!(chat_room.members_iter().count(), 1);
     }
 
     #[tokio::test]
-    async fn set_and_get_motd() {
+    async fn reject_new_session_with_same_key_in_single_session_mode() {
+        let channel = MockChannel::new(100);
+        let (old_exit_tx, old_exit_rx) = watch::channel(LeaveReason::default());
+        let (new_exit_tx, _new_exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
-        chat_room.set_motd("New MOTD".to_string());
+        chat_room.set_single_session_mode(SingleSessionMode::Reject);
 
-        assert_eq!(chat_room.motd(), "New MOTD");
+        let key = PubKey::default();
+        chat_room
+            .join(
+                1,
+                "alice".into(),
+                key.clone(),
+                "ssh".into(),
+                None,
+                channel.tx.clone(),
+                old_exit_tx,
+            )
+            .await
+            .unwrap();
+        let result = chat_room
+            .join(
+                2,
+                "bob".into(),
+                key,
+                "ssh".into(),
+                None,
+                channel.tx.clone(),
+                new_exit_tx,
+            )
+            .await;
+
+        assert!(result.is_err());
+        assert!(!old_exit_rx.has_changed().unwrap());
+        assert_eq!(chat_room.members_iter().count(), 1);
+        assert!(chat_room.try_get_name(&2).is_none());
     }
 
     #[tokio::test]
-    async fn add_and_remove_member() {
-        let (message_tx, _message_rx) = mpsc::channel(1);
-        let (_exit_tx, _exit_rx) = watch::channel(());
-        let user = User::default();
-        let username = UserName::from("alice");
+    async fn find_members_by_connection_ip() {
+        let channel = MockChannel::new(100);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
-        let member = RoomMember::new(user.clone(), message_tx, _exit_tx);
-        chat_room.add_member(username.clone(), member);
+        let peers = [
+            (1, "bob", "10.0.0.1:50000"),
+            (2, "alice", "10.0.0.1:50001"),
+            (3, "carol", "[::ffff:10.0.0.2]:50000"),
+        ];
+        for (id, name, addr) in peers {
+            chat_room
+                .join(
+                    id,
+                    name.into(),
+                    PubKey::default(),
+                    "ssh".into(),
+                    Some(addr.parse().unwrap()),
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+        }
 
-        assert!(chat_room.is_room_member(&username));
-        chat_room.remove_member(&username);
-        assert!(!chat_room.is_room_member(&username));
+        let alice = &chat_room.find_member(&UserName::from("alice")).user;
+        assert_eq!(alice.peer_addr(), Some("10.0.0.1:50001".parse().unwrap()));
+        assert_eq!(
+            chat_room.members_from_ip(&"10.0.0.1".parse().unwrap()),
+            vec![&UserName::from("alice"), &UserName::from("bob")]
+        );
+        assert_eq!(
+            chat_room.members_from_ip(&"::ffff:10.0.0.1".parse().unwrap()),
+            vec![&UserName::from("alice"), &UserName::from("bob")]
+        );
+        assert_eq!(
+            chat_room.members_from_ip(&"10.0.0.2".parse().unwrap()),
+            vec![&UserName::from("carol")]
+        );
+        assert!(chat_room
+            .members_from_ip(&"10.0.0.3".parse().unwrap())
+            .is_empty());
     }
 
     #[tokio::test]
-    async fn join_chat_room() {
-        let mut channel = MockChannel::new(5);
-        let (exit_tx, _exit_rx) = watch::channel(());
+    async fn list_away_members() {
+        let channel = MockChannel::new(100);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
-        // Populate chat history
-        let history_message =
-            message::Public::new(Author::from(User::default()), "Hi all!".to_string());
-        assert!(chat_room.send_message(history_message.into()).await.is_ok());
+        for (id, name) in [(1, "bob"), (2, "alice"), (3, "carol")] {
+            chat_room
+                .join(
+                    id,
+                    name.into(),
+                    PubKey::default(),
+                    "ssh".into(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+        }
+        assert!(chat_room.away_members().is_empty());
 
-        // Join user
-        let user = chat_room
+        chat_room
+            .find_member_mut(&UserName::from("bob"))
+            .user
+            .go_away("lunch".to_string());
+        chat_room
+            .find_member_mut(&UserName::from("alice"))
+            .user
+            .go_away("meeting".to_string());
+
+        let away = chat_room.away_members();
+        assert_eq!(
+            away.iter()
+                .map(|(name, reason, _)| (name.to_string(), reason.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("alice".to_string(), "meeting"),
+                ("bob".to_string(), "lunch")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn apply_username_color_to_current_and_new_members() {
+        let channel = MockChannel::new(20);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        let color = Color::Rgb { r: 1, g: 2, b: 3 };
+
+        let alice = UserName::from("alice");
+        let bob = UserName::from("bob");
+        for (id, name) in [(1, "alice"), (2, "bob")] {
+            chat_room
+                .join(
+                    id,
+                    name.into(),
+                    create_test_pubkey(),
+                    "ssh".into(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+        }
+        assert!(chat_room.set_username_color(&bob, Some(color)));
+        assert!(!chat_room.set_username_color(&UserName::from("dave"), Some(color)));
+        chat_room
             .join(
-                1,
-                "alice".to_string(),
-                PubKey::default(),
-                "ssh".to_string(),
+                3,
+                "carol".into(),
+                create_test_pubkey(),
+                "ssh".into(),
+                None,
                 channel.tx.clone(),
-                exit_tx,
+                exit_tx.clone(),
             )
-            .await;
+            .await
+            .unwrap();
 
-        assert!(user.is_ok());
-        assert!(chat_room.is_room_member("alice"));
-        assert_eq!(chat_room.names().get(&1).unwrap(), "alice");
+        let carol = UserName::from("carol");
+        for name in [&alice, &carol] {
+            let theme = chat_room.find_member(name).user.config().theme();
+            assert_eq!(
+                theme.username_colors(),
+                &HashMap::from([("bob".to_string(), color)])
+            );
+        }
 
-        // Receive exactly 3 messages
-        for _ in 0..3 {
-            match channel.rx.try_recv() {
-                Ok(msg) => channel.messages.push(msg),
-                Err(_) => panic!("Expected 3 messages but received less"),
-            }
+        chat_room.set_username_color(&bob, None);
+        for name in [&alice, &carol] {
+            let theme = chat_room.find_member(name).user.config().theme();
+            assert!(theme.username_colors().is_empty());
         }
+    }
 
-        // Check if there are more than 3 messages
-        match channel.rx.try_recv() {
-            Ok(_) => panic!("More than 3 messages were received"),
-            Err(_) => {}
+    #[tokio::test]
+    async fn keep_username_color_with_the_key_of_its_user() {
+        let channel = MockChannel::new(20);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        let color = Color::Rgb { r: 1, g: 2, b: 3 };
+
+        let alice = UserName::from("alice");
+        let bob = UserName::from("bob");
+        for (id, name) in [(1, "alice"), (2, "bob")] {
+            chat_room
+                .join(
+                    id,
+                    name.into(),
+                    create_test_pubkey(),
+                    "ssh".into(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
         }
+        chat_room.set_username_color(&bob, Some(color));
+
+        let robert = UserName::from("robert");
+        let mut member = chat_room.find_member(&bob).clone();
+        member.user.set_username(robert.clone());
+        chat_room.add_member(robert.clone(), member);
+        chat_room.remove_member(&bob);
+        chat_room.add_name(2, robert.clone());
+        let theme = chat_room.find_member(&alice).user.config().theme();
+        assert_eq!(
+            theme.username_colors(),
+            &HashMap::from([("robert".to_string(), color)])
+        );
 
-        assert!(channel.messages[0].contains("Welcome!"),);
-        assert!(channel.messages[1].contains("Hi all!"),);
-        assert!(channel.messages[2].contains("alice joined. (Connected: 1)"),);
+        chat_room.leave(&2, LeaveReason::default()).await.unwrap();
+        chat_room
+            .join(
+                3,
+                "robert".into(),
+                create_test_pubkey(),
+                "ssh".into(),
+                None,
+                channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+        for name in [&alice, &robert] {
+            let theme = chat_room.find_member(name).user.config().theme();
+            assert!(theme.username_colors().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn switch_mute_all_but_exempt_members() {
+        let channel = MockChannel::new(20);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        let op_key = PubKey::default();
+
+        for (id, name) in [(1, "alice"), (2, "bob"), (3, "carol")] {
+            chat_room
+                .join(
+                    id,
+                    name.to_string(),
+                    match id {
+                        1 => op_key.clone(),
+                        _ => PubKey::default(),
+                    },
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx.clone(),
+                )
+                .await
+                .unwrap();
+        }
+        chat_room
+            .find_member_mut(&"carol".into())
+            .user
+            .switch_mute_mode();
+
+        let is_op = |user: &User| user.public_key().fingerprint() == op_key.fingerprint();
+        let is_muted = |room: &ChatRoom, name: &str| room.find_member(&name.into()).user.is_muted();
+
+        assert_eq!(chat_room.switch_mute_all(true, is_op), 1);
+        assert!(!is_muted(&chat_room, "alice"));
+        assert!(is_muted(&chat_room, "bob"));
+        assert!(is_muted(&chat_room, "carol"));
+
+        assert_eq!(chat_room.switch_mute_all(false, is_op), 2);
+        assert!(!is_muted(&chat_room, "alice"));
+        assert!(!is_muted(&chat_room, "bob"));
+        assert!(!is_muted(&chat_room, "carol"));
     }
 
     #[tokio::test]
     async fn leave_chat_room() {
         let mut channel = MockChannel::new(5);
-        let (exit_tx, _exit_rx) = watch::channel(());
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
         // Join user
@@ -431,12 +3060,13 @@ mod should {
                 "alice".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 channel.tx.clone(),
                 exit_tx,
             )
             .await;
 
-        chat_room.leave(&1).await.unwrap();
+        chat_room.leave(&1, LeaveReason::Quit).await.unwrap();
         assert!(!chat_room.is_room_member(&"alice"));
         assert!(chat_room.names().get(&1).is_none());
 
@@ -459,10 +3089,60 @@ mod should {
         assert!(channel.messages[2].contains("alice left: (After 0s)"));
     }
 
+    #[tokio::test]
+    async fn announce_leave_reason() {
+        let cases = [
+            (LeaveReason::Quit, "alice left: (After 0s)"),
+            (
+                LeaveReason::Kicked(None),
+                "alice was kicked from the server: (After 0s)",
+            ),
+            (
+                LeaveReason::Kicked(Some("bob".into())),
+                "alice was kicked from the server by bob: (After 0s)",
+            ),
+            (
+                LeaveReason::Banned("bob".into()),
+                "alice was banned from the server by bob: (After 0s)",
+            ),
+            (
+                LeaveReason::Idle,
+                "alice was disconnected for being idle: (After 0s)",
+            ),
+            (
+                LeaveReason::ServerShutdown,
+                "alice left as the server shut down: (After 0s)",
+            ),
+        ];
+
+        for (reason, announce) in cases {
+            let mut channel = MockChannel::new(5);
+            let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+            let mut chat_room = ChatRoom::new("Welcome!");
+            let _ = chat_room
+                .join(
+                    1,
+                    "alice".to_string(),
+                    PubKey::default(),
+                    "ssh".to_string(),
+                    None,
+                    channel.tx.clone(),
+                    exit_tx,
+                )
+                .await;
+
+            chat_room.leave(&1, reason).await.unwrap();
+            while let Ok(msg) = channel.rx.try_recv() {
+                channel.messages.push(msg);
+            }
+            assert!(channel.messages.last().unwrap().contains(announce));
+        }
+    }
+
     #[tokio::test]
     async fn send_system_messages() {
         let mut channel = MockChannel::new(5);
-        let (exit_tx, _exit_rx) = watch::channel(());
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
         let user = chat_room
@@ -471,6 +3151,7 @@ mod should {
                 "alice".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 channel.tx.clone(),
                 exit_tx.clone(),
             )
@@ -499,14 +3180,16 @@ mod should {
             Err(_) => {}
         }
 
-        assert!(channel.messages[2].contains("-> welcome to the chat"));
-        assert!(channel.messages[3].contains("Error: unknown command"));
+        assert!(channel.messages[2].contains("->"));
+        assert!(channel.messages[2].contains(" welcome to the chat"));
+        assert!(channel.messages[3].contains("Error:"));
+        assert!(channel.messages[3].contains(" unknown command"));
         assert!(channel.messages[4].contains("/help"));
     }
 
     #[tokio::test]
     async fn send_public_message() {
-        let (exit_tx, _exit_rx) = watch::channel(());
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
         let mut author_channel = MockChannel::new(5);
@@ -516,19 +3199,21 @@ mod should {
                 "alice".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 author_channel.tx.clone(),
                 exit_tx.clone(),
             )
             .await
             .unwrap();
 
-        let mut recipient_channel = MockChannel::new(5);
+        let mut recipient_channel = MockChannel::new(10);
         let _ = chat_room
             .join(
                 2,
                 "bob".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 recipient_channel.tx.clone(),
                 exit_tx.clone(),
             )
@@ -552,27 +3237,27 @@ mod should {
             Err(_) => {}
         }
 
-        // Receive exactly 4 messages
-        for _ in 0..4 {
+        // Receive exactly 6 messages
+        for _ in 0..6 {
             match recipient_channel.rx.try_recv() {
                 Ok(msg) => recipient_channel.messages.push(msg),
-                Err(_) => panic!("Expected 4 messages but received less"),
+                Err(_) => panic!("Expected 6 messages but received less"),
             }
         }
 
-        // Check if there are more than 4 messages
-        match author_channel.rx.try_recv() {
-            Ok(_) => panic!("More than 4 messages were received"),
+        // Check if there are more than 6 messages
+        match recipient_channel.rx.try_recv() {
+            Ok(_) => panic!("More than 6 messages were received"),
             Err(_) => {}
         }
 
         assert!(author_channel.messages[3].contains("Hello, World!"));
-        assert!(recipient_channel.messages[3].contains("Hello, World!"));
+        assert!(recipient_channel.messages[5].contains("Hello, World!"));
     }
 
     #[tokio::test]
     async fn not_send_public_message_if_author_is_muted() {
-        let (exit_tx, _exit_rx) = watch::channel(());
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
         let mut author_channel = MockChannel::new(5);
@@ -582,6 +3267,7 @@ mod should {
                 "alice".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 author_channel.tx.clone(),
                 exit_tx.clone(),
             )
@@ -590,19 +3276,150 @@ mod should {
 
         author.switch_mute_mode();
 
-        let mut recipient_channel = MockChannel::new(5);
+        let mut recipient_channel = MockChannel::new(10);
+        let _ = chat_room
+            .join(
+                2,
+                "bob".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                recipient_channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+
+        let msg = message::Public::new(author.into(), "Hello, World!".to_string());
+        assert!(chat_room.send_message(msg.into()).await.is_ok());
+
+        // Receive exactly 4 messages
+        for _ in 0..4 {
+            match author_channel.rx.try_recv() {
+                Ok(msg) => author_channel.messages.push(msg),
+                Err(_) => panic!("Expected 4 messages but received less"),
+            }
+        }
+
+        // Check if there are more than 4 messages
+        match author_channel.rx.try_recv() {
+            Ok(_) => panic!("More than 4 messages were received"),
+            Err(_) => {}
+        }
+
+        // Receive exactly 5 messages
+        for _ in 0..5 {
+            match recipient_channel.rx.try_recv() {
+                Ok(msg) => recipient_channel.messages.push(msg),
+                Err(_) => panic!("Expected 5 messages but received less"),
+            }
+        }
+
+        // Check if there are more than 5 messages
+        match recipient_channel.rx.try_recv() {
+            Ok(_) => panic!("More than 5 messages were received"),
+            Err(_) => {}
+        }
+
+        assert!(author_channel.messages[3].contains("You are muted and cannot send messages."));
+        assert!(recipient_channel.messages[4].contains("bob joined"));
+    }
+
+    #[tokio::test]
+    async fn keep_key_mute_after_rename() {
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        let key = PubKey::default();
+
+        let mut author_channel = MockChannel::new(10);
+        let author = chat_room
+            .join(
+                1,
+                "alice".to_string(),
+                key.clone(),
+                "ssh".to_string(),
+                None,
+                author_channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+
+        let mut recipient_channel = MockChannel::new(10);
         let _ = chat_room
             .join(
                 2,
                 "bob".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
+                recipient_channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+
+        assert!(chat_room.switch_key_mute(&key.fingerprint()));
+
+        let new_username = UserName::from("alice2");
+        let member = chat_room.find_member_mut(author.username());
+        member.user.set_username(new_username.clone());
+        let member = member.clone();
+        chat_room.add_member(new_username.clone(), member.clone());
+        chat_room.remove_member(author.username());
+        chat_room.add_name(author.id(), new_username);
+
+        while author_channel.rx.try_recv().is_ok() {}
+        while recipient_channel.rx.try_recv().is_ok() {}
+
+        let msg = message::Public::new(member.user.into(), "Hello, World!".to_string());
+        assert!(chat_room.send_message(msg.into()).await.is_ok());
+
+        assert!(recipient_channel.rx.try_recv().is_err());
+        let notice = author_channel.rx.try_recv().unwrap();
+        assert!(notice.contains("You are muted and cannot send messages."));
+
+        assert!(!chat_room.switch_key_mute(&key.fingerprint()));
+    }
+
+    #[tokio::test]
+    async fn not_send_public_message_if_author_is_ignored_by_recipient() {
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+
+        let mut author_channel = MockChannel::new(5);
+        let author = chat_room
+            .join(
+                1,
+                "alice".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                author_channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+
+        let mut recipient_channel = MockChannel::new(10);
+        let recipient = chat_room
+            .join(
+                2,
+                "bob".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
                 recipient_channel.tx.clone(),
                 exit_tx.clone(),
             )
             .await
             .unwrap();
 
+        chat_room
+            .find_member_mut(recipient.username())
+            .user
+            .ignore(author.id());
+
         let msg = message::Public::new(author.into(), "Hello, World!".to_string());
         assert!(chat_room.send_message(msg.into()).await.is_ok());
 
@@ -620,27 +3437,27 @@ mod should {
             Err(_) => {}
         }
 
-        // Receive exactly 3 messages
-        for _ in 0..3 {
+        // Receive exactly 5 messages
+        for _ in 0..5 {
             match recipient_channel.rx.try_recv() {
                 Ok(msg) => recipient_channel.messages.push(msg),
-                Err(_) => panic!("Expected 3 messages but received less"),
+                Err(_) => panic!("Expected 5 messages but received less"),
             }
         }
 
-        // Check if there are more than 3 messages
-        match author_channel.rx.try_recv() {
-            Ok(_) => panic!("More than 3 messages were received"),
+        // Check if there are more than 5 messages
+        match recipient_channel.rx.try_recv() {
+            Ok(_) => panic!("More than 5 messages were received"),
             Err(_) => {}
         }
 
-        assert!(author_channel.messages[3].contains("You are muted and cannot send messages."));
-        assert!(recipient_channel.messages[2].contains("bob joined"));
+        assert!(author_channel.messages[3].contains("Hello, World!"));
+        assert!(recipient_channel.messages[4].contains("bob joined"));
     }
 
     #[tokio::test]
-    async fn not_send_public_message_if_author_is_ignored_by_recipient() {
-        let (exit_tx, _exit_rx) = watch::channel(());
+    async fn not_send_public_message_if_author_is_not_in_focus_by_recipient() {
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
         let mut author_channel = MockChannel::new(5);
@@ -650,19 +3467,21 @@ mod should {
                 "alice".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 author_channel.tx.clone(),
                 exit_tx.clone(),
             )
             .await
             .unwrap();
 
-        let mut recipient_channel = MockChannel::new(5);
+        let mut recipient_channel = MockChannel::new(10);
         let recipient = chat_room
             .join(
                 2,
                 "bob".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 recipient_channel.tx.clone(),
                 exit_tx.clone(),
             )
@@ -672,7 +3491,7 @@ mod should {
         chat_room
             .find_member_mut(recipient.username())
             .user
-            .ignore(author.id());
+            .focus(usize::MAX); // focus at least one user, any user, except the message author
 
         let msg = message::Public::new(author.into(), "Hello, World!".to_string());
         assert!(chat_room.send_message(msg.into()).await.is_ok());
@@ -691,49 +3510,51 @@ mod should {
             Err(_) => {}
         }
 
-        // Receive exactly 3 messages
-        for _ in 0..3 {
+        // Receive exactly 5 messages
+        for _ in 0..5 {
             match recipient_channel.rx.try_recv() {
                 Ok(msg) => recipient_channel.messages.push(msg),
-                Err(_) => panic!("Expected 3 messages but received less"),
+                Err(_) => panic!("Expected 5 messages but received less"),
             }
         }
 
-        // Check if there are more than 3 messages
-        match author_channel.rx.try_recv() {
-            Ok(_) => panic!("More than 3 messages were received"),
+        // Check if there are more than 5 messages
+        match recipient_channel.rx.try_recv() {
+            Ok(_) => panic!("More than 5 messages were received"),
             Err(_) => {}
         }
 
         assert!(author_channel.messages[3].contains("Hello, World!"));
-        assert!(recipient_channel.messages[2].contains("bob joined"));
+        assert!(recipient_channel.messages[4].contains("bob joined"));
     }
 
     #[tokio::test]
-    async fn not_send_public_message_if_author_is_not_in_focus_by_recipient() {
-        let (exit_tx, _exit_rx) = watch::channel(());
+    async fn not_send_public_message_if_author_is_excluded_by_recipient() {
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
-        let mut author_channel = MockChannel::new(5);
+        let author_channel = MockChannel::new(10);
         let author = chat_room
             .join(
                 1,
                 "alice".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 author_channel.tx.clone(),
                 exit_tx.clone(),
             )
             .await
             .unwrap();
 
-        let mut recipient_channel = MockChannel::new(5);
+        let mut recipient_channel = MockChannel::new(10);
         let recipient = chat_room
             .join(
                 2,
                 "bob".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 recipient_channel.tx.clone(),
                 exit_tx.clone(),
             )
@@ -743,46 +3564,76 @@ mod should {
         chat_room
             .find_member_mut(recipient.username())
             .user
-            .focus(usize::MAX); // focus at least one user, any user, except the message author
+            .exclude(author.id());
 
         let msg = message::Public::new(author.into(), "Hello, World!".to_string());
         assert!(chat_room.send_message(msg.into()).await.is_ok());
 
-        // Receive exactly 4 messages
-        for _ in 0..4 {
-            match author_channel.rx.try_recv() {
-                Ok(msg) => author_channel.messages.push(msg),
-                Err(_) => panic!("Expected 4 messages but received less"),
-            }
+        while let Ok(msg) = recipient_channel.rx.try_recv() {
+            recipient_channel.messages.push(msg);
         }
+        assert!(!recipient_channel
+            .messages
+            .iter()
+            .any(|msg| msg.contains("Hello, World!")));
+    }
 
-        // Check if there are more than 4 messages
-        match author_channel.rx.try_recv() {
-            Ok(_) => panic!("More than 4 messages were received"),
-            Err(_) => {}
-        }
+    #[tokio::test]
+    async fn restore_focus_on_reconnect_with_same_key() {
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        let key = PubKey::default();
 
-        // Receive exactly 3 messages
-        for _ in 0..3 {
-            match recipient_channel.rx.try_recv() {
-                Ok(msg) => recipient_channel.messages.push(msg),
-                Err(_) => panic!("Expected 3 messages but received less"),
-            }
-        }
+        let channel = MockChannel::new(20);
+        chat_room
+            .join(
+                1,
+                "alice".to_string(),
+                key.clone(),
+                "ssh".to_string(),
+                None,
+                channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+        let bob = chat_room
+            .join(
+                2,
+                "bob".to_string(),
+                key.clone(),
+                "ssh".to_string(),
+                None,
+                channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
 
-        // Check if there are more than 3 messages
-        match author_channel.rx.try_recv() {
-            Ok(_) => panic!("More than 3 messages were received"),
-            Err(_) => {}
-        }
+        chat_room
+            .find_member_mut(&UserName::from("alice"))
+            .user
+            .exclude(bob.id());
+        chat_room.leave(&1, LeaveReason::Quit).await.unwrap();
 
-        assert!(author_channel.messages[3].contains("Hello, World!"));
-        assert!(recipient_channel.messages[2].contains("bob joined"));
+        let alice = chat_room
+            .join(
+                3,
+                "alice".to_string(),
+                key.clone(),
+                "ssh".to_string(),
+                None,
+                channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+        assert!(alice.excluded().contains(&bob.id()));
     }
 
     #[tokio::test]
     async fn send_emote_message() {
-        let (exit_tx, _exit_rx) = watch::channel(());
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
         let mut author_channel = MockChannel::new(5);
@@ -792,19 +3643,21 @@ mod should {
                 "alice".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 author_channel.tx.clone(),
                 exit_tx.clone(),
             )
             .await
             .unwrap();
 
-        let mut recipient_channel = MockChannel::new(5);
+        let mut recipient_channel = MockChannel::new(10);
         let _ = chat_room
             .join(
                 2,
                 "bob".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 recipient_channel.tx.clone(),
                 exit_tx.clone(),
             )
@@ -828,27 +3681,78 @@ mod should {
             Err(_) => {}
         }
 
-        // Receive exactly 4 messages
-        for _ in 0..4 {
+        // Receive exactly 6 messages
+        for _ in 0..6 {
             match recipient_channel.rx.try_recv() {
                 Ok(msg) => recipient_channel.messages.push(msg),
-                Err(_) => panic!("Expected 4 messages but received less"),
+                Err(_) => panic!("Expected 6 messages but received less"),
             }
         }
 
-        // Check if there are more than 4 messages
-        match author_channel.rx.try_recv() {
-            Ok(_) => panic!("More than 4 messages were received"),
+        // Check if there are more than 6 messages
+        match recipient_channel.rx.try_recv() {
+            Ok(_) => panic!("More than 6 messages were received"),
             Err(_) => {}
         }
 
         assert!(author_channel.messages[3].contains("Hello, World!"));
-        assert!(recipient_channel.messages[3].contains("Hello, World!"));
+        assert!(recipient_channel.messages[5].contains("Hello, World!"));
+    }
+
+    #[tokio::test]
+    async fn highlight_targeted_emote_message_for_target() {
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+
+        let mut author_channel = MockChannel::new(5);
+        let author = chat_room
+            .join(
+                1,
+                "alice".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                author_channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+
+        let mut target_channel = MockChannel::new(10);
+        let target = chat_room
+            .join(
+                2,
+                "bob".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                target_channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+
+        let msg = message::Emote::new_targeted(author.into(), target.id(), "slaps bob".into());
+        assert!(chat_room.send_message(msg.into()).await.is_ok());
+
+        while let Ok(msg) = author_channel.rx.try_recv() {
+            author_channel.messages.push(msg);
+        }
+        while let Ok(msg) = target_channel.rx.try_recv() {
+            target_channel.messages.push(msg);
+        }
+
+        let author_copy = author_channel.messages.last().unwrap();
+        let target_copy = target_channel.messages.last().unwrap();
+        assert!(author_copy.contains("alice slaps bob"));
+        assert!(!author_copy.ends_with(BEL));
+        assert!(target_copy.contains("alice slaps bob"));
+        assert!(target_copy.ends_with(BEL));
     }
 
     #[tokio::test]
     async fn not_send_emote_message_if_author_is_muted() {
-        let (exit_tx, _exit_rx) = watch::channel(());
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
         let mut author_channel = MockChannel::new(5);
@@ -858,6 +3762,7 @@ mod should {
                 "alice".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 author_channel.tx.clone(),
                 exit_tx.clone(),
             )
@@ -866,13 +3771,14 @@ mod should {
 
         author.switch_mute_mode();
 
-        let mut recipient_channel = MockChannel::new(5);
+        let mut recipient_channel = MockChannel::new(10);
         let _ = chat_room
             .join(
                 2,
                 "bob".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 recipient_channel.tx.clone(),
                 exit_tx.clone(),
             )
@@ -896,27 +3802,27 @@ mod should {
             Err(_) => {}
         }
 
-        // Receive exactly 3 messages
-        for _ in 0..3 {
+        // Receive exactly 5 messages
+        for _ in 0..5 {
             match recipient_channel.rx.try_recv() {
                 Ok(msg) => recipient_channel.messages.push(msg),
-                Err(_) => panic!("Expected 3 messages but received less"),
+                Err(_) => panic!("Expected 5 messages but received less"),
             }
         }
 
-        // Check if there are more than 3 messages
-        match author_channel.rx.try_recv() {
-            Ok(_) => panic!("More than 3 messages were received"),
+        // Check if there are more than 5 messages
+        match recipient_channel.rx.try_recv() {
+            Ok(_) => panic!("More than 5 messages were received"),
             Err(_) => {}
         }
 
         assert!(author_channel.messages[3].contains("You are muted and cannot send messages."));
-        assert!(recipient_channel.messages[2].contains("bob joined"));
+        assert!(recipient_channel.messages[4].contains("bob joined"));
     }
 
     #[tokio::test]
     async fn not_send_emote_message_if_author_is_ignored_by_recipient() {
-        let (exit_tx, _exit_rx) = watch::channel(());
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
         let mut author_channel = MockChannel::new(5);
@@ -926,19 +3832,21 @@ mod should {
                 "alice".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 author_channel.tx.clone(),
                 exit_tx.clone(),
             )
             .await
             .unwrap();
 
-        let mut recipient_channel = MockChannel::new(5);
+        let mut recipient_channel = MockChannel::new(10);
         let recipient = chat_room
             .join(
                 2,
                 "bob".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 recipient_channel.tx.clone(),
                 exit_tx.clone(),
             )
@@ -967,27 +3875,27 @@ mod should {
             Err(_) => {}
         }
 
-        // Receive exactly 3 messages
-        for _ in 0..3 {
+        // Receive exactly 5 messages
+        for _ in 0..5 {
             match recipient_channel.rx.try_recv() {
                 Ok(msg) => recipient_channel.messages.push(msg),
-                Err(_) => panic!("Expected 3 messages but received less"),
+                Err(_) => panic!("Expected 5 messages but received less"),
             }
         }
 
-        // Check if there are more than 3 messages
-        match author_channel.rx.try_recv() {
-            Ok(_) => panic!("More than 3 messages were received"),
+        // Check if there are more than 5 messages
+        match recipient_channel.rx.try_recv() {
+            Ok(_) => panic!("More than 5 messages were received"),
             Err(_) => {}
         }
 
         assert!(author_channel.messages[3].contains("Hello, World!"));
-        assert!(recipient_channel.messages[2].contains("bob joined"));
+        assert!(recipient_channel.messages[4].contains("bob joined"));
     }
 
     #[tokio::test]
     async fn send_announce_message() {
-        let (exit_tx, _exit_rx) = watch::channel(());
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
         let mut author_channel = MockChannel::new(5);
@@ -997,19 +3905,21 @@ mod should {
                 "alice".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 author_channel.tx.clone(),
                 exit_tx.clone(),
             )
             .await
             .unwrap();
 
-        let mut recipient_channel = MockChannel::new(5);
+        let mut recipient_channel = MockChannel::new(10);
         let _ = chat_room
             .join(
                 2,
                 "bob".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 recipient_channel.tx.clone(),
                 exit_tx.clone(),
             )
@@ -1033,27 +3943,27 @@ mod should {
             Err(_) => {}
         }
 
-        // Receive exactly 4 messages
-        for _ in 0..4 {
+        // Receive exactly 6 messages
+        for _ in 0..6 {
             match recipient_channel.rx.try_recv() {
                 Ok(msg) => recipient_channel.messages.push(msg),
-                Err(_) => panic!("Expected 4 messages but received less"),
+                Err(_) => panic!("Expected 6 messages but received less"),
             }
         }
 
-        // Check if there are more than 4 messages
-        match author_channel.rx.try_recv() {
-            Ok(_) => panic!("More than 4 messages were received"),
+        // Check if there are more than 6 messages
+        match recipient_channel.rx.try_recv() {
+            Ok(_) => panic!("More than 6 messages were received"),
             Err(_) => {}
         }
 
         assert!(author_channel.messages[3].contains("Hello, World!"));
-        assert!(recipient_channel.messages[3].contains("Hello, World!"));
+        assert!(recipient_channel.messages[5].contains("Hello, World!"));
     }
 
     #[tokio::test]
     async fn not_send_announce_message_if_author_is_muted() {
-        let (exit_tx, _exit_rx) = watch::channel(());
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
         let mut author_channel = MockChannel::new(5);
@@ -1063,6 +3973,7 @@ mod should {
                 "alice".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 author_channel.tx.clone(),
                 exit_tx.clone(),
             )
@@ -1071,13 +3982,14 @@ mod should {
 
         author.switch_mute_mode();
 
-        let mut recipient_channel = MockChannel::new(5);
+        let mut recipient_channel = MockChannel::new(10);
         let _ = chat_room
             .join(
                 2,
                 "bob".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 recipient_channel.tx.clone(),
                 exit_tx.clone(),
             )
@@ -1101,27 +4013,27 @@ mod should {
             Err(_) => {}
         }
 
-        // Receive exactly 3 messages
-        for _ in 0..3 {
+        // Receive exactly 5 messages
+        for _ in 0..5 {
             match recipient_channel.rx.try_recv() {
                 Ok(msg) => recipient_channel.messages.push(msg),
-                Err(_) => panic!("Expected 3 messages but received less"),
+                Err(_) => panic!("Expected 5 messages but received less"),
             }
         }
 
-        // Check if there are more than 3 messages
-        match author_channel.rx.try_recv() {
-            Ok(_) => panic!("More than 3 messages were received"),
+        // Check if there are more than 5 messages
+        match recipient_channel.rx.try_recv() {
+            Ok(_) => panic!("More than 5 messages were received"),
             Err(_) => {}
         }
 
         assert!(author_channel.messages[3].contains("You are muted and cannot send messages."));
-        assert!(recipient_channel.messages[2].contains("bob joined"));
+        assert!(recipient_channel.messages[4].contains("bob joined"));
     }
 
     #[tokio::test]
     async fn not_send_announce_message_if_author_is_ignored_by_recipient() {
-        let (exit_tx, _exit_rx) = watch::channel(());
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
         let mut author_channel = MockChannel::new(5);
@@ -1131,19 +4043,21 @@ mod should {
                 "alice".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 author_channel.tx.clone(),
                 exit_tx.clone(),
             )
             .await
             .unwrap();
 
-        let mut recipient_channel = MockChannel::new(5);
+        let mut recipient_channel = MockChannel::new(10);
         let recipient = chat_room
             .join(
                 2,
                 "bob".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 recipient_channel.tx.clone(),
                 exit_tx.clone(),
             )
@@ -1172,27 +4086,27 @@ mod should {
             Err(_) => {}
         }
 
-        // Receive exactly 3 messages
-        for _ in 0..3 {
+        // Receive exactly 5 messages
+        for _ in 0..5 {
             match recipient_channel.rx.try_recv() {
                 Ok(msg) => recipient_channel.messages.push(msg),
-                Err(_) => panic!("Expected 3 messages but received less"),
+                Err(_) => panic!("Expected 5 messages but received less"),
             }
         }
 
-        // Check if there are more than 3 messages
-        match author_channel.rx.try_recv() {
-            Ok(_) => panic!("More than 3 messages were received"),
+        // Check if there are more than 5 messages
+        match recipient_channel.rx.try_recv() {
+            Ok(_) => panic!("More than 5 messages were received"),
             Err(_) => {}
         }
 
         assert!(author_channel.messages[3].contains("Hello, World!"));
-        assert!(recipient_channel.messages[2].contains("bob joined"));
+        assert!(recipient_channel.messages[4].contains("bob joined"));
     }
 
     #[tokio::test]
     async fn send_private_message() {
-        let (exit_tx, _exit_rx) = watch::channel(());
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
         let mut author_channel = MockChannel::new(5);
@@ -1202,19 +4116,21 @@ mod should {
                 "alice".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 author_channel.tx.clone(),
                 exit_tx.clone(),
             )
             .await
             .unwrap();
 
-        let mut recipient_channel = MockChannel::new(5);
+        let mut recipient_channel = MockChannel::new(10);
         let recipient = chat_room
             .join(
                 2,
                 "bob".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 recipient_channel.tx.clone(),
                 exit_tx.clone(),
             )
@@ -1238,27 +4154,128 @@ mod should {
             Err(_) => {}
         }
 
-        // Receive exactly 4 messages
-        for _ in 0..4 {
+        // Receive exactly 6 messages
+        for _ in 0..6 {
             match recipient_channel.rx.try_recv() {
                 Ok(msg) => recipient_channel.messages.push(msg),
-                Err(_) => panic!("Expected 4 messages but received less"),
+                Err(_) => panic!("Expected 6 messages but received less"),
             }
         }
 
-        // Check if there are more than 4 messages
-        match author_channel.rx.try_recv() {
-            Ok(_) => panic!("More than 4 messages were received"),
+        // Check if there are more than 6 messages
+        match recipient_channel.rx.try_recv() {
+            Ok(_) => panic!("More than 6 messages were received"),
             Err(_) => {}
         }
 
         assert!(author_channel.messages[2].contains("bob joined"));
-        assert!(recipient_channel.messages[3].contains("Hello, Bob!"));
+        assert!(recipient_channel.messages[5].contains("Hello, Bob!"));
+    }
+
+    #[tokio::test]
+    async fn count_unread_private_messages_until_recipient_speaks() {
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+        chat_room.set_unread_badge(true);
+
+        let author_channel = MockChannel::new(10);
+        let author = chat_room
+            .join(
+                1,
+                "alice".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                author_channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+
+        let recipient_channel = MockChannel::new(10);
+        let recipient = chat_room
+            .join(
+                2,
+                "bob".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                recipient_channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            let msg = message::Private::new(
+                author.clone().into(),
+                recipient.clone().into(),
+                "Hello, Bob!".to_string(),
+            );
+            assert!(chat_room.send_message(msg.into()).await.is_ok());
+        }
+        assert_eq!(chat_room.find_member_by_id(2).unread_count(), 2);
+        assert_eq!(chat_room.find_member_by_id(1).unread_count(), 0);
+
+        let msg = message::Public::new(recipient.into(), "Hi!".to_string());
+        assert!(chat_room.send_message(msg.into()).await.is_ok());
+        assert_eq!(chat_room.find_member_by_id(2).unread_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn report_private_message_delivery() {
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let mut chat_room = ChatRoom::new("Welcome!");
+
+        let author_channel = MockChannel::new(10);
+        let author = chat_room
+            .join(
+                1,
+                "alice".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                author_channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+
+        let recipient_channel = MockChannel::new(10);
+        let recipient = chat_room
+            .join(
+                2,
+                "bob".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                recipient_channel.tx.clone(),
+                exit_tx.clone(),
+            )
+            .await
+            .unwrap();
+
+        let msg = message::Private::new(
+            author.clone().into(),
+            recipient.clone().into(),
+            "Hello, Bob!".to_string(),
+        );
+        let delivery = chat_room.send_private_message(msg).await.unwrap();
+        assert_eq!(delivery, PrivateDelivery::Delivered);
+
+        chat_room
+            .find_member_mut(recipient.username())
+            .user
+            .ignore(author.id());
+
+        let msg = message::Private::new(author.into(), recipient.into(), "Hello, Bob!".to_string());
+        let delivery = chat_room.send_private_message(msg).await.unwrap();
+        assert_eq!(delivery, PrivateDelivery::Ignored);
     }
 
     #[tokio::test]
     async fn not_send_private_message_from_muted_author() {
-        let (exit_tx, _exit_rx) = watch::channel(());
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
         let mut author_channel = MockChannel::new(5);
@@ -1268,6 +4285,7 @@ mod should {
                 "alice".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 author_channel.tx.clone(),
                 exit_tx.clone(),
             )
@@ -1276,13 +4294,14 @@ mod should {
 
         author.switch_mute_mode();
 
-        let mut recipient_channel = MockChannel::new(5);
+        let mut recipient_channel = MockChannel::new(10);
         let recipient = chat_room
             .join(
                 2,
                 "bob".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 recipient_channel.tx.clone(),
                 exit_tx.clone(),
             )
@@ -1306,28 +4325,28 @@ mod should {
             Err(_) => {}
         }
 
-        // Receive exactly 3 messages
-        for _ in 0..3 {
+        // Receive exactly 5 messages
+        for _ in 0..5 {
             match recipient_channel.rx.try_recv() {
                 Ok(msg) => recipient_channel.messages.push(msg),
-                Err(_) => panic!("Expected 3 messages but received less"),
+                Err(_) => panic!("Expected 5 messages but received less"),
             }
         }
 
-        // Check if there are more than 3 messages
-        match author_channel.rx.try_recv() {
-            Ok(_) => panic!("More than 3 messages were received"),
+        // Check if there are more than 5 messages
+        match recipient_channel.rx.try_recv() {
+            Ok(_) => panic!("More than 5 messages were received"),
             Err(_) => {}
         }
 
         assert!(author_channel.messages[3].contains("You are muted and cannot send messages."));
-        assert!(recipient_channel.messages[2].contains("bob joined"));
+        assert!(recipient_channel.messages[4].contains("bob joined"));
     }
 
     #[tokio::test]
     async fn find_name_by_prefix() {
         let channel = MockChannel::new(10);
-        let (exit_tx, _exit_rx) = watch::channel(());
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
         assert_eq!(chat_room.find_name_by_prefix("", ""), None);
@@ -1339,6 +4358,7 @@ mod should {
                 "john".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 channel.tx.clone(),
                 exit_tx.clone(),
             )
@@ -1353,6 +4373,7 @@ mod should {
                 "johnathan".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 channel.tx.clone(),
                 exit_tx,
             )
@@ -1367,7 +4388,7 @@ mod should {
     #[tokio::test]
     async fn try_get_name() {
         let channel = MockChannel::new(5);
-        let (exit_tx, _exit_rx) = watch::channel(());
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
         let mut chat_room = ChatRoom::new("Welcome!");
 
         let _ = chat_room
@@ -1376,6 +4397,7 @@ mod should {
                 "alice".to_string(),
                 PubKey::default(),
                 "ssh".to_string(),
+                None,
                 channel.tx.clone(),
                 exit_tx,
             )
@@ -0,0 +1,44 @@
+use std::io::ErrorKind;
+
+use crate::utils;
+
+/// File where the room remembers keys across restarts, one record per
+/// line. Records are kept in memory first and then written to the file,
+/// so a failed write only forgets them once the server restarts
+#[derive(Debug)]
+pub struct SeenFile {
+    path: String,
+}
+
+impl SeenFile {
+    /// Opens the file and returns it with its content. A missing file
+    /// reads as empty and is created on the first write
+    pub fn open(path: &str) -> std::io::Result<(Self, String)> {
+        let content = match utils::fs::read_file_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+        let file = Self {
+            path: path.to_string(),
+        };
+        Ok((file, content))
+    }
+
+    pub fn append(&self, record: &str) -> std::io::Result<()> {
+        utils::fs::append_line(&self.path, record)
+    }
+
+    pub fn overwrite(&self, records: &str) -> std::io::Result<()> {
+        utils::fs::write_string_to_file(&self.path, records)
+    }
+}
+
+/// Path of a seen file in a fresh temporary directory, which is removed
+/// when the returned guard is dropped
+#[cfg(test)]
+pub fn temp_seen_file() -> (assert_fs::TempDir, String) {
+    let temp_dir = assert_fs::TempDir::new().unwrap();
+    let path = temp_dir.path().join("seen").to_str().unwrap().to_string();
+    (temp_dir, path)
+}
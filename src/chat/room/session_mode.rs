@@ -0,0 +1,27 @@
+use std::fmt::Display;
+
+use strum::EnumString;
+
+/// Describes how the room handles a new login by a key that already
+/// has a connected session
+#[derive(Debug, Clone, Copy, PartialEq, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum SingleSessionMode {
+    /// Disconnect the older session and let the new one in
+    Evict,
+    /// Keep the older session and refuse the new one
+    Reject,
+}
+
+impl Display for SingleSessionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                SingleSessionMode::Evict => "evict",
+                SingleSessionMode::Reject => "reject",
+            }
+        )
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::chat::message::Author;
+
+/// Remaining times at which the countdown of a scheduled shutdown is
+/// announced to the room
+const COUNTDOWN_MARKS: [Duration; 8] = [
+    Duration::from_secs(3600),
+    Duration::from_secs(1800),
+    Duration::from_secs(600),
+    Duration::from_secs(300),
+    Duration::from_secs(60),
+    Duration::from_secs(30),
+    Duration::from_secs(10),
+    Duration::from_secs(5),
+];
+
+/// Server shutdown requested by an operator
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledShutdown {
+    pub deadline: Instant,
+    pub by: Author,
+}
+
+impl ScheduledShutdown {
+    pub fn new(delay: Duration, by: Author) -> Self {
+        Self {
+            deadline: Instant::now() + delay,
+            by,
+        }
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+}
+
+/// Returns how long to wait from the remaining time until the next
+/// countdown announcement, or until the deadline if no mark is left
+pub fn countdown_step(remaining: Duration) -> Duration {
+    COUNTDOWN_MARKS
+        .iter()
+        .find(|mark| **mark < remaining)
+        .map(|mark| remaining - *mark)
+        .unwrap_or(remaining)
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn wait_until_next_countdown_mark() {
+        assert_eq!(
+            countdown_step(Duration::from_secs(400)),
+            Duration::from_secs(100)
+        );
+        assert_eq!(
+            countdown_step(Duration::from_secs(300)),
+            Duration::from_secs(240)
+        );
+        assert_eq!(
+            countdown_step(Duration::from_secs(7200)),
+            Duration::from_secs(3600)
+        );
+    }
+
+    #[test]
+    fn wait_until_deadline_after_last_mark() {
+        assert_eq!(
+            countdown_step(Duration::from_secs(5)),
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            countdown_step(Duration::from_secs(3)),
+            Duration::from_secs(3)
+        );
+    }
+}
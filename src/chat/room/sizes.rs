@@ -0,0 +1,84 @@
+use std::fmt::Display;
+
+/// Sizes of the room's per-user maps, logged periodically to spot state
+/// that outlives the users it belongs to
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RoomSizes {
+    pub members: usize,
+    pub names: usize,
+    pub ratelims: usize,
+    pub repeats: usize,
+    pub cooldowns: usize,
+    pub slow_mode: usize,
+    pub strikes: usize,
+    pub saved_focus: usize,
+    pub votekicks: usize,
+    /// Keys still within the window of their first join
+    pub newcomers: usize,
+    /// Ids of users no longer in the room still ignored or focused by
+    /// a member or kept in a saved focus filter
+    pub stale_refs: usize,
+}
+
+impl RoomSizes {
+    /// Tells if any per-user map holds more entries than there are
+    /// members, or any member still refers to a departed user
+    pub fn has_leaks(&self) -> bool {
+        let per_user = [
+            self.names,
+            self.ratelims,
+            self.repeats,
+            self.cooldowns,
+            self.slow_mode,
+            self.strikes,
+            self.votekicks,
+        ];
+        per_user.iter().any(|size| *size > self.members) || self.stale_refs > 0
+    }
+}
+
+impl Display for RoomSizes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "members={} names={} ratelims={} repeats={} cooldowns={} slow_mode={} strikes={} saved_focus={} votekicks={} newcomers={} stale_refs={}",
+            self.members,
+            self.names,
+            self.ratelims,
+            self.repeats,
+            self.cooldowns,
+            self.slow_mode,
+            self.strikes,
+            self.saved_focus,
+            self.votekicks,
+            self.newcomers,
+            self.stale_refs
+        )
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn report_maps_larger_than_members_as_leaks() {
+        let sizes = RoomSizes {
+            members: 2,
+            names: 2,
+            ratelims: 2,
+            ..Default::default()
+        };
+        assert!(!sizes.has_leaks());
+        assert!(RoomSizes {
+            ratelims: 3,
+            ..sizes
+        }
+        .has_leaks());
+        assert!(RoomSizes {
+            stale_refs: 1,
+            ..sizes
+        }
+        .has_leaks());
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+type UserId = usize;
+
+/// Span over which the room message rate is measured
+const FLOOD_WINDOW: Duration = Duration::from_secs(10);
+
+const DEFAULT_SLOW_MODE_COOLDOWN: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlowModeChange {
+    Entered,
+    Left,
+}
+
+/// Tracks the public messages sent across the room and turns on slow
+/// mode, a minimum time between two messages of every user, once more
+/// than the threshold were sent within the window. Slow mode is lifted
+/// after the rate falls back to half of the threshold
+#[derive(Debug)]
+pub struct SlowMode {
+    threshold: usize,
+    cooldown: Duration,
+    active: bool,
+    sent: VecDeque<Instant>,
+    last_sent: HashMap<UserId, Instant>,
+}
+
+impl Default for SlowMode {
+    fn default() -> Self {
+        Self::new(0, DEFAULT_SLOW_MODE_COOLDOWN)
+    }
+}
+
+impl SlowMode {
+    /// A threshold of zero never turns slow mode on
+    pub fn new(threshold: usize, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            active: false,
+            sent: VecDeque::new(),
+            last_sent: HashMap::new(),
+        }
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    /// Lifts slow mode if the room calmed down since the last message
+    pub fn refresh(&mut self) -> Option<SlowModeChange> {
+        self.refresh_at(Instant::now())
+    }
+
+    /// Returns the time the user has to wait before sending a message
+    /// while slow mode is on
+    pub fn check(&self, user_id: UserId) -> Option<Duration> {
+        self.check_at(user_id, Instant::now())
+    }
+
+    /// Counts a message sent by the user towards the room rate
+    pub fn register(&mut self, user_id: UserId) -> Option<SlowModeChange> {
+        self.register_at(user_id, Instant::now())
+    }
+
+    pub fn forget(&mut self, user_id: &UserId) {
+        self.last_sent.remove(user_id);
+    }
+
+    /// Number of users with a tracked last message
+    pub fn tracked_users(&self) -> usize {
+        self.last_sent.len()
+    }
+
+    fn refresh_at(&mut self, now: Instant) -> Option<SlowModeChange> {
+        while let Some(sent_at) = self.sent.front() {
+            if now.duration_since(*sent_at) < FLOOD_WINDOW {
+                break;
+            }
+            self.sent.pop_front();
+        }
+        if self.active && self.sent.len() <= self.threshold / 2 {
+            self.active = false;
+            return Some(SlowModeChange::Left);
+        }
+        None
+    }
+
+    fn check_at(&self, user_id: UserId, now: Instant) -> Option<Duration> {
+        if !self.active {
+            return None;
+        }
+        let elapsed = now.duration_since(*self.last_sent.get(&user_id)?);
+        self.cooldown
+            .checked_sub(elapsed)
+            .filter(|left| !left.is_zero())
+    }
+
+    fn register_at(&mut self, user_id: UserId, now: Instant) -> Option<SlowModeChange> {
+        self.last_sent.insert(user_id, now);
+        if self.threshold == 0 {
+            return None;
+        }
+        self.sent.push_back(now);
+        if !self.active && self.sent.len() > self.threshold {
+            self.active = true;
+            return Some(SlowModeChange::Entered);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn enter_slow_mode_over_the_threshold() {
+        let mut slow_mode = SlowMode::new(3, Duration::from_secs(5));
+        let now = Instant::now();
+        for user_id in 1..=3 {
+            assert_eq!(slow_mode.register_at(user_id, now), None);
+        }
+        assert_eq!(slow_mode.check_at(1, now), None);
+        assert_eq!(slow_mode.register_at(4, now), Some(SlowModeChange::Entered));
+
+        let later = now + Duration::from_secs(2);
+        assert_eq!(slow_mode.check_at(1, later), Some(Duration::from_secs(3)));
+        assert_eq!(slow_mode.check_at(5, later), None);
+        assert_eq!(slow_mode.check_at(1, now + Duration::from_secs(5)), None);
+    }
+
+    #[test]
+    fn leave_slow_mode_once_the_room_calms_down() {
+        let mut slow_mode = SlowMode::new(3, Duration::from_secs(5));
+        let now = Instant::now();
+        for user_id in 1..=4 {
+            slow_mode.register_at(user_id, now);
+        }
+        assert!(slow_mode.active);
+        assert_eq!(slow_mode.refresh_at(now + Duration::from_secs(1)), None);
+
+        let later = now + FLOOD_WINDOW;
+        assert_eq!(slow_mode.refresh_at(later), Some(SlowModeChange::Left));
+        assert!(!slow_mode.active);
+        assert_eq!(slow_mode.check_at(1, later), None);
+    }
+
+    #[test]
+    fn never_enter_slow_mode_without_threshold() {
+        let mut slow_mode = SlowMode::default();
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert_eq!(slow_mode.register_at(1, now), None);
+        }
+        assert!(!slow_mode.active);
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+type UserId = usize;
+
+/// Number of votes a vote kick needs to pass, either a fixed count or
+/// a share of the users online other than the target
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VoteKickThreshold {
+    Count(usize),
+    Percent(usize),
+}
+
+impl VoteKickThreshold {
+    /// Votes needed out of the users able to vote, at least one
+    pub fn needed(&self, electorate: usize) -> usize {
+        match self {
+            VoteKickThreshold::Count(count) => *count,
+            VoteKickThreshold::Percent(percent) => (electorate * percent).div_ceil(100),
+        }
+        .max(1)
+    }
+}
+
+impl FromStr for VoteKickThreshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let threshold = match s.strip_suffix('%') {
+            Some(percent) => match percent.parse::<usize>() {
+                Ok(percent) if (1..=100).contains(&percent) => Self::Percent(percent),
+                _ => return Err("percentage must be between 1% and 100%".to_string()),
+            },
+            None => match s.parse::<usize>() {
+                Ok(count) if count > 0 => Self::Count(count),
+                _ => {
+                    return Err(
+                        "expected a number of votes, e.g. 3, or a share, e.g. 50%".to_string()
+                    )
+                }
+            },
+        };
+        Ok(threshold)
+    }
+}
+
+impl Display for VoteKickThreshold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VoteKickThreshold::Count(count) => write!(f, "{}", count),
+            VoteKickThreshold::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VoteKickTally {
+    Started { votes: usize, needed: usize },
+    Counted { votes: usize, needed: usize },
+    Passed { votes: usize, needed: usize },
+}
+
+#[derive(Debug)]
+struct VoteKickPoll {
+    voters: HashSet<String>,
+    started_at: Instant,
+}
+
+/// Open votes to kick a user, keyed by the target. A vote passes once
+/// enough distinct keys voted for it within the window, so a user
+/// can't vote again from another session
+#[derive(Debug)]
+pub struct VoteKicks {
+    threshold: VoteKickThreshold,
+    window: Duration,
+    polls: HashMap<UserId, VoteKickPoll>,
+}
+
+impl VoteKicks {
+    pub fn new(threshold: VoteKickThreshold, window: Duration) -> Self {
+        Self {
+            threshold,
+            window,
+            polls: HashMap::new(),
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Counts the vote of the key to kick the target, starting a vote
+    /// if there is none. The electorate is the number of distinct keys
+    /// online other than the target's. A passed vote is closed
+    pub fn vote(
+        &mut self,
+        target: UserId,
+        target_key: &str,
+        voter_key: &str,
+        electorate: usize,
+    ) -> Result<VoteKickTally, String> {
+        self.vote_at(target, target_key, voter_key, electorate, Instant::now())
+    }
+
+    /// Closes the votes open for longer than the window and returns
+    /// their targets
+    pub fn expire(&mut self) -> Vec<UserId> {
+        self.expire_at(Instant::now())
+    }
+
+    /// Closes the vote to kick the target, telling if there was one
+    pub fn cancel(&mut self, target: &UserId) -> bool {
+        self.polls.remove(target).is_some()
+    }
+
+    /// Drops the vote to kick a user who left
+    pub fn forget(&mut self, user_id: &UserId) {
+        self.polls.remove(user_id);
+    }
+
+    /// Drops the votes of a key no longer online
+    pub fn forget_voter(&mut self, voter_key: &str) {
+        for poll in self.polls.values_mut() {
+            poll.voters.remove(voter_key);
+        }
+    }
+
+    /// Number of open votes
+    pub fn open_polls(&self) -> usize {
+        self.polls.len()
+    }
+
+    fn vote_at(
+        &mut self,
+        target: UserId,
+        target_key: &str,
+        voter_key: &str,
+        electorate: usize,
+        now: Instant,
+    ) -> Result<VoteKickTally, String> {
+        if target_key == voter_key {
+            return Err("you can't vote to kick yourself".to_string());
+        }
+        let needed = self.threshold.needed(electorate);
+        let started = !self.polls.contains_key(&target);
+        let poll = self.polls.entry(target).or_insert_with(|| VoteKickPoll {
+            voters: HashSet::new(),
+            started_at: now,
+        });
+        if !poll.voters.insert(voter_key.to_string()) {
+            return Err("you already voted to kick them".to_string());
+        }
+        let votes = poll.voters.len();
+        if votes >= needed {
+            self.polls.remove(&target);
+            return Ok(VoteKickTally::Passed { votes, needed });
+        }
+        Ok(match started {
+            true => VoteKickTally::Started { votes, needed },
+            false => VoteKickTally::Counted { votes, needed },
+        })
+    }
+
+    fn expire_at(&mut self, now: Instant) -> Vec<UserId> {
+        let expired = self
+            .polls
+            .iter()
+            .filter(|(_, poll)| now.duration_since(poll.started_at) >= self.window)
+            .map(|(target, _)| *target)
+            .collect::<Vec<UserId>>();
+        for target in &expired {
+            self.polls.remove(target);
+        }
+        expired
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn parse_count_and_percent_thresholds() {
+        assert_eq!("3".parse(), Ok(VoteKickThreshold::Count(3)));
+        assert_eq!("50%".parse(), Ok(VoteKickThreshold::Percent(50)));
+        assert!("0".parse::<VoteKickThreshold>().is_err());
+        assert!("0%".parse::<VoteKickThreshold>().is_err());
+        assert!("101%".parse::<VoteKickThreshold>().is_err());
+        assert!("many".parse::<VoteKickThreshold>().is_err());
+
+        assert_eq!(VoteKickThreshold::Percent(50).needed(5), 3);
+        assert_eq!(VoteKickThreshold::Percent(50).needed(0), 1);
+        assert_eq!(VoteKickThreshold::Count(2).needed(10), 2);
+    }
+
+    #[test]
+    fn pass_once_enough_distinct_keys_voted() {
+        let mut votekicks = VoteKicks::new(VoteKickThreshold::Count(2), Duration::from_secs(60));
+        let now = Instant::now();
+        assert_eq!(
+            votekicks.vote_at(1, "target", "alice", 3, now),
+            Ok(VoteKickTally::Started {
+                votes: 1,
+                needed: 2
+            })
+        );
+        assert!(votekicks.vote_at(1, "target", "alice", 3, now).is_err());
+        assert!(votekicks.vote_at(1, "target", "target", 3, now).is_err());
+        assert_eq!(
+            votekicks.vote_at(1, "target", "bob", 3, now),
+            Ok(VoteKickTally::Passed {
+                votes: 2,
+                needed: 2
+            })
+        );
+        assert_eq!(votekicks.open_polls(), 0);
+    }
+
+    #[test]
+    fn expire_votes_after_the_window() {
+        let mut votekicks = VoteKicks::new(VoteKickThreshold::Count(3), Duration::from_secs(60));
+        let now = Instant::now();
+        votekicks.vote_at(1, "target", "alice", 5, now).unwrap();
+        votekicks
+            .vote_at(4, "other", "alice", 5, now + Duration::from_secs(30))
+            .unwrap();
+        assert_eq!(votekicks.expire_at(now + Duration::from_secs(60)), vec![1]);
+        assert_eq!(votekicks.open_polls(), 1);
+
+        votekicks.forget(&4);
+        assert_eq!(votekicks.open_polls(), 0);
+    }
+
+    #[test]
+    fn drop_votes_of_keys_gone_offline() {
+        let mut votekicks = VoteKicks::new(VoteKickThreshold::Count(2), Duration::from_secs(60));
+        let now = Instant::now();
+        votekicks.vote_at(1, "target", "alice", 3, now).unwrap();
+        votekicks.forget_voter("alice");
+        assert_eq!(
+            votekicks.vote_at(1, "target", "alice", 3, now),
+            Ok(VoteKickTally::Counted {
+                votes: 1,
+                needed: 2
+            })
+        );
+    }
+}
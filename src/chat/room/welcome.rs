@@ -0,0 +1,86 @@
+use std::collections::HashSet;
+
+use super::seen_file::SeenFile;
+
+use crate::utils;
+
+/// Private greeting sent to keys joining the room for the first time.
+/// Fingerprints of keys seen before are kept in memory and, when a seen
+/// file is set, appended to it so first-time detection survives restarts
+#[derive(Debug)]
+pub struct FirstJoinWelcome {
+    message: String,
+    seen: HashSet<String>,
+    seen_file: Option<SeenFile>,
+}
+
+impl FirstJoinWelcome {
+    pub fn new(message: &str) -> Self {
+        Self {
+            message: message.replace('\n', utils::NEWLINE),
+            seen: HashSet::new(),
+            seen_file: None,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn has_seen(&self, fingerprint: &str) -> bool {
+        self.seen.contains(fingerprint)
+    }
+
+    /// Loads seen fingerprints from the file, one per line, and keeps
+    /// appending new ones to it
+    pub fn load_seen_file(&mut self, path: &str) -> std::io::Result<()> {
+        let (file, content) = SeenFile::open(path)?;
+        let fingerprints = content.lines().map(str::trim).filter(|l| !l.is_empty());
+        self.seen.extend(fingerprints.map(String::from));
+        self.seen_file = Some(file);
+        Ok(())
+    }
+
+    /// Marks the fingerprint as seen and returns `true` if this is the
+    /// first time it joins
+    pub fn register(&mut self, fingerprint: &str) -> std::io::Result<bool> {
+        if !self.seen.insert(fingerprint.to_string()) {
+            return Ok(false);
+        }
+        if let Some(file) = &self.seen_file {
+            file.append(fingerprint)?;
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::chat::room::seen_file::temp_seen_file;
+
+    #[test]
+    fn register_first_join_only_once() {
+        let mut welcome = FirstJoinWelcome::new("Hi!");
+        assert!(welcome.register("SHA256:abc").unwrap());
+        assert!(!welcome.register("SHA256:abc").unwrap());
+        assert!(welcome.register("SHA256:def").unwrap());
+    }
+
+    #[test]
+    fn remember_seen_keys_across_restarts() {
+        let (_temp_dir, path) = temp_seen_file();
+        let open = || {
+            let mut welcome = FirstJoinWelcome::new("Hi!");
+            welcome.load_seen_file(&path).unwrap();
+            welcome
+        };
+
+        let mut welcome = open();
+        assert!(welcome.register("SHA256:abc").unwrap());
+
+        let mut welcome = open();
+        assert!(!welcome.register("SHA256:abc").unwrap());
+        assert!(welcome.register("SHA256:def").unwrap());
+    }
+}
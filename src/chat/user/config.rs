@@ -1,9 +1,15 @@
-use super::{DisplayName, TimestampMode, UserTheme};
+use std::collections::BTreeSet;
+
+use super::{DisplayName, PromptTemplate, TimestampMode, UserTheme};
 
 #[derive(Debug, Clone)]
 pub struct HighlightRegex(regex::Regex);
 
 impl HighlightRegex {
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
     // Method to find the first match in the text
     pub fn find<'a>(&'a self, text: &'a str) -> Option<&'a str> {
         self.0.find(text).map(|m| m.as_str())
@@ -42,6 +48,11 @@ pub struct UserConfig {
     timestamp_mode: TimestampMode,
     quiet: bool,
     bell: bool,
+    emoji: bool,
+    read_receipts: bool,
+    prompt_template: Option<PromptTemplate>,
+    focused: BTreeSet<usize>,
+    excluded: BTreeSet<usize>,
 }
 
 impl Default for UserConfig {
@@ -49,10 +60,15 @@ impl Default for UserConfig {
         Self {
             bell: true,
             quiet: false,
+            emoji: false,
+            read_receipts: false,
+            prompt_template: None,
             highlight: None,
             display_name: Default::default(),
             theme: Default::default(),
             timestamp_mode: Default::default(),
+            focused: Default::default(),
+            excluded: Default::default(),
         }
     }
 }
@@ -74,6 +90,22 @@ impl UserConfig {
         self.bell
     }
 
+    /// Whether `:shortcode:` emoji are expanded in the user's messages
+    pub fn emoji(&self) -> bool {
+        self.emoji
+    }
+
+    /// Whether the user is told when their PMs are seen, and lets the
+    /// senders of PMs know when they see them
+    pub fn read_receipts(&self) -> bool {
+        self.read_receipts
+    }
+
+    /// The user's own prompt layout, overriding the room's default
+    pub fn prompt_template(&self) -> Option<&PromptTemplate> {
+        self.prompt_template.as_ref()
+    }
+
     pub fn timestamp_mode(&self) -> &TimestampMode {
         &self.timestamp_mode
     }
@@ -82,10 +114,42 @@ impl UserConfig {
         &self.display_name
     }
 
+    /// Users whose messages are the only ones shown, if any
+    pub fn focused(&self) -> &BTreeSet<usize> {
+        &self.focused
+    }
+
+    /// Users whose messages are hidden while everyone else is shown
+    pub fn excluded(&self) -> &BTreeSet<usize> {
+        &self.excluded
+    }
+
+    /// Checks whether messages from the user pass the focus filter
+    pub fn is_in_focus(&self, id: usize) -> bool {
+        !self.excluded.contains(&id) && (self.focused.is_empty() || self.focused.contains(&id))
+    }
+
     pub fn switch_quiet_mode(&mut self) {
         self.quiet = !self.quiet;
     }
 
+    pub fn switch_emoji_mode(&mut self) {
+        self.emoji = !self.emoji;
+    }
+
+    pub fn switch_read_receipts(&mut self) {
+        self.read_receipts = !self.read_receipts;
+    }
+
+    /// Whether highlights, PMs and summons ring the user's terminal
+    pub fn set_bell(&mut self, bell: bool) {
+        self.bell = bell;
+    }
+
+    pub fn set_prompt_template(&mut self, template: Option<PromptTemplate>) {
+        self.prompt_template = template;
+    }
+
     pub fn set_timestamp_mode(&mut self, mode: TimestampMode) {
         self.timestamp_mode = mode;
     }
@@ -100,7 +164,20 @@ impl UserConfig {
         self.display_name = self.theme.style_username(username).to_string().into();
     }
 
-    pub(in crate::chat::user) fn set_theme(&mut self, theme: UserTheme) {
+    pub(in crate::chat::user) fn set_theme(&mut self, mut theme: UserTheme) {
+        theme.set_username_colors(self.theme.username_colors().clone());
         self.theme = theme;
     }
+
+    pub(in crate::chat::user) fn focused_mut(&mut self) -> &mut BTreeSet<usize> {
+        &mut self.focused
+    }
+
+    pub(in crate::chat::user) fn excluded_mut(&mut self) -> &mut BTreeSet<usize> {
+        &mut self.excluded
+    }
+
+    pub(in crate::chat::user) fn theme_mut(&mut self) -> &mut UserTheme {
+        &mut self.theme
+    }
 }
@@ -1,5 +1,6 @@
 mod config;
 mod display_name;
+mod prompt_template;
 mod status;
 mod theme;
 mod timestamp_mode;
@@ -8,8 +9,9 @@ mod username;
 
 pub use config::UserConfig;
 pub use display_name::DisplayName;
+pub use prompt_template::PromptTemplate;
 pub use status::UserStatus;
-pub use theme::{Theme, UserTheme};
+pub use theme::{parse_hex_color, Theme, ThemeChoice, UserTheme};
 pub use timestamp_mode::TimestampMode;
 pub use user::User;
 pub use username::UserName;
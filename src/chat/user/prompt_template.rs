@@ -0,0 +1,112 @@
+use std::str::FromStr;
+
+use regex::{Captures, Regex};
+
+use super::{User, UserStatus};
+
+/// Longest template accepted, so the prompt leaves room for the input
+const MAX_PROMPT_TEMPLATE_LEN: usize = 64;
+
+lazy_static::lazy_static! {
+    static ref RE_PLACEHOLDER: Regex = Regex::new(r"\{([^{}]*)\}").unwrap();
+}
+
+/// Layout of the input prompt with `{name}`, `{status}` and `{users}`
+/// placeholders, e.g. `{name} ({users} online)`. The name is expanded
+/// with the user's theme, the rest of the template is shown as typed
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptTemplate(String);
+
+impl PromptTemplate {
+    /// Fills in the placeholders for the user in a room of the given
+    /// number of members
+    pub fn expand(&self, user: &User, users: usize) -> String {
+        RE_PLACEHOLDER
+            .replace_all(&self.0, |caps: &Captures| match &caps[1] {
+                "name" => user.config().display_name().to_string(),
+                "status" => match user.status() {
+                    UserStatus::Active => "active".to_string(),
+                    UserStatus::Away { .. } => "away".to_string(),
+                },
+                "users" => users.to_string(),
+                _ => caps[0].to_string(),
+            })
+            .to_string()
+    }
+}
+
+impl FromStr for PromptTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err("prompt template is empty".to_string());
+        }
+        if s.chars().count() > MAX_PROMPT_TEMPLATE_LEN {
+            return Err(format!(
+                "prompt template is over {} characters",
+                MAX_PROMPT_TEMPLATE_LEN
+            ));
+        }
+        if s.chars().any(char::is_control) {
+            return Err("prompt template must not contain control characters".to_string());
+        }
+        for caps in RE_PLACEHOLDER.captures_iter(s) {
+            match &caps[1] {
+                "name" | "status" | "users" => {}
+                other => return Err(format!("unknown prompt placeholder: {{{}}}", other)),
+            }
+        }
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl std::fmt::Display for PromptTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::pubkey::PubKey;
+    use russh_keys::key::KeyPair;
+
+    fn create_test_user() -> User {
+        let key_pair = KeyPair::generate_ed25519().unwrap();
+        let key = PubKey::from(key_pair.clone_public_key().unwrap());
+        User::new(1, "alice".into(), "ssh".to_string(), key)
+    }
+
+    #[test]
+    fn expand_placeholders() {
+        let mut user = create_test_user();
+        let template = "{name} ({status}, {users} online)"
+            .parse::<PromptTemplate>()
+            .unwrap();
+        let name = user.config().display_name().to_string();
+        assert_eq!(
+            template.expand(&user, 3),
+            format!("{} (active, 3 online)", name)
+        );
+
+        user.go_away("lunch".to_string());
+        assert_eq!(
+            template.expand(&user, 1),
+            format!("{} (away, 1 online)", name)
+        );
+    }
+
+    #[test]
+    fn reject_invalid_templates() {
+        assert!("".parse::<PromptTemplate>().is_err());
+        assert!("\x1b[31m{name}".parse::<PromptTemplate>().is_err());
+        assert!("{name}\x07".parse::<PromptTemplate>().is_err());
+        assert!("{nick}".parse::<PromptTemplate>().is_err());
+        assert!("x"
+            .repeat(MAX_PROMPT_TEMPLATE_LEN + 1)
+            .parse::<PromptTemplate>()
+            .is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use chrono::{DateTime, Utc};
 
 #[derive(Clone, Debug, PartialEq)]
@@ -6,6 +8,7 @@ pub enum UserStatus {
     Away {
         reason: String,
         since: DateTime<Utc>,
+        until: Option<DateTime<Utc>>,
     },
 }
 
@@ -14,3 +17,31 @@ impl Default for UserStatus {
         Self::Active
     }
 }
+
+/// Reads the return ETA given at the end of an away reason, e.g. `lunch,
+/// back in 30m`
+pub fn parse_away_eta(reason: &str) -> Option<Duration> {
+    let reason = reason.to_lowercase();
+    let (_, eta) = reason.rsplit_once("back in ")?;
+    let eta = eta.trim_end_matches(|c: char| c.is_ascii_punctuation() || c.is_whitespace());
+    humantime::parse_duration(eta).ok()
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn parse_eta_at_end_of_away_reason() {
+        assert_eq!(
+            parse_away_eta("lunch back in 30m"),
+            Some(Duration::from_secs(1800))
+        );
+        assert_eq!(
+            parse_away_eta("meeting, Back in 1h 15m!"),
+            Some(Duration::from_secs(4500))
+        );
+        assert_eq!(parse_away_eta("lunch"), None);
+        assert_eq!(parse_away_eta("back in a bit"), None);
+    }
+}
@@ -1,9 +1,15 @@
-use crossterm::style::{Attribute, Color, StyledContent, Stylize};
+use crossterm::style::{Attribute, Color, SetAttribute, StyledContent, Stylize};
 use fnv::FnvHasher;
+use rand::seq::IteratorRandom;
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::hash::{Hash, Hasher};
+use std::str::FromStr;
 use strum::{EnumIter, EnumString, IntoEnumIterator};
 
+use super::{User, UserConfig};
+use crate::chat::message::{self, Author, MessageFormatter};
+
 #[derive(Debug, Clone)]
 enum ThemeColor {
     // Include predefined crossterm colors
@@ -12,6 +18,8 @@ enum ThemeColor {
     Green,
     DarkGreen,
     DarkYellow,
+    Cyan,
+    Red,
     White,
 
     // Custom colors
@@ -26,6 +34,8 @@ impl From<ThemeColor> for Color {
             ThemeColor::Green => Color::Green,
             ThemeColor::DarkGreen => Color::DarkGreen,
             ThemeColor::DarkYellow => Color::DarkYellow,
+            ThemeColor::Cyan => Color::Cyan,
+            ThemeColor::Red => Color::Red,
             ThemeColor::White => Color::White,
             ThemeColor::FromString(s) => {
                 let mut hasher = FnvHasher::default();
@@ -82,41 +92,139 @@ impl Default for Theme {
     }
 }
 
+/// Theme asked for with `/theme`: either a named one, or `random` and
+/// `next` that are resolved against the theme currently in use
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThemeChoice {
+    Named(Theme),
+    Random,
+    Next,
+}
+
+impl ThemeChoice {
+    pub fn from_prefix(prefix: &str) -> Option<ThemeChoice> {
+        Theme::from_prefix(prefix)
+            .map(ThemeChoice::Named)
+            .or_else(|| {
+                [ThemeChoice::Random, ThemeChoice::Next]
+                    .into_iter()
+                    .find(|choice| choice.to_string().starts_with(prefix))
+            })
+    }
+
+    /// Picks the theme to switch to. `random` never picks the current
+    /// theme, `next` wraps around after the last one
+    pub fn resolve(self, current: Theme) -> Theme {
+        match self {
+            ThemeChoice::Named(theme) => theme,
+            ThemeChoice::Random => Theme::iter()
+                .filter(|theme| *theme != current)
+                .choose(&mut rand::thread_rng())
+                .unwrap_or(current),
+            ThemeChoice::Next => Theme::iter()
+                .cycle()
+                .skip_while(|theme| *theme != current)
+                .nth(1)
+                .unwrap_or(current),
+        }
+    }
+}
+
+impl Default for ThemeChoice {
+    fn default() -> Self {
+        Self::Named(Theme::default())
+    }
+}
+
+impl FromStr for ThemeChoice {
+    type Err = strum::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "random" => Ok(ThemeChoice::Random),
+            "next" => Ok(ThemeChoice::Next),
+            _ => s.parse::<Theme>().map(ThemeChoice::Named),
+        }
+    }
+}
+
+impl Display for ThemeChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThemeChoice::Named(theme) => write!(f, "{}", theme),
+            ThemeChoice::Random => write!(f, "random"),
+            ThemeChoice::Next => write!(f, "next"),
+        }
+    }
+}
+
 impl Into<UserTheme> for Theme {
     fn into(self) -> UserTheme {
         match self {
             Theme::Colors => UserTheme {
+                theme: Theme::Colors,
+                markup: true,
                 text_fg: ThemeColor::White.into(),
                 system_text_fg: ThemeColor::DarkGrey.into(),
                 tagged_username_fg: ThemeColor::Black.into(),
                 tagged_username_bg: ThemeColor::DarkYellow.into(),
-                username_fg: |s| ThemeColor::FromString(s).into(),
+                username_fg: |s, color| color.unwrap_or_else(|| ThemeColor::FromString(s).into()),
+                username_colors: HashMap::new(),
+                system_prefix_fg: Some(ThemeColor::Cyan.into()),
+                error_prefix_fg: Some(ThemeColor::Red.into()),
+                announce_prefix_fg: Some(ThemeColor::DarkYellow.into()),
             },
             Theme::Mono => UserTheme {
+                theme: Theme::Mono,
+                markup: false,
                 text_fg: ThemeColor::White.into(),
                 system_text_fg: ThemeColor::White.into(),
                 tagged_username_fg: ThemeColor::White.into(),
                 tagged_username_bg: ThemeColor::DarkGrey.into(),
-                username_fg: |_| ThemeColor::White.into(),
+                username_fg: |_, _| ThemeColor::White.into(),
+                username_colors: HashMap::new(),
+                system_prefix_fg: None,
+                error_prefix_fg: None,
+                announce_prefix_fg: None,
             },
             Theme::Hacker => UserTheme {
+                theme: Theme::Hacker,
+                markup: true,
                 text_fg: ThemeColor::Green.into(),
                 system_text_fg: ThemeColor::DarkGreen.into(),
                 tagged_username_fg: ThemeColor::DarkGreen.into(),
                 tagged_username_bg: ThemeColor::Green.into(),
-                username_fg: |_| ThemeColor::Green.into(),
+                username_fg: |_, _| ThemeColor::Green.into(),
+                username_colors: HashMap::new(),
+                system_prefix_fg: Some(ThemeColor::Green.into()),
+                error_prefix_fg: Some(ThemeColor::Green.into()),
+                announce_prefix_fg: Some(ThemeColor::Green.into()),
             },
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct UserTheme {
+    theme: Theme,
     text_fg: Color,
     system_text_fg: Color,
-    username_fg: fn(String) -> Color,
+    username_fg: fn(String, Option<Color>) -> Color,
+    username_colors: HashMap<String, Color>,
     tagged_username_fg: Color,
     tagged_username_bg: Color,
+    system_prefix_fg: Option<Color>,
+    error_prefix_fg: Option<Color>,
+    announce_prefix_fg: Option<Color>,
+    markup: bool,
+}
+
+/// Themes are equal when they come from the same predefined theme with
+/// the same username colors, as every other style follows from those
+impl PartialEq for UserTheme {
+    fn eq(&self, other: &Self) -> bool {
+        self.theme == other.theme && self.username_colors == other.username_colors
+    }
 }
 
 impl Default for UserTheme {
@@ -126,6 +234,11 @@ impl Default for UserTheme {
 }
 
 impl UserTheme {
+    /// Predefined theme the styles come from
+    pub fn theme(&self) -> Theme {
+        self.theme
+    }
+
     pub fn style_text<'a>(&self, s: &'a str) -> StyledContent<&'a str> {
         s.with(self.text_fg)
     }
@@ -134,17 +247,369 @@ impl UserTheme {
         s.with(self.system_text_fg)
     }
 
+    /// Styles the `->` prefix of system messages. Themes without a prefix
+    /// color leave it plain.
+    pub fn style_system<'a>(&self, s: &'a str) -> StyledContent<&'a str> {
+        style_optional(s, self.system_prefix_fg)
+    }
+
+    pub fn style_error<'a>(&self, s: &'a str) -> StyledContent<&'a str> {
+        style_optional(s, self.error_prefix_fg)
+    }
+
+    pub fn style_announce<'a>(&self, s: &'a str) -> StyledContent<&'a str> {
+        style_optional(s, self.announce_prefix_fg)
+    }
+
     pub fn style_username<'a>(&self, s: &'a str) -> StyledContent<&'a str> {
         s.with(self.get_username_fg(s))
     }
 
+    /// Styles the marker shown after the names of newcomers
+    pub fn style_newcomer_marker(&self) -> StyledContent<&'static str> {
+        "(new)".with(self.system_text_fg)
+    }
+
     pub fn style_tagged_username<'a>(&self, s: &'a str) -> StyledContent<&'a str> {
         s.on(self.tagged_username_bg)
             .with(self.tagged_username_fg)
             .attribute(Attribute::Bold)
     }
 
+    /// Renders `*bold*` and `_underline_` spans in the text into
+    /// terminal attributes. Themes without markup only strip the markers.
+    /// Markers outside of a span, e.g. in ASCII art, are left as is.
+    /// Use `\\*` or `\\_` for a literal marker.
+    pub fn render_markup(&self, s: &str) -> String {
+        let (mut bold, mut underline) = (false, false);
+        let mut rendered = String::with_capacity(s.len());
+        for token in parse_markup(s) {
+            let attribute = match token {
+                MarkupToken::Char(c) => {
+                    rendered.push(c);
+                    continue;
+                }
+                MarkupToken::Marker('*') => {
+                    bold = !bold;
+                    match bold {
+                        true => Attribute::Bold,
+                        false => Attribute::NormalIntensity,
+                    }
+                }
+                MarkupToken::Marker(_) => {
+                    underline = !underline;
+                    match underline {
+                        true => Attribute::Underlined,
+                        false => Attribute::NoUnderline,
+                    }
+                }
+            };
+            if self.markup {
+                rendered.push_str(&SetAttribute(attribute).to_string());
+            }
+        }
+        rendered
+    }
+
+    /// Sample lines of a public message from the user, a system message,
+    /// an error, an announce and a few usernames, styled in this theme
+    pub fn preview(&self, username: &str) -> Vec<String> {
+        let mut config = UserConfig::default();
+        *config.theme_mut() = self.clone();
+        let author = |name: &str| {
+            let mut user = User::default();
+            user.set_username(name.into());
+            Author::from(user)
+        };
+        let names = [username, "alice", "bob", "carol", "dave"]
+            .iter()
+            .map(|name| self.style_username(name).to_string())
+            .collect::<Vec<String>>();
+        vec![
+            message::Public::new(author(username), "hello, how does this look?".to_string())
+                .format(&config),
+            message::System::new(author(username), "a system message".to_string()).format(&config),
+            message::Error::new(author(username), "something went wrong".to_string())
+                .format(&config),
+            message::Announce::new(author("alice"), "joined".to_string()).format(&config),
+            names.join(", "),
+        ]
+    }
+
+    pub fn username_colors(&self) -> &HashMap<String, Color> {
+        &self.username_colors
+    }
+
+    /// Pins colors to the usernames, overriding the hash-based ones in
+    /// themes that color usernames
+    pub fn set_username_colors(&mut self, colors: HashMap<String, Color>) {
+        self.username_colors = colors;
+    }
+
     fn get_username_fg(&self, arg: &str) -> Color {
-        (self.username_fg)(arg.to_string())
+        let color = self.username_colors.get(arg).copied();
+        (self.username_fg)(arg.to_string(), color)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum MarkupToken {
+    Char(char),
+    Marker(char),
+}
+
+/// Splits text with `*bold*` and `_underline_` spans into plain pieces
+/// and marker toggles. A span opens with a marker before a word and
+/// closes with the same marker after a word on that line, so markers
+/// elsewhere, as in ASCII art, are kept as literals. A backslash makes
+/// a marker that would open a span literal.
+fn parse_markup(s: &str) -> Vec<MarkupToken> {
+    let chars = s.chars().collect::<Vec<char>>();
+    let mut closers = vec![false; chars.len()];
+    let mut open = vec![];
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let before = i.checked_sub(1).map(|j| chars[j]);
+        if closers[i] {
+            open.retain(|marker| *marker != c);
+            tokens.push(MarkupToken::Marker(c));
+        } else if c == '\\'
+            && chars.get(i + 1).is_some_and(|next| is_marker(*next))
+            && find_span_end(&chars, i + 1, before).is_some()
+        {
+            tokens.push(MarkupToken::Char(chars[i + 1]));
+            i += 1;
+        } else if is_marker(c) && !open.contains(&c) {
+            match find_span_end(&chars, i, before) {
+                Some(end) => {
+                    closers[end] = true;
+                    open.push(c);
+                    tokens.push(MarkupToken::Marker(c));
+                }
+                None => tokens.push(MarkupToken::Char(c)),
+            }
+        } else {
+            tokens.push(MarkupToken::Char(c));
+        }
+        i += 1;
+    }
+    tokens
+}
+
+fn is_marker(c: char) -> bool {
+    c == '*' || c == '_'
+}
+
+/// Returns the position of the marker closing the span that the marker
+/// at `start` opens, if it opens one. `before` is the character ahead
+/// of the marker
+fn find_span_end(chars: &[char], start: usize, before: Option<char>) -> Option<usize> {
+    let marker = chars[start];
+    if before.is_some_and(|c| !c.is_whitespace() && !is_marker(c) && !"(\"'".contains(c)) {
+        return None;
+    }
+    let first = *chars.get(start + 1)?;
+    if !first.is_alphanumeric() && !is_marker(first) && !"(\"'".contains(first) {
+        return None;
+    }
+    if first == marker {
+        return None;
+    }
+    (start + 2..chars.len())
+        .take_while(|j| !matches!(chars[*j], '\n' | '\r'))
+        .find(|j| {
+            let last = chars[j - 1];
+            let after = chars.get(j + 1);
+            chars[*j] == marker
+                && last != marker
+                && (last.is_alphanumeric() || is_marker(last) || ".!?)\"'".contains(last))
+                && after
+                    .is_none_or(|c| c.is_whitespace() || is_marker(*c) || ".,;:!?)\"'".contains(*c))
+        })
+}
+
+fn style_optional(s: &str, color: Option<Color>) -> StyledContent<&str> {
+    match color {
+        Some(color) => s.with(color),
+        None => s.stylize(),
+    }
+}
+
+/// Parses a hex RGB color in the `#rrggbb` or `rrggbb` form.
+pub fn parse_hex_color(s: &str) -> Option<Color> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(Color::Rgb {
+        r: channel(0)?,
+        g: channel(2)?,
+        b: channel(4)?,
+    })
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn resolve_random_and_next_theme_choices() {
+        assert_eq!("Hacker".parse(), Ok(ThemeChoice::Named(Theme::Hacker)));
+        assert_eq!("random".parse(), Ok(ThemeChoice::Random));
+        assert_eq!(ThemeChoice::from_prefix("ne"), Some(ThemeChoice::Next));
+        assert_eq!(
+            ThemeChoice::from_prefix("m"),
+            Some(ThemeChoice::Named(Theme::Mono))
+        );
+
+        assert_eq!(ThemeChoice::Next.resolve(Theme::Colors), Theme::Mono);
+        assert_eq!(ThemeChoice::Next.resolve(Theme::Hacker), Theme::Colors);
+        for theme in Theme::iter() {
+            assert_ne!(ThemeChoice::Random.resolve(theme), theme);
+        }
+    }
+
+    #[test]
+    fn keep_message_prefixes_plain_in_mono_theme() {
+        let theme: UserTheme = Theme::Mono.into();
+        assert_eq!(theme.style_system("->").to_string(), "->");
+        assert_eq!(theme.style_error("-> Error:").to_string(), "-> Error:");
+        assert_eq!(theme.style_announce("*").to_string(), "*");
+    }
+
+    #[test]
+    fn color_message_prefixes_in_hacker_theme() {
+        let theme: UserTheme = Theme::Hacker.into();
+        assert_eq!(
+            theme.style_system("->").to_string(),
+            "\u{1b}[38;5;10m->\u{1b}[39m"
+        );
+        assert_eq!(
+            theme.style_error("-> Error:").to_string(),
+            "\u{1b}[38;5;10m-> Error:\u{1b}[39m"
+        );
+        assert_eq!(
+            theme.style_announce("*").to_string(),
+            "\u{1b}[38;5;10m*\u{1b}[39m"
+        );
+    }
+
+    #[test]
+    fn preview_theme_with_sample_lines() {
+        let theme: UserTheme = Theme::Mono.into();
+        assert_eq!(
+            theme.preview("eve"),
+            vec![
+                "\u{1b}[38;5;15meve\u{1b}[39m: hello, how does this look?",
+                "->\u{1b}[38;5;15m a system message\u{1b}[39m",
+                "-> Error:\u{1b}[38;5;15m something went wrong\u{1b}[39m",
+                " *\u{1b}[38;5;15m alice joined\u{1b}[39m",
+                "\u{1b}[38;5;15meve\u{1b}[39m, \u{1b}[38;5;15malice\u{1b}[39m, \u{1b}[38;5;15mbob\u{1b}[39m, \u{1b}[38;5;15mcarol\u{1b}[39m, \u{1b}[38;5;15mdave\u{1b}[39m",
+            ]
+        );
+    }
+
+    #[test]
+    fn remember_predefined_theme() {
+        for theme in Theme::iter() {
+            let user_theme: UserTheme = theme.into();
+            assert_eq!(user_theme.theme(), theme);
+        }
+    }
+
+    #[test]
+    fn render_markup_as_attributes() {
+        let theme: UserTheme = Theme::Colors.into();
+        assert_eq!(
+            theme.render_markup("a *bold* and _under_ line"),
+            "a \x1b[1mbold\x1b[22m and \x1b[4munder\x1b[24m line"
+        );
+    }
+
+    #[test]
+    fn strip_markup_in_mono_theme() {
+        let theme: UserTheme = Theme::Mono.into();
+        assert_eq!(
+            theme.render_markup("a *bold* and _under_ line"),
+            "a bold and under line"
+        );
+    }
+
+    #[test]
+    fn keep_escaped_and_unpaired_markers() {
+        let theme: UserTheme = Theme::Mono.into();
+        assert_eq!(theme.render_markup("\\*not bold*"), "*not bold*");
+        assert_eq!(theme.render_markup("2 \\* 3 = 6"), "2 \\* 3 = 6");
+        assert_eq!(theme.render_markup("5 * 3"), "5 * 3");
+        assert_eq!(theme.render_markup("*a* * _b"), "a * _b");
+        assert_eq!(theme.render_markup("back\\\\slash"), "back\\\\slash");
+        assert_eq!(theme.render_markup("snake_case_name"), "snake_case_name");
+    }
+
+    #[test]
+    fn render_nested_markup_and_markup_in_punctuation() {
+        let theme: UserTheme = Theme::Mono.into();
+        assert_eq!(theme.render_markup("*_both_*"), "both");
+        assert_eq!(theme.render_markup("(*see* _this_)."), "(see this).");
+        assert_eq!(
+            theme.render_markup("*Welcome!* to _the_ room"),
+            "Welcome! to the room"
+        );
+    }
+
+    #[test]
+    fn keep_ascii_art_as_is() {
+        let art = [
+            r" _____ _           _      _ ",
+            r"/  __ \ |         | |    | |",
+            r"| /  \/ |__   __ _| |_ __| |",
+            r"| |   | '_ \ / _` | __/ _` |",
+            r"| \__/\ | | | (_| | || (_| |",
+            r" \____/_| |_|\__,_|\__\__,_|",
+            r"  /\_/\   *****   _| |_",
+            r" ( o.o )  * * *  |_   _|",
+        ]
+        .join("\r\n");
+        for theme in [Theme::Colors, Theme::Mono] {
+            let theme: UserTheme = theme.into();
+            assert_eq!(theme.render_markup(&art), art);
+        }
+    }
+
+    #[test]
+    fn parse_hex_colors() {
+        let color = Color::Rgb {
+            r: 0xff,
+            g: 0x88,
+            b: 0x00,
+        };
+        assert_eq!(parse_hex_color("#ff8800"), Some(color));
+        assert_eq!(parse_hex_color("FF8800"), Some(color));
+        assert_eq!(parse_hex_color("#ff880"), None);
+        assert_eq!(parse_hex_color("#gg8800"), None);
+    }
+
+    #[test]
+    fn prefer_username_color_override() {
+        let color = Color::Rgb { r: 1, g: 2, b: 3 };
+        let mut theme: UserTheme = Theme::Colors.into();
+        theme.set_username_colors(HashMap::from([("bob".to_string(), color)]));
+        assert_eq!(theme.get_username_fg("bob"), color);
+        assert_ne!(theme.get_username_fg("alice"), color);
+
+        theme.set_username_colors(HashMap::new());
+        assert_ne!(theme.get_username_fg("bob"), color);
+    }
+
+    #[test]
+    fn ignore_username_color_override_in_mono_theme() {
+        let color = Color::Rgb { r: 1, g: 2, b: 3 };
+        let mut theme: UserTheme = Theme::Mono.into();
+        theme.set_username_colors(HashMap::from([("bob".to_string(), color)]));
+        assert_eq!(theme.get_username_fg("bob"), Color::White);
     }
 }
@@ -7,6 +7,9 @@ use strum::{EnumIter, EnumString, IntoEnumIterator};
 pub enum TimestampMode {
     Time,
     DateTime,
+    /// Elapsed time since the viewing user joined, so every user sees a
+    /// different prefix for the same message
+    Relative,
     Off,
 }
 
@@ -27,7 +30,7 @@ impl TimestampMode {
         match self {
             TimestampMode::Time => Some("%H:%M"),
             TimestampMode::DateTime => Some("%Y-%m-%d %H:%M:%S"),
-            TimestampMode::Off => None,
+            TimestampMode::Relative | TimestampMode::Off => None,
         }
     }
 
@@ -44,6 +47,7 @@ impl Display for TimestampMode {
             match self {
                 TimestampMode::Time => "time",
                 TimestampMode::DateTime => "datetime",
+                TimestampMode::Relative => "relative",
                 TimestampMode::Off => "off",
             }
         )
@@ -1,13 +1,15 @@
 use chrono::{DateTime, Utc};
-use std::collections::BTreeSet;
+use crossterm::style::Color;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::Display;
+use std::net::SocketAddr;
 use std::time::Duration;
 
 use crate::pubkey::PubKey;
 use crate::utils;
 
 use super::config::UserConfig;
-use super::status::UserStatus;
+use super::status::{parse_away_eta, UserStatus};
 use super::{UserName, UserTheme};
 
 #[derive(Debug, Default, Clone, PartialEq)]
@@ -16,17 +18,21 @@ pub struct User {
     username: UserName,
     config: UserConfig,
     status: UserStatus,
+    last_away_reason: Option<String>,
 
     public_key: PubKey,
 
     reply_to: Option<usize>,
     is_muted: bool,
+    is_newcomer: bool,
 
     ignored: BTreeSet<usize>,
-    focused: BTreeSet<usize>,
+    pending_ignore: HashSet<UserName>,
+    pending_focus: HashSet<UserName>,
 
     joined_at: DateTime<Utc>,
     ssh_client: String,
+    peer_addr: Option<SocketAddr>,
 }
 
 impl User {
@@ -46,10 +52,18 @@ impl User {
         self.is_muted = !self.is_muted;
     }
 
+    /// Marks the user away. A return ETA at the end of the reason, e.g.
+    /// `back in 30m`, sets when they are expected back
     pub fn go_away(&mut self, reason: String) {
+        let since = Utc::now();
+        let until = parse_away_eta(&reason)
+            .and_then(|eta| chrono::Duration::from_std(eta).ok())
+            .map(|eta| since + eta);
+        self.last_away_reason = Some(reason.clone());
         self.status = UserStatus::Away {
             reason,
-            since: Utc::now(),
+            since,
+            until,
         };
     }
 
@@ -69,6 +83,30 @@ impl User {
         self.update_highlight();
     }
 
+    pub fn set_username_colors(&mut self, colors: HashMap<String, Color>) {
+        self.config.theme_mut().set_username_colors(colors);
+        self.update_display_name();
+    }
+
+    /// Address the user connected from. Only operators may see it, so it
+    /// is left out of the user's `Display`
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    pub fn set_peer_addr(&mut self, addr: Option<SocketAddr>) {
+        self.peer_addr = addr;
+    }
+
+    /// Version string the SSH client sent on connect
+    pub fn ssh_client(&self) -> &str {
+        &self.ssh_client
+    }
+
+    pub fn joined_at(&self) -> DateTime<Utc> {
+        self.joined_at
+    }
+
     pub fn joined_duration(&self) -> Duration {
         let now = Utc::now();
         let secs = now.signed_duration_since(self.joined_at).num_seconds() as u64;
@@ -87,6 +125,15 @@ impl User {
         self.is_muted
     }
 
+    /// Whether the user's key was first seen recently
+    pub fn is_newcomer(&self) -> bool {
+        self.is_newcomer
+    }
+
+    pub fn mark_newcomer(&mut self) {
+        self.is_newcomer = true;
+    }
+
     pub fn config(&self) -> &UserConfig {
         &self.config
     }
@@ -99,6 +146,12 @@ impl User {
         &self.status
     }
 
+    /// Reason given the last time the user went away, kept after they
+    /// are back
+    pub fn last_away_reason(&self) -> Option<&String> {
+        self.last_away_reason.as_ref()
+    }
+
     pub fn reply_to(&self) -> &Option<usize> {
         &self.reply_to
     }
@@ -112,15 +165,21 @@ impl User {
     }
 
     pub fn focused(&self) -> &BTreeSet<usize> {
-        &self.focused
+        self.config.focused()
+    }
+
+    pub fn excluded(&self) -> &BTreeSet<usize> {
+        self.config.excluded()
     }
 
     pub fn unignore(&mut self, id: &usize) {
         self.ignored.remove(id);
     }
 
+    /// Removes the user from both the focused and the excluded users
     pub fn unfocus(&mut self, id: &usize) {
-        self.focused.remove(id);
+        self.config.focused_mut().remove(id);
+        self.config.excluded_mut().remove(id);
     }
 
     pub fn ignore(&mut self, id: usize) {
@@ -128,11 +187,47 @@ impl User {
     }
 
     pub fn focus(&mut self, id: usize) {
-        self.focused.insert(id);
+        self.config.excluded_mut().remove(&id);
+        self.config.focused_mut().insert(id);
+    }
+
+    /// Hides messages from the user while the rest are still shown
+    pub fn exclude(&mut self, id: usize) {
+        self.config.focused_mut().remove(&id);
+        self.config.excluded_mut().insert(id);
+    }
+
+    /// Ignores the user with the name once they join
+    pub fn ignore_later(&mut self, name: UserName) {
+        self.pending_ignore.insert(name);
+    }
+
+    /// Focuses on the user with the name once they join
+    pub fn focus_later(&mut self, name: UserName) {
+        self.pending_focus.insert(name);
+    }
+
+    /// Applies the pending ignore or focus waiting for the name to the
+    /// user who joined with it
+    pub fn resolve_pending(&mut self, name: &UserName, id: usize) {
+        if self.pending_ignore.remove(name) {
+            self.ignore(id);
+        }
+        if self.pending_focus.remove(name) {
+            self.focus(id);
+        }
     }
 
     pub fn unfocus_all(&mut self) {
-        self.focused.clear();
+        self.config.focused_mut().clear();
+        self.config.excluded_mut().clear();
+    }
+
+    /// Replaces the focus filter, e.g. with the one saved from a
+    /// previous session
+    pub fn restore_focus(&mut self, focused: BTreeSet<usize>, excluded: BTreeSet<usize>) {
+        *self.config.focused_mut() = focused;
+        *self.config.excluded_mut() = excluded;
     }
 
     pub fn set_reply_to(&mut self, reply_to: usize) {
@@ -152,8 +247,12 @@ impl Display for User {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "name: {}{} > fingerprint: {}{} > client: {}{} > joined: {} ago",
+            "name: {}{}{} > fingerprint: {}{} > client: {}{} > joined: {} ago",
             self.username,
+            match self.is_newcomer {
+                true => " (new)",
+                false => "",
+            },
             utils::NEWLINE,
             format!("SHA256: {}", self.public_key.fingerprint()),
             utils::NEWLINE,
@@ -164,7 +263,7 @@ impl Display for User {
 
         match &self.status {
             UserStatus::Active => Ok(()),
-            UserStatus::Away { reason, since } => {
+            UserStatus::Away { reason, since, .. } => {
                 let now = Utc::now();
                 let secs = now.signed_duration_since(since).num_seconds() as u64;
                 write!(
@@ -199,14 +298,32 @@ mod should {
         let mut user = create_test_user();
         user.go_away("BRB".to_string());
         match user.status() {
-            UserStatus::Away { reason, since } => {
+            UserStatus::Away {
+                reason,
+                since,
+                until,
+            } => {
                 assert_eq!(reason, "BRB");
                 assert_eq!(since.timestamp(), Utc::now().timestamp());
+                assert_eq!(until, &None);
             }
             _ => panic!("User is not away"),
         }
         user.return_active();
         assert_eq!(user.status(), &UserStatus::Active);
+        assert_eq!(user.last_away_reason(), Some(&"BRB".to_string()));
+    }
+
+    #[test]
+    fn go_away_with_return_eta() {
+        let mut user = create_test_user();
+        user.go_away("lunch, back in 30m".to_string());
+        match user.status() {
+            UserStatus::Away { since, until, .. } => {
+                assert_eq!(*until, Some(*since + chrono::Duration::minutes(30)));
+            }
+            _ => panic!("User is not away"),
+        }
     }
 
     #[test]
@@ -261,6 +378,14 @@ mod should {
         assert!(display.contains("joined: 0s ago"));
     }
 
+    #[test]
+    fn hide_peer_address_from_display() {
+        let mut user = create_test_user();
+        user.set_peer_addr(Some("10.0.0.42:50000".parse().unwrap()));
+        assert_eq!(user.peer_addr(), Some("10.0.0.42:50000".parse().unwrap()));
+        assert!(!format!("{}", user).contains("10.0.0.42"));
+    }
+
     #[test]
     fn display_format_away_user() {
         let mut user = create_test_user();
@@ -294,12 +419,48 @@ mod should {
         assert_eq!(user.reply_to(), &Some(3));
     }
 
+    #[test]
+    fn exclude_and_focus_are_mutually_exclusive() {
+        let mut user = create_test_user();
+        user.focus(2);
+        user.exclude(2);
+        assert!(!user.focused().contains(&2));
+        assert!(user.excluded().contains(&2));
+        assert!(!user.config().is_in_focus(2));
+        assert!(user.config().is_in_focus(3));
+
+        user.focus(2);
+        assert!(user.focused().contains(&2));
+        assert!(!user.excluded().contains(&2));
+        assert!(!user.config().is_in_focus(3));
+    }
+
+    #[test]
+    fn resolve_pending_ignore_and_focus() {
+        let mut user = User::default();
+        user.ignore_later(UserName::from("bob"));
+        user.focus_later(UserName::from("carol"));
+
+        user.resolve_pending(&UserName::from("dave"), 4);
+        assert!(user.ignored().is_empty());
+        assert!(user.focused().is_empty());
+
+        user.resolve_pending(&UserName::from("bob"), 2);
+        user.resolve_pending(&UserName::from("carol"), 3);
+        assert!(user.ignored().contains(&2));
+        assert!(user.focused().contains(&3));
+
+        user.resolve_pending(&UserName::from("bob"), 5);
+        assert!(!user.ignored().contains(&5));
+    }
+
     #[test]
     fn unfocus_all() {
         let mut user = create_test_user();
         user.focus(2);
-        user.focus(3);
+        user.exclude(3);
         user.unfocus_all();
         assert!(user.focused().is_empty());
+        assert!(user.excluded().is_empty());
     }
 }
@@ -1,4 +1,14 @@
 use clap::Parser;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use strum::IntoEnumIterator;
+
+use crate::chat::{
+    Command, CommandProps, LookalikeNameMode, PromptTemplate, SingleSessionMode, Theme,
+    TimestampMode, VoteKickThreshold, DEFAULT_MOTD_MAX_LEN,
+};
+use crate::logger::LogFormat;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -7,6 +17,27 @@ pub struct Cli {
     #[arg(long, default_value_t = 22)]
     pub port: u16,
 
+    /// Address to listen on, e.g. `0.0.0.0:2222` or `[::]:2222` for IPv6
+    /// (dual-stack where the OS allows it). Defaults to all IPv4
+    /// interfaces on `--port`
+    #[arg(long, value_name = "ADDR", conflicts_with = "port")]
+    pub bind: Option<SocketAddr>,
+
+    /// Also listen on a Unix domain socket at this path, e.g. for a local
+    /// proxy. The socket file is removed on shutdown
+    #[arg(long, value_name = "PATH")]
+    pub unix_socket: Option<PathBuf>,
+
+    /// Listen only on `--unix-socket`, not on TCP
+    #[arg(long, requires = "unix_socket")]
+    pub no_tcp: bool,
+
+    /// Load and validate the identity, MOTD, oplist, whitelist and other
+    /// files, print a summary and exit without starting the server. Exits
+    /// non-zero if any of them is invalid
+    #[arg(long)]
+    pub check: bool,
+
     /// Private key to identify server with. Defaults to a temporary
     /// ed25519 key
     #[arg(short = 'i', long, value_name = "KEY")]
@@ -20,15 +51,218 @@ pub struct Cli {
     #[arg(long, value_name = "FILE")]
     pub whitelist: Option<String>,
 
+    /// Keep names given up by operators reserved for their keys for
+    /// this long, e.g. `10m`, so no one can impersonate them
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub reserve_op_names: Option<Duration>,
+
+    /// Hold the name of a user whose connection dropped for this long,
+    /// e.g. `2m`, so reconnecting with the same key gets it back
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub reconnect_window: Option<Duration>,
+
+    /// Disconnect users who haven't typed anything for this long, e.g.
+    /// `2h`, to free their connection slots. Operators are exempt
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub idle_disconnect: Option<Duration>,
+
+    /// Probe connections quiet for this long with TCP and SSH keepalives,
+    /// so users whose connection silently died, e.g. behind a NAT, are
+    /// dropped after a few unanswered probes. `0s` disables keepalives
+    #[arg(long, value_name = "DURATION", default_value = "30s", value_parser = humantime::parse_duration)]
+    pub keepalive: Duration,
+
     /// Optional file with a message of the day or welcome message
     #[arg(long, value_name = "FILE")]
     pub motd: Option<String>,
 
+    /// Optional directory of `.ans` banners shown in turn to joining
+    /// users instead of a single message of the day
+    #[arg(long, value_name = "DIR", conflicts_with = "motd")]
+    pub motd_dir: Option<String>,
+
+    /// Show the message of the day to a returning key only once in this
+    /// long, e.g. `24h`, instead of on every join
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub motd_interval: Option<Duration>,
+
+    /// File to remember when each key was last shown the message of the
+    /// day, so the interval holds across restarts
+    #[arg(long, value_name = "FILE", requires = "motd_interval")]
+    pub motd_seen: Option<String>,
+
+    /// Longest message of the day in bytes operators may set with
+    /// `/motd`. Longer ones are refused
+    #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_MOTD_MAX_LEN)]
+    pub motd_max_len: usize,
+
+    /// Optional file with a goodbye banner written to users right
+    /// before they leave with `/exit` or are kicked
+    #[arg(long, value_name = "FILE")]
+    pub goodbye: Option<String>,
+
+    /// Optional file with a private welcome sent to keys that join
+    /// for the first time
+    #[arg(long, value_name = "FILE")]
+    pub welcome_file: Option<String>,
+
+    /// File to remember keys that have joined before, so the welcome
+    /// is not repeated after a restart
+    #[arg(long, value_name = "FILE", requires = "welcome_file")]
+    pub welcome_seen: Option<String>,
+
+    /// Allow one session per public key: `evict` disconnects the older
+    /// session on a duplicate login, `reject` refuses the new one
+    #[arg(long, value_name = "MODE")]
+    pub single_session: Option<SingleSessionMode>,
+
+    /// Maximum number of sessions connected with the same public key at
+    /// once. Operators are exempt. 0 disables the limit
+    #[arg(long, value_name = "COUNT", default_value_t = 0)]
+    pub max_per_key: usize,
+
+    /// Check names against lookalikes of other members' names, e.g. with
+    /// a Cyrillic letter swapped in: `warn` lets the name through with a
+    /// warning to the room, `reject` refuses it
+    #[arg(long, value_name = "MODE")]
+    pub lookalike_names: Option<LookalikeNameMode>,
+
+    /// Number of identical messages in a row a user may send within 30
+    /// seconds before the next ones are dropped. 0 disables the check
+    #[arg(long, value_name = "COUNT", default_value_t = 0)]
+    pub max_repeats: usize,
+
+    /// Minimum time between two uses of a command by the same user, e.g.
+    /// `slap=10s`. May be repeated, `0s` lifts a cooldown. `slap`, `me`
+    /// and `shrug` have short cooldowns by default. Operators are exempt
+    #[arg(long, value_name = "CMD=DURATION", value_parser = parse_command_cooldown)]
+    pub command_cooldown: Vec<(String, Duration)>,
+
+    /// Number of public messages across the room within 10 seconds
+    /// that turns on slow mode until the room calms down, e.g. during a
+    /// raid. Operators are exempt. 0 disables slow mode
+    #[arg(long, value_name = "COUNT", default_value_t = 0)]
+    pub slow_mode_threshold: usize,
+
+    /// Minimum time between two messages of the same user while slow
+    /// mode is on
+    #[arg(long, value_name = "DURATION", default_value = "5s", value_parser = humantime::parse_duration)]
+    pub slow_mode_cooldown: Duration,
+
+    /// Votes needed for `/votekick` to kick a user, either a number,
+    /// e.g. `3`, or a share of the users online, e.g. `50%`. Vote kicks
+    /// are disabled if not set. Operators can't be vote kicked
+    #[arg(long, value_name = "THRESHOLD")]
+    pub votekick: Option<VoteKickThreshold>,
+
+    /// Time a vote kick stays open to gather votes
+    #[arg(long, value_name = "DURATION", default_value = "5m", value_parser = humantime::parse_duration)]
+    pub votekick_window: Duration,
+
+    /// Time a poll stays open to gather votes before its results are
+    /// announced
+    #[arg(long, value_name = "DURATION", default_value = "10m", value_parser = humantime::parse_duration)]
+    pub poll_window: Duration,
+
+    /// Let only operators start polls with `/poll`
+    #[arg(long)]
+    pub poll_ops_only: bool,
+
+    /// Mark users whose key joined for the first time within this long,
+    /// e.g. `7d`, as new next to their name. Keys in the welcome seen
+    /// file count as regulars
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub newcomer_window: Option<Duration>,
+
+    /// File to remember when keys first joined, so newcomers stay
+    /// marked after a restart
+    #[arg(long, value_name = "FILE", requires = "newcomer_window")]
+    pub newcomer_seen: Option<String>,
+
+    /// Queue private messages to offline whitelisted users for this
+    /// long, e.g. `7d`, and deliver them on their next join. Names are
+    /// taken from the comments of the whitelist keys
+    #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub offline_messages: Option<Duration>,
+
+    /// Optional file of blocked words, one per line. Public messages
+    /// and emotes containing any of them are dropped
+    #[arg(long, value_name = "FILE")]
+    pub blocklist: Option<String>,
+
+    /// Number of blocked messages within 10 minutes that get a user
+    /// kicked. 0 only drops the messages
+    #[arg(long, value_name = "COUNT", default_value_t = 3)]
+    pub max_strikes: usize,
+
+    /// Sigil that starts a command, e.g. `!` for `!help`
+    #[arg(long, value_name = "PREFIX", default_value = "/", value_parser = parse_cmd_prefix)]
+    pub cmd_prefix: String,
+
+    /// Show the number of unread private messages in the prompt,
+    /// e.g. `[alice (2)]`. Redraws the prompt whenever it changes
+    #[arg(long)]
+    pub unread_badge: bool,
+
+    /// Default layout of the prompt with `{name}`, `{status}` and
+    /// `{users}` placeholders, e.g. `{name} ({users} online)`. Users may
+    /// pick their own with `/prompt`
+    #[arg(long, value_name = "TEMPLATE")]
+    pub prompt_template: Option<PromptTemplate>,
+
+    /// Theme users start with until they pick their own with `/theme`
+    /// or the `CHATD_THEME` variable
+    #[arg(long, value_name = "THEME", value_parser = parse_theme)]
+    pub default_theme: Option<Theme>,
+
+    /// Timestamp mode users start with until they pick their own with
+    /// `/timestamp` or the `CHATD_TIMESTAMP` variable
+    #[arg(long, value_name = "MODE", value_parser = parse_timestamp_mode)]
+    pub default_timestamp: Option<TimestampMode>,
+
+    /// Append a record of operator actions, e.g. kicks, bans, mutes and
+    /// whitelist changes, to this file as JSON lines
+    #[arg(long, value_name = "FILE")]
+    pub audit_log: Option<String>,
+
     /// Write chat log to this file
     #[arg(long, value_name = "FILE")]
     pub log: Option<String>,
 
+    /// Log output format: `text` or `json` (one object per line)
+    #[arg(long, value_name = "FORMAT", default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
     /// Turn debugging information on
     #[arg(short, long, action = clap::ArgAction::Count)]
     pub debug: u8,
 }
+
+/// Parses a `<command>=<duration>` pair, e.g. `slap=10s`
+fn parse_command_cooldown(s: &str) -> Result<(String, Duration), String> {
+    let (cmd, cooldown) = s
+        .split_once('=')
+        .ok_or_else(|| "expected <command>=<duration>, e.g. slap=10s".to_string())?;
+    if !Command::iter().any(|command| command.cmd() == cmd) {
+        return Err(format!("unknown command: {}", cmd));
+    }
+    let cooldown = humantime::parse_duration(cooldown).map_err(|err| err.to_string())?;
+    Ok((cmd.to_string(), cooldown))
+}
+
+fn parse_cmd_prefix(s: &str) -> Result<String, String> {
+    if s.is_empty() || s.contains(char::is_whitespace) {
+        return Err("prefix must be non-empty and have no whitespace".to_string());
+    }
+    Ok(s.to_string())
+}
+
+fn parse_theme(s: &str) -> Result<Theme, String> {
+    s.parse::<Theme>()
+        .map_err(|_| format!("expected one of: {}", Theme::values().join(", ")))
+}
+
+fn parse_timestamp_mode(s: &str) -> Result<TimestampMode, String> {
+    s.parse::<TimestampMode>()
+        .map_err(|_| format!("expected one of: {}", TimestampMode::values().join(", ")))
+}
@@ -1,20 +1,101 @@
-use log::SetLoggerError;
+use std::fmt::Display;
+
+use chrono::Utc;
+use log::kv::{self, Key, Value, VisitSource};
+use log::{Record, SetLoggerError};
 use log4rs::append::console::{ConsoleAppender, Target};
 use log4rs::append::file::FileAppender;
 use log4rs::config::{Appender, Config, Root};
 use log4rs::encode::pattern::PatternEncoder;
+use log4rs::encode::{self, Encode};
+use serde_json::{Map, Value as JsonValue};
+use strum::EnumString;
 
 lazy_static::lazy_static! {
     static ref LOG_ENCODER: Box<PatternEncoder> = Box::new(PatternEncoder::new("{d(%Y-%m-%d %H:%M:%S)} | {({l}):5.5} | {f}:{L} — {m}{n}"));
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Default, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+impl Display for LogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                LogFormat::Text => "text",
+                LogFormat::Json => "json",
+            }
+        )
+    }
+}
+
+/// Encodes a record into a JSON object with the timestamp, level,
+/// target and message, plus the structured key-values attached to
+/// the record (e.g. `session_id`)
+#[derive(Debug)]
+struct JsonEncoder;
+
+impl Encode for JsonEncoder {
+    fn encode(&self, w: &mut dyn encode::Write, record: &Record) -> anyhow::Result<()> {
+        let line = format_json_record(record)?;
+        w.write_all(line.as_bytes())?;
+        w.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+struct JsonFields<'a>(&'a mut Map<String, JsonValue>);
+
+impl<'a, 'kvs> VisitSource<'kvs> for JsonFields<'a> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let value = match (value.to_u64(), value.to_i64(), value.to_bool()) {
+            (Some(n), _, _) => JsonValue::from(n),
+            (_, Some(n), _) => JsonValue::from(n),
+            (_, _, Some(b)) => JsonValue::from(b),
+            _ => JsonValue::from(value.to_string()),
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+fn format_json_record(record: &Record) -> anyhow::Result<String> {
+    let mut fields = Map::new();
+    fields.insert("timestamp".into(), Utc::now().to_rfc3339().into());
+    fields.insert("level".into(), record.level().as_str().into());
+    fields.insert("target".into(), record.target().into());
+    fields.insert("message".into(), record.args().to_string().into());
+    record.key_values().visit(&mut JsonFields(&mut fields))?;
+    Ok(serde_json::to_string(&fields)?)
+}
+
+fn encoder(format: LogFormat) -> Box<dyn Encode> {
+    match format {
+        LogFormat::Text => LOG_ENCODER.to_owned(),
+        LogFormat::Json => Box::new(JsonEncoder),
+    }
+}
+
 #[cfg(not(tarpaulin_include))]
-pub fn setup(output: Option<String>, level: log::LevelFilter) -> Result<(), SetLoggerError> {
+pub fn setup(
+    output: Option<String>,
+    level: log::LevelFilter,
+    format: LogFormat,
+) -> Result<(), SetLoggerError> {
     // Configure a console appender
     let console_appender = {
         let console = ConsoleAppender::builder()
             .target(Target::Stderr)
-            .encoder(LOG_ENCODER.to_owned())
+            .encoder(encoder(format))
             .build();
         Appender::builder().build("console", Box::new(console))
     };
@@ -23,7 +104,7 @@ pub fn setup(output: Option<String>, level: log::LevelFilter) -> Result<(), SetL
     let file_appender = match output {
         Some(path) => {
             let logfile = FileAppender::builder()
-                .encoder(LOG_ENCODER.to_owned())
+                .encoder(encoder(format))
                 .build(path)
                 .unwrap();
             Some(Appender::builder().build("logfile", Box::new(logfile)))
@@ -48,3 +129,37 @@ pub fn setup(output: Option<String>, level: log::LevelFilter) -> Result<(), SetL
 
     Ok(())
 }
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn parse_log_format() {
+        assert_eq!("json".parse::<LogFormat>(), Ok(LogFormat::Json));
+        assert_eq!("Text".parse::<LogFormat>(), Ok(LogFormat::Text));
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn format_record_as_json_with_fields() {
+        let fields: &[(&str, kv::Value)] = &[("session_id", 7u64.into()), ("user", "alice".into())];
+        let line = format_json_record(
+            &Record::builder()
+                .level(log::Level::Info)
+                .target("chatd::server")
+                .args(format_args!("session started"))
+                .key_values(&fields)
+                .build(),
+        )
+        .unwrap();
+
+        let json: JsonValue = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["level"], "INFO");
+        assert_eq!(json["target"], "chatd::server");
+        assert_eq!(json["message"], "session started");
+        assert_eq!(json["session_id"], 7);
+        assert_eq!(json["user"], "alice");
+        assert!(json["timestamp"].is_string());
+    }
+}
@@ -1,10 +1,12 @@
-use auth::{Auth, PubKeyFileManager};
-use chat::ChatRoom;
+use anyhow::Context;
+use auth::{AuditLog, Auth, Permission, PubKeyFileManager};
+use chat::{ChatRoom, FirstJoinWelcome, MotdThrottle};
 use clap::Parser;
 use cli::Cli;
 use log::LevelFilter;
 use russh_keys::key::KeyPair;
 use server::{ChatServer, SessionRepository};
+use std::net::SocketAddr;
 
 mod auth;
 mod chat;
@@ -20,69 +22,298 @@ mod utils;
 async fn main() {
     let cli = Cli::parse();
 
+    if cli.check {
+        let code = match check_config(&cli) {
+            true => 0,
+            false => 1,
+        };
+        std::process::exit(code);
+    }
+
     // Initiate logger
     let level = match cli.debug {
         0 => LevelFilter::Info,
         1 => LevelFilter::Debug,
         _ => LevelFilter::max(),
     };
-    if let Err(err) = logger::setup(cli.log, level) {
+    if let Err(err) = logger::setup(cli.log.clone(), level, cli.log_format) {
         panic!("Failed to setup logger: {}", err);
     }
 
+    chat::set_command_prefix(&cli.cmd_prefix);
+
     // Initiate server keys
-    let key_pair = match cli.identity {
-        None => KeyPair::generate_ed25519().expect("Failed to generate a new ed25519 key pair"),
-        Some(path) => {
-            let key =
-                utils::fs::read_file_to_string(&path).expect("Failed to read the identity file");
-            russh_keys::decode_secret_key(&key, None)
-                .expect("Failed to decode the secret key from the identity file")
+    let key_pair = load_identity(cli.identity.as_deref()).unwrap_or_else(|err| panic!("{:#}", err));
+    let server_keys = vec![key_pair];
+
+    // Initiate server <-> session repository message channel
+    let (tx, rx) = tokio::sync::mpsc::channel(1000);
+
+    // Initate authorization service
+    let auth = load_auth(&cli).unwrap_or_else(|err| panic!("{:#}", err));
+
+    // Initate server and session repository
+    let room = load_room(&cli).unwrap_or_else(|err| panic!("{:#}", err));
+    let repository = SessionRepository::new(rx);
+    let addr = cli
+        .bind
+        .unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], cli.port)));
+    let mut server = ChatServer::new(addr, &server_keys, tx, auth, room);
+    if let Some(threshold) = cli.idle_disconnect {
+        server.set_idle_disconnect(threshold);
+    }
+    if !cli.keepalive.is_zero() {
+        server.set_keepalive(cli.keepalive);
+    }
+    if let Some(path) = cli.unix_socket.clone() {
+        server.set_unix_socket(path, !cli.no_tcp);
+    }
+
+    // Run the server
+    server.run(repository).await.expect("Failed running server");
+}
+
+/// Loads every file the server is configured with and prints what was
+/// found, without binding the port. Returns whether all of them are
+/// valid
+fn check_config(cli: &Cli) -> bool {
+    let mut ok = true;
+    let mut report = |name: &str, result: anyhow::Result<String>| match result {
+        Ok(summary) => println!("{}: {}", name, summary),
+        Err(err) => {
+            ok = false;
+            println!("{}: error: {:#}", name, err);
         }
     };
-    let server_keys = vec![key_pair];
 
-    // Initiate server oplist file manager
-    let mut oplist_manager = None;
-    if let Some(path) = cli.oplist {
-        oplist_manager = Some(PubKeyFileManager::new(&path));
+    report(
+        "identity",
+        load_identity(cli.identity.as_deref()).map(|key| match cli.identity {
+            Some(_) => format!("{} key", key.name()),
+            None => "temporary ed25519 key".to_string(),
+        }),
+    );
+    if let Err(err) = check_stdin_conflict(cli) {
+        report("keys", Err(err));
+    } else {
+        report(
+            "oplist",
+            check_oplist(pubkey_manager(cli.oplist.as_deref(), "CHATD_OPLIST")),
+        );
+        report(
+            "whitelist",
+            check_keys(pubkey_manager(cli.whitelist.as_deref(), "CHATD_WHITELIST")),
+        );
+    }
+    report("room", load_room(cli).map(|_| "ok".to_string()));
+    if let Some(path) = &cli.audit_log {
+        report(
+            "audit log",
+            AuditLog::open(path)
+                .map(|_| "ok".to_string())
+                .context("Failed to open the audit log"),
+        );
+    }
+
+    match ok {
+        true => println!("Configuration is valid"),
+        false => println!("Configuration has errors"),
+    }
+    ok
+}
+
+/// Loads all keys of the manager and fails on lines that would be
+/// skipped when the server loads them
+fn check_keys(manager: Option<PubKeyFileManager>) -> anyhow::Result<String> {
+    let manager = match manager {
+        Some(manager) => manager,
+        None => return Ok("not set".to_string()),
     };
+    let keys = manager
+        .load_keys_with_expiry()
+        .context("Failed to load public keys")?;
+    let invalid = manager.invalid_lines()?;
+    if let Some((line, text)) = invalid.first() {
+        anyhow::bail!(
+            "{} invalid line(s), the first is line {}: {}",
+            invalid.len(),
+            line,
+            text
+        );
+    }
+    Ok(format!("{} key(s)", keys.len()))
+}
 
-    // Initiate server whitelist file manager
-    let mut whitelist_manager = None;
-    if let Some(path) = cli.whitelist {
-        whitelist_manager = Some(PubKeyFileManager::new(&path));
+/// Checks the oplist keys like any other and fails on operators whose
+/// permissions the server would skip them for
+fn check_oplist(manager: Option<PubKeyFileManager>) -> anyhow::Result<String> {
+    let summary = check_keys(manager.clone())?;
+    if let Some(manager) = manager {
+        let permissions = manager
+            .load_key_option("permissions")
+            .context("Failed to load operator permissions")?;
+        for (key, list) in permissions {
+            if let Err(err) = Permission::parse_list(&list) {
+                anyhow::bail!("malformed permissions of {}: {}", key.fingerprint(), err);
+            }
+        }
+    }
+    Ok(summary)
+}
+
+fn load_identity(path: Option<&str>) -> anyhow::Result<KeyPair> {
+    let key_pair = match path {
+        None => KeyPair::generate_ed25519().context("Failed to generate a new ed25519 key pair")?,
+        Some(path) => {
+            let key =
+                utils::fs::read_file_to_string(path).context("Failed to read the identity file")?;
+            russh_keys::decode_secret_key(&key, None)
+                .context("Failed to decode the secret key from the identity file")?
+        }
     };
+    Ok(key_pair)
+}
 
-    // Initiate motd
-    let motd = match cli.motd {
-        Some(path) => utils::fs::read_file_to_string(&path).expect("Failed to read the MOTD file"),
-        None => include_str!("../motd.ans").to_string(),
+fn check_stdin_conflict(cli: &Cli) -> anyhow::Result<()> {
+    if cli.oplist.as_deref() == Some("-") && cli.whitelist.as_deref() == Some("-") {
+        anyhow::bail!("Only one of --oplist and --whitelist can be read from stdin");
     }
-    .replace("\n", "\n\r"); // normalize line endings into \r
+    Ok(())
+}
 
-    // Initiate server <-> session repository message channel
-    let (tx, rx) = tokio::sync::mpsc::channel(1000);
+fn load_auth(cli: &Cli) -> anyhow::Result<Auth> {
+    check_stdin_conflict(cli)?;
+
+    // Initiate server oplist file manager
+    let oplist_manager = pubkey_manager(cli.oplist.as_deref(), "CHATD_OPLIST");
+
+    // Initiate server whitelist file manager
+    let whitelist_manager = pubkey_manager(cli.whitelist.as_deref(), "CHATD_WHITELIST");
 
-    // Initate authorization service
     let mut auth = Auth::default();
     if let Some(whitelist) = whitelist_manager {
         auth.set_whitelist(whitelist);
         auth.enable_whitelist_mode();
         auth.load_trusted_keys()
-            .expect("Failed to load public keys from whitelist");
+            .context("Failed to load public keys from whitelist")?;
     }
+    auth.set_name_reservation(cli.reserve_op_names);
     if let Some(oplist) = oplist_manager {
         auth.set_oplist(oplist);
         auth.load_operators()
-            .expect("Failed to load public keys from oplist");
+            .context("Failed to load public keys from oplist")?;
     }
+    if let Some(path) = &cli.audit_log {
+        let audit_log = AuditLog::open(path).context("Failed to open the audit log")?;
+        auth.set_audit_log(audit_log);
+    }
+    Ok(auth)
+}
 
-    // Initate server and session repository
-    let room = ChatRoom::new(&motd);
-    let repository = SessionRepository::new(rx);
-    let mut server = ChatServer::new(cli.port, &server_keys, tx, auth, room);
+fn load_room(cli: &Cli) -> anyhow::Result<ChatRoom> {
+    let motd = include_str!("../motd.ans").replace("\n", utils::NEWLINE); // normalize line endings into \r
+    let mut room = ChatRoom::new(&motd);
+    if let Some(path) = &cli.motd {
+        room.load_motd_file(path)
+            .context("Failed to read the MOTD file")?;
+    }
+    if let Some(path) = &cli.motd_dir {
+        room.load_motd_dir(path)
+            .context("Failed to read the MOTD directory")?;
+    }
+    if let Some(interval) = cli.motd_interval {
+        let mut throttle = MotdThrottle::new(interval);
+        if let Some(path) = &cli.motd_seen {
+            throttle
+                .load_file(path)
+                .context("Failed to read the MOTD seen file")?;
+        }
+        room.set_motd_throttle(throttle);
+    }
+    room.set_motd_max_len(cli.motd_max_len);
+    if let Some(path) = &cli.goodbye {
+        room.load_goodbye_file(path)
+            .context("Failed to read the goodbye file")?;
+    }
+    if let Some(path) = &cli.welcome_file {
+        let message =
+            utils::fs::read_file_to_string(path).context("Failed to read the welcome file")?;
+        let mut welcome = FirstJoinWelcome::new(&message);
+        if let Some(path) = &cli.welcome_seen {
+            welcome
+                .load_seen_file(path)
+                .context("Failed to read the seen keys file")?;
+        }
+        room.set_welcome(welcome);
+    }
+    if let Some(mode) = cli.single_session {
+        room.set_single_session_mode(mode);
+    }
+    room.set_max_per_key(cli.max_per_key);
+    if let Some(mode) = cli.lookalike_names {
+        room.set_lookalike_name_mode(mode);
+    }
+    room.set_max_repeats(cli.max_repeats);
+    for (cmd, cooldown) in &cli.command_cooldown {
+        room.set_command_cooldown(cmd, *cooldown);
+    }
+    room.set_slow_mode(cli.slow_mode_threshold, cli.slow_mode_cooldown);
+    if let Some(threshold) = cli.votekick {
+        room.set_votekick(threshold, cli.votekick_window);
+    }
+    room.set_poll_window(cli.poll_window);
+    room.set_polls_ops_only(cli.poll_ops_only);
+    if let Some(window) = cli.newcomer_window {
+        room.set_newcomer_window(window);
+        if let Some(path) = &cli.newcomer_seen {
+            room.load_newcomers_seen_file(path)
+                .context("Failed to read the newcomers seen file")?;
+        }
+    }
+    if let Some(ttl) = cli.offline_messages {
+        room.set_offline_messages(ttl);
+    }
+    if let Some(path) = &cli.blocklist {
+        room.load_blocklist_file(path)
+            .context("Failed to read the blocklist file")?;
+    }
+    room.set_max_strikes(cli.max_strikes);
+    room.set_unread_badge(cli.unread_badge);
+    if let Some(template) = &cli.prompt_template {
+        room.set_prompt_template(template.clone());
+    }
+    if let Some(theme) = cli.default_theme {
+        room.set_default_theme(theme);
+    }
+    if let Some(mode) = cli.default_timestamp {
+        room.set_default_timestamp_mode(mode);
+    }
+    if let Some(window) = cli.reconnect_window {
+        room.set_reconnect_window(window);
+    }
+    Ok(room)
+}
 
-    // Run the server
-    server.run(repository).await.expect("Failed running server");
+/// Builds a key manager from a file path (`-` reads keys from stdin)
+/// and inline keys given in the `env_var` environment variable.
+/// Keys from both sources are merged.
+fn pubkey_manager(path: Option<&str>, env_var: &str) -> Option<PubKeyFileManager> {
+    let mut inline_keys = std::env::var(env_var).ok();
+    let mut manager = match path {
+        Some("-") => {
+            let stdin = std::io::read_to_string(std::io::stdin())
+                .expect("Failed to read public keys from stdin");
+            inline_keys = Some(inline_keys.unwrap_or_default() + "\n" + &stdin);
+            None
+        }
+        Some(path) => Some(PubKeyFileManager::new(path)),
+        None => None,
+    };
+
+    if let Some(keys) = inline_keys {
+        manager
+            .get_or_insert_with(PubKeyFileManager::default)
+            .set_inline_keys(&keys);
+    }
+    manager
 }
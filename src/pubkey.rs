@@ -3,6 +3,9 @@ use std::hash::Hash;
 
 use russh_keys::key::{KeyPair, PublicKey};
 
+/// Length of an unpadded base64 SHA-256 digest
+const FINGERPRINT_LEN: usize = 43;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PubKey(PublicKey);
 
@@ -11,7 +14,7 @@ impl PubKey {
         self.0.fingerprint()
     }
 
-    #[cfg(test)]
+    /// Key in the OpenSSH `<type> <base64>` format
     pub fn long(&self) -> String {
         use russh_keys::PublicKeyBase64;
         let pk = self.0.public_key_base64();
@@ -19,6 +22,18 @@ impl PubKey {
     }
 }
 
+/// Reads a key fingerprint as printed by `/whois`, with or without the
+/// `SHA256:` prefix. Returns the bare fingerprint if the text is shaped
+/// like one
+pub fn parse_fingerprint(s: &str) -> Option<&str> {
+    let fingerprint = s.strip_prefix("SHA256:").map(str::trim_start).unwrap_or(s);
+    let is_base64 = |c: char| c.is_ascii_alphanumeric() || c == '+' || c == '/';
+    match fingerprint.len() == FINGERPRINT_LEN && fingerprint.chars().all(is_base64) {
+        true => Some(fingerprint),
+        false => None,
+    }
+}
+
 impl Into<PublicKey> for PubKey {
     fn into(self) -> PublicKey {
         self.0
@@ -99,6 +114,17 @@ mod tests {
         assert_eq!(pubkey.fingerprint(), public_key.fingerprint());
     }
 
+    #[test]
+    fn test_parse_fingerprint() {
+        let fingerprint = generate_test_key().fingerprint();
+        assert_eq!(parse_fingerprint(&fingerprint), Some(fingerprint.as_str()));
+        let prefixed = format!("SHA256:{}", fingerprint);
+        assert_eq!(parse_fingerprint(&prefixed), Some(fingerprint.as_str()));
+        assert_eq!(parse_fingerprint("alice"), None);
+        assert_eq!(parse_fingerprint(&"a".repeat(42)), None);
+        assert_eq!(parse_fingerprint(&format!("{}!", &fingerprint[1..])), None);
+    }
+
     #[test]
     fn test_pubkey_display() {
         let public_key = generate_test_key();
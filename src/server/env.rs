@@ -6,6 +6,8 @@ const ENV_PREFIX: &str = "CHATD_";
 pub enum Env {
     Theme(String),
     Timestamp(String),
+    Ignore(Vec<String>),
+    Focus(Vec<String>),
 }
 
 impl FromStr for Env {
@@ -22,10 +24,22 @@ impl FromStr for Env {
 
         let theme_var = format!("{}THEME", ENV_PREFIX);
         let timestamp_var = format!("{}TIMESTAMP", ENV_PREFIX);
+        let ignore_var = format!("{}IGNORE", ENV_PREFIX);
+        let focus_var = format!("{}FOCUS", ENV_PREFIX);
+        let names = || {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
 
         match name {
             _ if name == theme_var => Ok(Env::Theme(value.to_string())),
             _ if name == timestamp_var => Ok(Env::Timestamp(value.to_string())),
+            _ if name == ignore_var => Ok(Env::Ignore(names())),
+            _ if name == focus_var => Ok(Env::Focus(names())),
             _ => Err("Unknown environment variable type"),
         }
     }
@@ -57,6 +71,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_valid_ignore_and_focus_env_vars() {
+        let env = Env::from_str("CHATD_IGNORE=bob, carol,,").unwrap();
+        if let Env::Ignore(names) = env {
+            assert_eq!(names, vec!["bob", "carol"]);
+        } else {
+            panic!("Expected Env::Ignore variant");
+        }
+
+        let env = Env::from_str("CHATD_FOCUS=dave").unwrap();
+        if let Env::Focus(names) = env {
+            assert_eq!(names, vec!["dave"]);
+        } else {
+            panic!("Expected Env::Focus variant");
+        }
+    }
+
     #[test]
     fn test_unknown_env_var_type() {
         let env_var = "CHATD_UNKNOWN=value";
@@ -0,0 +1,220 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{info, warn};
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+
+use crate::auth::Auth;
+use crate::chat::message;
+use crate::chat::{ChatRoom, LeaveReason};
+
+/// Longest time between two checks for idle members
+const IDLE_SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Periodically disconnects members who haven't typed anything for
+/// longer than the threshold, freeing their connection slots. Never returns.
+pub async fn sweep_idle_members(
+    room: Arc<Mutex<ChatRoom>>,
+    auth: Arc<Mutex<Auth>>,
+    threshold: Duration,
+) {
+    let step = threshold.min(IDLE_SWEEP_INTERVAL);
+    loop {
+        sleep(step).await;
+        let mut room = room.lock().await;
+        let auth = auth.lock().await;
+        disconnect_idle_members(&mut room, &auth, threshold).await;
+    }
+}
+
+/// Tells every non-operator member idle for at least the threshold
+/// why they are leaving and closes their session. A member that fails
+/// to be disconnected doesn't hold up the others. Returns the number
+/// of members disconnected
+async fn disconnect_idle_members(room: &mut ChatRoom, auth: &Auth, threshold: Duration) -> usize {
+    let mut count = 0;
+    for (_, member) in room.members_iter() {
+        let idle = member.input_idle_duration();
+        if idle < threshold || auth.is_op(member.user.public_key()) {
+            continue;
+        }
+
+        info!(
+            "Disconnecting user {} after being idle for {}",
+            member.user.username(),
+            humantime::format_duration(idle)
+        );
+        let message = message::System::new(
+            member.user.clone().into(),
+            format!(
+                "You have been idle for {}, disconnecting. Bye!",
+                humantime::format_duration(idle)
+            ),
+        );
+        if let Err(err) = member.send_message(message.into()).await {
+            warn!(
+                "Failed to tell user {} about the idle disconnect: {}",
+                member.user.username(),
+                err
+            );
+        }
+        if let Err(err) = member.exit(LeaveReason::Idle) {
+            warn!(
+                "Failed to disconnect idle user {}: {}",
+                member.user.username(),
+                err
+            );
+            continue;
+        }
+        count += 1;
+    }
+    count
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::pubkey::PubKey;
+    use russh_keys::key::KeyPair;
+    use tokio::sync::{mpsc, watch};
+
+    fn create_test_pubkey() -> PubKey {
+        let key_pair = KeyPair::generate_ed25519().unwrap();
+        PubKey::from(key_pair.clone_public_key().unwrap())
+    }
+
+    #[tokio::test]
+    async fn disconnect_idle_members_except_operators() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+
+        let mut sessions = vec![];
+        for (id, name) in [(1, "alice"), (2, "bob")] {
+            let key = create_test_pubkey();
+            if name == "bob" {
+                auth.add_operator(key.clone());
+            }
+            let (message_tx, message_rx) = mpsc::channel(10);
+            let (exit_tx, exit_rx) = watch::channel(LeaveReason::default());
+            room.join(
+                id,
+                name.to_string(),
+                key,
+                "ssh".to_string(),
+                None,
+                message_tx,
+                exit_tx,
+            )
+            .await
+            .unwrap();
+            sessions.push((message_rx, exit_rx));
+        }
+
+        let count = disconnect_idle_members(&mut room, &auth, Duration::ZERO).await;
+        assert_eq!(count, 1);
+
+        let (alice_rx, alice_exit_rx) = &mut sessions[0];
+        assert!(alice_exit_rx.has_changed().unwrap());
+        assert_eq!(*alice_exit_rx.borrow(), LeaveReason::Idle);
+        let mut farewell = None;
+        while let Ok(msg) = alice_rx.try_recv() {
+            farewell = Some(msg);
+        }
+        assert!(farewell.unwrap().contains("You have been idle for"));
+
+        let (_, bob_exit_rx) = &sessions[1];
+        assert!(!bob_exit_rx.has_changed().unwrap());
+    }
+
+    #[tokio::test]
+    async fn keep_recently_active_members() {
+        let mut room = ChatRoom::new("Welcome!");
+        let auth = Auth::default();
+        let key = create_test_pubkey();
+        let (message_tx, _message_rx) = mpsc::channel(10);
+        let (exit_tx, exit_rx) = watch::channel(LeaveReason::default());
+        room.join(
+            1,
+            "alice".to_string(),
+            key,
+            "ssh".to_string(),
+            None,
+            message_tx,
+            exit_tx,
+        )
+        .await
+        .unwrap();
+
+        let count = disconnect_idle_members(&mut room, &auth, Duration::from_secs(3600)).await;
+        assert_eq!(count, 0);
+        assert!(!exit_rx.has_changed().unwrap());
+    }
+
+    #[tokio::test]
+    async fn keep_members_who_typed_without_sending_messages() {
+        let mut room = ChatRoom::new("Welcome!");
+        let auth = Auth::default();
+        let (message_tx, _message_rx) = mpsc::channel(10);
+        let (exit_tx, exit_rx) = watch::channel(LeaveReason::default());
+        room.join(
+            1,
+            "alice".to_string(),
+            create_test_pubkey(),
+            "ssh".to_string(),
+            None,
+            message_tx,
+            exit_tx,
+        )
+        .await
+        .unwrap();
+
+        let threshold = Duration::from_millis(100);
+        sleep(threshold).await;
+        room.register_input(1);
+        assert_eq!(
+            disconnect_idle_members(&mut room, &auth, threshold).await,
+            0
+        );
+        assert!(!exit_rx.has_changed().unwrap());
+
+        sleep(threshold).await;
+        assert_eq!(
+            disconnect_idle_members(&mut room, &auth, threshold).await,
+            1
+        );
+        assert!(exit_rx.has_changed().unwrap());
+    }
+
+    #[tokio::test]
+    async fn disconnect_other_idle_members_when_one_fails() {
+        let mut room = ChatRoom::new("Welcome!");
+        let auth = Auth::default();
+
+        let mut sessions = vec![];
+        for (id, name) in [(1, "alice"), (2, "bob"), (3, "carol")] {
+            let (message_tx, message_rx) = mpsc::channel(10);
+            let (exit_tx, exit_rx) = watch::channel(LeaveReason::default());
+            room.join(
+                id,
+                name.to_string(),
+                create_test_pubkey(),
+                "ssh".to_string(),
+                None,
+                message_tx,
+                exit_tx,
+            )
+            .await
+            .unwrap();
+            sessions.push((name, message_rx, exit_rx));
+        }
+        // The session of bob is gone
+        sessions.retain(|(name, _, _)| *name != "bob");
+
+        let count = disconnect_idle_members(&mut room, &auth, Duration::ZERO).await;
+        assert_eq!(count, 2);
+        for (_, _, exit_rx) in &sessions {
+            assert_eq!(*exit_rx.borrow(), LeaveReason::Idle);
+        }
+    }
+}
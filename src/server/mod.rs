@@ -1,7 +1,13 @@
 mod env;
+mod idle;
+mod room_sizes;
+mod room_tick;
 mod server;
 mod session;
 mod session_workflow;
+mod shutdown;
+mod tcp;
+mod unix_socket;
 
 pub use server::ChatServer;
 pub use session::SessionRepository;
@@ -0,0 +1,24 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, warn};
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+
+use crate::chat::ChatRoom;
+
+/// Time between two logs of the room map sizes
+const ROOM_SIZES_LOG_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Periodically logs the sizes of the room's per-user maps, warning
+/// when they hold state of users who already left. Never returns.
+pub async fn log_room_sizes(room: Arc<Mutex<ChatRoom>>) {
+    loop {
+        sleep(ROOM_SIZES_LOG_INTERVAL).await;
+        let sizes = room.lock().await.sizes();
+        match sizes.has_leaks() {
+            true => warn!("Room keeps state of departed users: {}", sizes),
+            false => debug!("Room map sizes: {}", sizes),
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::warn;
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+
+use crate::chat::ChatRoom;
+
+/// Time between two checks for room state that ran out of time
+const ROOM_TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Periodically closes what ran out of time in the room and tells the
+/// room about it, so nobody has to type for it to happen. Never returns.
+pub async fn tick_room(room: Arc<Mutex<ChatRoom>>) {
+    loop {
+        sleep(ROOM_TICK_INTERVAL).await;
+        let mut room = room.lock().await;
+        if let Err(err) = room.announce_expired_vote_kicks().await {
+            warn!("Failed to announce expired vote kicks: {}", err);
+        }
+        if let Err(err) = room.announce_expired_poll().await {
+            warn!("Failed to announce the expired poll: {}", err);
+        }
+        if let Err(err) = room.announce_slow_mode_end().await {
+            warn!("Failed to announce the end of slow mode: {}", err);
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -13,6 +16,7 @@ use crate::chat::ChatRoom;
 
 use super::session::{SessionRepositoryEvent, ThinHandler};
 use super::SessionRepository;
+use super::{idle, room_sizes, room_tick, shutdown, tcp, unix_socket};
 
 /// Maximum size of the internal server event buffer.
 ///
@@ -23,34 +27,66 @@ use super::SessionRepository;
 /// blocking, ensure the buffer size exceeds the room history size.
 const SERVER_EVENT_BUFFER_SIZE: usize = 30;
 
+/// Number of SSH keepalive requests left unanswered before a session
+/// is considered dead
+const KEEPALIVE_MAX: usize = 3;
+
 #[derive(Clone)]
 pub struct ChatServer {
-    id_increment: usize,
-    port: u16,
+    id_increment: Arc<AtomicUsize>,
+    addr: SocketAddr,
+    listen_tcp: bool,
+    unix_socket: Option<PathBuf>,
     server_keys: Vec<KeyPair>,
     auth: Arc<Mutex<Auth>>,
     room: Arc<Mutex<ChatRoom>>,
     repo_event_sender: Sender<SessionRepositoryEvent>,
+    idle_disconnect: Option<Duration>,
+    keepalive: Option<Duration>,
 }
 
 impl ChatServer {
     pub fn new(
-        port: u16,
+        addr: SocketAddr,
         server_keys: &[KeyPair],
         repo_event_sender: Sender<SessionRepositoryEvent>,
         auth: Auth,
         room: ChatRoom,
     ) -> Self {
         Self {
-            port,
+            addr,
+            listen_tcp: true,
+            unix_socket: None,
             repo_event_sender,
-            id_increment: 0,
+            id_increment: Arc::new(AtomicUsize::new(0)),
             server_keys: server_keys.to_vec(),
             auth: Arc::new(Mutex::new(auth)),
             room: Arc::new(Mutex::new(room)),
+            idle_disconnect: None,
+            keepalive: None,
         }
     }
 
+    /// Disconnects users who haven't typed anything for this long.
+    /// Operators are never disconnected for being idle
+    pub fn set_idle_disconnect(&mut self, threshold: Duration) {
+        self.idle_disconnect = Some(threshold);
+    }
+
+    /// Probes connections quiet for this long, both with TCP keepalive
+    /// and SSH keepalive requests, so sessions of peers that vanished
+    /// are torn down instead of lingering in the room
+    pub fn set_keepalive(&mut self, interval: Duration) {
+        self.keepalive = Some(interval);
+    }
+
+    /// Also accepts sessions on a Unix domain socket at the path, or
+    /// only there if the TCP address isn't kept
+    pub fn set_unix_socket(&mut self, path: PathBuf, keep_tcp: bool) {
+        self.unix_socket = Some(path);
+        self.listen_tcp = keep_tcp;
+    }
+
     pub async fn run(&mut self, mut repository: SessionRepository) -> anyhow::Result<()> {
         let room = self.room.clone();
         let auth = self.auth.clone();
@@ -60,18 +96,69 @@ impl ChatServer {
             repository.wait_for_sessions(room, auth).await;
         });
 
+        if let Some(threshold) = self.idle_disconnect {
+            info!(
+                "Spawning a thread to disconnect users idle for {}",
+                humantime::format_duration(threshold)
+            );
+            spawn(idle::sweep_idle_members(
+                self.room.clone(),
+                self.auth.clone(),
+                threshold,
+            ));
+        }
+
+        spawn(room_sizes::log_room_sizes(self.room.clone()));
+        spawn(room_tick::tick_room(self.room.clone()));
+
         let config = Config {
             event_buffer_size: SERVER_EVENT_BUFFER_SIZE,
             inactivity_timeout: Some(Duration::from_secs(3600)),
             auth_rejection_time: Duration::from_secs(3),
             auth_rejection_time_initial: Some(Duration::from_secs(0)),
+            keepalive_interval: self.keepalive,
+            keepalive_max: KEEPALIVE_MAX,
             keys: self.server_keys.clone(),
             ..Default::default()
         };
 
-        info!("Server is running on {} port!", self.port);
-        self.run_on_address(Arc::new(config), ("0.0.0.0", self.port))
-            .await?;
+        let room = self.room.clone();
+        let shutdown_rx = room.lock().await.subscribe_shutdown();
+
+        let config = Arc::new(config);
+        let addr = self.addr;
+        let listen_tcp = self.listen_tcp;
+        let keepalive = self.keepalive;
+        let mut tcp_server = self.clone();
+        let unix_socket = self.unix_socket.clone();
+        let mut unix_server = self.clone();
+
+        let tcp = async {
+            match listen_tcp {
+                true => {
+                    info!("Server is running on {}!", addr);
+                    tcp::run_on_tcp(&mut tcp_server, config.clone(), addr, keepalive).await
+                }
+                false => std::future::pending().await,
+            }
+        };
+        let unix = async {
+            match &unix_socket {
+                Some(path) => {
+                    info!("Server is running on unix socket {:?}!", path);
+                    unix_socket::run_on_unix_socket(&mut unix_server, config.clone(), path).await
+                }
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            result = tcp => result?,
+            result = unix => result?,
+            _ = shutdown::wait_for_scheduled_shutdown(room, shutdown_rx) => {
+                info!("Server is shut down by an operator");
+            }
+        }
 
         Ok(())
     }
@@ -83,9 +170,10 @@ impl Server for ChatServer {
 
     fn new_client(&mut self, peer_addr: Option<std::net::SocketAddr>) -> Self::Handler {
         info!("New client created for peer {:?}", peer_addr);
-        self.id_increment += 1;
+        let id = self.id_increment.fetch_add(1, Ordering::Relaxed) + 1;
         Self::Handler::new(
-            self.id_increment,
+            id,
+            peer_addr,
             self.auth.clone(),
             self.repo_event_sender.clone(),
         )
@@ -113,12 +201,13 @@ mod should {
     #[tokio::test]
     async fn check_if_server_runs_and_port_is_in_use() {
         let port = 22;
+        let addr = SocketAddr::from(([0, 0, 0, 0], port));
         let server_keys = vec![KeyPair::generate_ed25519().unwrap()];
         let (tx, _rx) = mpsc::channel(100);
         let auth = Auth::default();
         let room = ChatRoom::new("Welcome!");
 
-        let mut chat_server = ChatServer::new(port, &server_keys, tx, auth, room);
+        let mut chat_server = ChatServer::new(addr, &server_keys, tx, auth, room);
         let (_, rx) = tokio::sync::mpsc::channel(1);
         let repository = SessionRepository::new(rx);
 
@@ -134,4 +223,148 @@ mod should {
         // Cleanup: stop the server
         drop(server_handle);
     }
+
+    #[tokio::test]
+    async fn run_server_on_ipv6_address() {
+        let addr: SocketAddr = "[::1]:2223".parse().unwrap();
+        let server_keys = vec![KeyPair::generate_ed25519().unwrap()];
+        let (tx, _rx) = mpsc::channel(100);
+        let auth = Auth::default();
+        let room = ChatRoom::new("Welcome!");
+
+        let mut chat_server = ChatServer::new(addr, &server_keys, tx, auth, room);
+        let (_, rx) = tokio::sync::mpsc::channel(1);
+        let repository = SessionRepository::new(rx);
+
+        let server_handle = run_server_in_background(&mut chat_server, repository).await;
+        sleep(Duration::from_millis(100)).await;
+
+        let connect_result = tokio::net::TcpStream::connect(addr).await;
+        assert!(
+            connect_result.is_ok(),
+            "Server should accept IPv6 connections"
+        );
+
+        drop(server_handle);
+    }
+
+    struct TrustingClient;
+
+    #[async_trait::async_trait]
+    impl russh::client::Handler for TrustingClient {
+        type Error = russh::Error;
+
+        async fn check_server_key(
+            &mut self,
+            _server_public_key: &russh_keys::key::PublicKey,
+        ) -> Result<bool, Self::Error> {
+            Ok(true)
+        }
+    }
+
+    #[tokio::test]
+    async fn drop_connection_that_stops_answering_keepalives() {
+        use std::sync::atomic::AtomicBool;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let addr: SocketAddr = "127.0.0.1:2225".parse().unwrap();
+        let interval = Duration::from_millis(200);
+        let server_keys = vec![KeyPair::generate_ed25519().unwrap()];
+        let (tx, _rx) = mpsc::channel(100);
+        let auth = Auth::default();
+        let room = ChatRoom::new("Welcome!");
+
+        let mut chat_server = ChatServer::new(addr, &server_keys, tx, auth, room);
+        chat_server.set_keepalive(interval);
+        let (_, rx) = tokio::sync::mpsc::channel(1);
+        let repository = SessionRepository::new(rx);
+
+        let server_handle = run_server_in_background(&mut chat_server, repository).await;
+        sleep(Duration::from_millis(100)).await;
+
+        // Relay the client to the server until the link is cut, then drop
+        // whatever either side sends, like a NAT that forgot the mapping
+        let (mut server_read, mut server_write) = tokio::net::TcpStream::connect(addr)
+            .await
+            .unwrap()
+            .into_split();
+        let (client_stream, relay_stream) = tokio::io::duplex(64 * 1024);
+        let (mut relay_read, mut relay_write) = tokio::io::split(relay_stream);
+        let cut = Arc::new(AtomicBool::new(false));
+
+        let upstream_cut = cut.clone();
+        tokio::spawn(async move {
+            let mut buf = [0; 4096];
+            while let Ok(n) = relay_read.read(&mut buf).await {
+                if n == 0 {
+                    break;
+                }
+                if !upstream_cut.load(Ordering::Relaxed) {
+                    let _ = server_write.write_all(&buf[..n]).await;
+                }
+            }
+            // Keep the connection to the server open
+            std::future::pending::<()>().await;
+        });
+        let downstream_cut = cut.clone();
+        let downstream = tokio::spawn(async move {
+            let mut buf = [0; 4096];
+            loop {
+                match server_read.read(&mut buf).await {
+                    Ok(0) | Err(_) => return std::time::Instant::now(),
+                    Ok(n) if !downstream_cut.load(Ordering::Relaxed) => {
+                        let _ = relay_write.write_all(&buf[..n]).await;
+                    }
+                    Ok(_) => {}
+                }
+            }
+        });
+
+        let config = Arc::new(russh::client::Config::default());
+        let mut client = russh::client::connect_stream(config, client_stream, TrustingClient)
+            .await
+            .unwrap();
+        let key = Arc::new(KeyPair::generate_ed25519().unwrap());
+        client.authenticate_publickey("alice", key).await.unwrap();
+
+        cut.store(true, Ordering::Relaxed);
+        let cut_at = std::time::Instant::now();
+        let closed_at = tokio::time::timeout(Duration::from_secs(5), downstream)
+            .await
+            .expect("Server should drop the dead connection")
+            .unwrap();
+        assert!(closed_at.duration_since(cut_at) < interval * (KEEPALIVE_MAX as u32 + 3));
+
+        drop(server_handle);
+    }
+
+    #[tokio::test]
+    async fn run_server_on_unix_socket_and_remove_it_on_shutdown() {
+        let addr: SocketAddr = "127.0.0.1:2224".parse().unwrap();
+        let path = std::env::temp_dir().join(format!("chatd-{}.sock", std::process::id()));
+        let server_keys = vec![KeyPair::generate_ed25519().unwrap()];
+        let (tx, _rx) = mpsc::channel(100);
+        let auth = Auth::default();
+        let room = ChatRoom::new("Welcome!");
+
+        let mut chat_server = ChatServer::new(addr, &server_keys, tx, auth, room);
+        chat_server.set_unix_socket(path.clone(), false);
+        let (_, rx) = tokio::sync::mpsc::channel(1);
+        let repository = SessionRepository::new(rx);
+
+        let server_handle = run_server_in_background(&mut chat_server, repository).await;
+        sleep(Duration::from_millis(100)).await;
+
+        let connect_result = tokio::net::UnixStream::connect(&path).await;
+        assert!(
+            connect_result.is_ok(),
+            "Server should accept connections on the unix socket"
+        );
+        let connect_result = tokio::net::TcpStream::connect(addr).await;
+        assert!(connect_result.is_err(), "Server should not listen on TCP");
+
+        server_handle.abort();
+        let _ = server_handle.await;
+        assert!(!path.exists(), "Socket file should be removed");
+    }
 }
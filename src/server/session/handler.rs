@@ -1,3 +1,4 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use log::{error, info};
@@ -16,6 +17,7 @@ use super::{SessionEvent, SessionRepositoryEvent};
 /// Server handler. Each client will have their own handler.
 pub struct ThinHandler {
     id: usize,
+    peer_addr: Option<SocketAddr>,
     connect_username: String,
     public_key: Option<PublicKey>,
     auth: Arc<Mutex<auth::Auth>>,
@@ -26,11 +28,13 @@ pub struct ThinHandler {
 impl ThinHandler {
     pub fn new(
         id: usize,
+        peer_addr: Option<SocketAddr>,
         auth: Arc<Mutex<auth::Auth>>,
         repo_event_sender: Sender<SessionRepositoryEvent>,
     ) -> ThinHandler {
         ThinHandler {
             id,
+            peer_addr,
             connect_username: String::new(),
             public_key: None,
             auth,
@@ -59,10 +63,11 @@ impl Handler for ThinHandler {
         channel: Channel<Msg>,
         session: &mut Session,
     ) -> Result<bool, Self::Error> {
-        info!("Starting a new session id={}", self.id);
+        info!(session_id = self.id; "Starting a new session id={}", self.id);
 
         let id = self.id;
         let connect_username = self.connect_username.clone();
+        let peer_addr = self.peer_addr;
         let ssh_id = String::from_utf8_lossy(session.remote_sshid()).to_string();
         let key = self
             .public_key
@@ -81,11 +86,12 @@ impl Handler for ThinHandler {
                 ssh_id,
                 connect_username,
                 key.into(),
+                peer_addr,
                 terminal_handle,
                 session_event_rx,
             );
             if let Err(err) = sender.send(event).await {
-                error!("Failed to send NewSession event for channel {id}: {err}");
+                error!(session_id = id; "Failed to send NewSession event for channel {id}: {err}");
             }
         });
 
@@ -98,9 +104,16 @@ impl Handler for ThinHandler {
         user: &str,
         pk: &PublicKey,
     ) -> Result<Auth, Self::Error> {
-        info!("Public key offered auth request for user {}", user);
+        info!(session_id = self.id, user; "Public key offered auth request for user {}", user);
 
         let mut auth = self.auth.lock().await;
+        if let Some(addr) = self.peer_addr.filter(|addr| auth.is_ip_banned(&addr.ip())) {
+            info!(session_id = self.id, user; "Rejected banned address {} for user {}", addr, user);
+            return Ok(Auth::Reject {
+                proceed_with_methods: Some(MethodSet::PUBLICKEY),
+            });
+        }
+
         if !auth.is_whitelist_enabled() {
             return Ok(Auth::Accept);
         }
@@ -109,6 +122,11 @@ impl Handler for ThinHandler {
         if auth.is_trusted(&pub_key) && !auth.check_bans(&user, &pub_key) {
             return Ok(Auth::Accept);
         }
+        if !auth.is_trusted(&pub_key) {
+            // Let the client prove it holds the key before its access
+            // request is recorded, see `auth_publickey`
+            return Ok(Auth::Accept);
+        }
 
         Ok(Auth::Reject {
             proceed_with_methods: Some(MethodSet::PUBLICKEY),
@@ -117,9 +135,22 @@ impl Handler for ThinHandler {
 
     async fn auth_publickey(&mut self, user: &str, pk: &PublicKey) -> Result<Auth, Self::Error> {
         info!(
+            session_id = self.id, user;
             "Public key auth request for user {} using key {:?}",
             user, pk
         );
+
+        let mut auth = self.auth.lock().await;
+        let pub_key: PubKey = pk.into();
+        if auth.is_whitelist_enabled() && !auth.is_trusted(&pub_key) {
+            info!(session_id = self.id, user; "Recorded access request for key {}", pub_key.fingerprint());
+            auth.record_access_request(pub_key, user, self.peer_addr);
+            return Ok(Auth::Reject {
+                proceed_with_methods: Some(MethodSet::PUBLICKEY),
+            });
+        }
+        drop(auth);
+
         self.connect_username = String::from(user);
         self.public_key = Some(pk.clone());
         Ok(Auth::Accept)
@@ -127,6 +158,7 @@ impl Handler for ThinHandler {
 
     async fn auth_password(&mut self, user: &str, password: &str) -> Result<Auth, Self::Error> {
         info!(
+            session_id = self.id, user;
             "Password auth request for user {} using credentials {}",
             user, password
         );
@@ -142,7 +174,7 @@ impl Handler for ThinHandler {
         submethods: &str,
         response: Option<Response<'async_trait>>,
     ) -> Result<Auth, Self::Error> {
-        info!("Keyboard interactive auth request for user {}", user);
+        info!(session_id = self.id, user; "Keyboard interactive auth request for user {}", user);
         Ok(Auth::Reject {
             proceed_with_methods: Some(MethodSet::PUBLICKEY),
         })
@@ -263,12 +295,12 @@ impl Handler for ThinHandler {
 impl Drop for ThinHandler {
     fn drop(&mut self) {
         if let Some(sender) = &self.session_event_sender {
-            info!("Clean up from disconnected session id={}", self.id);
+            info!(session_id = self.id; "Clean up from disconnected session id={}", self.id);
             let sender = sender.clone();
             let channel = self.id;
             tokio::spawn(async move {
                 if let Err(err) = sender.send(SessionEvent::Disconnect).await {
-                    error!("Failed to send Disconnect event for channel {channel}: {err}");
+                    error!(session_id = channel; "Failed to send Disconnect event for channel {channel}: {err}");
                 }
             });
         }
@@ -375,7 +407,7 @@ mod tests {
 
         let auth = auth::Auth::default();
         let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-        let handler = ThinHandler::new(1, Arc::new(Mutex::new(auth.clone())), tx.clone());
+        let handler = ThinHandler::new(1, None, Arc::new(Mutex::new(auth.clone())), tx.clone());
 
         test_session(Client {}, handler, |c| async move {
             let _ = c.channel_open_session().await;
@@ -385,7 +417,7 @@ mod tests {
                 Ok(event) => assert!(matches!(
                     event,
                     SessionRepositoryEvent::NewSession(
-                        id, _, username, _, _, _
+                        id, _, username, _, _, _, _
                     ) if id == 1 && username == "user".to_string()
                 )),
                 Err(err) => panic!("{}", err),
@@ -414,7 +446,7 @@ mod tests {
 
         let auth = auth::Auth::default();
         let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-        let handler = ThinHandler::new(1, Arc::new(Mutex::new(auth.clone())), tx.clone());
+        let handler = ThinHandler::new(1, None, Arc::new(Mutex::new(auth.clone())), tx.clone());
 
         test_session(
             Client {},
@@ -432,7 +464,7 @@ mod tests {
                 let timeout_duration = Duration::from_secs(1);
                 match receive_event(&mut rx, timeout_duration).await {
                     Ok(event) => match event {
-                        SessionRepositoryEvent::NewSession(_, _, _, _, _, mut event_rx) => {
+                        SessionRepositoryEvent::NewSession(_, _, _, _, _, _, mut event_rx) => {
                             match receive_event(&mut event_rx, timeout_duration).await {
                                 Ok(event) => assert!(matches!(event, SessionEvent::Env(name, value) if name == "THEME" && value == "mono")),
                                 Err(err) => panic!("{}", err),
@@ -471,7 +503,7 @@ mod tests {
 
         let auth = auth::Auth::default();
         let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-        let handler = ThinHandler::new(1, Arc::new(Mutex::new(auth.clone())), tx.clone());
+        let handler = ThinHandler::new(1, None, Arc::new(Mutex::new(auth.clone())), tx.clone());
 
         test_session(
             Client {},
@@ -483,7 +515,7 @@ mod tests {
                 let timeout_duration = Duration::from_secs(1);
                 match receive_event(&mut rx, timeout_duration).await {
                     Ok(event) => match event {
-                        SessionRepositoryEvent::NewSession(_, _, _, _, _, mut event_rx) => {
+                        SessionRepositoryEvent::NewSession(_, _, _, _, _, _, mut event_rx) => {
                             match receive_event(&mut event_rx, timeout_duration).await {
                                 Ok(event) => assert!(matches!(event, SessionEvent::WindowResize(cw, rh) if cw == 100 && rh == 50)),
                                 Err(err) => panic!("{}", err),
@@ -517,7 +549,7 @@ mod tests {
 
         let auth = auth::Auth::default();
         let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-        let handler = ThinHandler::new(1, Arc::new(Mutex::new(auth.clone())), tx.clone());
+        let handler = ThinHandler::new(1, None, Arc::new(Mutex::new(auth.clone())), tx.clone());
 
         test_session(
             Client {},
@@ -529,7 +561,7 @@ mod tests {
                 let timeout_duration = Duration::from_secs(1);
                 match receive_event(&mut rx, timeout_duration).await {
                     Ok(event) => match event {
-                        SessionRepositoryEvent::NewSession(_, _, _, _, _, mut event_rx) => {
+                        SessionRepositoryEvent::NewSession(_, _, _, _, _, _, mut event_rx) => {
                             match receive_event(&mut event_rx, timeout_duration).await {
                                 Ok(event) => assert!(matches!(event, SessionEvent::WindowResize(cw, rh) if cw == 100 && rh == 50)),
                                 Err(err) => panic!("{}", err),
@@ -563,7 +595,7 @@ mod tests {
 
         let auth = auth::Auth::default();
         let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-        let handler = ThinHandler::new(1, Arc::new(Mutex::new(auth.clone())), tx.clone());
+        let handler = ThinHandler::new(1, None, Arc::new(Mutex::new(auth.clone())), tx.clone());
 
         test_session(Client {}, handler, |c| async move {
             let channel = c.channel_open_session().await.unwrap();
@@ -573,7 +605,7 @@ mod tests {
             let timeout_duration = Duration::from_secs(1);
             match receive_event(&mut rx, timeout_duration).await {
                 Ok(event) => match event {
-                    SessionRepositoryEvent::NewSession(_, _, _, _, _, mut event_rx) => {
+                    SessionRepositoryEvent::NewSession(_, _, _, _, _, _, mut event_rx) => {
                         match receive_event(&mut event_rx, timeout_duration).await {
                             Ok(event) => {
                                 assert!(matches!(event, SessionEvent::Data(bytes) if bytes == data))
@@ -593,7 +625,7 @@ mod tests {
     async fn test_auth_keyboard_interactive() {
         let auth = auth::Auth::default();
         let (tx, _) = tokio::sync::mpsc::channel(1);
-        let mut handler = ThinHandler::new(1, Arc::new(Mutex::new(auth.clone())), tx.clone());
+        let mut handler = ThinHandler::new(1, None, Arc::new(Mutex::new(auth.clone())), tx.clone());
         let response = handler.auth_keyboard_interactive("user", "", None).await;
         assert!(
             matches!(response, Ok(Auth::Reject { proceed_with_methods }) if proceed_with_methods == Some(MethodSet::PUBLICKEY))
@@ -604,7 +636,7 @@ mod tests {
     async fn test_auth_password() {
         let auth = auth::Auth::default();
         let (tx, _) = tokio::sync::mpsc::channel(1);
-        let mut handler = ThinHandler::new(1, Arc::new(Mutex::new(auth.clone())), tx.clone());
+        let mut handler = ThinHandler::new(1, None, Arc::new(Mutex::new(auth.clone())), tx.clone());
         let response = handler.auth_password("user", "password").await;
         assert!(
             matches!(response, Ok(Auth::Reject { proceed_with_methods }) if proceed_with_methods == Some(MethodSet::PUBLICKEY))
@@ -615,7 +647,7 @@ mod tests {
     async fn test_auth_publickey() {
         let auth = auth::Auth::default();
         let (tx, _) = tokio::sync::mpsc::channel(1);
-        let mut handler = ThinHandler::new(1, Arc::new(Mutex::new(auth.clone())), tx.clone());
+        let mut handler = ThinHandler::new(1, None, Arc::new(Mutex::new(auth.clone())), tx.clone());
 
         let pubkey = create_public_key();
         let response = handler.auth_publickey("user", &pubkey).await;
@@ -631,7 +663,7 @@ mod tests {
         auth.disable_whitelist_mode();
 
         let (tx, _) = tokio::sync::mpsc::channel(1);
-        let mut handler = ThinHandler::new(1, Arc::new(Mutex::new(auth.clone())), tx.clone());
+        let mut handler = ThinHandler::new(1, None, Arc::new(Mutex::new(auth.clone())), tx.clone());
         let response = handler
             .auth_publickey_offered("user", &create_public_key())
             .await;
@@ -653,24 +685,75 @@ mod tests {
         auth.ban_fingerprint(&banned_user_pk.fingerprint(), Duration::from_secs(60));
 
         let (tx, _) = tokio::sync::mpsc::channel(1);
-        let mut handler = ThinHandler::new(1, Arc::new(Mutex::new(auth.clone())), tx.clone());
+        let mut handler = ThinHandler::new(1, None, Arc::new(Mutex::new(auth.clone())), tx.clone());
 
         // Any trusted and not banned user is allowed
         let response = handler.auth_publickey_offered("user", &user_pk).await;
         assert!(matches!(response, Ok(Auth::Accept)));
 
-        // Any user not in the whitelist is not allowed
+        // Any user not in the whitelist is asked to prove the key first
         let response = handler.auth_publickey_offered("guest", &guest_pk).await;
+        assert!(matches!(response, Ok(Auth::Accept)));
+
+        // Any trusted user whose name or fingerprint is banned is not allowed
+        let response = handler
+            .auth_publickey_offered("banned_user", &banned_user_pk)
+            .await;
         assert!(
             matches!(response, Ok(Auth::Reject { proceed_with_methods }) if proceed_with_methods == Some(MethodSet::PUBLICKEY))
         );
+    }
 
-        // Any trusted user whose name or fingerprint is banned is not allowed
+    #[tokio::test]
+    async fn test_record_access_request_only_once_key_is_proven() {
+        let guest_pk = create_public_key();
+        let mut auth = auth::Auth::default();
+        auth.enable_whitelist_mode();
+        let auth = Arc::new(Mutex::new(auth));
+
+        let (tx, _) = tokio::sync::mpsc::channel(1);
+        let peer_addr = Some("10.0.0.42:50000".parse().unwrap());
+        let mut handler = ThinHandler::new(1, peer_addr, auth.clone(), tx);
+
+        // Offering a key proves nothing, anyone can send a public key
+        let response = handler.auth_publickey_offered("guest", &guest_pk).await;
+        assert!(matches!(response, Ok(Auth::Accept)));
+        assert!(auth.lock().await.access_requests().is_empty());
+
+        let response = handler.auth_publickey("guest", &guest_pk).await;
+        assert!(
+            matches!(response, Ok(Auth::Reject { proceed_with_methods }) if proceed_with_methods == Some(MethodSet::PUBLICKEY))
+        );
+        assert_eq!(handler.public_key(), &None);
+        let auth = auth.lock().await;
+        let requests = auth.access_requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].username, "guest");
+        assert_eq!(requests[0].addr, peer_addr);
+    }
+
+    #[tokio::test]
+    async fn test_auth_publickey_offered_from_banned_ip_range() {
+        let mut auth = auth::Auth::default();
+        auth.disable_whitelist_mode();
+        auth.ban_ip("10.0.0.0/24".parse().unwrap(), Duration::from_secs(60));
+        let auth = Arc::new(Mutex::new(auth));
+
+        let (tx, _) = tokio::sync::mpsc::channel(1);
+        let peer_addr = Some("10.0.0.42:50000".parse().unwrap());
+        let mut handler = ThinHandler::new(1, peer_addr, auth.clone(), tx.clone());
         let response = handler
-            .auth_publickey_offered("banned_user", &guest_pk)
+            .auth_publickey_offered("user", &create_public_key())
             .await;
         assert!(
             matches!(response, Ok(Auth::Reject { proceed_with_methods }) if proceed_with_methods == Some(MethodSet::PUBLICKEY))
         );
+
+        let peer_addr = Some("10.0.1.42:50000".parse().unwrap());
+        let mut handler = ThinHandler::new(2, peer_addr, auth, tx);
+        let response = handler
+            .auth_publickey_offered("user", &create_public_key())
+            .await;
+        assert!(matches!(response, Ok(Auth::Accept)));
     }
 }
@@ -1,5 +1,8 @@
 use std::fmt::Debug;
+use std::io::Write;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Instant;
 
 use log::{error, info, trace, warn};
 use terminal_keycode::KeyCode;
@@ -8,10 +11,15 @@ use tokio::sync::mpsc::{self, Receiver};
 use tokio::sync::{watch, Mutex};
 
 use crate::auth::Auth;
-use crate::chat::ChatRoom;
+use crate::chat::{message, ChatRoom, LeaveReason};
 use crate::pubkey::PubKey;
 use crate::server::session_workflow::{self, WorkflowContext, WorkflowHandler};
-use crate::terminal::{keyboard_decoder, Terminal, TerminalHandle};
+use crate::terminal::{keyboard_decoder, CloseHandle, Terminal, TerminalHandle};
+use crate::utils::sanitize;
+
+/// Number of rendered messages queued for a session before new ones
+/// are dropped
+const MESSAGE_BUFFER_SIZE: usize = 100;
 
 type SessionId = usize;
 type SessionSshId = String;
@@ -31,6 +39,7 @@ pub enum SessionRepositoryEvent {
         SessionSshId,
         SessionConnectUsername,
         PubKey,
+        Option<SocketAddr>,
         TerminalHandle,
         Receiver<SessionEvent>,
     ),
@@ -39,12 +48,13 @@ pub enum SessionRepositoryEvent {
 impl Debug for SessionRepositoryEvent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::NewSession(arg0, arg1, arg2, arg3, _arg4, _arg5) => f
+            Self::NewSession(arg0, arg1, arg2, arg3, arg4, _arg5, _arg6) => f
                 .debug_tuple("NewSession")
                 .field(arg0)
                 .field(arg1)
                 .field(arg2)
                 .field(arg3)
+                .field(arg4)
                 .finish(),
         }
     }
@@ -64,26 +74,71 @@ impl SessionRepository {
     pub async fn wait_for_sessions(&mut self, room: Arc<Mutex<ChatRoom>>, auth: Arc<Mutex<Auth>>) {
         while let Some(event) = self.repo_event_receiver.recv().await {
             match event {
-                SessionRepositoryEvent::NewSession(id, ssh_id, username, pk, handle, event_rx) => {
+                SessionRepositoryEvent::NewSession(
+                    id,
+                    ssh_id,
+                    username,
+                    pk,
+                    peer_addr,
+                    handle,
+                    event_rx,
+                ) => {
                     let room = room.clone();
                     let auth = auth.clone();
 
                     let mut terminal = Terminal::new(handle);
-                    let (message_tx, message_rx) = mpsc::channel(100);
-                    let (exit_tx, exit_rx) = watch::channel(());
+                    let (message_tx, message_rx) = mpsc::channel(MESSAGE_BUFFER_SIZE);
+                    let (exit_tx, exit_rx) = watch::channel(LeaveReason::default());
 
                     spawn(async move {
-                        {
+                        let (unread_rx, prompt_rx) = {
                             let mut room = room.lock().await;
-                            let join_result = room
-                                .join(id, username, pk, ssh_id, message_tx, exit_tx)
-                                .await;
-                            if let Ok(user) = join_result {
-                                terminal.set_prompt(&user.config().display_name());
+                            let (is_reserved, is_op) = {
+                                let auth = auth.lock().await;
+                                (
+                                    auth.is_name_reserved(&sanitize::name(&username), &pk),
+                                    auth.is_op(&pk),
+                                )
+                            };
+                            let name = match is_reserved {
+                                true => String::new(),
+                                false => username.clone(),
+                            };
+                            let join_result = match room.check_max_per_key(&pk, is_op) {
+                                Ok(()) => {
+                                    room.join(id, name, pk, ssh_id, peer_addr, message_tx, exit_tx)
+                                        .await
+                                }
+                                Err(err) => Err(err),
+                            };
+                            match join_result {
+                                Ok(user) => {
+                                    terminal.set_prompt(&room.prompt(&user));
+                                    if is_reserved {
+                                        let message = message::Error::new(
+                                            user.clone().into(),
+                                            format!(
+                                                "\"{}\" name is reserved, joined as {}",
+                                                username,
+                                                user.username()
+                                            ),
+                                        );
+                                        let _ = room.send_message(message.into()).await;
+                                    }
+                                    let member = room.find_member_by_id(id);
+                                    (member.subscribe_unread(), member.subscribe_prompt())
+                                }
+                                Err(err) => {
+                                    warn!(session_id = id; "Session id={id} failed to join the room: {err}");
+                                    let _ = terminal.print_message(&format!("Error: {err}"));
+                                    terminal.exit();
+                                    return;
+                                }
                             }
-                        }
+                        };
                         Self::handle_session(
-                            id, room, auth, terminal, event_rx, message_rx, exit_rx,
+                            id, room, auth, terminal, event_rx, message_rx, unread_rx, prompt_rx,
+                            exit_rx,
                         )
                         .await;
                     });
@@ -92,22 +147,27 @@ impl SessionRepository {
         }
     }
 
-    async fn handle_session(
+    #[allow(clippy::too_many_arguments)]
+    async fn handle_session<H>(
         id: SessionId,
         room: Arc<Mutex<ChatRoom>>,
         auth: Arc<Mutex<Auth>>,
-        terminal: Terminal<TerminalHandle>,
+        terminal: Terminal<H>,
         event_rx: Receiver<SessionEvent>,
         message_rx: Receiver<String>,
-        exit_rx: watch::Receiver<()>,
-    ) {
+        unread_rx: watch::Receiver<usize>,
+        prompt_rx: watch::Receiver<String>,
+        exit_rx: watch::Receiver<LeaveReason>,
+    ) where
+        H: Clone + Write + CloseHandle + Send + 'static,
+    {
         let terminal = Arc::new(Mutex::new(terminal));
         let (disconnect_tx, disconnect_rx) = watch::channel(());
 
         let session_handle = spawn(Self::process_session_events(
             id,
             room.clone(),
-            auth,
+            auth.clone(),
             terminal.clone(),
             event_rx,
             disconnect_tx,
@@ -116,8 +176,11 @@ impl SessionRepository {
         let room_handle = spawn(Self::process_room_events(
             id,
             room.clone(),
+            auth.clone(),
             terminal,
             message_rx,
+            unread_rx,
+            prompt_rx,
             exit_rx,
             disconnect_rx,
         ));
@@ -128,28 +191,44 @@ impl SessionRepository {
         trace!("Fell through the session tasks, indicating disconnection on session. Threads are closed");
     }
 
-    async fn process_session_events(
+    async fn process_session_events<H>(
         id: SessionId,
         room: Arc<Mutex<ChatRoom>>,
         auth: Arc<Mutex<Auth>>,
-        terminal: Arc<Mutex<Terminal<TerminalHandle>>>,
+        terminal: Arc<Mutex<Terminal<H>>>,
         mut event_rx: Receiver<SessionEvent>,
         disconnect_tx: watch::Sender<()>,
-    ) {
-        info!("Session events processing task for id={id} is started");
+    ) where
+        H: Clone + Write + CloseHandle + Send + 'static,
+    {
+        info!(session_id = id; "Session events processing task for id={id} is started");
+
+        let mut decoder = keyboard_decoder::KeyboardDecoder::default();
+        let mut paste = None;
 
         while let Some(event) = event_rx.recv().await {
             match event {
                 SessionEvent::Data(data) => {
+                    let received_at = Instant::now();
                     let mut room = room.lock().await;
                     let mut auth = auth.lock().await;
                     let mut term = terminal.lock().await;
 
-                    let user = room.find_member_by_id(id).user.clone();
+                    let user = match room.try_find_member_by_id(id) {
+                        Some(member) => member.user.clone(),
+                        None => {
+                            info!(session_id = id; "Closing session id={id} which is no room member anymore");
+                            term.exit();
+                            return;
+                        }
+                    };
+                    room.register_input(id);
                     let mut ctx = WorkflowContext::new(user);
+                    ctx.created_at = received_at;
+                    ctx.paste = paste.take();
 
                     let mut print_input = false;
-                    let codes = keyboard_decoder::decode_bytes_to_codes(&data);
+                    let codes = decoder.decode(&data);
                     for code in codes {
                         if let Err(err) = match code {
                             KeyCode::Char(_) | KeyCode::Space => {
@@ -173,13 +252,15 @@ impl SessionRepository {
                                     .await
                             }
                         } {
-                            error!("Failed to execute workflow for user {}: {}", id, err);
+                            error!(session_id = id; "Failed to execute workflow for user {}: {}", id, err);
                         }
                     }
 
+                    paste = ctx.paste.take();
+
                     if print_input {
                         if let Err(err) = term.print_input_line() {
-                            error!("Failed to execute workflow for user {}: {}", id, err);
+                            error!(session_id = id; "Failed to execute workflow for user {}: {}", id, err);
                         }
                     }
                 }
@@ -188,63 +269,251 @@ impl SessionRepository {
                     let mut auth = auth.lock().await;
                     let mut term = terminal.lock().await;
 
-                    let user = room.find_member_by_id(id).user.clone();
+                    let user = match room.try_find_member_by_id(id) {
+                        Some(member) => member.user.clone(),
+                        None => {
+                            info!(session_id = id; "Closing session id={id} which is no room member anymore");
+                            term.exit();
+                            return;
+                        }
+                    };
                     let mut ctx = WorkflowContext::new(user);
 
                     if let Err(err) = session_workflow::env(name, value)
                         .execute(&mut ctx, &mut term, &mut room, &mut auth)
                         .await
                     {
-                        error!("Failed to execute env workflow for user {}: {}", id, err);
+                        error!(session_id = id; "Failed to execute env workflow for user {}: {}", id, err);
                     }
                 }
                 SessionEvent::Disconnect => {
                     let _ = disconnect_tx.send(());
-                    info!("Session events processing task for id={id} is finished");
+                    info!(session_id = id; "Session events processing task for id={id} is finished");
                     return;
                 }
                 SessionEvent::WindowResize(width, height) => {
                     let mut terminal = terminal.lock().await;
-                    terminal.set_size(width, height);
+                    if let Err(err) = terminal.resize(width, height) {
+                        error!(session_id = id; "Failed to redraw input line for user {}: {}", id, err);
+                    }
                 }
             }
         }
     }
 
-    async fn process_room_events(
+    #[allow(clippy::too_many_arguments)]
+    async fn process_room_events<H>(
         id: SessionId,
         room: Arc<Mutex<ChatRoom>>,
-        terminal: Arc<Mutex<Terminal<TerminalHandle>>>,
+        auth: Arc<Mutex<Auth>>,
+        terminal: Arc<Mutex<Terminal<H>>>,
         mut message_rx: Receiver<String>,
-        mut exit_rx: watch::Receiver<()>,
+        mut unread_rx: watch::Receiver<usize>,
+        mut prompt_rx: watch::Receiver<String>,
+        mut exit_rx: watch::Receiver<LeaveReason>,
         mut disconnect_rx: watch::Receiver<()>,
-    ) {
-        info!("Render task for id={id} is started");
+    ) where
+        H: Clone + Write + CloseHandle + Send + 'static,
+    {
+        info!(session_id = id; "Render task for id={id} is started");
 
         tokio::select! {
             _ = exit_rx.changed() => {
-                terminal.lock().await.exit();
-                if let Err(err) = room.lock().await.leave(&id).await {
-                    error!("Failed to exit the server by user {}: {}", id, err);
+                let mut term = terminal.lock().await;
+                // Show what was sent right before the exit, e.g. a farewell
+                let mut msgs = vec![];
+                while let Ok(msg) = message_rx.try_recv() {
+                    msgs.push(msg);
+                }
+                let _ = term.print_messages(&msgs);
+                term.exit();
+                drop(term);
+                let reason = exit_rx.borrow().clone();
+                if let Err(err) = Self::leave_room(id, &room, &auth, reason).await {
+                    error!(session_id = id; "Failed to exit the server by user {}: {}", id, err);
                 }
-                info!("Render task for id={id} aborted because session is closed by a user");
+                info!(session_id = id; "Render task for id={id} aborted because session is closed by a user");
                 return;
             }
             _ = disconnect_rx.changed() => {
-                if let Err(err) = room.lock().await.leave(&id).await {
-                    error!("Failed to disconnect user {} from the server: {}", id, err);
+                room.lock().await.hold_name_for_reconnect(&id);
+                if let Err(err) = Self::leave_room(id, &room, &auth, LeaveReason::Quit).await {
+                    error!(session_id = id; "Failed to disconnect user {} from the server: {}", id, err);
                 }
-                info!("Render task for id={id} aborted because session is disconnected");
+                info!(session_id = id; "Render task for id={id} aborted because session is disconnected");
                 return;
             }
             _ = async {
-                while let Some(msg) = message_rx.recv().await {
-                    let _ = terminal.lock().await.print_message(&msg);
+                loop {
+                    tokio::select! {
+                        msg = message_rx.recv() => match msg {
+                            Some(msg) => {
+                                // Render everything already queued as one frame
+                                let mut msgs = vec![msg];
+                                while let Ok(msg) = message_rx.try_recv() {
+                                    msgs.push(msg);
+                                }
+                                let _ = terminal.lock().await.print_messages(&msgs);
+                            }
+                            None => break,
+                        },
+                        Ok(()) = unread_rx.changed() => {
+                            let count = *unread_rx.borrow_and_update();
+                            let mut terminal = terminal.lock().await;
+                            terminal.set_prompt_unread(count);
+                            let _ = terminal.print_input_line();
+                        }
+                        Ok(()) = prompt_rx.changed() => {
+                            let prompt = prompt_rx.borrow_and_update().clone();
+                            let mut terminal = terminal.lock().await;
+                            terminal.set_prompt(&prompt);
+                            let _ = terminal.print_input_line();
+                        }
+                    }
                 }
             } => {
                 // Warning: This situation is uncommon and should not occur under normal circumstances.
-                warn!("Render task for id={id} finished its work");
+                warn!(session_id = id; "Render task for id={id} finished its work");
             }
         }
     }
+
+    /// Removes the session user from the room, keeping an operator's
+    /// name reserved for them for a while
+    async fn leave_room(
+        id: SessionId,
+        room: &Arc<Mutex<ChatRoom>>,
+        auth: &Arc<Mutex<Auth>>,
+        reason: LeaveReason,
+    ) -> anyhow::Result<()> {
+        info!(session_id = id; "User id={id} leaves the room: {reason}");
+        let mut room = room.lock().await;
+        if let Some(name) = room.try_get_name(&id).cloned() {
+            let user = &room.find_member(&name).user;
+            auth.lock().await.reserve_op_name(&name, user.public_key());
+        }
+        room.leave(&id, reason).await
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::chat::SingleSessionMode;
+    use mockall::mock;
+
+    mock! {
+        pub Handle {}
+
+        impl Write for Handle {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize>;
+            fn flush(&mut self) -> std::io::Result<()>;
+        }
+
+        impl Clone for Handle {
+            fn clone(&self) -> Self;
+        }
+
+        impl CloseHandle for Handle {
+            fn close(&mut self) {}
+        }
+    }
+
+    fn closing_handle() -> MockHandle {
+        let mut handle = MockHandle::new();
+        handle.expect_write().returning(|buf| Ok(buf.len()));
+        handle.expect_flush().returning(|| Ok(()));
+        handle.expect_close().times(1..).return_const(());
+        handle
+    }
+
+    #[tokio::test]
+    async fn close_session_sending_input_without_being_a_member() {
+        let room = Arc::new(Mutex::new(ChatRoom::new("Welcome!")));
+        let auth = Arc::new(Mutex::new(Auth::default()));
+        let terminal = Arc::new(Mutex::new(Terminal::new(closing_handle())));
+        let (event_tx, event_rx) = mpsc::channel(10);
+        let (disconnect_tx, mut disconnect_rx) = watch::channel(());
+
+        let session = spawn(SessionRepository::process_session_events(
+            1,
+            room,
+            auth,
+            terminal,
+            event_rx,
+            disconnect_tx,
+        ));
+        event_tx
+            .send(SessionEvent::Data(b"/users\r".to_vec()))
+            .await
+            .unwrap();
+
+        assert!(session.await.is_ok());
+        assert!(disconnect_rx.changed().await.is_err());
+        assert!(event_tx.is_closed());
+    }
+
+    #[tokio::test]
+    async fn let_evicted_session_leave_the_room_and_close_on_input() {
+        let mut room = ChatRoom::new("Welcome!");
+        room.set_single_session_mode(SingleSessionMode::Evict);
+        let room = Arc::new(Mutex::new(room));
+        let auth = Arc::new(Mutex::new(Auth::default()));
+        let (message_tx, message_rx) = mpsc::channel(MESSAGE_BUFFER_SIZE);
+        let (exit_tx, exit_rx) = watch::channel(LeaveReason::default());
+        let (unread_rx, prompt_rx) = {
+            let mut room = room.lock().await;
+            room.join(
+                1,
+                "alice".into(),
+                PubKey::default(),
+                "ssh".into(),
+                None,
+                message_tx,
+                exit_tx,
+            )
+            .await
+            .unwrap();
+            let member = room.find_member_by_id(1);
+            (member.subscribe_unread(), member.subscribe_prompt())
+        };
+        let (event_tx, event_rx) = mpsc::channel(10);
+        let session = spawn(SessionRepository::handle_session(
+            1,
+            room.clone(),
+            auth,
+            Terminal::new(closing_handle()),
+            event_rx,
+            message_rx,
+            unread_rx,
+            prompt_rx,
+            exit_rx,
+        ));
+
+        let (message_tx, _message_rx) = mpsc::channel(MESSAGE_BUFFER_SIZE);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        room.lock()
+            .await
+            .join(
+                2,
+                "alice".into(),
+                PubKey::default(),
+                "ssh".into(),
+                None,
+                message_tx,
+                exit_tx,
+            )
+            .await
+            .unwrap();
+        event_tx
+            .send(SessionEvent::Data(b"hello\r".to_vec()))
+            .await
+            .unwrap();
+        drop(event_tx);
+
+        assert!(session.await.is_ok());
+        let room = room.lock().await;
+        assert!(room.try_get_name(&1).is_none());
+        assert!(room.try_get_name(&2).is_some());
+    }
 }
@@ -6,9 +6,9 @@ use super::WorkflowContext;
 
 use crate::auth::Auth;
 use crate::chat::{
-    ChatRoom, Command, CommandProps, OplistCommand, OplistLoadMode, Theme, TimestampMode,
-    WhitelistCommand, WhitelistLoadMode, CHAT_COMMANDS, NOOP_CHAT_COMMANDS, OPLIST_COMMANDS,
-    WHITELIST_COMMANDS,
+    command_prefix, ChatRoom, Command, CommandProps, OplistCommand, OplistLoadMode, ThemeChoice,
+    TimestampMode, UserName, WhitelistCommand, WhitelistLoadMode, CHAT_COMMANDS,
+    NOOP_CHAT_COMMANDS, OPLIST_COMMANDS, WHITELIST_COMMANDS,
 };
 use crate::terminal::{CloseHandle, Terminal};
 
@@ -61,13 +61,17 @@ where
             true => CHAT_COMMANDS.clone(),
             false => NOOP_CHAT_COMMANDS.clone(),
         };
-        let complete_cmd = match commands.iter().find(|c| c.has_prefix(&cmd_prefix)) {
+        let cmd_name = match cmd_prefix.strip_prefix(command_prefix()) {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        let complete_cmd = match commands.iter().find(|c| c.has_prefix(cmd_name)) {
             Some(cmd) => cmd,
             None => return Ok(()),
         };
 
         if cursor_pos > 0 && cursor_pos <= cmd_prefix_end_pos {
-            paste_complete_text(terminal, cmd_end_pos, &complete_cmd.cmd())?;
+            paste_complete_text(terminal, cmd_end_pos, &complete_cmd.display_cmd())?;
             return Ok(());
         }
 
@@ -154,13 +158,58 @@ where
                     TimestampMode::from_prefix(prefix)
                 })?;
             }
+            Command::Bell(_) => {
+                let bell = words_iter.next().unwrap_or_default();
+                complete_argument(bell, cmd_end_pos, terminal, |prefix| {
+                    ["on", "off"]
+                        .into_iter()
+                        .find(|value| value.starts_with(prefix))
+                })?;
+            }
+            Command::Poll(_) => {
+                let subcmd = words_iter.next().unwrap_or_default();
+                complete_argument(subcmd, cmd_end_pos, terminal, |prefix| {
+                    ["close"]
+                        .into_iter()
+                        .find(|value| value.starts_with(prefix))
+                })?;
+            }
             Command::Theme(_) => {
                 let theme = words_iter.next().unwrap_or_default();
                 complete_argument(theme, cmd_end_pos, terminal, |prefix| {
-                    Theme::from_prefix(prefix)
+                    ThemeChoice::from_prefix(prefix)
+                })?;
+            }
+            Command::Unignore(_) => {
+                let user = words_iter.next().unwrap_or_default();
+                let ignored = context
+                    .user
+                    .ignored()
+                    .iter()
+                    .filter_map(|id| room.try_get_name(id))
+                    .collect::<Vec<&UserName>>();
+                complete_argument(user, cmd_end_pos, terminal, |prefix| {
+                    find_name_in(&ignored, prefix)
                 })?;
             }
-            cmd if cmd.args().starts_with("<user>") || cmd.args().starts_with("[user]") => {
+            Command::Focus(_) => {
+                let user = words_iter.next().unwrap_or_default();
+                let focused = context
+                    .user
+                    .focused()
+                    .iter()
+                    .filter_map(|id| room.try_get_name(id))
+                    .collect::<Vec<&UserName>>();
+                complete_argument(user, cmd_end_pos, terminal, |prefix| {
+                    match prefix.strip_prefix('-') {
+                        Some(prefix) => {
+                            find_name_in(&focused, prefix).map(|name| format!("-{}", name))
+                        }
+                        None => room.find_name_by_prefix(prefix, context.user.username().as_ref()),
+                    }
+                })?;
+            }
+            cmd if cmd.args().starts_with("<user") || cmd.args().starts_with("[user]") => {
                 let user = words_iter.next().unwrap_or_default();
                 complete_argument(user, cmd_end_pos, terminal, |prefix| {
                     room.find_name_by_prefix(prefix, context.user.username().as_ref())
@@ -198,6 +247,19 @@ where
     Ok(())
 }
 
+/// Picks the first name in alphabetical order starting with the prefix
+fn find_name_in(names: &[&UserName], prefix: &str) -> Option<String> {
+    if prefix.is_empty() {
+        return None;
+    }
+    names
+        .iter()
+        .map(|name| name.as_ref())
+        .filter(|name: &&str| name.starts_with(prefix))
+        .min()
+        .map(str::to_string)
+}
+
 fn get_argument_details(arg: &str, prev_arg_end_pos: usize) -> (String, usize, usize) {
     let arg_prefix = arg.trim().to_string();
     let arg_end_pos = prev_arg_end_pos + arg.len();
@@ -221,7 +283,7 @@ fn paste_complete_text<H: Clone + Write + CloseHandle + Send>(
 
 #[cfg(test)]
 mod should {
-    use crate::chat::User;
+    use crate::chat::{LeaveReason, User};
     use crate::pubkey::PubKey;
     use mockall::mock;
     use tokio::sync::{mpsc, watch};
@@ -281,10 +343,15 @@ mod should {
             ("/us", "/users"),
             ("/wh", "/whois"),
             ("/th", "/theme"),
-            ("/qu", "/quiet"),
+            ("/qui", "/quiet"),
+            ("/quo", "/quote"),
             ("/ig", "/ignore"),
             ("/un", "/unignore"),
             ("/ti", "/timestamp"),
+            ("/op", "/ops"),
+            ("/set", "/settings"),
+            ("/po", "/poll"),
+            ("/vo", "/vote"),
         ];
 
         terminal
@@ -296,7 +363,7 @@ mod should {
         terminal
             .handle()
             .expect_flush()
-            .times(14)
+            .times(19)
             .returning(|| Ok(()));
 
         for (prefix, command) in prefix_command_map {
@@ -320,10 +387,14 @@ mod should {
             ("/ba", "/ban"),
             ("/mu", "/mute"),
             ("/ki", "/kick"),
+            ("/co", "/color"),
+            ("/unb", "/unban"),
+            ("/shu", "/shutdown"),
             ("/mo", "/motd"),
             ("/bann", "/banned"),
-            ("/op", "/oplist"),
+            ("/opl", "/oplist"),
             ("/whi", "/whitelist"),
+            ("/bl", "/blocklist"),
         ];
 
         terminal
@@ -335,7 +406,7 @@ mod should {
         terminal
             .handle()
             .expect_flush()
-            .times(7)
+            .times(11)
             .returning(|| Ok(()));
 
         for (prefix, command) in prefix_command_map {
@@ -358,10 +429,14 @@ mod should {
             ("/ba", "/ban"),
             ("/mu", "/mute"),
             ("/ki", "/kick"),
+            ("/co", "/color"),
+            ("/unb", "/unban"),
+            ("/shu", "/shutdown"),
             ("/mo", "/motd"),
             ("/bann", "/banned"),
-            ("/op", "/oplist"),
+            ("/opl", "/oplist"),
             ("/whi", "/whitelist"),
+            ("/bl", "/blocklist"),
         ];
 
         terminal
@@ -458,10 +533,10 @@ mod should {
     }
 
     #[tokio::test]
-    async fn complete_timestamp_argument() {
+    async fn complete_bell_argument() {
         let (mut auth, mut terminal, mut room, mut context, mut autocomplete) = setup!();
 
-        let prefix_full_map = vec![("ti", "time"), ("da", "datetime")];
+        let prefix_full_map = vec![("o", "on"), ("of", "off")];
 
         terminal
             .handle()
@@ -475,6 +550,62 @@ mod should {
             .times(2)
             .returning(|| Ok(()));
 
+        for (prefix, value) in prefix_full_map {
+            terminal.input.clear();
+            terminal
+                .input
+                .insert_before_cursor(&[b"/bell ", prefix.as_bytes()].concat());
+
+            let _ = autocomplete
+                .handle(&mut context, &mut terminal, &mut room, &mut auth)
+                .await;
+
+            assert_eq!(terminal.input.to_string(), format!("/bell {value} "));
+        }
+    }
+
+    #[tokio::test]
+    async fn complete_poll_close() {
+        let (mut auth, mut terminal, mut room, mut context, mut autocomplete) = setup!();
+
+        terminal
+            .handle()
+            .expect_write()
+            .times(..)
+            .returning(|buf| Ok(buf.len()));
+
+        terminal
+            .handle()
+            .expect_flush()
+            .times(1)
+            .returning(|| Ok(()));
+
+        terminal.input.insert_before_cursor(b"/poll cl");
+        let _ = autocomplete
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await;
+
+        assert_eq!(terminal.input.to_string(), "/poll close ");
+    }
+
+    #[tokio::test]
+    async fn complete_timestamp_argument() {
+        let (mut auth, mut terminal, mut room, mut context, mut autocomplete) = setup!();
+
+        let prefix_full_map = vec![("ti", "time"), ("da", "datetime"), ("re", "relative")];
+
+        terminal
+            .handle()
+            .expect_write()
+            .times(..)
+            .returning(|buf| Ok(buf.len()));
+
+        terminal
+            .handle()
+            .expect_flush()
+            .times(3)
+            .returning(|| Ok(()));
+
         for (prefix, command) in prefix_full_map {
             terminal.input.clear();
             terminal
@@ -498,26 +629,28 @@ mod should {
         let mut bob = User::default();
         bob.set_username("bob".into());
 
-        let (alice_msg_tx, _) = mpsc::channel(1);
-        let (alice_exit_tx, _) = watch::channel(());
+        let (alice_msg_tx, _alice_msg_rx) = mpsc::channel(1);
+        let (alice_exit_tx, _) = watch::channel(LeaveReason::default());
         room.join(
             1,
             alice.username().clone().into(),
             PubKey::default(),
             String::default(),
+            None,
             alice_msg_tx,
             alice_exit_tx,
         )
         .await
         .unwrap();
 
-        let (bob_msg_tx, _) = mpsc::channel(1);
-        let (bob_exit_tx, _) = watch::channel(());
+        let (bob_msg_tx, _bob_msg_rx) = mpsc::channel(1);
+        let (bob_exit_tx, _) = watch::channel(LeaveReason::default());
         room.join(
             2,
             bob.username().clone().into(),
             PubKey::default(),
             String::default(),
+            None,
             bob_msg_tx,
             bob_exit_tx,
         )
@@ -552,6 +685,70 @@ mod should {
         }
     }
 
+    #[tokio::test]
+    async fn complete_unignore_from_ignored_and_unfocus_from_focused_users() {
+        let (mut auth, mut terminal, mut room, _, mut autocomplete) = setup!();
+
+        let (mut users, mut receivers) = (vec![], vec![]);
+        for (id, name) in [(1, "alice"), (2, "bob"), (3, "bobby"), (4, "carol")] {
+            let (msg_tx, msg_rx) = mpsc::channel(1);
+            receivers.push(msg_rx);
+            let (exit_tx, _) = watch::channel(LeaveReason::default());
+            let user = room
+                .join(
+                    id,
+                    name.to_string(),
+                    PubKey::default(),
+                    String::default(),
+                    None,
+                    msg_tx,
+                    exit_tx,
+                )
+                .await
+                .unwrap();
+            users.push(user);
+        }
+        let mut alice = users[0].clone();
+        alice.ignore(3);
+        alice.ignore(4);
+        alice.focus(2);
+        alice.focus(4);
+        let mut context = WorkflowContext::new(alice);
+
+        let input_full_map = vec![
+            ("/unignore bo", "/unignore bobby "),
+            ("/unignore c", "/unignore carol "),
+            ("/unignore a", "/unignore a"),
+            ("/focus ca", "/focus carol "),
+            ("/focus -ca", "/focus -carol "),
+            ("/focus -bo", "/focus -bob "),
+            ("/focus -al", "/focus -al"),
+        ];
+
+        terminal
+            .handle()
+            .expect_write()
+            .times(..)
+            .returning(|buf| Ok(buf.len()));
+
+        terminal
+            .handle()
+            .expect_flush()
+            .times(5)
+            .returning(|| Ok(()));
+
+        for (input, full) in input_full_map {
+            terminal.input.clear();
+            terminal.input.insert_before_cursor(input.as_bytes());
+
+            let _ = autocomplete
+                .handle(&mut context, &mut terminal, &mut room, &mut auth)
+                .await;
+
+            assert_eq!(terminal.input.to_string(), full);
+        }
+    }
+
     #[tokio::test]
     async fn complete_whitelist_subcommand() {
         let (mut auth, mut terminal, mut room, mut context, mut autocomplete) = setup!();
@@ -714,26 +911,28 @@ mod should {
         let mut bob = User::default();
         bob.set_username("bob".into());
 
-        let (alice_msg_tx, _) = mpsc::channel(1);
-        let (alice_exit_tx, _) = watch::channel(());
+        let (alice_msg_tx, _alice_msg_rx) = mpsc::channel(1);
+        let (alice_exit_tx, _) = watch::channel(LeaveReason::default());
         room.join(
             1,
             alice.username().clone().into(),
             PubKey::default(),
             String::default(),
+            None,
             alice_msg_tx,
             alice_exit_tx,
         )
         .await
         .unwrap();
 
-        let (bob_msg_tx, _) = mpsc::channel(1);
-        let (bob_exit_tx, _) = watch::channel(());
+        let (bob_msg_tx, _bob_msg_rx) = mpsc::channel(1);
+        let (bob_exit_tx, _) = watch::channel(LeaveReason::default());
         room.join(
             2,
             bob.username().clone().into(),
             PubKey::default(),
             String::default(),
+            None,
             bob_msg_tx,
             bob_exit_tx,
         )
@@ -779,26 +978,28 @@ mod should {
         let mut bob = User::default();
         bob.set_username("bob".into());
 
-        let (alice_msg_tx, _) = mpsc::channel(1);
-        let (alice_exit_tx, _) = watch::channel(());
+        let (alice_msg_tx, _alice_msg_rx) = mpsc::channel(1);
+        let (alice_exit_tx, _) = watch::channel(LeaveReason::default());
         room.join(
             1,
             alice.username().clone().into(),
             PubKey::default(),
             String::default(),
+            None,
             alice_msg_tx,
             alice_exit_tx,
         )
         .await
         .unwrap();
 
-        let (bob_msg_tx, _) = mpsc::channel(1);
-        let (bob_exit_tx, _) = watch::channel(());
+        let (bob_msg_tx, _bob_msg_rx) = mpsc::channel(1);
+        let (bob_exit_tx, _) = watch::channel(LeaveReason::default());
         room.join(
             2,
             bob.username().clone().into(),
             PubKey::default(),
             String::default(),
+            None,
             bob_msg_tx,
             bob_exit_tx,
         )
@@ -1,15 +1,23 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeSet;
 use std::io::Write;
+use std::net::IpAddr;
+use std::time::Duration;
+use strum::IntoEnumIterator;
 
-use crate::auth::{Auth, BanAttribute, BanQuery};
-use crate::chat::message::Message;
+use crate::auth::{Auth, BanAttribute, BanQuery, IpRange, Permission, UnbanQuery};
+use crate::chat::message::{Message, MessageBaseOps};
 use crate::chat::{
-    format_commands, message, ChatRoom, Command, OplistCommand, OplistLoadMode, Theme,
-    TimestampMode, User, UserName, UserStatus, WhitelistCommand, WhitelistLoadMode,
+    command_prefix, format_command_details, format_commands, message, parse_hex_color, ChatRoom,
+    Command, CommandProps, LeaveReason, LookalikeNameMode, MotdCommand, OplistCommand,
+    OplistLoadMode, Pin, PollCommand, PrivateDelivery, Theme, TimestampMode, User, UserName,
+    UserStatus, UserTheme, VoteKickTally, WhitelistCommand, WhitelistLoadMode,
     VISIBLE_NOOP_CHAT_COMMANDS, VISIBLE_OPLIST_COMMANDS, VISIBLE_OP_CHAT_COMMANDS,
     VISIBLE_WHITELIST_COMMANDS,
 };
-use crate::terminal::{CloseHandle, Terminal};
+use crate::pubkey::{self, PubKey};
+use crate::terminal::{layout_columns, CloseHandle, Terminal};
 use crate::utils::{self, sanitize};
 
 use super::handler::WorkflowHandler;
@@ -50,31 +58,49 @@ where
         let user = context.user.clone();
         let username = &user.username();
 
+        if !auth.is_op(user.public_key()) {
+            if let Some(left) = room.check_command_cooldown(user.id(), command.cmd()) {
+                let left = Duration::from_secs(left.as_secs() + (left.subsec_nanos() > 0) as u64);
+                let message = message::Error::new(
+                    user.into(),
+                    format!(
+                        "{} is cooling down, try again in {}",
+                        command.display_cmd(),
+                        humantime::format_duration(left)
+                    ),
+                );
+                room.send_message(message.into()).await?;
+                return Ok(());
+            }
+        }
+
         match command {
             Command::Exit => {
+                room.send_goodbye(username).await;
                 let member = room.find_member(username);
-                member.exit()?;
+                member.exit(LeaveReason::Quit)?;
             }
             Command::Away(reason) => {
                 let member = room.find_member_mut(username);
-                member.user.go_away(reason.to_string());
+                let reason = reason
+                    .clone()
+                    .or_else(|| member.user.last_away_reason().cloned())
+                    .unwrap_or_else(|| "away".to_string());
+                member.user.go_away(reason.clone());
 
-                let message = message::Emote::new(
-                    member.user.clone().into(),
-                    format!("has gone away: \"{}\"", reason),
-                );
+                let user = member.user.clone();
+                terminal.set_prompt(&room.prompt(&user));
+                let message =
+                    message::Emote::new(user.into(), format!("has gone away: \"{}\"", reason));
                 room.send_message(message.into()).await?;
             }
             Command::Back => {
                 let member = room.find_member_mut(username);
-                if let UserStatus::Away {
-                    reason: _,
-                    since: _,
-                } = &member.user.status()
-                {
+                if let UserStatus::Away { .. } = &member.user.status() {
                     member.user.return_active();
-                    let message =
-                        message::Emote::new(member.user.clone().into(), "is back".to_string());
+                    let user = member.user.clone();
+                    terminal.set_prompt(&room.prompt(&user));
+                    let message = message::Emote::new(user.into(), "is back".to_string());
                     room.send_message(message.into()).await?;
                 }
             }
@@ -93,10 +119,38 @@ where
                     break 'label;
                 }
 
-                if let Some(_) = room.try_find_member(&new_username) {
+                if auth.is_name_reserved(&new_username, user.public_key()) {
+                    let message = message::Error::new(
+                        user.into(),
+                        format!("\"{}\" name is reserved", new_username),
+                    );
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+
+                if room.is_name_taken(&new_username) {
+                    let message = message::Error::new(
+                        user.into(),
+                        format!(
+                            "\"{}\" name is already taken; try {}",
+                            new_username,
+                            room.suggest_available_name(&new_username)
+                        ),
+                    );
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+
+                let lookalike = match room.lookalike_name_mode() {
+                    Some(_) => room.find_lookalike(&new_username, Some(user.id())).cloned(),
+                    None => None,
+                };
+                if let (Some(LookalikeNameMode::Reject), Some(other)) =
+                    (room.lookalike_name_mode(), &lookalike)
+                {
                     let message = message::Error::new(
                         user.into(),
-                        format!("\"{}\" name is already taken", new_username),
+                        format!("\"{}\" name looks too much like {}", new_username, other),
                     );
                     room.send_message(message.into()).await?;
                     break 'label;
@@ -110,22 +164,51 @@ where
 
                 let old_name = user.username();
                 let user_id = user.id();
+                auth.reserve_op_name(old_name, user.public_key());
 
                 let member = room.find_member_mut(username);
                 member.user.set_username(new_username.clone());
-                terminal.set_prompt(&member.user.config().display_name());
 
                 let member = member.clone();
-                room.add_member(new_username.clone(), member);
+                terminal.set_prompt(&room.prompt(&member.user));
+                room.add_member(new_username.clone(), member.clone());
                 room.remove_member(&old_name);
                 room.add_name(user_id, new_username);
+
+                if let Some(other) = lookalike {
+                    let message = message::Announce::new(
+                        member.user.into(),
+                        format!("looks a lot like {}, beware of impersonation", other),
+                    );
+                    room.send_message(message.into()).await?;
+                }
             }
-            Command::Msg(to_username, msg) => 'label: {
+            Command::Msg(to_usernames, msg) if to_usernames.len() == 1 => 'label: {
                 let from = room.find_member(username).user.clone();
-                let to_username = UserName::from(to_username);
+                let to_username = UserName::from(&to_usernames[0]);
 
                 match room.try_find_member_mut(&to_username).map(|a| &mut a.user) {
                     None => {
+                        if let Some(queued) = queue_offline_pm(room, auth, &from, &to_username, msg)
+                        {
+                            let message: Message = match queued {
+                                Ok(()) => message::System::new(
+                                    from.into(),
+                                    format!(
+                                        "{} is offline, the PM will be delivered on their next join",
+                                        to_username
+                                    ),
+                                )
+                                .into(),
+                                Err(err) => message::Error::new(
+                                    from.into(),
+                                    format!("PM to {} not queued: {}", to_username, err),
+                                )
+                                .into(),
+                            };
+                            room.send_message(message).await?;
+                            break 'label;
+                        }
                         let message =
                             message::Error::new(from.into(), format!("user is not found"));
                         room.send_message(message.into()).await?;
@@ -148,23 +231,81 @@ where
                             to.clone().into(),
                             msg.to_string(),
                         );
-                        room.send_message(message.into()).await?;
+                        let delivery = room.send_private_message(message).await?;
+                        report_pm_delivery(room, from, &name, &status, delivery).await?;
+                    }
+                }
+            }
+            Command::Msg(to_usernames, msg) => 'label: {
+                let from = room.find_member(username).user.clone();
+                let mut delivered = vec![];
+                let mut away = vec![];
+                let mut ignoring = vec![];
+                let mut not_found = vec![];
+                let mut queued = vec![];
+                let mut has_self = false;
+
+                for to_username in to_usernames {
+                    let to_username = UserName::from(to_username);
+                    let to = match room.try_find_member_mut(&to_username).map(|m| &mut m.user) {
+                        None => {
+                            match queue_offline_pm(room, auth, &from, &to_username, msg) {
+                                Some(Ok(())) => queued.push(to_username.to_string()),
+                                _ => not_found.push(to_username.to_string()),
+                            }
+                            continue;
+                        }
+                        Some(to) if from.id().eq(&to.id()) => {
+                            has_self = true;
+                            continue;
+                        }
+                        Some(to) => to,
+                    };
+                    let status = to.status().clone();
+                    to.set_reply_to(from.id());
 
-                        match status {
-                            UserStatus::Away { reason, since: _ } => {
-                                let message = message::System::new(
-                                    from.into(),
-                                    format!(
-                                        "Sent PM to {}, but they're away now: {}",
-                                        name, reason
-                                    ),
-                                );
-                                room.send_message(message.into()).await?;
+                    let message =
+                        message::Private::new(from.clone().into(), to.clone().into(), msg.clone());
+                    match room.send_private_message(message).await? {
+                        PrivateDelivery::Muted => break 'label,
+                        PrivateDelivery::Ignored => ignoring.push(to_username.to_string()),
+                        PrivateDelivery::Delivered => {
+                            if let UserStatus::Away { reason, until, .. } = status {
+                                away.push(match away_eta(until) {
+                                    Some(eta) => format!("{} ({}, {})", to_username, reason, eta),
+                                    None => format!("{} ({})", to_username, reason),
+                                });
                             }
-                            UserStatus::Active => {}
+                            delivered.push(to_username.to_string());
                         }
                     }
                 }
+
+                let mut report = vec![];
+                if !delivered.is_empty() {
+                    report.push(format!("PM delivered to {}", delivered.join(", ")));
+                }
+                if !away.is_empty() {
+                    report.push(format!("away now: {}", away.join(", ")));
+                }
+                if !ignoring.is_empty() {
+                    report.push(format!("ignoring you: {}", ignoring.join(", ")));
+                }
+                if !queued.is_empty() {
+                    report.push(format!("queued for next join: {}", queued.join(", ")));
+                }
+                if !not_found.is_empty() {
+                    report.push(format!("not found: {}", not_found.join(", ")));
+                }
+                if has_self {
+                    report.push("you can't message yourself".to_string());
+                }
+
+                let message: Message = match delivered.is_empty() && queued.is_empty() {
+                    true => message::Error::new(from.into(), report.join("; ")).into(),
+                    false => message::System::new(from.into(), report.join("; ")).into(),
+                };
+                room.send_message(message).await?;
             }
             Command::Reply(message_body) => 'label: {
                 let member = room.find_member(username);
@@ -188,9 +329,13 @@ where
 
                 let member = room.find_member(target_name.unwrap());
                 let to = member.user.clone();
-                let message =
-                    message::Private::new(from.into(), to.into(), (*message_body).to_string());
-                room.send_message(message.into()).await?;
+                let message = message::Private::new(
+                    from.clone().into(),
+                    to.clone().into(),
+                    (*message_body).to_string(),
+                );
+                let delivery = room.send_private_message(message).await?;
+                report_pm_delivery(room, from, to.username(), to.status(), delivery).await?;
             }
             Command::Users => {
                 let member = room.find_member(username);
@@ -199,21 +344,199 @@ where
                 let mut usernames = room.names().values().collect::<Vec<&UserName>>();
                 usernames.sort_by_key(|a| a.to_lowercase());
 
+                let theme = user.config().theme();
                 let colorized_names = usernames
                     .iter()
-                    .map(|u| user.config().theme().style_username(u).to_string())
+                    .map(|u| match room.find_member(u).user.is_newcomer() {
+                        true => format!(
+                            "{} {}",
+                            theme.style_username(u),
+                            theme.style_newcomer_marker()
+                        ),
+                        false => theme.style_username(u).to_string(),
+                    })
                     .collect::<Vec<String>>();
 
                 let body = format!(
-                    "{} connected: {}",
+                    "{} connected:{}",
                     room.names().len(),
-                    colorized_names.join(", ")
+                    format_columns(&colorized_names, terminal.width())
                 );
 
                 let message = message::System::new(user.into(), body);
                 room.send_message(message.into()).await?;
             }
-            Command::Whois(target_username) => {
+            Command::Ops => {
+                let member = room.find_member(username);
+                let user = member.user.clone();
+
+                let mut usernames = room
+                    .members_iter()
+                    .map(|(_, m)| &m.user)
+                    .filter(|u| auth.is_op(u.public_key()))
+                    .map(|u| u.username())
+                    .collect::<Vec<&UserName>>();
+                usernames.sort_by_key(|a| a.to_lowercase());
+
+                let body = match usernames.is_empty() {
+                    true => "no operators are connected".to_string(),
+                    false => format!(
+                        "{} operators connected: {}",
+                        usernames.len(),
+                        usernames
+                            .iter()
+                            .map(|u| user.config().theme().style_username(u).to_string())
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    ),
+                };
+
+                let message = message::System::new(user.into(), body);
+                room.send_message(message.into()).await?;
+            }
+            Command::Afk => {
+                let member = room.find_member(username);
+                let user = member.user.clone();
+
+                let away = room.away_members();
+                let body = match away.is_empty() {
+                    true => "nobody is away".to_string(),
+                    false => {
+                        let lines = away
+                            .iter()
+                            .map(|(name, reason, duration)| {
+                                format!(
+                                    "{}: {} ({} ago)",
+                                    user.config().theme().style_username(name),
+                                    reason,
+                                    humantime::format_duration(*duration)
+                                )
+                            })
+                            .collect::<Vec<String>>();
+                        format!(
+                            "{} away:{}{}",
+                            away.len(),
+                            utils::NEWLINE,
+                            lines.join(utils::NEWLINE)
+                        )
+                    }
+                };
+
+                let message = message::System::new(user.into(), body);
+                room.send_message(message.into()).await?;
+            }
+            Command::Whois(None) => {
+                let user = room.find_member(username).user.clone();
+                let yes_no = |value: bool| if value { "yes" } else { "no" };
+                let key = user.public_key().clone();
+                let info = format!(
+                    "{}{nl} > theme: {}{nl} > timestamp: {}{nl} > trusted: {}{nl} > operator: {}",
+                    user,
+                    user.config().theme().theme(),
+                    user.config().timestamp_mode(),
+                    yes_no(auth.is_trusted(&key)),
+                    match auth.op_permissions(&key).filter(|_| auth.is_op(&key)) {
+                        Some(permissions) => Permission::format_list(permissions),
+                        None => yes_no(auth.is_op(&key)).to_string(),
+                    },
+                    nl = utils::NEWLINE,
+                );
+                let message = message::System::new(user.into(), info);
+                room.send_message(message.into()).await?;
+            }
+            Command::Paste => {
+                context.paste = Some(Default::default());
+                let message = message::System::new(
+                    user.into(),
+                    format!(
+                        "Paste mode is ON. Send the lines, then \".\" on its own line to post them or {}cancel to discard them",
+                        command_prefix()
+                    ),
+                );
+                room.send_message(message.into()).await?;
+            }
+            Command::Remind(delay, text) => 'label: {
+                let delay = match humantime::parse_duration(delay) {
+                    Ok(delay) => delay,
+                    Err(err) => {
+                        let message = message::Error::new(
+                            user.into(),
+                            format!("invalid reminder duration: {}", err),
+                        );
+                        room.send_message(message.into()).await?;
+                        break 'label;
+                    }
+                };
+
+                let reminder =
+                    message::System::new(user.clone().into(), format!("Reminder: {}", text));
+                let message: Message = match room
+                    .find_member(username)
+                    .schedule_reminder(delay, reminder.into())
+                {
+                    true => message::System::new(
+                        user.into(),
+                        format!(
+                            "You will be reminded in {}",
+                            humantime::format_duration(delay)
+                        ),
+                    )
+                    .into(),
+                    false => message::Error::new(
+                        user.into(),
+                        "you have too many pending reminders".to_string(),
+                    )
+                    .into(),
+                };
+                room.send_message(message).await?;
+            }
+            Command::Settings => {
+                let user = room.find_member(username).user.clone();
+                let cfg = user.config();
+                let on_off = |on: bool| if on { "on" } else { "off" };
+                let settings = format!(
+                    "theme: {}{nl} > timestamp: {}{nl} > quiet: {}{nl} > bell: {}{nl} > emoji: {}{nl} > read receipts: {}{nl} > prompt: {}{nl} > highlight: {}{nl} > ignored: {}{nl} > focused: {}{nl} > hidden: {}",
+                    cfg.theme().theme(),
+                    cfg.timestamp_mode(),
+                    on_off(cfg.quiet()),
+                    on_off(cfg.bell()),
+                    on_off(cfg.emoji()),
+                    on_off(cfg.read_receipts()),
+                    cfg.prompt_template()
+                        .map_or("default".to_string(), |template| template.to_string()),
+                    cfg.highlight().map_or("none", |highlight| highlight.as_str()),
+                    user.ignored().len(),
+                    cfg.focused().len(),
+                    cfg.excluded().len(),
+                    nl = utils::NEWLINE,
+                );
+                let message = message::System::new(user.into(), settings);
+                room.send_message(message.into()).await?;
+            }
+            Command::Stats => {
+                let member = room.find_member(username);
+                let user = member.user.clone();
+                let others = room.members_iter().map(|(_, member)| &member.user);
+                let ignored_by = others
+                    .clone()
+                    .filter(|other| other.ignored().contains(&user.id()))
+                    .count();
+                let focused_by = others
+                    .filter(|other| other.focused().contains(&user.id()))
+                    .count();
+                let stats = format!(
+                    "messages sent: {}{nl} > connected: {}{nl} > idle: {}{nl} > ignored by: {}{nl} > focused by: {}",
+                    member.sent_count(),
+                    humantime::format_duration(user.joined_duration()),
+                    humantime::format_duration(member.idle_duration()),
+                    ignored_by,
+                    focused_by,
+                    nl = utils::NEWLINE,
+                );
+                let message = message::System::new(user.into(), stats);
+                room.send_message(message.into()).await?;
+            }
+            Command::Whois(Some(target_username)) => {
                 let member = room.find_member(username);
                 let user = member.user.clone();
                 let target_username = UserName::from(target_username);
@@ -221,7 +544,17 @@ where
                     .try_find_member(&target_username)
                     .map(|member| &member.user)
                 {
-                    Some(target) => message::System::new(user.into(), target.to_string()).into(),
+                    Some(target) => {
+                        let mut info = target.to_string();
+                        if auth.has_permission(user.public_key(), Permission::Inspect) {
+                            let addr = match target.peer_addr() {
+                                Some(addr) => addr.to_string(),
+                                None => "local".to_string(),
+                            };
+                            info.push_str(&format!("{} > address: {}", utils::NEWLINE, addr));
+                        }
+                        message::System::new(user.into(), info).into()
+                    }
                     None => message::Error::new(user.into(), "user not found".to_string()).into(),
                 };
                 room.send_message(message).await?;
@@ -245,8 +578,9 @@ where
                     .map(|member| &member.user);
 
                 let message = if let Some(t) = target {
-                    message::Emote::new(
+                    message::Emote::new_targeted(
                         user.into(),
+                        t.id(),
                         format!("hits {} with a squishy banana.", t.username()),
                     )
                     .into()
@@ -265,19 +599,97 @@ where
                 let message = message::Emote::new(user.into(), "¯\\_(◕‿◕)_/¯".to_string());
                 room.send_message(message.into()).await?;
             }
-            Command::Me(action) => {
+            Command::Me(action) => 'label: {
                 let member = room.find_member(username);
                 let user = member.user.clone();
+                // Line breaks would let the action fake lines from others
+                let action = action
+                    .as_deref()
+                    .map(sanitize::single_line)
+                    .filter(|action| !action.is_empty());
+                if let Some(action) = &action {
+                    if !auth.is_op(user.public_key()) && room.check_blocklist(&user, action).await?
+                    {
+                        break 'label;
+                    }
+                }
                 let message = message::Emote::new(
                     user.into(),
                     match action {
-                        Some(s) => format!("{}", s),
+                        Some(s) => s,
                         None => format!("is at a loss for words."),
                     },
                 );
                 room.send_message(message.into()).await?;
             }
-            Command::Help => {
+            Command::Quote(quoted_username, text) => 'label: {
+                let member = room.find_member(username);
+                let user = member.user.clone();
+                let quote = match room.last_public_message(quoted_username) {
+                    Some(quoted) => message::Quote::new(
+                        quoted.from().username().to_string(),
+                        sanitize::single_line(quoted.message_body()),
+                    ),
+                    None => {
+                        let message = message::Error::new(
+                            user.into(),
+                            format!("{} has no recent message to quote", quoted_username),
+                        );
+                        room.send_message(message.into()).await?;
+                        break 'label;
+                    }
+                };
+                if !auth.is_op(user.public_key()) && room.check_public_message(&user, text).await? {
+                    break 'label;
+                }
+                let member = room.find_member_mut(username);
+                member.update_last_sent_time(Utc::now());
+                member.count_sent_message();
+                let message = message::Public::new_quoting(user.into(), quote, text.to_string());
+                room.send_message(message.into()).await?;
+            }
+            Command::Help(Some(name)) => {
+                let member = room.find_member(username);
+                let user = member.user.clone();
+
+                let prefix = command_prefix();
+                let name = name.strip_prefix(prefix).unwrap_or(name);
+                let is_op = auth.is_op(user.public_key());
+                let command = VISIBLE_NOOP_CHAT_COMMANDS
+                    .iter()
+                    .chain(VISIBLE_OP_CHAT_COMMANDS.iter().filter(|_| is_op))
+                    .find(|cmd| cmd.cmd() == name);
+
+                let message: Message = match command {
+                    None => message::Error::new(
+                        user.into(),
+                        format!("no such command: {}{}", prefix, name),
+                    )
+                    .into(),
+                    Some(command) => {
+                        let mut help = format_command_details(command);
+                        let subcommands = match command {
+                            Command::Whitelist(_) => {
+                                Some(format_commands(&VISIBLE_WHITELIST_COMMANDS))
+                            }
+                            Command::Oplist(_) => Some(format_commands(&VISIBLE_OPLIST_COMMANDS)),
+                            _ => None,
+                        };
+                        if let Some(subcommands) = subcommands {
+                            help.push_str(&format!(
+                                "{}{}Subcommands: {}{}",
+                                utils::NEWLINE,
+                                utils::NEWLINE,
+                                utils::NEWLINE,
+                                subcommands
+                            ));
+                        }
+                        message::System::new(user.into(), help).into()
+                    }
+                };
+                room.send_message(message).await?;
+            }
+            Command::Help(None) => {
                 let member = room.find_member(username);
                 let user = member.user.clone();
 
@@ -310,6 +722,118 @@ where
                 );
                 room.send_message(message.into()).await?;
             }
+            Command::Emoji => {
+                let member = room.find_member_mut(username);
+                member.user.config_mut().switch_emoji_mode();
+                let message = message::System::new(
+                    member.user.clone().into(),
+                    match member.user.config().emoji() {
+                        true => "Emoji expansion is toggled ON",
+                        false => "Emoji expansion is toggled OFF",
+                    }
+                    .to_string(),
+                );
+                room.send_message(message.into()).await?;
+            }
+            Command::Bell(bell) => {
+                let member = room.find_member_mut(username);
+                member.user.config_mut().set_bell(*bell);
+                let message = message::System::new(
+                    member.user.clone().into(),
+                    match bell {
+                        true => "Bell is ON",
+                        false => "Bell is OFF",
+                    }
+                    .to_string(),
+                );
+                room.send_message(message.into()).await?;
+            }
+            Command::Receipts => {
+                let member = room.find_member_mut(username);
+                member.user.config_mut().switch_read_receipts();
+                let message = message::System::new(
+                    member.user.clone().into(),
+                    match member.user.config().read_receipts() {
+                        true => "Read receipts are toggled ON",
+                        false => "Read receipts are toggled OFF",
+                    }
+                    .to_string(),
+                );
+                room.send_message(message.into()).await?;
+            }
+            Command::Prompt(template) => {
+                let member = room.find_member_mut(username);
+                member
+                    .user
+                    .config_mut()
+                    .set_prompt_template(template.clone());
+                let user = member.user.clone();
+                terminal.set_prompt(&room.prompt(&user));
+                let message = message::System::new(
+                    user.into(),
+                    match template {
+                        Some(template) => format!("Prompt set to: {}", template),
+                        None => "Prompt is reset to the default".to_string(),
+                    },
+                );
+                room.send_message(message.into()).await?;
+            }
+            Command::Redraw => {
+                terminal.clear_screen()?;
+                room.feed_history(username).await;
+            }
+            Command::Sessions(None) => {
+                let sessions = room.sessions_of_key(&user.public_key().fingerprint());
+                let body = format_sessions("Your sessions:", &sessions, Some(user.id()));
+                let message = message::System::new(user.into(), body);
+                room.send_message(message.into()).await?;
+            }
+            Command::Sessions(Some(number)) => 'label: {
+                let sessions = room.sessions_of_key(&user.public_key().fingerprint());
+                let target = match number.checked_sub(1).and_then(|n| sessions.get(n)) {
+                    Some(target) => (*target).clone(),
+                    None => {
+                        let message =
+                            message::Error::new(user.into(), format!("no session {}", number));
+                        room.send_message(message.into()).await?;
+                        break 'label;
+                    }
+                };
+                if target.id() == user.id() {
+                    let message = message::Error::new(
+                        user.into(),
+                        format!(
+                            "that is this session, use {} to close it",
+                            Command::Exit.display_cmd()
+                        ),
+                    );
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+
+                let member = room.find_member(target.username());
+                let message = message::System::new(
+                    target.clone().into(),
+                    "This session was closed from another session".to_string(),
+                );
+                member.send_message(message.into()).await?;
+                member.exit(LeaveReason::Quit)?;
+                let message = message::System::new(
+                    user.into(),
+                    format!("Closed session {} of {}", number, target.username()),
+                );
+                room.send_message(message.into()).await?;
+            }
+            Command::Pinned => {
+                let message = message::System::new(
+                    user.into(),
+                    match room.pins().is_empty() {
+                        true => "no pinned messages".to_string(),
+                        false => room.pins().render(),
+                    },
+                );
+                room.send_message(message.into()).await?;
+            }
             Command::Timestamp(mode) => {
                 let member = room.find_member_mut(username);
                 member.user.config_mut().set_timestamp_mode(*mode);
@@ -319,20 +843,25 @@ where
                         TimestampMode::Time | TimestampMode::DateTime => {
                             "Timestamp is toggled ON, timezone is UTC"
                         }
+                        TimestampMode::Relative => {
+                            "Timestamp is toggled ON, relative to when you joined"
+                        }
                         TimestampMode::Off => "Timestamp is toggled OFF",
                     }
                     .to_string(),
                 );
                 room.send_message(message.into()).await?;
             }
-            Command::Theme(theme) => {
+            Command::Theme(choice) => {
                 let member = room.find_member_mut(username);
+                let theme = choice.resolve(member.user.config().theme().theme());
                 let message = message::System::new(user.into(), format!("Set theme: {}", theme));
-                member.user.set_theme((*theme).into());
-                terminal.set_prompt(&member.user.config().display_name());
+                member.user.set_theme(theme.into());
+                let user = member.user.clone();
+                terminal.set_prompt(&room.prompt(&user));
                 room.send_message(message.into()).await?;
             }
-            Command::Themes => {
+            Command::Themes(false) => {
                 let member = room.find_member(username);
                 let user = member.user.clone();
                 let message = message::System::new(
@@ -341,6 +870,25 @@ where
                 );
                 room.send_message(message.into()).await?;
             }
+            Command::Themes(true) => {
+                let name = user.username().to_string();
+                let nl = utils::NEWLINE;
+                let previews = Theme::iter()
+                    .map(|theme| {
+                        let user_theme: UserTheme = theme.into();
+                        let lines = user_theme.preview(&name);
+                        format!("{}:{nl}   {}", theme, lines.join(&format!("{nl}   ")))
+                    })
+                    .collect::<Vec<String>>();
+                let message = message::System::new(
+                    user.into(),
+                    format!(
+                        "Theme previews:{nl} > {}",
+                        previews.join(&format!("{nl} > "))
+                    ),
+                );
+                room.send_message(message.into()).await?;
+            }
             Command::Ignore(target) => 'label: {
                 let member = room.find_member(username);
                 let user = member.user.clone();
@@ -356,9 +904,9 @@ where
                     let message_text = match ignored_usernames.is_empty() {
                         true => "0 users ignored".to_string(),
                         false => format!(
-                            "{} users ignored: {}",
+                            "{} users ignored:{}",
                             ignored_usernames.len(),
-                            ignored_usernames.join(", ")
+                            format_columns(&ignored_usernames, terminal.width())
                         ),
                     };
 
@@ -442,20 +990,34 @@ where
                 let user = member.user.clone();
 
                 if target.is_none() {
-                    let focused_usernames: Vec<String> = user
-                        .focused()
-                        .iter()
-                        .filter_map(|id| room.try_get_name(id))
-                        .map(|name| user.config().theme().style_username(name).to_string())
-                        .collect();
+                    let style_names = |ids: &BTreeSet<usize>| -> Vec<String> {
+                        ids.iter()
+                            .filter_map(|id| room.try_get_name(id))
+                            .map(|name| user.config().theme().style_username(name).to_string())
+                            .collect()
+                    };
+                    let focused_usernames = style_names(user.focused());
+                    let excluded_usernames = style_names(user.excluded());
 
-                    let message_text = match focused_usernames.is_empty() {
-                        true => "Focusing no users".to_string(),
-                        false => format!(
-                            "Focusing on {} users: {}",
+                    let mut lines = vec![];
+                    if !focused_usernames.is_empty() {
+                        lines.push(format!(
+                            "Focusing on {} users:{}",
                             focused_usernames.len(),
-                            focused_usernames.join(", ")
-                        ),
+                            format_columns(&focused_usernames, terminal.width())
+                        ));
+                    }
+                    if !excluded_usernames.is_empty() {
+                        lines.push(format!(
+                            "Excluding {} users:{}",
+                            excluded_usernames.len(),
+                            format_columns(&excluded_usernames, terminal.width())
+                        ));
+                    }
+
+                    let message_text = match lines.is_empty() {
+                        true => "Focusing no users".to_string(),
+                        false => lines.join(utils::NEWLINE),
                     };
 
                     let message = message::System::new(user.into(), message_text);
@@ -475,14 +1037,25 @@ where
                 }
 
                 let mut focused = vec![];
+                let mut excluded = vec![];
                 for target_username in target.split(",") {
-                    let target_username = UserName::from(target_username);
+                    let (target_username, exclude) = match target_username.strip_prefix('-') {
+                        Some(name) => (UserName::from(name), true),
+                        None => (UserName::from(target_username), false),
+                    };
                     match room
                         .try_find_member(&target_username)
                         .map(|a| a.user.id().clone())
                     {
                         None => continue,
                         Some(target_id) if target_id == user.id() => continue,
+                        Some(target_id) if exclude => {
+                            if user.excluded().contains(&target_id) {
+                                continue;
+                            }
+                            room.find_member_mut(username).user.exclude(target_id);
+                            excluded.push(target_username);
+                        }
                         Some(target_id) if user.focused().contains(&target_id) => continue,
                         Some(target_id) => {
                             room.find_member_mut(username).user.focus(target_id);
@@ -491,36 +1064,181 @@ where
                     }
                 }
 
-                let focused_usernames: Vec<String> = focused
-                    .iter()
-                    .map(|name| user.config().theme().style_username(name).to_string())
-                    .collect();
-
-                let message_text = match focused_usernames.is_empty() {
-                    true => "No online users found to focus".to_string(),
-                    false => format!(
+                let style_names = |names: &[UserName]| -> Vec<String> {
+                    names
+                        .iter()
+                        .map(|name| user.config().theme().style_username(name).to_string())
+                        .collect()
+                };
+                let focused_usernames = style_names(&focused);
+                let excluded_usernames = style_names(&excluded);
+
+                let mut lines = vec![];
+                if !focused_usernames.is_empty() {
+                    lines.push(format!(
                         "Focusing on {} users: {}",
                         focused_usernames.len(),
                         focused_usernames.join(", ")
-                    ),
+                    ));
+                }
+                if !excluded_usernames.is_empty() {
+                    lines.push(format!(
+                        "Excluding {} users: {}",
+                        excluded_usernames.len(),
+                        excluded_usernames.join(", ")
+                    ));
+                }
+
+                let message_text = match lines.is_empty() {
+                    true => "No online users found to focus".to_string(),
+                    false => lines.join(utils::NEWLINE),
                 };
 
                 let message = message::System::new(user.into(), message_text);
                 room.send_message(message.into()).await?;
             }
             Command::Version => {
-                let message =
-                    message::System::new(user.into(), format!("{}", env!("CARGO_PKG_VERSION")));
+                let message = message::System::new(
+                    user.into(),
+                    format!(
+                        "chatd {} ({}), russh {}, SSH-2.0",
+                        env!("CARGO_PKG_VERSION"),
+                        env!("GIT_HASH"),
+                        env!("RUSSH_VERSION")
+                    ),
+                );
                 room.send_message(message.into()).await?;
             }
             Command::Uptime => {
-                let message = message::System::new(user.into(), room.uptime());
+                let message = message::System::new(
+                    user.into(),
+                    format!(
+                        "up {}, {} users (peak {})",
+                        room.uptime(),
+                        room.names().len(),
+                        room.peak_members()
+                    ),
+                );
+                room.send_message(message.into()).await?;
+            }
+            Command::Ping => {
+                let elapsed = context.created_at.elapsed();
+                let message = message::System::new(
+                    user.into(),
+                    format!(
+                        "pong: processed in {:.2?}, {} connected",
+                        elapsed,
+                        room.names().len()
+                    ),
+                );
+                room.send_message(message.into()).await?;
+            }
+            Command::MuteAll | Command::UnmuteAll => 'label: {
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Mute) {
+                    let message = message::Error::new(user.into(), err);
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+
+                let muted = *command == Command::MuteAll;
+                let count = room.switch_mute_all(muted, |member| auth.is_op(member.public_key()));
+                match muted {
+                    true => auth.audit(&user, "mute-all", ""),
+                    false => auth.audit(&user, "unmute-all", ""),
+                }
+                let message = message::Announce::new(
+                    user.into(),
+                    match muted {
+                        true => format!("muted everyone but the operators ({} users)", count),
+                        false => format!("unmuted everyone ({} users)", count),
+                    },
+                );
+                room.send_message(message.into()).await?;
+            }
+            Command::WhoIp(ip) => 'label: {
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Inspect)
+                {
+                    let message = message::Error::new(user.into(), err);
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+
+                let ip = match ip.parse::<IpAddr>() {
+                    Ok(ip) => ip,
+                    Err(_) => {
+                        let message =
+                            message::Error::new(user.into(), "invalid IP address".to_string());
+                        room.send_message(message.into()).await?;
+                        break 'label;
+                    }
+                };
+                let names = room.members_from_ip(&ip);
+                let body = match names.is_empty() {
+                    true => format!("no users connected from {}", ip),
+                    false => format!(
+                        "{} connected from {}: {}",
+                        names.len(),
+                        ip,
+                        names
+                            .iter()
+                            .map(|name| user.config().theme().style_username(name).to_string())
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    ),
+                };
+                let message = message::System::new(user.into(), body);
+                room.send_message(message.into()).await?;
+            }
+            Command::SessionsOf(target_username) => 'label: {
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Inspect)
+                {
+                    let message = message::Error::new(user.into(), err);
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+
+                let target_username = UserName::from(target_username);
+                let fingerprint = match room.try_find_member(&target_username) {
+                    Some(member) => member.user.public_key().fingerprint(),
+                    None => {
+                        let message =
+                            message::Error::new(user.into(), "user not found".to_string());
+                        room.send_message(message.into()).await?;
+                        break 'label;
+                    }
+                };
+                let sessions = room.sessions_of_key(&fingerprint);
+                let header = format!("Sessions of {}:", fingerprint);
+                let body = format_sessions(&header, &sessions, None);
+                let message = message::System::new(user.into(), body);
                 room.send_message(message.into()).await?;
             }
             Command::Mute(target_username) => 'label: {
-                if !auth.is_op(&user.public_key().clone().into()) {
-                    let message =
-                        message::Error::new(user.into(), "must be an operator".to_string());
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Mute) {
+                    let message = message::Error::new(user.into(), err);
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+
+                if let Some(fingerprint) = pubkey::parse_fingerprint(target_username) {
+                    if user.public_key().fingerprint() == fingerprint {
+                        let message =
+                            message::Error::new(user.into(), "you can't mute yourself".to_string());
+                        room.send_message(message.into()).await?;
+                        break 'label;
+                    }
+                    let is_muted = room.switch_key_mute(fingerprint);
+                    match is_muted {
+                        true => auth.audit(&user, "mute", fingerprint),
+                        false => auth.audit(&user, "unmute", fingerprint),
+                    }
+                    let message = message::System::new(
+                        user.into(),
+                        match is_muted {
+                            true => format!("Muted key: {}", fingerprint),
+                            false => format!("Unmuted key: {}", fingerprint),
+                        },
+                    );
                     room.send_message(message.into()).await?;
                     break 'label;
                 }
@@ -545,6 +1263,10 @@ where
                     Some(target) => {
                         target.switch_mute_mode();
                         let target = target.clone();
+                        match target.is_muted() {
+                            true => auth.audit(&user, "mute", target.username()),
+                            false => auth.audit(&user, "unmute", target.username()),
+                        }
                         let message = message::System::new(
                             user.into(),
                             format!(
@@ -561,38 +1283,147 @@ where
                     }
                 }
             }
-            Command::Motd(new_motd) => 'label: {
-                if new_motd.is_none() {
-                    let message = message::System::new(user.into(), room.motd().clone());
+            Command::Motd(motd_command) => 'label: {
+                if *motd_command == MotdCommand::View {
+                    let motd = user.config().theme().render_markup(room.motd());
+                    let message = message::System::new(user.into(), motd);
                     room.send_message(message.into()).await?;
                     break 'label;
                 }
 
-                if !auth.is_op(&user.public_key().clone().into()) {
-                    let message = message::Error::new(
-                        user.into(),
-                        "must be an operator to modify the MOTD".to_string(),
-                    );
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Room) {
+                    let message =
+                        message::Error::new(user.into(), format!("{} to modify the MOTD", err));
                     room.send_message(message.into()).await?;
                     break 'label;
                 }
 
-                room.set_motd(new_motd.as_deref().unwrap().to_string());
+                let action = match motd_command {
+                    MotdCommand::View => unreachable!(),
+                    MotdCommand::Reload => match room.reload_motd() {
+                        None => {
+                            let message = message::Error::new(
+                                user.into(),
+                                "no MOTD file in the server configuration, nothing to reload"
+                                    .to_string(),
+                            );
+                            room.send_message(message.into()).await?;
+                            break 'label;
+                        }
+                        Some(Err(err)) => {
+                            let message = message::Error::new(
+                                user.into(),
+                                format!("failed to reload the MOTD: {}", err),
+                            );
+                            room.send_message(message.into()).await?;
+                            break 'label;
+                        }
+                        Some(Ok(_)) => {
+                            auth.audit(&user, "motd-reload", "");
+                            "reloaded"
+                        }
+                    },
+                    MotdCommand::Set(motd) => {
+                        if let Err(err) = room.set_motd(motd) {
+                            let message = message::Error::new(user.into(), err);
+                            room.send_message(message.into()).await?;
+                            break 'label;
+                        }
+                        auth.audit(&user, "motd-set", motd);
+                        "set new"
+                    }
+                    MotdCommand::On | MotdCommand::Off => {
+                        let enabled = *motd_command == MotdCommand::On;
+                        room.set_motd_enabled(enabled);
+                        match enabled {
+                            true => auth.audit(&user, "motd-on", ""),
+                            false => auth.audit(&user, "motd-off", ""),
+                        }
+                        let message = message::System::new(
+                            user.into(),
+                            format!(
+                                "Message of the day on join is {}",
+                                if enabled { "on" } else { "off" }
+                            ),
+                        );
+                        room.send_message(message.into()).await?;
+                        break 'label;
+                    }
+                    MotdCommand::Append(line) => {
+                        if let Err(err) = room.append_motd(line) {
+                            let message = message::Error::new(user.into(), err);
+                            room.send_message(message.into()).await?;
+                            break 'label;
+                        }
+                        auth.audit(&user, "motd-append", line);
+                        "appended a line to the"
+                    }
+                    MotdCommand::Prepend(line) => {
+                        if let Err(err) = room.prepend_motd(line) {
+                            let message = message::Error::new(user.into(), err);
+                            room.send_message(message.into()).await?;
+                            break 'label;
+                        }
+                        auth.audit(&user, "motd-prepend", line);
+                        "prepended a line to the"
+                    }
+                };
 
                 let message = message::Announce::new(
                     user.into(),
                     format!(
-                        "set new message of the day: {}-> {}",
+                        "{} message of the day: {}-> {}",
+                        action,
                         utils::NEWLINE,
                         room.motd()
                     ),
                 );
                 room.send_message(message.into()).await?;
             }
-            Command::Kick(target_username) => 'label: {
-                if !auth.is_op(&user.public_key().clone().into()) {
-                    let message =
-                        message::Error::new(user.into(), "must be an operator".to_string());
+            Command::Shutdown(delay) => 'label: {
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Shutdown)
+                {
+                    let message = message::Error::new(user.into(), err);
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+
+                if delay == "cancel" {
+                    let message: Message = match room.cancel_shutdown() {
+                        true => {
+                            auth.audit(&user, "shutdown-cancel", "");
+                            message::Announce::new(
+                                user.into(),
+                                "cancelled the server shutdown".to_string(),
+                            )
+                            .into()
+                        }
+                        false => {
+                            message::Error::new(user.into(), "no shutdown is scheduled".to_string())
+                                .into()
+                        }
+                    };
+                    room.send_message(message).await?;
+                    break 'label;
+                }
+
+                match humantime::parse_duration(delay) {
+                    Ok(duration) => {
+                        room.schedule_shutdown(duration, &user);
+                        auth.audit(&user, "shutdown", delay);
+                    }
+                    Err(err) => {
+                        let message = message::Error::new(
+                            user.into(),
+                            format!("invalid shutdown delay: {}", err),
+                        );
+                        room.send_message(message.into()).await?;
+                    }
+                }
+            }
+            Command::Kick(target_username, reason) => 'label: {
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Kick) {
+                    let message = message::Error::new(user.into(), err);
                     room.send_message(message.into()).await?;
                     break 'label;
                 }
@@ -606,138 +1437,588 @@ where
                         break 'label;
                     }
                     Some(member) => {
-                        let message = message::Announce::new(
+                        let farewell = match reason {
+                            Some(reason) => {
+                                member.set_leave_note(reason.clone());
+                                format!("You have been kicked from the server: {}", reason)
+                            }
+                            None => "You have been kicked from the server".to_string(),
+                        };
+                        let message = message::System::new(member.user.clone().into(), farewell);
+                        member.send_message(message.into()).await?;
+                        room.send_goodbye(&target_username).await;
+                        room.find_member(&target_username)
+                            .exit(LeaveReason::Kicked(Some(user.username().clone())))?;
+                        auth.audit(&user, "kick", &target_username);
+                    }
+                }
+            }
+            Command::Summon(target_username) => 'label: {
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Kick) {
+                    let message = message::Error::new(user.into(), err);
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+
+                let target_username = UserName::from(target_username);
+                let member = match room.try_find_member_mut(&target_username) {
+                    Some(member) if member.user.id() == user.id() => {
+                        let message = message::Error::new(
                             user.into(),
-                            format!("kicked {} from the server", target_username),
+                            "you can't summon yourself".to_string(),
                         );
-                        member.exit()?;
                         room.send_message(message.into()).await?;
+                        break 'label;
                     }
-                }
-            }
-            Command::Ban(query) => 'label: {
-                if !auth.is_op(&user.public_key().clone().into()) {
-                    let message =
-                        message::Error::new(user.into(), "must be an operator".to_string());
+                    Some(member) => member,
+                    None => {
+                        let message =
+                            message::Error::new(user.into(), "user not found".to_string());
+                        room.send_message(message.into()).await?;
+                        break 'label;
+                    }
+                };
+                if let Some(left) = member.register_summon() {
+                    let left = Duration::from_secs(left.as_secs() + 1);
+                    let message = message::Error::new(
+                        user.into(),
+                        format!(
+                            "{} was summoned recently, try again in {}",
+                            target_username,
+                            humantime::format_duration(left)
+                        ),
+                    );
                     room.send_message(message.into()).await?;
                     break 'label;
                 }
 
-                let query = query.parse::<BanQuery>();
-                if let Err(err) = query {
-                    let message = message::Error::new(user.into(), err.to_string());
+                let banner = format!("*** {} is summoning you ***", user.username());
+                let bells = match member.user.config().bell() {
+                    true => utils::BEL.to_string().repeat(3),
+                    false => String::new(),
+                };
+                let alert = format!(
+                    "{}{}",
+                    bells,
+                    member.user.config().theme().style_tagged_username(&banner)
+                );
+                let message = message::System::new(member.user.clone().into(), alert);
+                member.send_message(message.into()).await?;
+                auth.audit(&user, "summon", &target_username);
+                let message =
+                    message::System::new(user.into(), format!("Summoned {}", target_username));
+                room.send_message(message.into()).await?;
+            }
+            Command::Votekick(target_username) => 'label: {
+                room.announce_expired_vote_kicks().await?;
+                let target_username = UserName::from(target_username);
+                let target = match room.try_find_member(&target_username) {
+                    Some(member) => member.user.clone(),
+                    None => {
+                        let message =
+                            message::Error::new(user.into(), "user not found".to_string());
+                        room.send_message(message.into()).await?;
+                        break 'label;
+                    }
+                };
+                if auth.is_op(target.public_key()) {
+                    let message = message::Error::new(
+                        user.into(),
+                        "operators can't be vote kicked".to_string(),
+                    );
                     room.send_message(message.into()).await?;
                     break 'label;
                 }
 
-                let mut messages: Vec<Message> = vec![];
+                let tally = match room.vote_kick(user.id(), target.id()) {
+                    Ok(tally) => tally,
+                    Err(err) => {
+                        let message = message::Error::new(user.into(), err);
+                        room.send_message(message.into()).await?;
+                        break 'label;
+                    }
+                };
+                let text = match tally {
+                    VoteKickTally::Started { votes, needed } => format!(
+                        "started a vote to kick {} ({}/{} votes within {})",
+                        target_username,
+                        votes,
+                        needed,
+                        humantime::format_duration(room.votekick_window().unwrap_or_default())
+                    ),
+                    VoteKickTally::Counted { votes, needed } => {
+                        format!("voted to kick {} ({}/{})", target_username, votes, needed)
+                    }
+                    VoteKickTally::Passed { votes, needed } => format!(
+                        "voted to kick {} ({}/{}), the vote passed",
+                        target_username, votes, needed
+                    ),
+                };
+                let message = message::Announce::new(user.clone().into(), text);
+                room.send_message(message.into()).await?;
 
-                match query.unwrap() {
-                    BanQuery::Single { name, duration } => {
-                        let target_username = UserName::from(&name);
-                        match room.try_find_member(&target_username) {
-                            Some(member) => {
-                                auth.ban_fingerprint(
-                                    &member.user.public_key().fingerprint(),
-                                    duration,
-                                );
-                                let message = message::Announce::new(
-                                    user.clone().into(),
-                                    format!("banned {} from the server", member.user.username()),
-                                );
-                                member.exit()?;
-                                messages.push(message.into());
+                if let VoteKickTally::Passed { .. } = tally {
+                    let member = room.find_member_mut(&target_username);
+                    member.set_leave_note("by a vote".to_string());
+                    let message = message::System::new(
+                        member.user.clone().into(),
+                        "You have been kicked from the server by a vote".to_string(),
+                    );
+                    member.send_message(message.into()).await?;
+                    room.send_goodbye(&target_username).await;
+                    room.find_member(&target_username)
+                        .exit(LeaveReason::Kicked(None))?;
+                }
+            }
+            Command::Poll(poll_command) => 'label: {
+                room.announce_expired_poll().await?;
+                match poll_command {
+                    PollCommand::View => {
+                        let body = match room.poll() {
+                            Some(poll) => format!(
+                                "Poll by {} with {} votes so far: {}",
+                                poll.by.username(),
+                                poll.voters(),
+                                poll.results()
+                            ),
+                            None => "there is no open poll".to_string(),
+                        };
+                        let message = message::System::new(user.into(), body);
+                        room.send_message(message.into()).await?;
+                    }
+                    PollCommand::Start(question, options) => {
+                        if room.polls_ops_only() {
+                            if let Some(err) =
+                                missing_permission(auth, user.public_key(), Permission::Room)
+                            {
+                                let message = message::Error::new(user.into(), err);
+                                room.send_message(message.into()).await?;
+                                break 'label;
                             }
+                        }
+                        if let Err(err) = room.start_poll(&user, question.clone(), options.clone())
+                        {
+                            let message = message::Error::new(user.into(), err);
+                            room.send_message(message.into()).await?;
+                            break 'label;
+                        }
+
+                        let mut text = format!("started a poll: {}", question);
+                        for (i, option) in options.iter().enumerate() {
+                            text += &format!("{} {}. {}", utils::NEWLINE, i + 1, option);
+                        }
+                        text += &format!(
+                            "{}Vote with {}vote <n> within {}",
+                            utils::NEWLINE,
+                            command_prefix(),
+                            humantime::format_duration(room.poll_window())
+                        );
+                        let message = message::Announce::new(user.into(), text);
+                        room.send_message(message.into()).await?;
+                    }
+                    PollCommand::Close => {
+                        let started_by = match room.poll() {
+                            Some(poll) => poll.by.id(),
                             None => {
-                                let message =
-                                    message::Error::new(user.into(), "user not found".to_string());
+                                let message = message::Error::new(
+                                    user.into(),
+                                    "there is no open poll".to_string(),
+                                );
                                 room.send_message(message.into()).await?;
                                 break 'label;
                             }
+                        };
+                        if started_by != user.id()
+                            && !auth.has_permission(user.public_key(), Permission::Room)
+                        {
+                            let message = message::Error::new(
+                                user.into(),
+                                "only operators and the user who started the poll can close it"
+                                    .to_string(),
+                            );
+                            room.send_message(message.into()).await?;
+                            break 'label;
                         }
+
+                        let poll = room.close_poll().expect("Poll should be open");
+                        let message = message::Announce::new(
+                            user.into(),
+                            format!("closed the poll: {}", poll.results()),
+                        );
+                        room.send_message(message.into()).await?;
+                    }
+                }
+            }
+            Command::Vote(option) => {
+                room.announce_expired_poll().await?;
+                let message: Message = match room.vote_in_poll(user.id(), *option) {
+                    Ok(choice) => {
+                        message::System::new(user.into(), format!("You voted for {}", choice))
+                            .into()
+                    }
+                    Err(err) => message::Error::new(user.into(), err).into(),
+                };
+                room.send_message(message).await?;
+            }
+            Command::Veto(target_username) => 'label: {
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Kick) {
+                    let message = message::Error::new(user.into(), err);
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+
+                room.announce_expired_vote_kicks().await?;
+                let target_username = UserName::from(target_username);
+                let target_id = match room.try_find_member(&target_username) {
+                    Some(member) => member.user.id(),
+                    None => {
+                        let message =
+                            message::Error::new(user.into(), "user not found".to_string());
+                        room.send_message(message.into()).await?;
+                        break 'label;
                     }
-                    BanQuery::Multiple(items) => {
-                        for item in items {
-                            match item.attribute {
-                                BanAttribute::Name(name) => {
-                                    let username = UserName::from(&name);
-                                    auth.ban_username(&username, item.duration);
-
-                                    for (_, member) in room.members_iter_mut() {
-                                        if member.user.username().eq(&username) {
-                                            let message = message::Announce::new(
-                                                user.clone().into(),
-                                                format!("banned {} from the server", username),
-                                            );
-                                            messages.push(message.into());
-                                            member.exit()?;
-                                        }
-                                    }
-                                }
-                                BanAttribute::Fingerprint(fingerprint) => {
-                                    auth.ban_fingerprint(&fingerprint, item.duration);
-
-                                    for (_, member) in room.members_iter_mut() {
-                                        if member.user.public_key().fingerprint().eq(&fingerprint) {
-                                            let message = message::Announce::new(
-                                                user.clone().into(),
-                                                format!(
-                                                    "banned {} from the server",
-                                                    member.user.username()
-                                                ),
-                                            );
-                                            messages.push(message.into());
-                                            member.exit()?;
-                                        }
-                                    }
-                                }
-                                BanAttribute::Ip(_) => todo!(),
+                };
+                if !room.cancel_vote_kick(target_id) {
+                    let message = message::Error::new(
+                        user.into(),
+                        format!("there is no vote to kick {}", target_username),
+                    );
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+                let message = message::Announce::new(
+                    user.clone().into(),
+                    format!("vetoed the vote to kick {}", target_username),
+                );
+                room.send_message(message.into()).await?;
+                auth.audit(&user, "veto", &target_username);
+            }
+            Command::Pin(text) => 'label: {
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Room) {
+                    let message = message::Error::new(user.into(), err);
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+
+                let pin = match text.strip_prefix('^') {
+                    Some(nth) => {
+                        let nth = match nth {
+                            "" => Some(1),
+                            nth => nth.parse::<usize>().ok(),
+                        };
+                        match nth.and_then(|nth| room.find_recent_message(nth)) {
+                            Some(pin) => pin,
+                            None => {
+                                let message = message::Error::new(
+                                    user.into(),
+                                    format!("no recent message {} to pin", text),
+                                );
+                                room.send_message(message.into()).await?;
+                                break 'label;
                             }
                         }
                     }
+                    None => Pin {
+                        author: user.username().to_string(),
+                        text: text.to_string(),
+                    },
+                };
+
+                let message = match room.pin(pin.clone()) {
+                    Ok(number) => {
+                        auth.audit(&user, "pin", &pin.text);
+                        message::System::new(
+                            user.into(),
+                            format!("pinned as {}: {}", number, pin.text),
+                        )
+                        .into()
+                    }
+                    Err(err) => message::Error::new(user.into(), err).into(),
+                };
+                room.send_message(message).await?;
+            }
+            Command::Unpin(number) => 'label: {
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Room) {
+                    let message = message::Error::new(user.into(), err);
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+
+                let message = match room.unpin(*number) {
+                    Some(pin) => {
+                        auth.audit(&user, "unpin", &pin.text);
+                        message::System::new(user.into(), format!("unpinned: {}", pin.text)).into()
+                    }
+                    None => {
+                        message::Error::new(user.into(), format!("no pinned message {}", number))
+                            .into()
+                    }
+                };
+                room.send_message(message).await?;
+            }
+            Command::ClearHistory => 'label: {
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Room) {
+                    let message = message::Error::new(user.into(), err);
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+
+                auth.audit(&user, "clearhistory", "");
+                let message =
+                    message::Announce::new(user.into(), "cleared the room history".to_string());
+                room.send_message(message.into()).await?;
+                room.clear_history();
+            }
+            Command::Color(target_username, color) => 'label: {
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Room) {
+                    let message = message::Error::new(user.into(), err);
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+
+                let color = match color.as_str() {
+                    "reset" => None,
+                    hex => match parse_hex_color(hex) {
+                        Some(color) => Some(color),
+                        None => {
+                            let message = message::Error::new(
+                                user.into(),
+                                "color must be a hex value like #ff8800, or reset".to_string(),
+                            );
+                            room.send_message(message.into()).await?;
+                            break 'label;
+                        }
+                    },
+                };
+
+                let target_username = UserName::from(target_username);
+                if !room.set_username_color(&target_username, color) {
+                    let message = message::Error::new(user.into(), "user not found".to_string());
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+                match color {
+                    Some(_) => auth.audit(&user, "color", &target_username),
+                    None => auth.audit(&user, "color-reset", &target_username),
                 }
 
                 let message = message::System::new(
                     user.into(),
-                    "Banning is complete. Offline users were silently banned.".to_string(),
+                    match color {
+                        Some(_) => format!("color of {} is now pinned", target_username),
+                        None => format!("color of {} is reset", target_username),
+                    },
                 );
-                messages.push(message.into());
+                room.send_message(message.into()).await?;
+            }
+            Command::Ban(args, dry_run) => 'label: {
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Ban) {
+                    let message = message::Error::new(user.into(), err);
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+
+                let plan = match args.parse::<BanQuery>() {
+                    Ok(query) => plan_ban(query, room),
+                    Err(err) => Err(err.to_string()),
+                };
+                let plan = match plan {
+                    Ok(plan) => plan,
+                    Err(err) => {
+                        let message = message::Error::new(user.into(), err);
+                        room.send_message(message.into()).await?;
+                        break 'label;
+                    }
+                };
+
+                let mut messages: Vec<Message> = plan
+                    .errors
+                    .iter()
+                    .map(|err| message::Error::new(user.clone().into(), err.clone()).into())
+                    .collect();
+
+                if *dry_run {
+                    let message = message::System::new(user.into(), plan.report());
+                    messages.push(message.into());
+                } else {
+                    for (entry, duration) in &plan.entries {
+                        match entry {
+                            BanEntry::Name(name) => auth.ban_username(name, *duration),
+                            BanEntry::Fingerprint(fingerprint) => {
+                                auth.ban_fingerprint(fingerprint, *duration)
+                            }
+                            BanEntry::Ip(range) => auth.ban_ip(*range, *duration),
+                        }
+                    }
+                    for target in &plan.targets {
+                        if let Some(member) = room.try_find_member(target) {
+                            member.exit(LeaveReason::Banned(user.username().clone()))?;
+                        }
+                    }
+
+                    auth.audit(&user, "ban", args);
+                    let message = message::System::new(
+                        user.into(),
+                        "Banning is complete. Offline users were silently banned.".to_string(),
+                    );
+                    messages.push(message.into());
+                }
 
                 for message in messages {
                     room.send_message(message).await?;
                 }
             }
             Command::Banned => 'label: {
-                use std::fmt::Write;
-
-                if !auth.is_op(&user.public_key().clone().into()) {
-                    let message =
-                        message::Error::new(user.into(), "must be an operator".to_string());
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Ban) {
+                    let message = message::Error::new(user.into(), err);
                     room.send_message(message.into()).await?;
                     break 'label;
                 }
 
                 let (names, fingerprints) = auth.banned();
-                let mut banned = String::new();
-                write!(banned, "Banned:").expect("Failed to write banned members to string");
+                let banned = names
+                    .iter()
+                    .map(|name| format!("\"name={}\"", name))
+                    .chain(
+                        fingerprints
+                            .iter()
+                            .map(|fingerprint| format!("\"fingerprint={}\"", fingerprint)),
+                    )
+                    .chain(auth.banned_ips().iter().map(|ip| format!("\"ip={}\"", ip)))
+                    .collect::<Vec<String>>();
 
-                for name in names {
-                    write!(banned, "{} \"name={}\"", utils::NEWLINE, name)
-                        .expect("Failed to write banned members to string");
+                let message = message::System::new(
+                    user.into(),
+                    format!("Banned:{}", format_columns(&banned, terminal.width())),
+                );
+                room.send_message(message.into()).await?;
+            }
+            Command::Requests => 'label: {
+                if let Some(err) =
+                    missing_permission(auth, user.public_key(), Permission::Whitelist)
+                {
+                    let message = message::Error::new(user.into(), err);
+                    room.send_message(message.into()).await?;
+                    break 'label;
                 }
 
-                for fingerprint in fingerprints {
-                    write!(banned, "{} \"fingerprint={}\"", utils::NEWLINE, fingerprint)
-                        .expect("Failed to write banned members to string");
+                let requests = auth.access_requests();
+                let body = match requests.is_empty() {
+                    true => "no pending access requests".to_string(),
+                    false => {
+                        let lines = requests
+                            .iter()
+                            .map(|request| {
+                                let ago =
+                                    Duration::from_secs(request.requested_at.elapsed().as_secs());
+                                let addr = request
+                                    .addr
+                                    .map(|addr| format!(" from {}", addr.ip()))
+                                    .unwrap_or_default();
+                                format!(
+                                    "{} > {}{} ({} ago): {}",
+                                    utils::NEWLINE,
+                                    request.username,
+                                    addr,
+                                    humantime::format_duration(ago),
+                                    request.key.long()
+                                )
+                            })
+                            .collect::<String>();
+                        format!(
+                            "{} pending access requests, approve with {}whitelist add <key>:{}",
+                            requests.len(),
+                            command_prefix(),
+                            lines
+                        )
+                    }
+                };
+                let message = message::System::new(user.into(), body);
+                room.send_message(message.into()).await?;
+            }
+            Command::Unban(query) => 'label: {
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Ban) {
+                    let message = message::Error::new(user.into(), err);
+                    room.send_message(message.into()).await?;
+                    break 'label;
                 }
 
-                let message = message::System::new(user.into(), banned);
-                room.send_message(message.into()).await?;
+                let attributes = match query.parse::<UnbanQuery>() {
+                    Ok(UnbanQuery::Single(name)) => vec![BanAttribute::Name(name)],
+                    Ok(UnbanQuery::Multiple(attributes)) => attributes,
+                    Err(err) => {
+                        let message = message::Error::new(user.into(), err.to_string());
+                        room.send_message(message.into()).await?;
+                        break 'label;
+                    }
+                };
+
+                let mut lifted = vec![];
+                for attribute in attributes {
+                    match attribute {
+                        BanAttribute::Name(name) if auth.unban_username(&name) => {
+                            lifted.push(format!("\"name={}\"", name))
+                        }
+                        BanAttribute::Fingerprint(fingerprint)
+                            if auth.unban_fingerprint(&fingerprint) =>
+                        {
+                            lifted.push(format!("\"fingerprint={}\"", fingerprint))
+                        }
+                        BanAttribute::Ip(ip) => match ip.parse::<IpRange>() {
+                            Ok(range) if auth.unban_ip(&range) => {
+                                lifted.push(format!("\"ip={}\"", range))
+                            }
+                            _ => {}
+                        },
+                        _ => {}
+                    }
+                }
+
+                if !lifted.is_empty() {
+                    auth.audit(&user, "unban", &lifted.join(" "));
+                }
+                let message: Message = match lifted.is_empty() {
+                    true => message::Error::new(user.into(), "no matching ban".to_string()).into(),
+                    false => message::System::new(
+                        user.into(),
+                        format!("Unbanned: {}", lifted.join(", ")),
+                    )
+                    .into(),
+                };
+                room.send_message(message).await?;
+            }
+            Command::ReloadBlocklist => 'label: {
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Room) {
+                    let message = message::Error::new(user.into(), err);
+                    room.send_message(message.into()).await?;
+                    break 'label;
+                }
+
+                let message: Message = match room.reload_blocklist() {
+                    None => message::Error::new(
+                        user.into(),
+                        "no blocklist file in the server configuration, nothing to reload"
+                            .to_string(),
+                    )
+                    .into(),
+                    Some(Err(err)) => message::Error::new(
+                        user.into(),
+                        format!("failed to reload the blocklist: {}", err),
+                    )
+                    .into(),
+                    Some(Ok(count)) => message::System::new(
+                        user.into(),
+                        format!("Reloaded the blocklist: {} blocked words", count),
+                    )
+                    .into(),
+                };
+                room.send_message(message).await?;
             }
             Command::Whitelist(command) => 'label: {
-                if !auth.is_op(&user.public_key().clone().into()) {
-                    let message =
-                        message::Error::new(user.into(), "must be an operator".to_string());
+                if let Some(err) =
+                    missing_permission(auth, user.public_key(), Permission::Whitelist)
+                {
+                    let message = message::Error::new(user.into(), err);
                     room.send_message(message.into()).await?;
                     break 'label;
                 }
@@ -745,9 +2026,8 @@ where
                 exec_whitelist_command(command, &user, room, auth).await?;
             }
             Command::Oplist(command) => 'label: {
-                if !auth.is_op(&user.public_key().clone().into()) {
-                    let message =
-                        message::Error::new(user.into(), "must be an operator".to_string());
+                if let Some(err) = missing_permission(auth, user.public_key(), Permission::Oplist) {
+                    let message = message::Error::new(user.into(), err);
                     room.send_message(message.into()).await?;
                     break 'label;
                 }
@@ -773,6 +2053,7 @@ async fn exec_whitelist_command(
     match command {
         WhitelistCommand::On => {
             auth.enable_whitelist_mode();
+            auth.audit(user, "whitelist-on", "");
             let message = message::System::new(
                 user.into(),
                 "Server whitelisting is now enabled".to_string(),
@@ -781,6 +2062,7 @@ async fn exec_whitelist_command(
         }
         WhitelistCommand::Off => {
             auth.disable_whitelist_mode();
+            auth.audit(user, "whitelist-off", "");
             let message = message::System::new(
                 user.into(),
                 "Server whitelisting is now disabled".to_string(),
@@ -790,6 +2072,7 @@ async fn exec_whitelist_command(
         WhitelistCommand::Add(users_or_keys) => {
             let mut invalid_keys = vec![];
             let mut invalid_users = vec![];
+            let (mut added, mut already_present) = (0, 0);
 
             let mut is_key = false;
             for user_or_key in users_or_keys.split_whitespace() {
@@ -801,7 +2084,10 @@ async fn exec_whitelist_command(
                 if is_key {
                     let key = user_or_key;
                     match russh_keys::parse_public_key_base64(&key) {
-                        Ok(pk) => auth.add_trusted_key(pk.into()),
+                        Ok(pk) => match auth.add_trusted_key(pk.into()) {
+                            true => added += 1,
+                            false => already_present += 1,
+                        },
                         Err(_) => invalid_keys.push(key.to_string()),
                     }
                     is_key = false;
@@ -809,12 +2095,22 @@ async fn exec_whitelist_command(
                     let user = user_or_key;
                     let username = UserName::from(user);
                     match room.try_find_member(&username).map(|m| &m.user) {
-                        Some(user) => auth.add_trusted_key(user.public_key().clone().into()),
+                        Some(user) => {
+                            match auth.add_trusted_key(user.public_key().clone()) {
+                                true => added += 1,
+                                false => already_present += 1,
+                            }
+                            auth.set_trusted_key_name(user.public_key().clone(), user.username());
+                        }
                         None => invalid_users.push(user.to_string()),
                     }
                 }
             }
 
+            if added > 0 {
+                auth.audit(user, "whitelist-add", users_or_keys);
+            }
+
             let mut messages = vec![];
             if !invalid_keys.is_empty() {
                 messages.push(format!("Invalid keys: {}", invalid_keys.join(", ")));
@@ -822,8 +2118,14 @@ async fn exec_whitelist_command(
             if !invalid_users.is_empty() {
                 messages.push(format!("Invalid users: {}", invalid_users.join(", ")));
             }
-            if messages.is_empty() {
-                messages.push(format!("Server whitelist is updated!"));
+            if added + already_present > 0 || messages.is_empty() {
+                messages.insert(
+                    0,
+                    format!(
+                        "Server whitelist is updated: {} added, {} already trusted",
+                        added, already_present
+                    ),
+                );
             }
 
             let message = message::System::new(user.into(), messages.join(utils::NEWLINE));
@@ -832,6 +2134,7 @@ async fn exec_whitelist_command(
         WhitelistCommand::Remove(users_or_keys) => {
             let mut invalid_keys = vec![];
             let mut invalid_users = vec![];
+            let mut removed = 0;
 
             let mut is_key = false;
             for user_or_key in users_or_keys.split_whitespace() {
@@ -843,7 +2146,10 @@ async fn exec_whitelist_command(
                 if is_key {
                     let key = user_or_key;
                     match russh_keys::parse_public_key_base64(&key) {
-                        Ok(pk) => auth.remove_trusted_key(pk.into()),
+                        Ok(pk) => {
+                            auth.remove_trusted_key(pk.into());
+                            removed += 1;
+                        }
                         Err(_) => invalid_keys.push(key.to_string()),
                     }
                     is_key = false;
@@ -851,12 +2157,19 @@ async fn exec_whitelist_command(
                     let user = user_or_key;
                     let username = UserName::from(user);
                     match room.try_find_member(&username).map(|m| &m.user) {
-                        Some(user) => auth.remove_trusted_key(user.public_key().clone()),
+                        Some(user) => {
+                            auth.remove_trusted_key(user.public_key().clone());
+                            removed += 1;
+                        }
                         None => invalid_users.push(user.to_string()),
                     }
                 }
             }
 
+            if removed > 0 {
+                auth.audit(user, "whitelist-remove", users_or_keys);
+            }
+
             let mut messages = vec![];
             if !invalid_keys.is_empty() {
                 messages.push(format!("Invalid keys: {}", invalid_keys.join(", ")));
@@ -877,6 +2190,11 @@ async fn exec_whitelist_command(
             }
             let message: Message = match auth.load_trusted_keys() {
                 Ok(_) => {
+                    auth.audit(
+                        user,
+                        "whitelist-load",
+                        load_mode_name(*mode == WhitelistLoadMode::Replace),
+                    );
                     let body = "Trusted keys are up-to-date with the whitelist file".to_string();
                     message::System::new(user.into(), body).into()
                 }
@@ -890,6 +2208,7 @@ async fn exec_whitelist_command(
         WhitelistCommand::Save => {
             let message: Message = match auth.save_trusted_keys() {
                 Ok(_) => {
+                    auth.audit(user, "whitelist-save", "");
                     let body = "Whitelist file is up-to-date with the trusted keys".to_string();
                     message::System::new(user.into(), body).into()
                 }
@@ -900,6 +2219,19 @@ async fn exec_whitelist_command(
             };
             room.send_message(message).await?;
         }
+        WhitelistCommand::Export(path) => {
+            let message: Message = match auth.export_trusted_keys(path) {
+                Ok(count) => {
+                    let body = format!("Exported {} trusted keys to {}", count, path);
+                    message::System::new(user.into(), body).into()
+                }
+                Err(err) => {
+                    let body = err.to_string();
+                    message::Error::new(user.into(), body).into()
+                }
+            };
+            room.send_message(message).await?;
+        }
         WhitelistCommand::Reverify => 'label: {
             if !auth.is_whitelist_enabled() {
                 let message = message::System::new(
@@ -911,21 +2243,12 @@ async fn exec_whitelist_command(
             }
 
             let auth = auth;
-            let mut kicked = vec![];
             for (_, member) in room.members_iter() {
                 if !auth.is_trusted(&member.user.public_key()) {
-                    kicked.push(member.user.clone());
-                    member.exit()?;
+                    member.exit(LeaveReason::Kicked(Some(user.username().clone())))?;
                 }
             }
-
-            for user in kicked {
-                let message = message::Announce::new(
-                    user.into(),
-                    "was kicked during pubkey reverification".to_string(),
-                );
-                room.send_message(message.into()).await?;
-            }
+            auth.audit(user, "whitelist-reverify", "");
         }
         WhitelistCommand::Status => {
             let auth = auth;
@@ -990,6 +2313,7 @@ async fn exec_oplist_command(
         OplistCommand::Add(users_or_keys) => {
             let mut invalid_keys = vec![];
             let mut invalid_users = vec![];
+            let (mut added, mut already_present) = (0, 0);
 
             let mut is_key = false;
             for user_or_key in users_or_keys.split_whitespace() {
@@ -1001,7 +2325,10 @@ async fn exec_oplist_command(
                 if is_key {
                     let key = user_or_key;
                     match russh_keys::parse_public_key_base64(&key) {
-                        Ok(pk) => auth.add_operator(pk.into()),
+                        Ok(pk) => match auth.add_operator(pk.into()) {
+                            true => added += 1,
+                            false => already_present += 1,
+                        },
                         Err(_) => invalid_keys.push(key.to_string()),
                     }
                     is_key = false;
@@ -1009,12 +2336,19 @@ async fn exec_oplist_command(
                     let user = user_or_key;
                     let username = UserName::from(user);
                     match room.try_find_member(&username).map(|m| &m.user) {
-                        Some(user) => auth.add_operator(user.public_key().clone()),
+                        Some(user) => match auth.add_operator(user.public_key().clone()) {
+                            true => added += 1,
+                            false => already_present += 1,
+                        },
                         None => invalid_users.push(user.to_string()),
                     }
                 }
             }
 
+            if added > 0 {
+                auth.audit(user, "oplist-add", users_or_keys);
+            }
+
             let mut messages = vec![];
             if !invalid_keys.is_empty() {
                 messages.push(format!("Invalid keys: {}", invalid_keys.join(", ")));
@@ -1022,9 +2356,14 @@ async fn exec_oplist_command(
             if !invalid_users.is_empty() {
                 messages.push(format!("Invalid users: {}", invalid_users.join(", ")));
             }
-
-            if messages.is_empty() {
-                messages.push(format!("Server operators list is updated!"));
+            if added + already_present > 0 || messages.is_empty() {
+                messages.insert(
+                    0,
+                    format!(
+                        "Server operators list is updated: {} added, {} already on the list",
+                        added, already_present
+                    ),
+                );
             }
 
             let message = message::System::new(user.into(), messages.join(utils::NEWLINE));
@@ -1033,6 +2372,7 @@ async fn exec_oplist_command(
         OplistCommand::Remove(users_or_keys) => {
             let mut invalid_keys = vec![];
             let mut invalid_users = vec![];
+            let mut removed = 0;
 
             let mut is_key = false;
             for user_or_key in users_or_keys.split_whitespace() {
@@ -1044,7 +2384,10 @@ async fn exec_oplist_command(
                 if is_key {
                     let key = user_or_key;
                     match russh_keys::parse_public_key_base64(&key) {
-                        Ok(pk) => auth.remove_operator(pk.into()),
+                        Ok(pk) => {
+                            auth.remove_operator(pk.into());
+                            removed += 1;
+                        }
                         Err(_) => invalid_keys.push(key.to_string()),
                     }
                     is_key = false;
@@ -1052,12 +2395,19 @@ async fn exec_oplist_command(
                     let user = user_or_key;
                     let username = UserName::from(user);
                     match room.try_find_member(&username).map(|m| &m.user) {
-                        Some(user) => auth.remove_operator(user.public_key().clone()),
+                        Some(user) => {
+                            auth.remove_operator(user.public_key().clone());
+                            removed += 1;
+                        }
                         None => invalid_users.push(user.to_string()),
                     }
                 }
             }
 
+            if removed > 0 {
+                auth.audit(user, "oplist-remove", users_or_keys);
+            }
+
             let mut messages = vec![];
             if !invalid_keys.is_empty() {
                 messages.push(format!("Invalid keys: {}", invalid_keys.join(", ")));
@@ -1078,6 +2428,11 @@ async fn exec_oplist_command(
             }
             let message: Message = match auth.load_operators() {
                 Ok(_) => {
+                    auth.audit(
+                        user,
+                        "oplist-load",
+                        load_mode_name(*mode == OplistLoadMode::Replace),
+                    );
                     let body = "Operators keys are up-to-date with the oplist file".to_string();
                     message::System::new(user.into(), body).into()
                 }
@@ -1091,6 +2446,7 @@ async fn exec_oplist_command(
         OplistCommand::Save => {
             let message: Message = match auth.save_operators() {
                 Ok(_) => {
+                    auth.audit(user, "oplist-save", "");
                     let body = "Oplist file is up-to-date with the operators".to_string();
                     message::System::new(user.into(), body).into()
                 }
@@ -1144,3 +2500,1412 @@ async fn exec_oplist_command(
 
     Ok(())
 }
+
+/// A ban condition to add, as listed by `/banned`
+enum BanEntry {
+    Name(UserName),
+    Fingerprint(String),
+    Ip(IpRange),
+}
+
+impl std::fmt::Display for BanEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BanEntry::Name(name) => write!(f, "name={}", name),
+            BanEntry::Fingerprint(fingerprint) => write!(f, "fingerprint={}", fingerprint),
+            BanEntry::Ip(range) => write!(f, "ip={}", range),
+        }
+    }
+}
+
+/// What a ban query resolves to before any of it is applied
+struct BanPlan {
+    entries: Vec<(BanEntry, Duration)>,
+    /// Members online who get disconnected
+    targets: Vec<UserName>,
+    /// Query items that are skipped, e.g. malformed addresses
+    errors: Vec<String>,
+}
+
+impl BanPlan {
+    /// Lists what the plan would do, for `/ban --dry-run`
+    fn report(&self) -> String {
+        let mut report = "Dry run, nothing was banned. Would ban:".to_string();
+        for (entry, duration) in &self.entries {
+            report += &format!(
+                "{} \"{}\" for {}",
+                utils::NEWLINE,
+                entry,
+                humantime::format_duration(*duration)
+            );
+        }
+        report += utils::NEWLINE;
+        match self.targets.is_empty() {
+            true => report += "No one online would be kicked",
+            false => {
+                let names = self
+                    .targets
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect::<Vec<String>>();
+                report += &format!("Would kick: {}", names.join(", "));
+            }
+        }
+        report
+    }
+}
+
+/// Resolves the ban query against the members online, without banning
+/// or kicking anyone yet
+fn plan_ban(query: BanQuery, room: &ChatRoom) -> Result<BanPlan, String> {
+    let mut plan = BanPlan {
+        entries: vec![],
+        targets: vec![],
+        errors: vec![],
+    };
+
+    match query {
+        BanQuery::Single { name, duration } => {
+            let member = room
+                .try_find_member(&UserName::from(&name))
+                .ok_or("user not found")?;
+            let fingerprint = member.user.public_key().fingerprint();
+            plan.entries
+                .push((BanEntry::Fingerprint(fingerprint), duration));
+            plan.targets.push(member.user.username().clone());
+        }
+        BanQuery::Multiple(items) => {
+            for item in items {
+                let entry = match item.attribute {
+                    BanAttribute::Name(name) => BanEntry::Name(UserName::from(&name)),
+                    BanAttribute::Fingerprint(fingerprint) => BanEntry::Fingerprint(fingerprint),
+                    BanAttribute::Ip(ip) => match ip.parse::<IpRange>() {
+                        Ok(range) => BanEntry::Ip(range),
+                        Err(err) => {
+                            plan.errors.push(format!("{}: {}", err, ip));
+                            continue;
+                        }
+                    },
+                };
+                for (username, member) in room.members_iter() {
+                    let matches = match &entry {
+                        BanEntry::Name(name) => member.user.username().eq(name),
+                        BanEntry::Fingerprint(fingerprint) => {
+                            member.user.public_key().fingerprint().eq(fingerprint)
+                        }
+                        BanEntry::Ip(range) => member
+                            .user
+                            .peer_addr()
+                            .is_some_and(|addr| range.contains(&addr.ip())),
+                    };
+                    if matches && !plan.targets.contains(username) {
+                        plan.targets.push(username.clone());
+                    }
+                }
+                plan.entries.push((entry, item.duration));
+            }
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Tells why the user can't run a command that needs the permission
+fn missing_permission(auth: &Auth, key: &PubKey, permission: Permission) -> Option<String> {
+    match auth.has_permission(key, permission) {
+        true => None,
+        false if auth.is_op(key) => Some(format!(
+            "must be an operator with the {} permission",
+            permission
+        )),
+        false => Some("must be an operator".to_string()),
+    }
+}
+
+/// Columns assumed when the client didn't tell its terminal size
+const DEFAULT_TERM_WIDTH: usize = 80;
+
+/// Lays out the items in columns fitting the terminal, each row on a
+/// line of its own, indented under the header
+fn format_columns(items: &[String], term_width: u16) -> String {
+    let width = match term_width {
+        0 => DEFAULT_TERM_WIDTH,
+        width => width as usize,
+    };
+    layout_columns(items, width - 1)
+        .iter()
+        .map(|row| format!("{} {}", utils::NEWLINE, row))
+        .collect()
+}
+
+/// Numbers the sessions under the header, marking the current one
+fn format_sessions(header: &str, sessions: &[&User], current: Option<usize>) -> String {
+    sessions
+        .iter()
+        .enumerate()
+        .fold(header.to_string(), |list, (i, session)| {
+            format!(
+                "{}{} {}. {} via {}, joined {} ago{}",
+                list,
+                utils::NEWLINE,
+                i + 1,
+                session.username(),
+                session.ssh_client(),
+                humantime::format_duration(session.joined_duration()),
+                match current == Some(session.id()) {
+                    true => " (this session)",
+                    false => "",
+                }
+            )
+        })
+}
+
+fn load_mode_name(is_replace: bool) -> &'static str {
+    match is_replace {
+        true => "replace",
+        false => "merge",
+    }
+}
+
+/// Queues the PM if the server keeps offline messages and the name
+/// belongs to a trusted key. Returns `None` otherwise
+fn queue_offline_pm(
+    room: &mut ChatRoom,
+    auth: &Auth,
+    from: &User,
+    to: &UserName,
+    text: &str,
+) -> Option<Result<(), String>> {
+    if !room.has_offline_messages() {
+        return None;
+    }
+    let fingerprint = auth.trusted_key_by_name(to.as_ref())?.fingerprint();
+    if room.is_author_muted(&from.clone().into()) {
+        return Some(Err("you are muted".to_string()));
+    }
+    Some(room.queue_offline_message(&fingerprint, from.username(), text))
+}
+
+/// Tells when an away user expects to be back, e.g. `back in ~30m`.
+/// Returns `None` if they gave no ETA
+fn away_eta(until: Option<DateTime<Utc>>) -> Option<String> {
+    let left = until?.signed_duration_since(Utc::now());
+    if left.num_minutes() > 0 {
+        let left = Duration::from_secs(left.num_minutes() as u64 * 60);
+        Some(format!("back in ~{}", humantime::format_duration(left)))
+    } else {
+        Some("should be back any moment".to_string())
+    }
+}
+
+/// Tells the author of a private message whether it reached the
+/// recipient
+async fn report_pm_delivery(
+    room: &mut ChatRoom,
+    from: User,
+    to: &UserName,
+    status: &UserStatus,
+    delivery: PrivateDelivery,
+) -> anyhow::Result<()> {
+    let message: Message = match (delivery, status) {
+        (PrivateDelivery::Muted, _) => return Ok(()),
+        (PrivateDelivery::Ignored, _) => message::System::new(
+            from.into(),
+            format!("PM to {} not delivered: they are ignoring you", to),
+        )
+        .into(),
+        (PrivateDelivery::Delivered, UserStatus::Away { reason, until, .. }) => {
+            let eta = match away_eta(*until) {
+                Some(eta) => format!(" ({})", eta),
+                None => String::new(),
+            };
+            message::System::new(
+                from.into(),
+                format!("Sent PM to {}, but they're away now: {}{}", to, reason, eta),
+            )
+            .into()
+        }
+        (PrivateDelivery::Delivered, UserStatus::Active) => {
+            message::System::new(from.into(), format!("PM delivered to {}", to)).into()
+        }
+    };
+    room.send_message(message).await
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::auth::{AuditLog, PubKeyFileManager};
+    use crate::chat::{ThemeChoice, VoteKickThreshold};
+    use crate::pubkey::PubKey;
+    use assert_fs::TempDir;
+    use mockall::mock;
+    use std::collections::HashSet;
+    use tokio::sync::{mpsc, watch};
+
+    mock! {
+        pub Handle {}
+
+        impl Write for Handle {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize>;
+            fn flush(&mut self) -> std::io::Result<()>;
+        }
+
+        impl Clone for Handle {
+            fn clone(&self) -> Self;
+        }
+
+        impl CloseHandle for Handle {
+            fn close(&mut self) {}
+        }
+    }
+
+    async fn join_members(
+        room: &mut ChatRoom,
+        names: &[&str],
+    ) -> Vec<(User, mpsc::Receiver<String>, watch::Receiver<LeaveReason>)> {
+        let mut members = vec![];
+        for name in names {
+            let (message_tx, message_rx) = mpsc::channel(10);
+            let (exit_tx, exit_rx) = watch::channel(LeaveReason::default());
+            let user = room
+                .join(
+                    room.names().len() + 1,
+                    name.to_string(),
+                    PubKey::default(),
+                    "ssh".to_string(),
+                    None,
+                    message_tx,
+                    exit_tx,
+                )
+                .await
+                .unwrap();
+            members.push((user, message_rx, exit_rx));
+        }
+        members
+    }
+
+    fn drain(rx: &mut mpsc::Receiver<String>) -> Vec<String> {
+        let mut messages = vec![];
+        while let Ok(msg) = rx.try_recv() {
+            messages.push(msg);
+        }
+        messages
+    }
+
+    #[tokio::test]
+    async fn keep_emote_with_line_breaks_on_a_single_line() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob"]).await;
+        drain(&mut members[1].1);
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut context = WorkflowContext::new(members[0].0.clone());
+        context.command = Some(Command::Me(Some(
+            "waves\n bob: hey everyone\u{2028}carol: hi".to_string(),
+        )));
+        let mut executor = CommandExecutor::new();
+        executor
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+
+        let received = drain(&mut members[1].1);
+        assert_eq!(received.len(), 1);
+        assert!(received[0].contains("alice waves bob: hey everyone carol: hi"));
+        assert!(!received[0].contains(['\n', '\r', '\u{2028}']));
+    }
+
+    #[tokio::test]
+    async fn kick_user_once_enough_users_voted() {
+        let mut room = ChatRoom::new("Welcome!");
+        room.set_votekick(VoteKickThreshold::Count(2), Duration::from_secs(300));
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob", "carol", "dave"]).await;
+        auth.add_operator(members[3].0.public_key().clone());
+        for (_, rx, _) in &mut members {
+            drain(rx);
+        }
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        for (voter, target) in [
+            (0, "alice"),
+            (0, "bob"),
+            (0, "bob"),
+            (0, "dave"),
+            (2, "bob"),
+        ] {
+            let mut context = WorkflowContext::new(members[voter].0.clone());
+            context.command = Some(Command::Votekick(target.to_string()));
+            executor
+                .handle(&mut context, &mut terminal, &mut room, &mut auth)
+                .await
+                .unwrap();
+        }
+
+        let alice = drain(&mut members[0].1);
+        assert!(alice[0].contains("you can't vote to kick yourself"));
+        assert!(alice[1].contains("alice started a vote to kick bob (1/2 votes within 5m)"));
+        assert!(alice[2].contains("you already voted to kick them"));
+        assert!(alice[3].contains("operators can't be vote kicked"));
+        assert!(alice[4].contains("carol voted to kick bob (2/2), the vote passed"));
+        assert!(drain(&mut members[1].1)
+            .last()
+            .unwrap()
+            .contains("You have been kicked from the server by a vote"));
+        assert_eq!(*members[1].2.borrow(), LeaveReason::Kicked(None));
+    }
+
+    #[tokio::test]
+    async fn list_would_be_ban_targets_in_dry_run() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob", "carol"]).await;
+        auth.add_operator(members[0].0.public_key().clone());
+        drain(&mut members[0].1);
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        let mut context = WorkflowContext::new(members[0].0.clone());
+        context.command = Some(Command::Ban(
+            "name=bob 1h ip=10.0.0.0/8 1d ip=nowhere 1h".to_string(),
+            true,
+        ));
+        executor
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+
+        let alice = drain(&mut members[0].1);
+        assert_eq!(alice.len(), 2);
+        assert!(alice[0].contains("nowhere"));
+        assert!(alice[1].contains("Dry run, nothing was banned"));
+        assert!(alice[1].contains("\"name=bob\" for 1h"));
+        assert!(alice[1].contains("\"ip=10.0.0.0/8\" for 1day"));
+        assert!(alice[1].contains("Would kick: bob"));
+        assert!(!alice[1].contains("carol"));
+
+        assert_eq!(auth.banned(), (vec![], vec![]));
+        assert!(auth.banned_ips().is_empty());
+        assert!(!members[1].2.has_changed().unwrap());
+        assert!(room.try_find_member(&"bob".into()).is_some());
+    }
+
+    #[tokio::test]
+    async fn kick_members_connected_from_banned_ip_range() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let members = join_members(&mut room, &["alice", "bob"]).await;
+        auth.add_operator(members[0].0.public_key().clone());
+        let (message_tx, _message_rx) = mpsc::channel(10);
+        let (exit_tx, carol_exit_rx) = watch::channel(LeaveReason::default());
+        room.join(
+            3,
+            "carol".to_string(),
+            PubKey::default(),
+            "ssh".to_string(),
+            Some("[::ffff:10.1.2.3]:50000".parse().unwrap()),
+            message_tx,
+            exit_tx,
+        )
+        .await
+        .unwrap();
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        let mut context = WorkflowContext::new(members[0].0.clone());
+        context.command = Some(Command::Ban("ip=10.0.0.0/8 1h".to_string(), false));
+        executor
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+
+        assert_eq!(*carol_exit_rx.borrow(), LeaveReason::Banned("alice".into()));
+        assert!(!members[1].2.has_changed().unwrap());
+        assert!(auth.is_ip_banned(&"10.1.2.3".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn restrict_operators_to_their_permissions() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob"]).await;
+        let alice_key = members[0].0.public_key().clone();
+        auth.add_operator(alice_key.clone());
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        for (command, permission) in [
+            (Command::Mute("bob".to_string()), Permission::Mute),
+            (Command::MuteAll, Permission::Mute),
+            (Command::Kick("bob".to_string(), None), Permission::Kick),
+            (Command::Summon("bob".to_string()), Permission::Kick),
+            (Command::Veto("bob".to_string()), Permission::Kick),
+            (Command::Ban("bob".to_string(), false), Permission::Ban),
+            (Command::Banned, Permission::Ban),
+            (Command::Unban("bob".to_string()), Permission::Ban),
+            (Command::WhoIp("10.0.0.1".to_string()), Permission::Inspect),
+            (Command::SessionsOf("bob".to_string()), Permission::Inspect),
+            (Command::Pin("hello".to_string()), Permission::Room),
+            (Command::ClearHistory, Permission::Room),
+            (
+                Command::Motd(MotdCommand::Set("hi".to_string())),
+                Permission::Room,
+            ),
+            (Command::ReloadBlocklist, Permission::Room),
+            (
+                Command::Shutdown("cancel".to_string()),
+                Permission::Shutdown,
+            ),
+            (Command::Requests, Permission::Whitelist),
+            (
+                Command::Whitelist(WhitelistCommand::On),
+                Permission::Whitelist,
+            ),
+            (
+                Command::Oplist(OplistCommand::Add("bob".to_string())),
+                Permission::Oplist,
+            ),
+        ] {
+            let others = Permission::iter().filter(|other| *other != permission);
+            auth.set_op_permissions(alice_key.clone(), others.collect());
+            drain(&mut members[0].1);
+
+            let mut context = WorkflowContext::new(members[0].0.clone());
+            context.command = Some(command.clone());
+            executor
+                .handle(&mut context, &mut terminal, &mut room, &mut auth)
+                .await
+                .unwrap();
+
+            let expected = format!("must be an operator with the {} permission", permission);
+            assert!(
+                drain(&mut members[0].1)
+                    .iter()
+                    .any(|msg| msg.contains(&expected)),
+                "{:?} should need the {} permission",
+                command,
+                permission
+            );
+        }
+        assert!(!auth.is_whitelist_enabled());
+        assert!(!auth.is_op(members[1].0.public_key()));
+
+        auth.set_op_permissions(alice_key, HashSet::from([Permission::Kick]));
+        let mut context = WorkflowContext::new(members[0].0.clone());
+        context.command = Some(Command::Kick("bob".to_string(), None));
+        executor
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+        assert_eq!(
+            *members[1].2.borrow(),
+            LeaveReason::Kicked(Some("alice".into()))
+        );
+    }
+
+    async fn run_poll_commands(
+        room: &mut ChatRoom,
+        members: &[(User, mpsc::Receiver<String>, watch::Receiver<LeaveReason>)],
+        commands: Vec<(usize, Command)>,
+    ) {
+        let mut auth = Auth::default();
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        for (member, command) in commands {
+            let mut context = WorkflowContext::new(members[member].0.clone());
+            context.command = Some(command);
+            executor
+                .handle(&mut context, &mut terminal, room, &mut auth)
+                .await
+                .unwrap();
+        }
+    }
+
+    fn start_drinks_poll() -> Command {
+        let options = vec!["tea".to_string(), "coffee".to_string()];
+        Command::Poll(PollCommand::Start("Drinks?".to_string(), options))
+    }
+
+    #[tokio::test]
+    async fn refuse_votes_without_an_open_poll() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut members = join_members(&mut room, &["alice"]).await;
+        drain(&mut members[0].1);
+
+        run_poll_commands(&mut room, &members, vec![(0, Command::Vote(1))]).await;
+
+        let alice = drain(&mut members[0].1);
+        assert!(alice[0].contains("there is no open poll"));
+    }
+
+    #[tokio::test]
+    async fn announce_started_poll_with_its_options() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut members = join_members(&mut room, &["alice", "bob"]).await;
+        for (_, rx, _) in &mut members {
+            drain(rx);
+        }
+
+        run_poll_commands(&mut room, &members, vec![(1, start_drinks_poll())]).await;
+
+        let alice = drain(&mut members[0].1);
+        assert!(alice[0].contains("bob started a poll: Drinks?"));
+        assert!(alice[0].contains("2. coffee"));
+        assert!(alice[0].contains("/vote <n> within 10m"));
+        assert!(room.poll().is_some());
+    }
+
+    #[tokio::test]
+    async fn count_one_poll_vote_per_key() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut members = join_members(&mut room, &["alice", "bob"]).await;
+        let (message_tx, message_rx) = mpsc::channel(10);
+        let (exit_tx, exit_rx) = watch::channel(LeaveReason::default());
+        let second_session = room
+            .join(
+                3,
+                "alice2".to_string(),
+                members[0].0.public_key().clone(),
+                "ssh".to_string(),
+                None,
+                message_tx,
+                exit_tx,
+            )
+            .await
+            .unwrap();
+        members.push((second_session, message_rx, exit_rx));
+        for (_, rx, _) in &mut members {
+            drain(rx);
+        }
+
+        let commands = vec![
+            (1, start_drinks_poll()),
+            (0, Command::Vote(2)),
+            (0, Command::Vote(1)),
+            (2, Command::Vote(1)),
+        ];
+        run_poll_commands(&mut room, &members, commands).await;
+
+        let alice = drain(&mut members[0].1);
+        assert!(alice[1].contains("You voted for coffee"));
+        assert!(alice[2].contains("you already voted in this poll"));
+        let second_session = drain(&mut members[2].1);
+        assert!(second_session[1].contains("you already voted in this poll"));
+        assert_eq!(room.poll().unwrap().tally(), vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn refuse_votes_for_unknown_options() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut members = join_members(&mut room, &["alice"]).await;
+        drain(&mut members[0].1);
+
+        let commands = vec![(0, start_drinks_poll()), (0, Command::Vote(3))];
+        run_poll_commands(&mut room, &members, commands).await;
+
+        let alice = drain(&mut members[0].1);
+        assert!(alice[1].contains("option must be a number from 1 to 2"));
+        assert_eq!(room.poll().unwrap().voters(), 0);
+    }
+
+    #[tokio::test]
+    async fn view_poll_with_votes_so_far() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut members = join_members(&mut room, &["alice", "bob"]).await;
+        for (_, rx, _) in &mut members {
+            drain(rx);
+        }
+
+        let commands = vec![
+            (1, start_drinks_poll()),
+            (0, Command::Vote(2)),
+            (0, Command::Poll(PollCommand::View)),
+        ];
+        run_poll_commands(&mut room, &members, commands).await;
+
+        let alice = drain(&mut members[0].1);
+        assert!(alice[2].contains("Poll by bob with 1 votes so far: Drinks?"));
+        assert!(alice[2].contains("2. coffee: 1 vote"));
+    }
+
+    #[tokio::test]
+    async fn close_poll_only_as_its_starter_or_operator() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut members = join_members(&mut room, &["alice", "bob", "carol"]).await;
+        for (_, rx, _) in &mut members {
+            drain(rx);
+        }
+
+        let commands = vec![
+            (1, start_drinks_poll()),
+            (0, Command::Vote(2)),
+            (2, Command::Vote(2)),
+            (2, Command::Poll(PollCommand::Close)),
+            (1, Command::Poll(PollCommand::Close)),
+        ];
+        run_poll_commands(&mut room, &members, commands).await;
+
+        let carol = drain(&mut members[2].1);
+        assert!(carol[2].contains("only operators and the user who started the poll can close it"));
+        let bob = drain(&mut members[1].1);
+        assert!(bob[1].contains("bob closed the poll: Drinks?"));
+        assert!(bob[1].contains("1. tea: 0 votes"));
+        assert!(bob[1].contains("2. coffee: 2 votes"));
+        assert!(room.poll().is_none());
+    }
+
+    #[tokio::test]
+    async fn let_only_operators_start_polls_if_configured() {
+        let mut room = ChatRoom::new("Welcome!");
+        room.set_polls_ops_only(true);
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice"]).await;
+        drain(&mut members[0].1);
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        let mut context = WorkflowContext::new(members[0].0.clone());
+        context.command = Some(Command::Poll(PollCommand::Start(
+            "Drinks?".to_string(),
+            vec!["tea".to_string(), "coffee".to_string()],
+        )));
+        executor
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+
+        let alice = drain(&mut members[0].1);
+        assert!(alice[0].contains("must be an operator"));
+        assert!(room.poll().is_none());
+    }
+
+    #[tokio::test]
+    async fn veto_vote_kick_as_operator() {
+        let mut room = ChatRoom::new("Welcome!");
+        room.set_votekick(VoteKickThreshold::Percent(100), Duration::from_secs(300));
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob", "carol"]).await;
+        auth.add_operator(members[2].0.public_key().clone());
+        for (_, rx, _) in &mut members {
+            drain(rx);
+        }
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        for (caller, command) in [
+            (0, Command::Veto("bob".to_string())),
+            (0, Command::Votekick("bob".to_string())),
+            (2, Command::Veto("bob".to_string())),
+            (2, Command::Veto("bob".to_string())),
+        ] {
+            let mut context = WorkflowContext::new(members[caller].0.clone());
+            context.command = Some(command);
+            executor
+                .handle(&mut context, &mut terminal, &mut room, &mut auth)
+                .await
+                .unwrap();
+        }
+
+        let carol = drain(&mut members[2].1);
+        assert!(carol
+            .last()
+            .unwrap()
+            .contains("there is no vote to kick bob"));
+        assert!(carol
+            .iter()
+            .any(|msg| msg.contains("carol vetoed the vote to kick bob")));
+        assert!(drain(&mut members[0].1)[0].contains("must be an operator"));
+        assert_eq!(*members[1].2.borrow(), LeaveReason::default());
+    }
+
+    #[tokio::test]
+    async fn record_kick_in_audit_log() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("audit.log");
+        let path = path.to_str().unwrap();
+
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        auth.set_audit_log(AuditLog::open(path).unwrap());
+
+        let members = join_members(&mut room, &["alice", "bob"]).await;
+        let op = members[0].0.clone();
+        auth.add_operator(op.public_key().clone());
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut context = WorkflowContext::new(op.clone());
+        context.command = Some(Command::Kick("bob".to_string(), None));
+        let mut executor = CommandExecutor::new();
+        executor
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+        assert_eq!(
+            *members[1].2.borrow(),
+            LeaveReason::Kicked(Some("alice".into()))
+        );
+
+        let mut content = String::new();
+        for _ in 0..100 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            content = std::fs::read_to_string(path).unwrap();
+            if !content.is_empty() {
+                break;
+            }
+        }
+        let entry: serde_json::Value = serde_json::from_str(content.trim()).unwrap();
+        assert_eq!(entry["op"], "alice");
+        assert_eq!(entry["fingerprint"], op.public_key().fingerprint());
+        assert_eq!(entry["action"], "kick");
+        assert_eq!(entry["target"], "bob");
+    }
+
+    #[tokio::test]
+    async fn record_shutdown_in_audit_log() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("audit.log");
+        let path = path.to_str().unwrap();
+
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        auth.set_audit_log(AuditLog::open(path).unwrap());
+
+        let members = join_members(&mut room, &["alice"]).await;
+        let op = members[0].0.clone();
+        auth.add_operator(op.public_key().clone());
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        for delay in ["10m", "cancel"] {
+            let mut context = WorkflowContext::new(op.clone());
+            context.command = Some(Command::Shutdown(delay.to_string()));
+            executor
+                .handle(&mut context, &mut terminal, &mut room, &mut auth)
+                .await
+                .unwrap();
+        }
+
+        let mut content = String::new();
+        for _ in 0..100 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            content = std::fs::read_to_string(path).unwrap();
+            if content.lines().count() == 2 {
+                break;
+            }
+        }
+        let entries: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["action"], "shutdown");
+        assert_eq!(entries[0]["target"], "10m");
+        assert_eq!(entries[1]["action"], "shutdown-cancel");
+    }
+
+    #[tokio::test]
+    async fn queue_pm_to_offline_trusted_user_until_they_join() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("whitelist");
+        let key = PubKey::default();
+        std::fs::write(&path, format!("{} bob\n", key.long())).unwrap();
+
+        let mut room = ChatRoom::new("Welcome!");
+        room.set_offline_messages(Duration::from_secs(3600));
+        let mut auth = Auth::default();
+        auth.set_whitelist(PubKeyFileManager::new(path.to_str().unwrap()));
+        auth.load_trusted_keys().unwrap();
+        let mut members = join_members(&mut room, &["alice"]).await;
+        drain(&mut members[0].1);
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut context = WorkflowContext::new(members[0].0.clone());
+        context.command = Some(Command::Msg(vec!["bob".to_string()], "ping".to_string()));
+        let mut executor = CommandExecutor::new();
+        executor
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+        let messages = drain(&mut members[0].1);
+        assert!(messages[0].contains("bob is offline"));
+
+        let (message_tx, mut message_rx) = mpsc::channel(10);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        room.join(
+            2,
+            "bob".to_string(),
+            key,
+            "ssh".to_string(),
+            None,
+            message_tx,
+            exit_tx,
+        )
+        .await
+        .unwrap();
+        let messages = drain(&mut message_rx);
+        assert!(messages
+            .iter()
+            .any(|msg| msg.contains("[offline message] alice") && msg.contains("ping")));
+    }
+
+    #[tokio::test]
+    async fn queue_pm_to_user_whitelisted_while_online() {
+        let mut room = ChatRoom::new("Welcome!");
+        room.set_offline_messages(Duration::from_secs(3600));
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob"]).await;
+        let op = members[0].0.clone();
+        auth.add_operator(op.public_key().clone());
+
+        let command = WhitelistCommand::Add("bob".to_string());
+        exec_whitelist_command(&command, &op, &mut room, &mut auth)
+            .await
+            .unwrap();
+        room.leave(&members[1].0.id(), LeaveReason::Quit)
+            .await
+            .unwrap();
+        drain(&mut members[0].1);
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut context = WorkflowContext::new(op);
+        context.command = Some(Command::Msg(vec!["bob".to_string()], "ping".to_string()));
+        let mut executor = CommandExecutor::new();
+        executor
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+        let messages = drain(&mut members[0].1);
+        assert!(messages[0].contains("bob is offline"));
+    }
+
+    #[tokio::test]
+    async fn answer_ping_with_processing_time_to_sender_only() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob"]).await;
+        for (_, rx, _) in members.iter_mut() {
+            drain(rx);
+        }
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut context = WorkflowContext::new(members[0].0.clone());
+        context.command = Some(Command::Ping);
+        let mut executor = CommandExecutor::new();
+        executor
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+
+        let messages = drain(&mut members[0].1);
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("pong: processed in"));
+        assert!(messages[0].contains("2 connected"));
+        assert!(drain(&mut members[1].1).is_empty());
+    }
+
+    #[tokio::test]
+    async fn summon_only_the_target_once_per_interval() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob", "carol"]).await;
+        for (_, rx, _) in members.iter_mut() {
+            drain(rx);
+        }
+        let op = members[0].0.clone();
+        auth.add_operator(op.public_key().clone());
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        for _ in 0..2 {
+            let mut context = WorkflowContext::new(op.clone());
+            context.command = Some(Command::Summon("bob".to_string()));
+            executor
+                .handle(&mut context, &mut terminal, &mut room, &mut auth)
+                .await
+                .unwrap();
+        }
+
+        let alerts = drain(&mut members[1].1);
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].contains("\x07\x07\x07"));
+        assert!(alerts[0].contains("alice is summoning you"));
+        assert!(drain(&mut members[2].1).is_empty());
+
+        let replies = drain(&mut members[0].1);
+        assert!(replies[0].contains("Summoned bob"));
+        assert!(replies[1].contains("bob was summoned recently"));
+    }
+
+    #[tokio::test]
+    async fn show_current_settings() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob"]).await;
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        for command in [
+            Command::Bell(false),
+            Command::Theme(ThemeChoice::Named(Theme::Hacker)),
+            Command::Timestamp(TimestampMode::Time),
+            Command::Ignore(Some("bob".to_string())),
+            Command::Settings,
+        ] {
+            drain(&mut members[0].1);
+            let mut context = WorkflowContext::new(members[0].0.clone());
+            context.command = Some(command);
+            executor
+                .handle(&mut context, &mut terminal, &mut room, &mut auth)
+                .await
+                .unwrap();
+        }
+
+        let settings = drain(&mut members[0].1).join("");
+        for line in [
+            "theme: hacker",
+            " > timestamp: time",
+            " > quiet: off",
+            " > bell: off",
+            " > prompt: default",
+            " > highlight: @alice",
+            " > ignored: 1",
+            " > focused: 0",
+        ] {
+            assert!(settings.contains(line), "{line} not in {settings}");
+        }
+    }
+
+    #[tokio::test]
+    async fn list_users_in_columns_fitting_the_terminal() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "你好", "bob", "zoë"]).await;
+        drain(&mut members[0].1);
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        terminal.set_size(20, 24);
+        let mut executor = CommandExecutor::new();
+        let mut context = WorkflowContext::new(members[0].0.clone());
+        context.command = Some(Command::Users);
+        executor
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+
+        let alice = drain(&mut members[0].1);
+        assert_eq!(alice.len(), 1);
+        let lines = alice[0].split(utils::NEWLINE).collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("4 connected:"));
+        assert!(lines[1].contains("alice") && lines[1].contains("bob"));
+        assert!(lines[1].contains("zoë"));
+        assert!(lines[2].contains("你好"));
+    }
+
+    #[tokio::test]
+    async fn show_version_with_build_details() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice"]).await;
+        drain(&mut members[0].1);
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        let mut context = WorkflowContext::new(members[0].0.clone());
+        context.command = Some(Command::Version);
+        executor
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+
+        let alice = drain(&mut members[0].1);
+        assert_eq!(alice.len(), 1);
+        assert!(alice[0].contains(&format!(
+            "chatd {} ({}), russh {}",
+            env!("CARGO_PKG_VERSION"),
+            env!("GIT_HASH"),
+            env!("RUSSH_VERSION")
+        )));
+    }
+
+    #[tokio::test]
+    async fn quote_latest_message_of_user() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob"]).await;
+        for text in ["first", "second"] {
+            let message = message::Public::new(members[1].0.clone().into(), text.to_string());
+            room.send_message(message.into()).await.unwrap();
+        }
+        for (_, rx, _) in members.iter_mut() {
+            drain(rx);
+        }
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        let mut context = WorkflowContext::new(members[0].0.clone());
+        context.command = Some(Command::Quote("bob".to_string(), "agreed".to_string()));
+        executor
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+
+        let bob = drain(&mut members[1].1);
+        assert_eq!(bob.len(), 1);
+        let (quoted, message) = bob[0].split_once(utils::NEWLINE).unwrap();
+        assert!(quoted.contains("bob") && quoted.contains("second"));
+        assert!(message.contains("alice") && message.ends_with("agreed"));
+    }
+
+    #[tokio::test]
+    async fn throttle_quotes_in_slow_mode() {
+        let mut room = ChatRoom::new("Welcome!");
+        room.set_slow_mode(1, Duration::from_secs(60));
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob"]).await;
+        let message = message::Public::new(members[1].0.clone().into(), "hi".to_string());
+        room.send_message(message.into()).await.unwrap();
+        for (_, rx, _) in members.iter_mut() {
+            drain(rx);
+        }
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        for text in ["agreed", "indeed", "exactly"] {
+            let mut context = WorkflowContext::new(members[0].0.clone());
+            context.command = Some(Command::Quote("bob".to_string(), text.to_string()));
+            executor
+                .handle(&mut context, &mut terminal, &mut room, &mut auth)
+                .await
+                .unwrap();
+        }
+
+        let alice = drain(&mut members[0].1);
+        assert!(alice.iter().any(|msg| msg.contains("Slow mode is on")));
+        assert!(alice
+            .iter()
+            .any(|msg| msg.contains("slow mode is on. Message dropped")));
+        let bob = drain(&mut members[1].1);
+        assert!(!bob.iter().any(|msg| msg.ends_with("exactly")));
+    }
+
+    #[tokio::test]
+    async fn refuse_to_rename_to_name_held_for_reconnect() {
+        let mut room = ChatRoom::new("Welcome!");
+        room.set_reconnect_window(Duration::from_secs(60));
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob"]).await;
+        room.hold_name_for_reconnect(&members[0].0.id());
+        room.leave(&members[0].0.id(), LeaveReason::Quit)
+            .await
+            .unwrap();
+        drain(&mut members[1].1);
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        let mut context = WorkflowContext::new(members[1].0.clone());
+        context.command = Some(Command::Name("alice".to_string()));
+        executor
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+
+        let bob = drain(&mut members[1].1);
+        assert_eq!(bob.len(), 1);
+        assert!(bob[0].contains("\"alice\" name is already taken; try alice2"));
+        assert!(room.is_room_member("bob"));
+    }
+
+    #[tokio::test]
+    async fn refuse_to_quote_user_without_recent_message() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob"]).await;
+        for (_, rx, _) in members.iter_mut() {
+            drain(rx);
+        }
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        for quoted in ["bob", "carol"] {
+            let mut context = WorkflowContext::new(members[0].0.clone());
+            context.command = Some(Command::Quote(quoted.to_string(), "agreed".to_string()));
+            executor
+                .handle(&mut context, &mut terminal, &mut room, &mut auth)
+                .await
+                .unwrap();
+
+            let alice = drain(&mut members[0].1);
+            assert_eq!(alice.len(), 1);
+            assert!(alice[0].contains(&format!("{} has no recent message to quote", quoted)));
+        }
+        assert!(drain(&mut members[1].1).is_empty());
+    }
+
+    #[tokio::test]
+    async fn suppress_bell_when_turned_off() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob", "carol"]).await;
+        let op = members[0].0.clone();
+        auth.add_operator(op.public_key().clone());
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        for (caller, command) in [
+            (1, Command::Bell(false)),
+            (0, Command::Summon("bob".to_string())),
+            (0, Command::Summon("carol".to_string())),
+            (0, Command::Msg(vec!["bob".to_string()], "psst".to_string())),
+            (
+                0,
+                Command::Msg(vec!["carol".to_string()], "psst".to_string()),
+            ),
+        ] {
+            for (_, rx, _) in members.iter_mut() {
+                drain(rx);
+            }
+            let mut context = WorkflowContext::new(members[caller].0.clone());
+            context.command = Some(command);
+            executor
+                .handle(&mut context, &mut terminal, &mut room, &mut auth)
+                .await
+                .unwrap();
+
+            let bob = drain(&mut members[1].1);
+            let carol = drain(&mut members[2].1);
+            assert!(bob.iter().all(|msg| !msg.contains(utils::BEL)));
+            if caller == 0 {
+                assert_eq!(bob.len() + carol.len(), 1);
+                assert!(carol.iter().all(|msg| msg.contains(utils::BEL)));
+            }
+        }
+        assert!(room
+            .find_member(members[2].0.username())
+            .user
+            .config()
+            .bell());
+    }
+
+    #[tokio::test]
+    async fn send_pins_to_new_joiners() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let members = join_members(&mut room, &["alice"]).await;
+        let op = members[0].0.clone();
+        auth.add_operator(op.public_key().clone());
+        let message = message::Public::new(op.clone().into(), "see the wiki".to_string());
+        room.send_message(message.into()).await.unwrap();
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        for command in [
+            Command::Pin("be nice".to_string()),
+            Command::Pin("^".to_string()),
+        ] {
+            let mut context = WorkflowContext::new(op.clone());
+            context.command = Some(command);
+            executor
+                .handle(&mut context, &mut terminal, &mut room, &mut auth)
+                .await
+                .unwrap();
+        }
+
+        let mut members = join_members(&mut room, &["bob"]).await;
+        let messages = drain(&mut members[0].1);
+        let pins = messages.iter().find(|msg| msg.contains("Pinned:")).unwrap();
+        assert!(pins.contains("1. alice: be nice"));
+        assert!(pins.contains("2. alice: see the wiki"));
+    }
+
+    #[tokio::test]
+    async fn tell_kick_reason_to_target_and_room() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob"]).await;
+        let op = members[0].0.clone();
+        auth.add_operator(op.public_key().clone());
+        for (_, rx, _) in members.iter_mut() {
+            drain(rx);
+        }
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut context = WorkflowContext::new(op.clone());
+        context.command = Some(Command::Kick(
+            "bob".to_string(),
+            Some("stop spamming".to_string()),
+        ));
+        let mut executor = CommandExecutor::new();
+        executor
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+        assert_eq!(
+            *members[1].2.borrow(),
+            LeaveReason::Kicked(Some("alice".into()))
+        );
+        let farewell = drain(&mut members[1].1);
+        assert!(farewell
+            .last()
+            .unwrap()
+            .contains("You have been kicked from the server: stop spamming"));
+
+        room.leave(
+            &members[1].0.id(),
+            LeaveReason::Kicked(Some("alice".into())),
+        )
+        .await
+        .unwrap();
+        let announces = drain(&mut members[0].1);
+        assert!(announces
+            .iter()
+            .any(|msg| msg.contains("bob was kicked from the server by alice: stop spamming")));
+    }
+
+    #[tokio::test]
+    async fn count_keys_already_on_the_lists() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob"]).await;
+        let op = members[0].0.clone();
+        auth.add_operator(op.public_key().clone());
+        auth.add_trusted_key(op.public_key().clone());
+        drain(&mut members[0].1);
+
+        let command = WhitelistCommand::Add("alice bob".to_string());
+        exec_whitelist_command(&command, &op, &mut room, &mut auth)
+            .await
+            .unwrap();
+        let messages = drain(&mut members[0].1);
+        assert!(messages[0].contains("1 added, 1 already trusted"));
+
+        let command = OplistCommand::Add("alice bob carol".to_string());
+        exec_oplist_command(&command, &op, &mut room, &mut auth)
+            .await
+            .unwrap();
+        let messages = drain(&mut members[0].1);
+        assert!(messages[0].contains("1 added, 1 already on the list"));
+        assert!(messages[0].contains("Invalid users: carol"));
+    }
+
+    #[tokio::test]
+    async fn list_and_close_own_sessions() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let key = PubKey::default();
+        let mut members = vec![];
+        for (id, name) in [(1, "alice"), (2, "alice2")] {
+            let (message_tx, message_rx) = mpsc::channel(10);
+            let (exit_tx, exit_rx) = watch::channel(LeaveReason::default());
+            let user = room
+                .join(
+                    id,
+                    name.to_string(),
+                    key.clone(),
+                    "ssh".to_string(),
+                    None,
+                    message_tx,
+                    exit_tx,
+                )
+                .await
+                .unwrap();
+            members.push((user, message_rx, exit_rx));
+        }
+        join_members(&mut room, &["bob"]).await;
+        for (_, rx, _) in members.iter_mut() {
+            drain(rx);
+        }
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut executor = CommandExecutor::new();
+        for command in [Command::Sessions(None), Command::Sessions(Some(2))] {
+            let mut context = WorkflowContext::new(members[0].0.clone());
+            context.command = Some(command);
+            executor
+                .handle(&mut context, &mut terminal, &mut room, &mut auth)
+                .await
+                .unwrap();
+        }
+
+        let messages = drain(&mut members[0].1);
+        assert!(messages[0].contains("1. alice via ssh"));
+        assert!(messages[0].contains("(this session)"));
+        assert!(messages[0].contains("2. alice2 via ssh"));
+        assert!(!messages[0].contains("bob"));
+        assert!(messages[1].contains("Closed session 2 of alice2"));
+        assert_eq!(*members[1].2.borrow(), LeaveReason::Quit);
+        assert!(members[1].2.has_changed().unwrap());
+        assert!(!members[0].2.has_changed().unwrap());
+    }
+
+    #[tokio::test]
+    async fn send_pm_to_each_listed_user() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob", "carol"]).await;
+        for (_, rx, _) in members.iter_mut() {
+            drain(rx);
+        }
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut context = WorkflowContext::new(members[0].0.clone());
+        context.command = Some(Command::Msg(
+            vec!["bob", "dave", "alice", "carol"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            "lunch?".to_string(),
+        ));
+        let mut executor = CommandExecutor::new();
+        executor
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+
+        for (_, rx, _) in members.iter_mut().skip(1) {
+            let messages = drain(rx);
+            assert_eq!(messages.len(), 1);
+            assert!(messages[0].contains("lunch?"));
+        }
+        let messages = drain(&mut members[0].1);
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0]
+            .contains("PM delivered to bob, carol; not found: dave; you can't message yourself"));
+    }
+
+    #[tokio::test]
+    async fn list_away_recipients_with_their_eta() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let mut members = join_members(&mut room, &["alice", "bob", "carol"]).await;
+        room.find_member_mut(&"bob".into())
+            .user
+            .go_away("lunch, back in 30m".to_string());
+        room.find_member_mut(&"carol".into())
+            .user
+            .go_away("meeting".to_string());
+        for (_, rx, _) in members.iter_mut() {
+            drain(rx);
+        }
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        let mut context = WorkflowContext::new(members[0].0.clone());
+        context.command = Some(Command::Msg(
+            vec!["bob".to_string(), "carol".to_string()],
+            "lunch?".to_string(),
+        ));
+        let mut executor = CommandExecutor::new();
+        executor
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+
+        let messages = drain(&mut members[0].1);
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0]
+            .contains("away now: bob (lunch, back in 30m, back in ~29m), carol (meeting)"));
+    }
+}
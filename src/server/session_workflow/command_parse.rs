@@ -3,7 +3,10 @@ use chrono::Utc;
 use std::io::Write;
 
 use crate::auth::Auth;
-use crate::chat::{message, ChatRoom, Command, CommandParseError};
+use crate::chat::{
+    command_prefix, message, suggest_command, ChatRoom, Command, CommandParseError, CommandProps,
+    VISIBLE_NOOP_CHAT_COMMANDS, VISIBLE_OP_CHAT_COMMANDS,
+};
 use crate::terminal::{CloseHandle, Terminal};
 
 use super::handler::{into_next, WorkflowHandler};
@@ -43,28 +46,48 @@ where
     ) -> anyhow::Result<()> {
         let user = context.user.clone();
 
-        let command_str = if let Some(str) = &context.command_str {
-            str
+        let input_str = if let Some(str) = &context.command_str {
+            str.clone()
         } else {
             return Ok(());
         };
 
-        let input_str = terminal.input.to_string();
-
-        match command_str.parse::<Command>() {
+        match input_str.parse::<Command>() {
             Err(err) if err == CommandParseError::NotRecognizedAsCommand => {
                 terminal.clear_input()?;
-                room.find_member_mut(&user.username())
-                    .update_last_sent_time(Utc::now());
+                if !auth.is_op(user.public_key())
+                    && room.check_public_message(&user, &input_str).await?
+                {
+                    return Ok(());
+                }
+                let member = room.find_member_mut(user.username());
+                member.update_last_sent_time(Utc::now());
+                member.count_sent_message();
                 let message = message::Public::new(user.clone().into(), input_str);
                 room.send_message(message.into()).await?;
             }
             Err(err) => {
                 terminal.input.push_to_history();
                 terminal.clear_input()?;
+                let mut error = format!("{}", err);
+                if err == CommandParseError::UnknownCommand {
+                    let name = input_str.split(' ').next().unwrap_or_default();
+                    let name = name.strip_prefix(command_prefix()).unwrap_or(name);
+                    let commands = match auth.is_op(user.public_key()) {
+                        true => [
+                            &VISIBLE_NOOP_CHAT_COMMANDS[..],
+                            &VISIBLE_OP_CHAT_COMMANDS[..],
+                        ]
+                        .concat(),
+                        false => VISIBLE_NOOP_CHAT_COMMANDS.clone(),
+                    };
+                    if let Some(cmd) = suggest_command(name, &commands) {
+                        error.push_str(&format!("; did you mean {}?", cmd.display_cmd()));
+                    }
+                }
                 let message = message::Command::new(user.clone().into(), input_str);
                 room.send_message(message.into()).await?;
-                let message = message::Error::new(user.clone().into(), format!("{}", err));
+                let message = message::Error::new(user.clone().into(), error);
                 room.send_message(message.into()).await?;
             }
             Ok(command) => {
@@ -1,9 +1,22 @@
+use std::time::Instant;
+
 use crate::chat::{Command, User};
 
+use super::paste_collector::PasteBuffer;
+
 pub struct WorkflowContext {
     pub user: User,
     pub command_str: Option<String>,
     pub command: Option<Command>,
+    /// Moment the input that triggered the workflow was received
+    pub created_at: Instant,
+    /// Set once the input burst has passed the rate limit, so the rest of
+    /// the lines of a multi-line paste are not charged again
+    pub is_rate_checked: bool,
+    /// Lines submitted so far from the same input
+    pub submitted_lines: usize,
+    /// Lines collected since `/paste`, kept across inputs by the session
+    pub paste: Option<PasteBuffer>,
 }
 
 impl WorkflowContext {
@@ -12,6 +25,10 @@ impl WorkflowContext {
             user,
             command_str: None,
             command: None,
+            created_at: Instant::now(),
+            is_rate_checked: false,
+            submitted_lines: 0,
+            paste: None,
         }
     }
 }
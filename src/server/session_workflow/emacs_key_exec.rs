@@ -60,6 +60,9 @@ where
                 terminal.input.yank();
                 terminal.print_input_line()?;
             }
+            KeyCode::CtrlL => {
+                terminal.clear_screen()?;
+            }
             KeyCode::CtrlU => {
                 terminal.clear_input()?;
             }
@@ -97,3 +100,58 @@ where
         &mut self.next
     }
 }
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::chat::User;
+    use mockall::mock;
+    use std::sync::{Arc, Mutex};
+
+    mock! {
+        pub Handle {}
+
+        impl Write for Handle {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize>;
+            fn flush(&mut self) -> std::io::Result<()>;
+        }
+
+        impl Clone for Handle {
+            fn clone(&self) -> Self;
+        }
+
+        impl CloseHandle for Handle {
+            fn close(&mut self) {}
+        }
+    }
+
+    #[tokio::test]
+    async fn clear_screen_and_keep_input_on_ctrl_l() {
+        let written = Arc::new(Mutex::new(vec![]));
+        let sink = written.clone();
+        let mut handle = MockHandle::new();
+        handle.expect_write().returning(move |buf| {
+            sink.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        });
+        handle.expect_flush().times(1).returning(|| Ok(()));
+
+        let mut terminal = Terminal::new(handle);
+        terminal.set_prompt("alice");
+        terminal.input.insert_before_cursor(b"draft");
+        let mut auth = Auth::default();
+        let mut room = ChatRoom::new("Welcome!");
+        let mut context = WorkflowContext::new(User::default());
+
+        let mut executor = EmacsKeyBindingExecutor::new(KeyCode::CtrlL);
+        executor
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+
+        assert_eq!(terminal.input.to_string(), "draft");
+        let written = String::from_utf8_lossy(&written.lock().unwrap()).to_string();
+        assert!(written.starts_with("\x1b[2J"));
+        assert!(written.ends_with("[alice] draft"));
+    }
+}
@@ -2,9 +2,10 @@ use async_trait::async_trait;
 use std::io::Write;
 
 use crate::auth::Auth;
-use crate::chat::ChatRoom;
+use crate::chat::{command_prefix, message, ChatRoom, UserName};
 use crate::server::env::Env;
 use crate::terminal::{CloseHandle, Terminal};
+use crate::utils;
 
 use super::handler::{into_next, WorkflowHandler};
 use super::WorkflowContext;
@@ -49,12 +50,47 @@ where
             Err(_) => None,
         };
 
-        if let Some(env) = env {
-            let command_str = match env {
-                Env::Theme(theme) => format!("/theme {}", theme),
-                Env::Timestamp(mode) => format!("/timestamp {}", mode),
-            };
-            context.command_str = Some(command_str);
+        let (names, focus) = match env {
+            Some(Env::Theme(theme)) => {
+                context.command_str = Some(format!("{}theme {}", command_prefix(), theme));
+                return Ok(());
+            }
+            Some(Env::Timestamp(mode)) => {
+                context.command_str = Some(format!("{}timestamp {}", command_prefix(), mode));
+                return Ok(());
+            }
+            Some(Env::Ignore(names)) => (names, false),
+            Some(Env::Focus(names)) => (names, true),
+            None => return Ok(()),
+        };
+
+        // Names not online yet are resolved as the users join
+        self.next = None;
+        let user = context.user.clone();
+        let (found, pending) = room.ignore_or_focus_by_name(user.username(), &names, focus);
+        let join = |names: Vec<UserName>| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let mut lines = vec![];
+        if !found.is_empty() {
+            lines.push(match focus {
+                true => format!("Focusing on: {}", join(found)),
+                false => format!("Ignoring: {}", join(found)),
+            });
+        }
+        if !pending.is_empty() {
+            lines.push(match focus {
+                true => format!("Will focus on once they join: {}", join(pending)),
+                false => format!("Will ignore once they join: {}", join(pending)),
+            });
+        }
+        if !lines.is_empty() {
+            let message = message::System::new(user.into(), lines.join(utils::NEWLINE));
+            room.send_message(message.into()).await?;
         }
 
         Ok(())
@@ -67,9 +103,11 @@ where
 
 #[cfg(test)]
 mod should {
-    use crate::chat::User;
+    use crate::chat::{LeaveReason, User};
+    use crate::pubkey::PubKey;
     use crate::server::session_workflow::command_exec::CommandExecutor;
     use mockall::mock;
+    use tokio::sync::{mpsc, watch};
 
     use super::*;
 
@@ -162,4 +200,42 @@ mod should {
 
         assert_eq!(context.command_str, None);
     }
+
+    async fn join(room: &mut ChatRoom, id: usize, name: &str) -> (User, mpsc::Receiver<String>) {
+        let (message_tx, message_rx) = mpsc::channel(10);
+        let (exit_tx, _) = watch::channel(LeaveReason::default());
+        let user = room
+            .join(
+                id,
+                name.to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                message_tx,
+                exit_tx,
+            )
+            .await
+            .unwrap();
+        (user, message_rx)
+    }
+
+    #[tokio::test]
+    async fn ignore_named_user_once_they_join() {
+        let (mut auth, mut terminal, mut room, _) = setup!();
+        let (alice, _alice_rx) = join(&mut room, 1, "alice").await;
+
+        let executor: CommandExecutor<MockHandle> = CommandExecutor::new();
+        let mut parser = EnvParser::new("CHATD_IGNORE".to_string(), "bob".to_string(), executor);
+        let mut context = WorkflowContext::new(alice.clone());
+        parser
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+        assert_eq!(context.command_str, None);
+        assert!(room.find_member(alice.username()).user.ignored().is_empty());
+
+        let (bob, _bob_rx) = join(&mut room, 2, "bob").await;
+        let alice = &room.find_member(alice.username()).user;
+        assert!(alice.ignored().contains(&bob.id()));
+    }
 }
@@ -8,6 +8,12 @@ use crate::terminal::{CloseHandle, Terminal};
 use super::handler::{into_next, WorkflowHandler};
 use super::WorkflowContext;
 
+/// Maximum number of lines submitted from a single input, e.g. a
+/// multi-line paste, as they are charged to the rate limit only once.
+/// Lines collected by `/paste` are not counted, the paste has limits
+/// of its own
+const MAX_LINES_PER_INPUT: usize = 10;
+
 #[derive(Default)]
 pub struct InputRateChecker<H>
 where
@@ -40,6 +46,26 @@ where
         room: &mut ChatRoom,
         auth: &mut Auth,
     ) -> anyhow::Result<()> {
+        if context.paste.is_none() {
+            context.submitted_lines += 1;
+        }
+        if context.submitted_lines > MAX_LINES_PER_INPUT {
+            if context.submitted_lines == MAX_LINES_PER_INPUT + 1 {
+                let body = format!(
+                    "too many lines at once. Lines after the first {} dropped",
+                    MAX_LINES_PER_INPUT
+                );
+                let message = message::Error::new(context.user.clone().into(), body);
+                room.send_message(message.into()).await?;
+            }
+            self.next = None;
+            return Ok(());
+        }
+
+        if context.is_rate_checked {
+            return Ok(());
+        }
+
         let no_ratelim_error_msg = format!(
             "User {} should have its own rate-limit in the server room",
             context.user.username()
@@ -57,6 +83,8 @@ where
             let message = message::Error::new(context.user.clone().into(), body);
             room.send_message(message.into()).await?;
             self.next = None;
+        } else {
+            context.is_rate_checked = true;
         }
 
         Ok(())
@@ -66,3 +94,86 @@ where
         &mut self.next
     }
 }
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::chat::LeaveReason;
+    use crate::pubkey::PubKey;
+    use crate::server::session_workflow::paste_collector::{PasteBuffer, PasteCollector};
+    use mockall::mock;
+    use tokio::sync::{mpsc, watch};
+
+    mock! {
+        pub Handle {}
+
+        impl Write for Handle {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize>;
+            fn flush(&mut self) -> std::io::Result<()>;
+        }
+
+        impl Clone for Handle {
+            fn clone(&self) -> Self;
+        }
+
+        impl CloseHandle for Handle {
+            fn close(&mut self) {}
+        }
+    }
+
+    #[tokio::test]
+    async fn drop_lines_past_the_limit_of_a_single_input() {
+        let mut room = ChatRoom::new("Welcome!");
+        let mut auth = Auth::default();
+        let mut terminal = Terminal::new(MockHandle::new());
+        let (message_tx, mut message_rx) = mpsc::channel(100);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let user = room
+            .join(
+                1,
+                "alice".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                message_tx,
+                exit_tx,
+            )
+            .await
+            .unwrap();
+        while message_rx.try_recv().is_ok() {}
+
+        // All the lines of the input share the context
+        let mut context = WorkflowContext::new(user);
+        let mut passed = 0;
+        for _ in 0..MAX_LINES_PER_INPUT * 3 {
+            let mut checker = InputRateChecker::new(PasteCollector::<MockHandle>::default());
+            checker
+                .handle(&mut context, &mut terminal, &mut room, &mut auth)
+                .await
+                .unwrap();
+            if checker.next().is_some() {
+                passed += 1;
+            }
+        }
+        assert_eq!(passed, MAX_LINES_PER_INPUT);
+
+        let mut errors = vec![];
+        while let Ok(msg) = message_rx.try_recv() {
+            errors.push(msg);
+        }
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("too many lines at once"));
+
+        // Lines collected by /paste are limited by the paste instead
+        context.submitted_lines = 0;
+        context.paste = Some(PasteBuffer::default());
+        for _ in 0..MAX_LINES_PER_INPUT * 3 {
+            let mut checker = InputRateChecker::new(PasteCollector::<MockHandle>::default());
+            checker
+                .handle(&mut context, &mut terminal, &mut room, &mut auth)
+                .await
+                .unwrap();
+            assert!(checker.next().is_some());
+        }
+    }
+}
@@ -7,6 +7,7 @@ use super::WorkflowContext;
 use crate::auth::Auth;
 use crate::chat::{message, ChatRoom};
 use crate::terminal::{CloseHandle, Terminal};
+use crate::utils::{emoji, sanitize};
 
 const INPUT_MAX_LEN: usize = 1024;
 
@@ -42,8 +43,13 @@ where
         room: &mut ChatRoom,
         auth: &mut Auth,
     ) -> anyhow::Result<()> {
-        let input_str = terminal.input.to_string();
-        if input_str.trim().is_empty() {
+        let mut input_str = sanitize::message(&terminal.input.to_string())
+            .trim_end()
+            .to_string();
+        if context.user.config().emoji() {
+            input_str = emoji::expand(&input_str);
+        }
+        if input_str.trim().is_empty() && context.paste.is_none() {
             self.next = None;
             return Ok(());
         }
@@ -59,6 +65,7 @@ where
         }
 
         context.command_str = Some(input_str);
+        room.notify_seen(context.user.username()).await;
         Ok(())
     }
 
@@ -184,4 +191,63 @@ mod should {
         assert_eq!(context.command_str, Some("/help".into()));
         assert!(parser.next().is_some());
     }
+
+    #[tokio::test]
+    async fn strip_escape_sequences_from_input() {
+        let (mut auth, mut terminal, mut room, mut context) = setup!();
+        let checker: InputRateChecker<MockHandle> = InputRateChecker::default();
+        let mut parser = InputValidator::new(checker);
+
+        terminal.input.clear();
+        terminal
+            .input
+            .insert_before_cursor(b"\x1b[2Jhello \x1b]0;pwned\x07world");
+
+        let _ = parser
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await;
+
+        assert_eq!(context.command_str, Some("hello world".into()));
+    }
+
+    #[tokio::test]
+    async fn trim_trailing_whitespace_and_control_bytes_from_input() {
+        let (mut auth, mut terminal, mut room, mut context) = setup!();
+        let checker: InputRateChecker<MockHandle> = InputRateChecker::default();
+        let mut parser = InputValidator::new(checker);
+
+        for (input, expected) in [
+            ("  hello  world   ".as_bytes(), "  hello  world"),
+            (b"ring\x07", "ring"),
+            ("привет \x07 ".as_bytes(), "привет"),
+            ("日本語\u{3000}".as_bytes(), "日本語"),
+        ] {
+            terminal.input.clear();
+            terminal.input.insert_before_cursor(input);
+            let _ = parser
+                .handle(&mut context, &mut terminal, &mut room, &mut auth)
+                .await;
+            assert_eq!(context.command_str, Some(expected.into()));
+        }
+    }
+
+    #[tokio::test]
+    async fn expand_emoji_only_when_enabled() {
+        let (mut auth, mut terminal, mut room, mut context) = setup!();
+        let checker: InputRateChecker<MockHandle> = InputRateChecker::default();
+        let mut parser = InputValidator::new(checker);
+
+        terminal.input.clear();
+        terminal.input.insert_before_cursor(b"hi :wave:");
+        let _ = parser
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await;
+        assert_eq!(context.command_str, Some("hi :wave:".into()));
+
+        context.user.config_mut().switch_emoji_mode();
+        let _ = parser
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await;
+        assert_eq!(context.command_str, Some("hi 👋".into()));
+    }
 }
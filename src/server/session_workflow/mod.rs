@@ -7,6 +7,7 @@ mod env_parse;
 mod handler;
 mod input_rate_checker;
 mod input_validator;
+mod paste_collector;
 
 use autocomplete::Autocomplete;
 use command_exec::CommandExecutor;
@@ -15,6 +16,7 @@ use emacs_key_exec::EmacsKeyBindingExecutor;
 use env_parse::EnvParser;
 use input_rate_checker::InputRateChecker;
 use input_validator::InputValidator;
+use paste_collector::PasteCollector;
 
 pub use context::WorkflowContext;
 pub use handler::WorkflowHandler;
@@ -50,6 +52,7 @@ pub fn env<H: Clone + Write + CloseHandle + Send + 'static>(
 pub fn input_submit<H: Clone + Write + CloseHandle + Send + 'static>() -> InputRateChecker<H> {
     let command_executor = CommandExecutor::new();
     let command_parser = CommandParser::new(command_executor);
-    let input_validator = InputValidator::new(command_parser);
+    let paste_collector = PasteCollector::new(command_parser);
+    let input_validator = InputValidator::new(paste_collector);
     InputRateChecker::new(input_validator)
 }
@@ -0,0 +1,328 @@
+use async_trait::async_trait;
+use std::io::Write;
+
+use super::handler::{into_next, WorkflowHandler};
+use super::WorkflowContext;
+
+use crate::auth::Auth;
+use crate::chat::{command_prefix, message, ChatRoom};
+use crate::terminal::{CloseHandle, Terminal};
+use crate::utils;
+
+/// Maximum number of lines in a single paste
+const PASTE_MAX_LINES: usize = 50;
+
+/// Maximum total length of the pasted lines
+const PASTE_MAX_LEN: usize = 4096;
+
+/// Line that ends a paste and posts it
+const PASTE_TERMINATOR: &str = ".";
+
+/// Lines collected by `/paste` until the terminator
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PasteBuffer {
+    lines: Vec<String>,
+    len: usize,
+}
+
+impl PasteBuffer {
+    /// Adds the line to the paste and returns an error if the paste
+    /// grows past its limits
+    pub fn push(&mut self, line: String) -> Result<(), String> {
+        if self.lines.len() >= PASTE_MAX_LINES {
+            return Err(format!("paste is over {} lines", PASTE_MAX_LINES));
+        }
+        if self.len + line.len() > PASTE_MAX_LEN {
+            return Err(format!("paste is over {} characters", PASTE_MAX_LEN));
+        }
+        self.len += line.len();
+        self.lines.push(line);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Joins the lines into a single indented block under a header
+    pub fn assemble(&self) -> String {
+        let header = match self.lines.len() {
+            1 => "[paste, 1 line]".to_string(),
+            n => format!("[paste, {} lines]", n),
+        };
+        self.lines.iter().fold(header, |block, line| {
+            format!("{}{}    {}", block, utils::NEWLINE, line)
+        })
+    }
+}
+
+/// Collects the submitted lines into the paste buffer while a paste is
+/// in progress. On the terminator the whole block is passed on as one
+/// public message, on `/cancel` the paste is discarded
+#[derive(Default)]
+pub struct PasteCollector<H>
+where
+    H: Clone + Write + CloseHandle + Send,
+{
+    next: Option<Box<dyn WorkflowHandler<H>>>,
+}
+
+impl<H> PasteCollector<H>
+where
+    H: Clone + Write + CloseHandle + Send,
+{
+    pub fn new(next: impl WorkflowHandler<H> + 'static) -> Self {
+        Self {
+            next: into_next(next),
+        }
+    }
+}
+
+#[async_trait]
+impl<H> WorkflowHandler<H> for PasteCollector<H>
+where
+    H: Clone + Write + CloseHandle + Send,
+{
+    #[allow(unused_variables)]
+    async fn handle(
+        &mut self,
+        context: &mut WorkflowContext,
+        terminal: &mut Terminal<H>,
+        room: &mut ChatRoom,
+        auth: &mut Auth,
+    ) -> anyhow::Result<()> {
+        let paste = match &mut context.paste {
+            Some(paste) => paste,
+            None => return Ok(()),
+        };
+        let line = context.command_str.take().unwrap_or_default();
+        terminal.clear_input()?;
+
+        if line.trim() == PASTE_TERMINATOR {
+            let paste = context.paste.take().unwrap_or_default();
+            match paste.is_empty() {
+                true => self.next = None,
+                false => context.command_str = Some(paste.assemble()),
+            }
+            return Ok(());
+        }
+
+        self.next = None;
+        if line.trim() == format!("{}cancel", command_prefix()) {
+            context.paste = None;
+            let message =
+                message::System::new(context.user.clone().into(), "Paste discarded".to_string());
+            room.send_message(message.into()).await?;
+            return Ok(());
+        }
+
+        if let Err(err) = paste.push(line.clone()) {
+            context.paste = None;
+            let message = message::Error::new(
+                context.user.clone().into(),
+                format!("paste dropped. The {}", err),
+            );
+            room.send_message(message.into()).await?;
+            return Ok(());
+        }
+
+        let message = message::Command::new(context.user.clone().into(), line);
+        room.send_message(message.into()).await?;
+        Ok(())
+    }
+
+    fn next(&mut self) -> &mut Option<Box<dyn WorkflowHandler<H>>> {
+        &mut self.next
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use mockall::mock;
+    use tokio::sync::{mpsc, watch};
+
+    use super::*;
+    use crate::chat::LeaveReason;
+    use crate::pubkey::PubKey;
+    use crate::server::session_workflow::command_exec::CommandExecutor;
+    use crate::server::session_workflow::command_parse::CommandParser;
+
+    mock! {
+        pub Handle {}
+
+        impl Write for Handle {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize>;
+            fn flush(&mut self) -> std::io::Result<()>;
+        }
+
+        impl Clone for Handle {
+            fn clone(&self) -> Self;
+        }
+
+        impl CloseHandle for Handle {
+            fn close(&mut self) {}
+        }
+    }
+
+    /// Joins alice to the room with a paste in progress
+    async fn setup() -> (
+        Auth,
+        Terminal<MockHandle>,
+        ChatRoom,
+        WorkflowContext,
+        mpsc::Receiver<String>,
+    ) {
+        let mut room = ChatRoom::new("Welcome!");
+        let (message_tx, mut message_rx) = mpsc::channel(20);
+        let (exit_tx, _exit_rx) = watch::channel(LeaveReason::default());
+        let user = room
+            .join(
+                1,
+                "alice".to_string(),
+                PubKey::default(),
+                "ssh".to_string(),
+                None,
+                message_tx,
+                exit_tx,
+            )
+            .await
+            .unwrap();
+        while message_rx.try_recv().is_ok() {}
+
+        let mut terminal = Terminal::new(MockHandle::new());
+        terminal
+            .handle()
+            .expect_write()
+            .returning(|buf| Ok(buf.len()));
+        terminal.handle().expect_flush().returning(|| Ok(()));
+        let mut context = WorkflowContext::new(user);
+        context.paste = Some(PasteBuffer::default());
+        (Auth::default(), terminal, room, context, message_rx)
+    }
+
+    fn collector() -> PasteCollector<MockHandle> {
+        PasteCollector::new(CommandParser::new(CommandExecutor::new()))
+    }
+
+    #[tokio::test]
+    async fn collect_line_and_stop_the_chain() {
+        let (mut auth, mut terminal, mut room, mut context, mut message_rx) = setup().await;
+        let mut collector = collector();
+        context.command_str = Some("fn main() {".to_string());
+
+        collector
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+
+        assert!(collector.next().is_none());
+        assert_eq!(context.command_str, None);
+        assert!(!context.paste.as_ref().unwrap().is_empty());
+        assert!(message_rx.try_recv().unwrap().contains("fn main() {"));
+    }
+
+    #[tokio::test]
+    async fn flush_collected_lines_on_terminator() {
+        let (mut auth, mut terminal, mut room, mut context, _message_rx) = setup().await;
+        for line in ["fn main() {", "}", PASTE_TERMINATOR] {
+            let mut collector = collector();
+            context.command_str = Some(line.to_string());
+            collector
+                .handle(&mut context, &mut terminal, &mut room, &mut auth)
+                .await
+                .unwrap();
+            assert_eq!(collector.next().is_some(), line == PASTE_TERMINATOR);
+        }
+
+        assert_eq!(context.paste, None);
+        assert_eq!(
+            context.command_str,
+            Some(format!(
+                "[paste, 2 lines]{nl}    fn main() {{{nl}    }}",
+                nl = utils::NEWLINE
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn post_nothing_on_terminator_of_empty_paste() {
+        let (mut auth, mut terminal, mut room, mut context, _message_rx) = setup().await;
+        let mut collector = collector();
+        context.command_str = Some(PASTE_TERMINATOR.to_string());
+
+        collector
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+
+        assert!(collector.next().is_none());
+        assert_eq!(context.paste, None);
+        assert_eq!(context.command_str, None);
+    }
+
+    #[tokio::test]
+    async fn discard_paste_on_cancel() {
+        let (mut auth, mut terminal, mut room, mut context, mut message_rx) = setup().await;
+        context
+            .paste
+            .as_mut()
+            .unwrap()
+            .push("x".to_string())
+            .unwrap();
+        let mut collector = collector();
+        context.command_str = Some(format!("{}cancel", command_prefix()));
+
+        collector
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+
+        assert!(collector.next().is_none());
+        assert_eq!(context.paste, None);
+        assert!(message_rx.try_recv().unwrap().contains("Paste discarded"));
+    }
+
+    #[tokio::test]
+    async fn pass_input_through_without_a_paste() {
+        let (mut auth, mut terminal, mut room, mut context, _message_rx) = setup().await;
+        context.paste = None;
+        let mut collector = collector();
+        context.command_str = Some("hello".to_string());
+
+        collector
+            .handle(&mut context, &mut terminal, &mut room, &mut auth)
+            .await
+            .unwrap();
+
+        assert!(collector.next().is_some());
+        assert_eq!(context.command_str, Some("hello".to_string()));
+    }
+
+    #[test]
+    fn assemble_lines_into_indented_block() {
+        let mut paste = PasteBuffer::default();
+        assert!(paste.is_empty());
+        paste.push("fn main() {".to_string()).unwrap();
+        paste.push("}".to_string()).unwrap();
+        assert_eq!(
+            paste.assemble(),
+            format!(
+                "[paste, 2 lines]{nl}    fn main() {{{nl}    }}",
+                nl = utils::NEWLINE
+            )
+        );
+    }
+
+    #[test]
+    fn refuse_lines_over_the_limits() {
+        let mut paste = PasteBuffer::default();
+        for _ in 0..PASTE_MAX_LINES {
+            paste.push("line".to_string()).unwrap();
+        }
+        assert!(paste.push("line".to_string()).is_err());
+
+        let mut paste = PasteBuffer::default();
+        paste.push("x".repeat(PASTE_MAX_LEN)).unwrap();
+        assert!(paste.push("x".to_string()).is_err());
+    }
+}
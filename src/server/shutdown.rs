@@ -0,0 +1,120 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{error, info};
+use tokio::sync::{watch, Mutex};
+use tokio::time::sleep;
+
+use crate::chat::message::{self, Author};
+use crate::chat::{countdown_step, ChatRoom, ScheduledShutdown};
+
+/// Maximum time to wait for the sessions to close after the room is
+/// told to shut down
+const SESSIONS_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Waits for a shutdown scheduled by an operator, announces the
+/// countdown to the room and closes all sessions once the deadline is
+/// reached. Returns when the server is ready to stop.
+pub async fn wait_for_scheduled_shutdown(
+    room: Arc<Mutex<ChatRoom>>,
+    mut shutdown_rx: watch::Receiver<Option<ScheduledShutdown>>,
+) {
+    'schedule: loop {
+        let scheduled = shutdown_rx.borrow_and_update().clone();
+        let shutdown = match scheduled {
+            Some(shutdown) => shutdown,
+            None => {
+                if shutdown_rx.changed().await.is_err() {
+                    std::future::pending::<()>().await;
+                }
+                continue 'schedule;
+            }
+        };
+
+        info!("Server shutdown is scheduled by {}", shutdown.by.username());
+        loop {
+            let remaining = shutdown.remaining();
+            if remaining.is_zero() {
+                break;
+            }
+
+            announce_countdown(&room, &shutdown.by, remaining).await;
+            tokio::select! {
+                _ = sleep(countdown_step(remaining)) => {}
+                _ = shutdown_rx.changed() => continue 'schedule,
+            }
+        }
+
+        info!(
+            "Shutting down the server as requested by {}",
+            shutdown.by.username()
+        );
+        if let Err(err) = room.lock().await.close_all(&shutdown.by).await {
+            error!("Failed to notify users about the shutdown: {}", err);
+        }
+        wait_for_sessions_to_close(&room).await;
+        return;
+    }
+}
+
+async fn announce_countdown(room: &Arc<Mutex<ChatRoom>>, by: &Author, remaining: Duration) {
+    let remaining = Duration::from_secs(remaining.as_secs_f64().round() as u64);
+    let message = message::Announce::new(
+        by.clone(),
+        format!(
+            "is shutting down the server in {}",
+            humantime::format_duration(remaining)
+        ),
+    );
+    if let Err(err) = room.lock().await.send_message(message.into()).await {
+        error!("Failed to announce the shutdown countdown: {}", err);
+    }
+}
+
+async fn wait_for_sessions_to_close(room: &Arc<Mutex<ChatRoom>>) {
+    let step = Duration::from_millis(100);
+    let mut waited = Duration::ZERO;
+    while waited < SESSIONS_CLOSE_TIMEOUT && room.lock().await.members_iter().count() > 0 {
+        sleep(step).await;
+        waited += step;
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::chat::User;
+
+    #[tokio::test]
+    async fn return_after_deadline_of_scheduled_shutdown() {
+        let room = Arc::new(Mutex::new(ChatRoom::new("Welcome!")));
+        let shutdown_rx = room.lock().await.subscribe_shutdown();
+        room.lock()
+            .await
+            .schedule_shutdown(Duration::from_millis(50), &User::default());
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(2),
+            wait_for_scheduled_shutdown(room, shutdown_rx),
+        )
+        .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn keep_waiting_after_cancelled_shutdown() {
+        let room = Arc::new(Mutex::new(ChatRoom::new("Welcome!")));
+        let shutdown_rx = room.lock().await.subscribe_shutdown();
+        room.lock()
+            .await
+            .schedule_shutdown(Duration::from_millis(100), &User::default());
+        assert!(room.lock().await.cancel_shutdown());
+
+        let result = tokio::time::timeout(
+            Duration::from_millis(300),
+            wait_for_scheduled_shutdown(room, shutdown_rx),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,50 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, warn};
+use russh::server::{run_stream, Config, Server};
+use socket2::{SockRef, TcpKeepalive};
+use tokio::net::{TcpListener, TcpStream};
+
+use super::ChatServer;
+
+/// Accepts sessions on the TCP address. With a keepalive interval, the
+/// kernel probes connections idle for that long, so a peer gone behind
+/// a NAT is detected even while the SSH layer has nothing to send
+pub async fn run_on_tcp(
+    server: &mut ChatServer,
+    config: Arc<Config>,
+    addr: SocketAddr,
+    keepalive: Option<Duration>,
+) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    loop {
+        let (stream, peer_addr) = listener.accept().await?;
+        if let Some(interval) = keepalive {
+            if let Err(err) = set_keepalive(&stream, interval) {
+                warn!("Failed to set TCP keepalive for {}: {}", peer_addr, err);
+            }
+        }
+
+        let config = config.clone();
+        let handler = server.new_client(Some(peer_addr));
+        tokio::spawn(async move {
+            match run_stream(config, stream, handler).await {
+                Ok(session) => {
+                    if let Err(err) = session.await {
+                        debug!("Session of {} ended with {:?}", peer_addr, err);
+                    }
+                }
+                Err(err) => debug!("Failed to set up session of {}: {:?}", peer_addr, err),
+            }
+        });
+    }
+}
+
+fn set_keepalive(stream: &TcpStream, interval: Duration) -> std::io::Result<()> {
+    let keepalive = TcpKeepalive::new()
+        .with_time(interval)
+        .with_interval(interval);
+    SockRef::from(stream).set_tcp_keepalive(&keepalive)
+}
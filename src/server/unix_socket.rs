@@ -0,0 +1,56 @@
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::bail;
+use log::{debug, warn};
+use russh::server::{run_stream, Config, Server};
+use tokio::net::UnixListener;
+
+use super::ChatServer;
+
+/// Socket file removed once the listener stops, so the next start can
+/// bind the same path
+struct SocketFile(PathBuf);
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.0) {
+            warn!("Failed to remove socket file {:?}: {}", self.0, err);
+        }
+    }
+}
+
+/// Accepts sessions on a Unix domain socket, e.g. behind a local proxy.
+/// Such peers have no address, so they are treated as local
+pub async fn run_on_unix_socket(
+    server: &mut ChatServer,
+    config: Arc<Config>,
+    path: &Path,
+) -> anyhow::Result<()> {
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            bail!("{:?} exists and is not a socket", path);
+        }
+        // Left behind by a server that didn't shut down cleanly
+        std::fs::remove_file(path)?;
+    }
+
+    let listener = UnixListener::bind(path)?;
+    let _socket_file = SocketFile(path.to_path_buf());
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let config = config.clone();
+        let handler = server.new_client(None);
+        tokio::spawn(async move {
+            match run_stream(config, stream, handler).await {
+                Ok(session) => {
+                    if let Err(err) = session.await {
+                        debug!("Session on the unix socket ended with {:?}", err);
+                    }
+                }
+                Err(err) => debug!("Failed to set up session on the unix socket: {:?}", err),
+            }
+        });
+    }
+}
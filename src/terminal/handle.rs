@@ -59,18 +59,24 @@ impl std::io::Write for TerminalHandle {
         Ok(buf.len())
     }
 
+    // The whole sink goes out as a single data packet, so output queued
+    // for one redraw can't be split by output of another writer.
     fn flush(&mut self) -> std::io::Result<()> {
         if self.closed {
             trace!(
                 "Terminal handle is already closed for channel {}. Ignoring this flush call",
                 self.channel_id
             );
+            self.sink.clear();
+            return Ok(());
+        }
+        if self.sink.is_empty() {
             return Ok(());
         }
 
         let handle = self.handle.clone();
         let channel_id = self.channel_id;
-        let data = self.sink.clone().into();
+        let data = std::mem::take(&mut self.sink).into();
         futures::executor::block_on(async move {
             let result = handle.data(channel_id, data).await;
             if result.is_err() {
@@ -81,7 +87,6 @@ impl std::io::Write for TerminalHandle {
             }
         });
 
-        self.sink.clear();
         Ok(())
     }
 }
@@ -11,8 +11,61 @@ pub fn decode_bytes_to_codes(bytes: &[u8]) -> Vec<KeyCode> {
     codes
 }
 
+/// Stateful decoder that keeps a trailing incomplete UTF-8 sequence
+/// between calls, so a multibyte character split across several SSH data
+/// packets is decoded as a single key code.
+///
+/// Line endings are normalized into `Enter`: a lone `\n` becomes `Enter`
+/// and the `\n` of a `\r\n` pair is dropped, so pasted text submits one
+/// line at a time whatever line endings it uses.
+#[derive(Debug, Default)]
+pub struct KeyboardDecoder {
+    pending: Vec<u8>,
+    after_enter: bool,
+}
+
+impl KeyboardDecoder {
+    pub fn decode(&mut self, bytes: &[u8]) -> Vec<KeyCode> {
+        self.pending.extend_from_slice(bytes);
+        let complete = self.pending.len() - incomplete_utf8_suffix_len(&self.pending);
+        let codes = decode_bytes_to_codes(&self.pending[..complete]);
+        self.pending.drain(..complete);
+
+        let mut normalized = Vec::with_capacity(codes.len());
+        for code in codes {
+            let after_enter = std::mem::replace(&mut self.after_enter, code == KeyCode::Enter);
+            match code {
+                KeyCode::Linefeed if after_enter => {}
+                KeyCode::Linefeed => normalized.push(KeyCode::Enter),
+                code => normalized.push(code),
+            }
+        }
+        normalized
+    }
+}
+
+/// Returns the length of an incomplete UTF-8 sequence at the end of the
+/// bytes, or zero if the bytes end on a character boundary
+fn incomplete_utf8_suffix_len(bytes: &[u8]) -> usize {
+    // A UTF-8 sequence is at most 4 bytes long, so only the last 3 bytes
+    // may belong to an unfinished one
+    for (offset, byte) in bytes.iter().rev().take(3).enumerate() {
+        let expected = match byte {
+            0b1000_0000..=0b1011_1111 => continue, // continuation byte
+            0b1100_0000..=0b1101_1111 => 2,
+            0b1110_0000..=0b1110_1111 => 3,
+            0b1111_0000..=0b1111_0111 => 4,
+            _ => return 0,
+        };
+        let available = offset + 1;
+        return if available < expected { available } else { 0 };
+    }
+    0
+}
+
 #[cfg(test)]
 mod should {
+    use super::super::input::TerminalInput;
     use super::*;
     use terminal_keycode::KeyCode;
 
@@ -39,4 +92,56 @@ mod should {
         let result = decode_bytes_to_codes(&bytes);
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn keep_multibyte_char_split_across_chunks_intact() {
+        let emoji = "🌍".as_bytes();
+        let mut decoder = KeyboardDecoder::default();
+        let mut input = TerminalInput::default();
+
+        let first = decoder.decode(&emoji[..2]);
+        assert!(first.is_empty());
+
+        let second = decoder.decode(&emoji[2..]);
+        assert_eq!(second, vec![KeyCode::Char('🌍')]);
+
+        for code in first.into_iter().chain(second) {
+            input.insert_before_cursor(&code.bytes());
+        }
+        assert_eq!(input.text(), "🌍");
+    }
+
+    #[test]
+    fn decode_complete_chunks_without_buffering() {
+        let mut decoder = KeyboardDecoder::default();
+        let bytes = "aé".as_bytes();
+        let expected = vec![KeyCode::Char('a'), KeyCode::Char('é')];
+        assert_eq!(decoder.decode(bytes), expected);
+        assert!(decoder.pending.is_empty());
+    }
+
+    #[test]
+    fn normalize_line_endings_into_enter() {
+        let mut decoder = KeyboardDecoder::default();
+        let bytes = b"a\r\nb\nc\r";
+        let expected = vec![
+            KeyCode::Char('a'),
+            KeyCode::Enter,
+            KeyCode::Char('b'),
+            KeyCode::Enter,
+            KeyCode::Char('c'),
+            KeyCode::Enter,
+        ];
+        assert_eq!(decoder.decode(bytes), expected);
+    }
+
+    #[test]
+    fn drop_linefeed_of_crlf_split_across_chunks() {
+        let mut decoder = KeyboardDecoder::default();
+        assert_eq!(
+            decoder.decode(b"a\r"),
+            vec![KeyCode::Char('a'), KeyCode::Enter]
+        );
+        assert_eq!(decoder.decode(b"\nb"), vec![KeyCode::Char('b')]);
+    }
 }
@@ -8,3 +8,4 @@ pub mod keyboard_decoder;
 
 pub use handle::{CloseHandle, TerminalHandle};
 pub use terminal::Terminal;
+pub use unicode::layout_columns;
@@ -15,6 +15,8 @@ where
 {
     pub input: TerminalInput,
     prompt: String,
+    prompt_name: String,
+    prompt_unread: usize,
     prompt_display_width: u16,
     handle: H,
     outbuff: Vec<u8>,
@@ -34,6 +36,8 @@ where
         Self {
             handle,
             prompt: String::new(),
+            prompt_name: String::new(),
+            prompt_unread: 0,
             prompt_display_width: 0,
             input: Default::default(),
             outbuff: vec![],
@@ -51,6 +55,10 @@ where
         &mut self.handle
     }
 
+    pub fn width(&self) -> u16 {
+        self.term_width
+    }
+
     pub fn set_size(&mut self, width: u16, height: u16) {
         self.term_width = width;
         self.term_height = height;
@@ -58,8 +66,33 @@ where
         self.refresh_input_end_coords();
     }
 
+    /// Updates the size and redraws the prompt with the input, so an
+    /// input line that wrapped under the old width is laid out again
+    pub fn resize(&mut self, width: u16, height: u16) -> anyhow::Result<()> {
+        self.set_size(width, height);
+        if self.term_width == 0 {
+            return Ok(());
+        }
+        self.print_input_line()
+    }
+
     pub fn set_prompt(&mut self, username: &str) {
-        self.prompt = format!("[{}] ", username.to_string());
+        self.prompt_name = username.to_string();
+        self.refresh_prompt();
+    }
+
+    /// Shows the number of unread private messages next to the name in
+    /// the prompt. Zero hides the badge.
+    pub fn set_prompt_unread(&mut self, count: usize) {
+        self.prompt_unread = count;
+        self.refresh_prompt();
+    }
+
+    fn refresh_prompt(&mut self) {
+        self.prompt = match self.prompt_unread {
+            0 => format!("[{}] ", self.prompt_name),
+            count => format!("[{} ({})] ", self.prompt_name, count),
+        };
         self.prompt_display_width = unicode::display_width(&self.prompt) as u16;
     }
 
@@ -69,6 +102,17 @@ where
         Ok(())
     }
 
+    /// Wipes the screen and draws the prompt with the pending input at
+    /// the top, e.g. to recover from a garbled terminal
+    pub fn clear_screen(&mut self) -> anyhow::Result<()> {
+        queue!(self.handle, Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+        self.input_end_x = 0;
+        self.input_end_y = 0;
+        self.cursor_x = 0;
+        self.cursor_y = 0;
+        self.print_input_line()
+    }
+
     pub fn exit(&mut self) {
         self.handle.close();
     }
@@ -84,8 +128,20 @@ where
     }
 
     pub fn print_message(&mut self, msg: &str) -> anyhow::Result<()> {
+        self.print_messages(&[msg])
+    }
+
+    /// Clears the prompt once, prints all the messages and redraws the
+    /// prompt with the input below them in a single flush, so a burst of
+    /// messages never leaves the input line between two of them
+    pub fn print_messages<S: AsRef<str>>(&mut self, msgs: &[S]) -> anyhow::Result<()> {
+        if msgs.is_empty() {
+            return Ok(());
+        }
         self.queue_prompt_cleanup()?;
-        self.queue_write_message(msg)?;
+        for msg in msgs {
+            self.queue_write_message(msg.as_ref())?;
+        }
         self.queue_write_prompt()?;
         self.queue_write_input()?;
         self.queue_write_outbuff()?;
@@ -125,6 +181,7 @@ where
     }
 
     fn queue_write_message(&mut self, msg: &str) -> anyhow::Result<()> {
+        let msg = unicode::break_long_runs(msg, self.term_width as usize);
         queue!(self.handle, style::Print(msg), style::Print(utils::NEWLINE))?;
         Ok(())
     }
@@ -301,6 +358,82 @@ mod should {
         assert_eq!(terminal.prompt_display_width, 7);
     }
 
+    #[test]
+    fn set_prompt_unread() {
+        let mut terminal = Terminal::new(TestHandle::default());
+        terminal.set_prompt("user");
+        terminal.set_prompt_unread(2);
+        assert_eq!(terminal.prompt, "[user (2)] ");
+        assert_eq!(terminal.prompt_display_width, 11);
+
+        terminal.set_prompt("new");
+        assert_eq!(terminal.prompt, "[new (2)] ");
+
+        terminal.set_prompt_unread(0);
+        assert_eq!(terminal.prompt, "[new] ");
+    }
+
+    #[test]
+    fn clear_screen_and_redraw_prompt() {
+        let mut terminal = Terminal::new(TestHandle::default());
+        terminal.set_size(80, 24);
+        terminal.set_prompt("user");
+        terminal.input.insert_before_cursor(b"draft");
+        terminal.input_end_y = 3;
+        terminal.cursor_y = 3;
+
+        terminal
+            .handle()
+            .mock
+            .expect_write()
+            .returning(|buf| Ok(buf.len()));
+
+        terminal
+            .handle()
+            .mock
+            .expect_flush()
+            .times(1)
+            .returning(|| Ok(()));
+
+        terminal.clear_screen().unwrap();
+
+        let written = String::from_utf8_lossy(&terminal.handle().written).to_string();
+        assert!(written.starts_with("\x1b[2J\x1b[1;1H"));
+        assert!(written.ends_with("[user] draft"));
+        assert_eq!(terminal.input_end_y, 0);
+        assert_eq!(terminal.cursor_x, 12);
+    }
+
+    #[test]
+    fn redraw_input_line_on_resize() {
+        let mut terminal = Terminal::new(TestHandle::default());
+        terminal.set_size(80, 24);
+        terminal.set_prompt("user");
+        let long_input = "a".repeat(100); // wraps into 2 rows
+        terminal.input.insert_before_cursor(long_input.as_bytes());
+
+        terminal
+            .handle()
+            .mock
+            .expect_write()
+            .returning(|buf| Ok(buf.len()));
+
+        terminal
+            .handle()
+            .mock
+            .expect_flush()
+            .times(1)
+            .returning(|| Ok(()));
+
+        terminal.resize(40, 24).unwrap();
+
+        assert_eq!(terminal.term_width, 40);
+        assert_eq!(terminal.input_end_x, 27);
+        assert_eq!(terminal.input_end_y, 2, "Input must end on the 3rd row");
+        assert_eq!(terminal.cursor_x, 27);
+        assert_eq!(terminal.cursor_y, 2);
+    }
+
     #[test]
     fn refresh_cursor_coordinates_on_resize() {
         let mut terminal = Terminal::new(TestHandle::default());
@@ -462,4 +595,43 @@ mod should {
         let written = String::from_utf8(terminal.handle().written.clone()).unwrap();
         assert_eq!(written, expected_output);
     }
+
+    #[test]
+    fn print_burst_of_messages_above_single_prompt() {
+        let mut terminal = Terminal::new(TestHandle::default());
+        terminal.set_size(80, 24);
+        terminal.set_prompt("user");
+        terminal.input.insert_before_cursor("draft".as_bytes());
+
+        terminal
+            .handle
+            .mock
+            .expect_write()
+            .returning(|buf| Ok(buf.len()));
+        terminal
+            .handle
+            .mock
+            .expect_flush()
+            .times(50)
+            .returning(|| Ok(()));
+
+        let mut expected_lines = vec![];
+        for batch in 0..50 {
+            let msgs = (0..20)
+                .map(|n| format!("[bob] message {} of batch {}", n, batch))
+                .collect::<Vec<String>>();
+            terminal.print_messages(&msgs).unwrap();
+            expected_lines.extend(msgs);
+        }
+
+        let written = String::from_utf8(terminal.handle().written.clone()).unwrap();
+        let lines = written
+            .split(utils::NEWLINE)
+            .map(|line| line.rsplit("\x1B[2K").next().unwrap())
+            .collect::<Vec<&str>>();
+        let (last, lines) = lines.split_last().unwrap();
+        assert_eq!(lines, expected_lines);
+        assert_eq!(*last, "[user] draft");
+        assert!(lines.iter().all(|line| !line.contains("[user]")));
+    }
 }
@@ -1,6 +1,8 @@
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
+use crate::utils;
+
 /// Zero Width Joiner (ZWJ) is a Unicode character that joins two or
 /// more other characters together in sequence to create a new emoji.
 const ZERO_WIDTH_JOINER: &str = "\u{200d}";
@@ -90,6 +92,117 @@ pub fn display_width(text: &str) -> usize {
     width
 }
 
+/// Breaks the runs of non-whitespace characters wider than `width`,
+/// e.g. a long URL or a flood of letters, at the column where the
+/// terminal would wrap them. Left to the terminal, such a run wraps
+/// wherever its idea of the widths says, which may differ from ours
+/// and misalign the lines that follow.
+///
+/// Graphemes are never split, ANSI escape sequences are kept whole,
+/// and text without long runs is returned as is.
+///
+/// ```
+/// assert_eq!(break_long_runs("abcdefg hi", 3), "abc\n\rdef\n\rg hi");
+/// ```
+pub fn break_long_runs(text: &str, width: usize) -> String {
+    if width == 0 {
+        return text.to_string();
+    }
+
+    // Pieces of the text with their display width: graphemes, and
+    // escape sequences as zero width pieces
+    let mut pieces: Vec<(&str, usize)> = vec![];
+    let mut graphemes = text.grapheme_indices(true).peekable();
+    while let Some((start, grapheme)) = graphemes.next() {
+        let is_escape = skip_ansi_escape_sequence(grapheme, &mut (&mut graphemes).map(|(_, g)| g));
+        let end = match is_escape {
+            true => graphemes.peek().map_or(text.len(), |(end, _)| *end),
+            false => start + grapheme.len(),
+        };
+        let width = match is_escape {
+            true => 0,
+            false => display_width_char(grapheme),
+        };
+        pieces.push((&text[start..end], width));
+    }
+
+    // Mark the pieces of the runs too wide to fit on a line
+    let mut in_long_run = vec![false; pieces.len()];
+    let mut run_start = 0;
+    let mut run_width = 0;
+    for i in 0..=pieces.len() {
+        let ends_run = pieces
+            .get(i)
+            .is_none_or(|(piece, _)| piece.chars().all(char::is_whitespace));
+        if !ends_run {
+            run_width += pieces[i].1;
+            continue;
+        }
+        if run_width > width {
+            in_long_run[run_start..i].fill(true);
+        }
+        run_start = i + 1;
+        run_width = 0;
+    }
+    if !in_long_run.contains(&true) {
+        return text.to_string();
+    }
+
+    let mut broken = String::with_capacity(text.len());
+    let mut column = 0;
+    for ((piece, piece_width), in_long_run) in pieces.into_iter().zip(in_long_run) {
+        if piece == "\n" || piece == "\r" {
+            column = 0;
+        } else if column + piece_width > width {
+            if in_long_run && column > 0 {
+                broken.push_str(utils::NEWLINE);
+            }
+            // Either broken here or wrapped by the terminal
+            column = 0;
+        }
+        broken.push_str(piece);
+        column += piece_width;
+    }
+    broken
+}
+
+/// Space left between two columns laid out by [`layout_columns`].
+const COLUMN_GAP: usize = 2;
+
+/// Lays out `items` in as many aligned columns as fit in `width`,
+/// filling the rows left to right, and returns the rows. Every column
+/// is as wide as the widest item by display width, so names with CJK
+/// characters or emoji line up with the rest.
+///
+/// Items wider than `width` get a row of their own.
+///
+/// ```
+/// assert_eq!(layout_columns(&["alice", "bob", "carol"], 14), ["alice  bob", "carol"]);
+/// ```
+pub fn layout_columns<S: AsRef<str>>(items: &[S], width: usize) -> Vec<String> {
+    let widths = items
+        .iter()
+        .map(|item| display_width(item.as_ref()))
+        .collect::<Vec<usize>>();
+    let column_width = widths.iter().copied().max().unwrap_or(0) + COLUMN_GAP;
+    let columns = ((width + COLUMN_GAP) / column_width).max(1);
+
+    items
+        .chunks(columns)
+        .zip(widths.chunks(columns))
+        .map(|(row, widths)| {
+            let mut line = String::new();
+            for (i, (item, item_width)) in row.iter().zip(widths).enumerate() {
+                line.push_str(item.as_ref());
+                if i + 1 < row.len() {
+                    line.push_str(&" ".repeat(column_width - item_width));
+                }
+            }
+            line
+        })
+        .collect()
+}
+
 /// Calculate the render width of a single Unicode character. Unicode
 /// characters may consist of multiple String characters, which is why
 /// the function argument takes a string.
@@ -199,6 +312,61 @@ mod tests {
         assert_eq!(graphemes.next(), Some("H"));
     }
 
+    #[test]
+    fn test_break_long_runs() {
+        let nl = utils::NEWLINE;
+        let broken = break_long_runs(&"a".repeat(25), 10);
+        assert_eq!(
+            broken,
+            format!("{a10}{nl}{a10}{nl}aaaaa", a10 = "a".repeat(10))
+        );
+
+        let text = "short words stay as is";
+        assert_eq!(break_long_runs(text, 5), text);
+        assert_eq!(break_long_runs(text, 0), text);
+
+        let colored = "\u{1b}[31mabcdef\u{1b}[0m ok";
+        assert_eq!(
+            break_long_runs(colored, 4),
+            format!("\u{1b}[31mabcd{nl}ef\u{1b}[0m ok")
+        );
+    }
+
+    #[test]
+    fn test_break_long_runs_without_splitting_graphemes() {
+        let text = "你好👩🏽é".repeat(20);
+        let broken = break_long_runs(&text, 7);
+        let lines = broken.split(utils::NEWLINE).collect::<Vec<&str>>();
+        assert!(lines.len() > 1);
+        for line in &lines {
+            assert!(display_width(line) <= 7, "`{}` is wider than 7", line);
+        }
+        let graphemes = lines
+            .iter()
+            .flat_map(|line| line.graphemes(true))
+            .collect::<Vec<&str>>();
+        assert_eq!(graphemes, text.graphemes(true).collect::<Vec<&str>>());
+    }
+
+    #[test]
+    fn test_layout_columns() {
+        assert_eq!(
+            layout_columns(&["alice", "bob", "carol"], 14),
+            ["alice  bob", "carol"]
+        );
+        assert_eq!(layout_columns(&["alice", "bob"], 3), ["alice", "bob"]);
+        assert!(layout_columns::<&str>(&[], 80).is_empty());
+    }
+
+    #[test]
+    fn test_layout_columns_with_mixed_width_names() {
+        let names = ["bob", "你好", "👩🏽‍💻dev", "\u{1b}[31mcafé\u{1b}[0m", "zoë"];
+        let rows = layout_columns(&names, 20);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0], "bob    你好   👩🏽‍💻dev");
+        assert_eq!(rows[1], "\u{1b}[31mcafé\u{1b}[0m   zoë");
+    }
+
     fn assert_width(string: &str, width: usize) {
         assert_eq!(
             display_width(string),
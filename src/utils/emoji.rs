@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use regex::{Captures, Regex};
+
+lazy_static::lazy_static! {
+    static ref RE_SHORTCODE: Regex = Regex::new(r":([a-z0-9_+-]+):").unwrap();
+    static ref EMOJI: HashMap<&'static str, &'static str> = HashMap::from([
+        ("smile", "😄"),
+        ("grin", "😁"),
+        ("joy", "😂"),
+        ("laughing", "😆"),
+        ("wink", "😉"),
+        ("blush", "😊"),
+        ("heart_eyes", "😍"),
+        ("sunglasses", "😎"),
+        ("thinking", "🤔"),
+        ("neutral_face", "😐"),
+        ("unamused", "😒"),
+        ("sweat_smile", "😅"),
+        ("cry", "😢"),
+        ("sob", "😭"),
+        ("angry", "😠"),
+        ("scream", "😱"),
+        ("sleeping", "😴"),
+        ("upside_down", "🙃"),
+        ("shrug", "🤷"),
+        ("facepalm", "🤦"),
+        ("wave", "👋"),
+        ("clap", "👏"),
+        ("pray", "🙏"),
+        ("muscle", "💪"),
+        ("ok_hand", "👌"),
+        ("eyes", "👀"),
+        ("+1", "👍"),
+        ("thumbsup", "👍"),
+        ("-1", "👎"),
+        ("thumbsdown", "👎"),
+        ("heart", "❤️"),
+        ("broken_heart", "💔"),
+        ("fire", "🔥"),
+        ("star", "⭐"),
+        ("sparkles", "✨"),
+        ("tada", "🎉"),
+        ("rocket", "🚀"),
+        ("100", "💯"),
+        ("check", "✅"),
+        ("x", "❌"),
+        ("warning", "⚠️"),
+        ("bug", "🐛"),
+        ("coffee", "☕"),
+        ("beer", "🍺"),
+        ("pizza", "🍕"),
+        ("banana", "🍌"),
+        ("monkey", "🐒"),
+        ("cat", "🐱"),
+        ("dog", "🐶"),
+        ("poop", "💩"),
+    ]);
+}
+
+/// Replaces known `:shortcode:` tokens with their emoji. Unknown codes
+/// and codes glued to a word, e.g. `foo:smile:`, are kept as typed
+pub fn expand(s: &str) -> String {
+    RE_SHORTCODE
+        .replace_all(s, |caps: &Captures| {
+            let token = caps.get(0).unwrap();
+            let is_glued = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+            if is_glued(s[..token.start()].chars().next_back())
+                || is_glued(s[token.end()..].chars().next())
+            {
+                return token.as_str().to_string();
+            }
+            match EMOJI.get(&caps[1]) {
+                Some(emoji) => emoji.to_string(),
+                None => token.as_str().to_string(),
+            }
+        })
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_known_codes() {
+        assert_eq!(expand(":smile:"), "😄");
+        assert_eq!(expand("ship it :rocket: :+1:"), "ship it 🚀 👍");
+        assert_eq!(expand("(:tada:)"), "(🎉)");
+    }
+
+    #[test]
+    fn test_expand_keeps_unknown_codes() {
+        assert_eq!(expand(":nope: :smile:"), ":nope: 😄");
+        assert_eq!(expand("meet at 10:30:45"), "meet at 10:30:45");
+    }
+
+    #[test]
+    fn test_expand_keeps_codes_inside_words() {
+        assert_eq!(expand("foo:smile:bar"), "foo:smile:bar");
+        assert_eq!(expand("foo:smile:"), "foo:smile:");
+        assert_eq!(expand(":smile:s"), ":smile:s");
+    }
+}
@@ -1,5 +1,5 @@
-use std::fs::File;
-use std::io::{BufRead, BufReader, Read};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
 use std::path::PathBuf;
 
 /// Reads the contents of a file into a string.
@@ -26,6 +26,34 @@ pub fn read_file_lines(file_path: &str) -> Result<Vec<Vec<u8>>, std::io::Error>
     Ok(result)
 }
 
+/// Appends a line to a file, creating the file if it doesn't exist.
+pub fn append_line(file_path: &str, line: &str) -> Result<(), std::io::Error> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(expand_tilde(file_path))?;
+    writeln!(file, "{}", line)
+}
+
+/// Writes a string to a file, replacing its contents.
+pub fn write_string_to_file(file_path: &str, contents: &str) -> Result<(), std::io::Error> {
+    std::fs::write(expand_tilde(file_path), contents)
+}
+
+/// Lists files in a directory with the given extension, sorted by
+/// path.
+pub fn list_files_with_extension(dir: &str, extension: &str) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    for entry in std::fs::read_dir(expand_tilde(dir))? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == extension) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
 /// Expands a tilde in a file path to the user's home directory.
 fn expand_tilde(path: &str) -> PathBuf {
     if path.starts_with("~") {
@@ -115,6 +143,33 @@ mod tests {
         assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::NotFound);
     }
 
+    #[test]
+    fn test_append_line_creates_missing_file() {
+        let temp_dir = TempDir::new().expect("failed to create temp dir");
+        let file_path = temp_dir.path().join("test_file.txt");
+        let file_path = file_path.to_str().unwrap();
+        append_line(file_path, "Line 1").unwrap();
+        append_line(file_path, "Line 2").unwrap();
+
+        assert_eq!(read_file_to_string(file_path).unwrap(), "Line 1\nLine 2\n");
+    }
+
+    #[test]
+    fn test_list_files_with_extension() {
+        let temp_dir = TempDir::new().expect("failed to create temp dir");
+        for name in ["b.ans", "a.ans", "notes.txt"] {
+            temp_dir.child(name).write_binary(b"art").unwrap();
+        }
+        let result = list_files_with_extension(temp_dir.path().to_str().unwrap(), "ans");
+
+        let names = result
+            .unwrap()
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a.ans", "b.ans"]);
+    }
+
     #[test]
     fn test_expand_tilde_with_home_dir() {
         let home_dir = dirs::home_dir().expect("failed to get home directory");
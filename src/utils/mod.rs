@@ -1,3 +1,4 @@
+pub mod emoji;
 pub mod fs;
 pub mod sanitize;
 pub mod ssh;
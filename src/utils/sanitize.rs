@@ -4,16 +4,98 @@ const MAX_LENGTH: usize = 16;
 
 lazy_static::lazy_static! {
     static ref RE_STRIP_NAME: Regex = Regex::new(r"[^\w.-]").unwrap();
+    // `\w` keeps the zero-width joiners, so formatting characters that
+    // are invisible or reorder the text around them are stripped apart
+    static ref RE_STRIP_INVISIBLE: Regex = Regex::new(concat!(
+        r"[\u{200B}-\u{200F}]",                 // Zero-width spaces, joiners and marks
+        r"|[\u{202A}-\u{202E}\u{2066}-\u{2069}]", // Bidi embeddings, overrides and isolates
+        r"|[\u{061C}\u{2060}\u{FEFF}]",          // Arabic letter mark, word joiner and BOM
+    ))
+    .unwrap();
+    static ref RE_STRIP_CONTROL: Regex = Regex::new(concat!(
+        r"\x1b\[[0-?]*[ -/]*[@-~]",             // CSI, e.g. cursor moves and screen clears
+        r"|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)?", // OSC, e.g. window title changes
+        r"|\x1b[PX^_][^\x1b]*(?:\x1b\\)?",     // DCS, SOS, PM and APC strings
+        r"|\x1b.?",                            // Any other escape sequence
+        r"|\p{Cc}",                            // Remaining C0 and C1 control characters
+    ))
+    .unwrap();
+    static ref RE_LINE_BREAKS: Regex =
+        Regex::new(r"\s*[\n\x0B\x0C\r\u{0085}\u{2028}\u{2029}]\s*").unwrap();
 }
 
 pub fn name(s: &str) -> String {
-    let s = RE_STRIP_NAME.replace_all(s, "").to_string();
-    let name_length = if s.len() <= MAX_LENGTH {
-        s.len()
-    } else {
-        MAX_LENGTH
-    };
-    s[..name_length].to_string()
+    let s = RE_STRIP_INVISIBLE.replace_all(s, "");
+    let s = RE_STRIP_NAME.replace_all(&s, "").to_string();
+    truncate_name(&s, MAX_LENGTH).to_string()
+}
+
+/// Appends the suffix to the name, shortening the name first so the
+/// result still fits in the maximum name length, e.g. `alice2`
+pub fn suffixed_name(name: &str, suffix: &str) -> String {
+    let name = truncate_name(name, MAX_LENGTH.saturating_sub(suffix.len()));
+    format!("{}{}", name, suffix)
+}
+
+fn truncate_name(s: &str, max_len: usize) -> &str {
+    let mut name_length = if s.len() <= max_len { s.len() } else { max_len };
+    while !s.is_char_boundary(name_length) {
+        name_length -= 1;
+    }
+    &s[..name_length]
+}
+
+/// Folds a name into a skeleton shared by names that look alike, e.g.
+/// `alice`, `ALICE`, `аlice` with a Cyrillic `а`, `a1ice` with a digit
+/// `1` and `aIice` with a capital `I`. As `I` is also the capital of
+/// `i`, both fold into `l`. Covers fullwidth forms and the common
+/// Cyrillic and Greek lookalikes of Latin letters
+pub fn name_skeleton(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            // Fullwidth ASCII variants, e.g. `ａ`
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            _ => c,
+        })
+        .map(|c| match c {
+            '1' | '|' | 'I' | 'i' | 'Ӏ' | 'ӏ' => 'l',
+            '0' | 'О' | 'о' | 'Ο' | 'ο' => 'o',
+            'А' | 'а' | 'Α' | 'α' => 'a',
+            'В' | 'в' | 'Β' | 'β' => 'b',
+            'С' | 'с' | 'ϲ' => 'c',
+            'Е' | 'е' | 'Ё' | 'ё' | 'Ε' | 'ε' => 'e',
+            'Н' | 'н' | 'Η' => 'h',
+            'К' | 'к' | 'Κ' | 'κ' => 'k',
+            'М' | 'м' | 'Μ' => 'm',
+            'Ν' | 'ν' => 'n',
+            'Р' | 'р' | 'Ρ' | 'ρ' => 'p',
+            'Ѕ' | 'ѕ' => 's',
+            'Т' | 'т' | 'Τ' | 'τ' => 't',
+            'У' | 'у' | 'Υ' | 'υ' => 'y',
+            'Х' | 'х' | 'Χ' | 'χ' => 'x',
+            'І' | 'і' | 'Ї' | 'ї' | 'Ι' | 'ι' | 'ı' => 'l',
+            'ј' => 'j',
+            'ԁ' => 'd',
+            'ԛ' => 'q',
+            'ԝ' => 'w',
+            _ => c,
+        })
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Strips terminal control characters and escape sequences from a
+/// message so it can't move the cursor, clear the screen or change the
+/// title of other users' terminals. Printable unicode is kept as is.
+pub fn message(s: &str) -> String {
+    RE_STRIP_CONTROL.replace_all(s, "").to_string()
+}
+
+/// Joins the lines of a text into one, so text shown after a user's
+/// name, e.g. an emote, can't start a line that looks like it came from
+/// someone else
+pub fn single_line(s: &str) -> String {
+    RE_LINE_BREAKS.replace_all(s.trim(), " ").to_string()
 }
 
 #[cfg(test)]
@@ -35,6 +117,37 @@ mod tests {
         assert_eq!(name("ExactlySixteen.."), "ExactlySixteen..");
     }
 
+    #[test]
+    fn test_name_strips_bidi_controls() {
+        assert_eq!(name("\u{202E}nimda"), "nimda");
+        assert_eq!(name("bob\u{202E}gpj.exe"), "bobgpj.exe");
+        assert_eq!(name("\u{202D}alice\u{202C}"), "alice");
+        assert_eq!(name("\u{2067}eve\u{2069}"), "eve");
+        assert_eq!(name("\u{200F}mallory\u{200E}"), "mallory");
+    }
+
+    #[test]
+    fn test_name_strips_zero_width_characters() {
+        assert_eq!(
+            name("a\u{200B}l\u{200C}i\u{200D}c\u{2060}e\u{FEFF}"),
+            "alice"
+        );
+        assert_eq!(name("\u{200D}\u{200D}"), "");
+    }
+
+    #[test]
+    fn test_name_truncation_keeps_whole_characters() {
+        assert_eq!(name("日本語日本語日本語"), "日本語日本");
+        assert_eq!(name("ééééééééé"), "éééééééé");
+    }
+
+    #[test]
+    fn test_suffixed_name_fits_max_length() {
+        assert_eq!(suffixed_name("alice", "2"), "alice2");
+        assert_eq!(suffixed_name("ExactlySixteen..", "12"), "ExactlySixteen12");
+        assert_eq!(suffixed_name("日本語日本", "12"), "日本語日12");
+    }
+
     #[test]
     fn test_name_empty_string() {
         assert_eq!(name(""), "");
@@ -44,4 +157,67 @@ mod tests {
     fn test_name_whitespace_only() {
         assert_eq!(name("   "), "");
     }
+
+    #[test]
+    fn test_name_skeleton_matches_lookalikes() {
+        assert_eq!(name_skeleton("alice"), "allce");
+        assert_eq!(name_skeleton("ALICE"), name_skeleton("alice"));
+        assert_eq!(name_skeleton("\u{430}lice"), name_skeleton("alice"));
+        assert_eq!(name_skeleton("alic\u{435}"), name_skeleton("alice"));
+        assert_eq!(name_skeleton("a1ice"), name_skeleton("alice"));
+        assert_eq!(name_skeleton("aIice"), name_skeleton("alice"));
+        assert_eq!(name_skeleton("\u{FF41}lice"), name_skeleton("alice"));
+        assert_eq!(name_skeleton("b0b"), name_skeleton("bob"));
+        assert_eq!(name_skeleton("\u{3BF}\u{3C1}"), name_skeleton("op"));
+    }
+
+    #[test]
+    fn test_name_skeleton_keeps_different_names_apart() {
+        assert_ne!(name_skeleton("alice"), name_skeleton("alicia"));
+        assert_ne!(name_skeleton("bob"), name_skeleton("rob"));
+        assert_ne!(name_skeleton("alice"), name_skeleton("alice2"));
+    }
+
+    #[test]
+    fn test_message_strips_csi_sequences() {
+        assert_eq!(message("hello\x1b[2Jworld"), "helloworld");
+        assert_eq!(message("\x1b[1;1H\x1b[31mred\x1b[0m"), "red");
+        assert_eq!(message("\u{9b}2Jclear"), "2Jclear");
+    }
+
+    #[test]
+    fn test_message_strips_osc_sequences() {
+        assert_eq!(message("\x1b]0;pwned\x07hi"), "hi");
+        assert_eq!(message("\x1b]2;pwned\x1b\\hi"), "hi");
+        assert_eq!(message("\x1b]8;;http://evil\x1b\\link"), "link");
+    }
+
+    #[test]
+    fn test_message_strips_control_characters() {
+        assert_eq!(message("a\x07b\x08c\rd\ne"), "abcde");
+        assert_eq!(message("lone escape\x1b"), "lone escape");
+        assert_eq!(message("\x1bcreset"), "reset");
+    }
+
+    #[test]
+    fn test_single_line_joins_lines() {
+        assert_eq!(
+            single_line("waves\n bob: hey everyone"),
+            "waves bob: hey everyone"
+        );
+        assert_eq!(
+            single_line("a\r\nb\u{2028}c\u{2029}d\u{0085}e"),
+            "a b c d e"
+        );
+        assert_eq!(single_line("\n\nwaves\n"), "waves");
+        assert_eq!(single_line("just waves"), "just waves");
+    }
+
+    #[test]
+    fn test_message_keeps_printable_unicode() {
+        assert_eq!(
+            message("Привет, 世界! 👋🏽 [brackets]"),
+            "Привет, 世界! 👋🏽 [brackets]"
+        );
+    }
 }
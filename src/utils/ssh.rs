@@ -15,6 +15,74 @@ pub fn split_ssh_key(key_bytes: &[u8]) -> Option<(String, String)> {
     Some((algo, key))
 }
 
+/// Splits an authorized_keys line into its leading options field (if
+/// any), the key algorithm and the base64-encoded key
+pub fn split_authorized_key(key_bytes: &[u8]) -> Option<(Option<String>, String, String)> {
+    let (options, rest) = split_key_options(key_bytes)?;
+    let (algo, key) = split_ssh_key(rest.as_bytes())?;
+    Some((options, algo, key))
+}
+
+/// Returns the comment after the key of an authorized_keys line, e.g.
+/// the name of the key owner
+pub fn authorized_key_comment(key_bytes: &[u8]) -> Option<String> {
+    let (_, rest) = split_key_options(key_bytes)?;
+    let comment = rest
+        .split_whitespace()
+        .skip(2)
+        .collect::<Vec<&str>>()
+        .join(" ");
+    match comment.is_empty() {
+        true => None,
+        false => Some(comment),
+    }
+}
+
+fn split_key_options(key_bytes: &[u8]) -> Option<(Option<String>, &str)> {
+    let line = std::str::from_utf8(key_bytes).ok()?.trim();
+    let first = line.split_whitespace().next()?;
+    if is_key_type(first) {
+        return Some((None, line));
+    }
+    let end = find_unquoted(line, |c| c.is_whitespace()).unwrap_or(line.len());
+    Some((Some(line[..end].to_string()), line[end..].trim_start()))
+}
+
+/// Returns the value of the named option from an authorized_keys
+/// options field, with surrounding quotes removed
+pub fn find_key_option(options: &str, name: &str) -> Option<String> {
+    let mut rest = options;
+    loop {
+        let end = find_unquoted(rest, |c| c == ',').unwrap_or(rest.len());
+        let option = &rest[..end];
+        if let Some((key, value)) = option.split_once('=') {
+            if key.eq_ignore_ascii_case(name) {
+                return Some(value.trim_matches('"').to_string());
+            }
+        }
+        if end == rest.len() {
+            return None;
+        }
+        rest = &rest[end + 1..];
+    }
+}
+
+fn is_key_type(s: &str) -> bool {
+    s.starts_with("ssh-") || s.starts_with("ecdsa-") || s.starts_with("sk-")
+}
+
+fn find_unquoted(s: &str, pred: impl Fn(char) -> bool) -> Option<usize> {
+    let mut quoted = false;
+    for (idx, c) in s.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            c if !quoted && pred(c) => return Some(idx),
+            _ => {}
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -38,4 +106,46 @@ mod tests {
         let key_bytes = b"ssh-rsa"; // Incomplete SSH key format
         assert!(split_ssh_key(key_bytes).is_none());
     }
+
+    #[test]
+    fn test_split_authorized_key_without_options() {
+        let key_bytes = b"ssh-ed25519 AAAAC3Nza user@host";
+        let (options, algo, key) = split_authorized_key(key_bytes).unwrap();
+        assert_eq!(options, None);
+        assert_eq!(algo, "ssh-ed25519");
+        assert_eq!(key, "AAAAC3Nza");
+    }
+
+    #[test]
+    fn test_split_authorized_key_with_options() {
+        let key_bytes = b"expiry-time=\"20250101\",command=\"echo hi\" ssh-ed25519 AAAAC3Nza";
+        let (options, algo, key) = split_authorized_key(key_bytes).unwrap();
+        assert_eq!(
+            options.as_deref(),
+            Some("expiry-time=\"20250101\",command=\"echo hi\"")
+        );
+        assert_eq!(algo, "ssh-ed25519");
+        assert_eq!(key, "AAAAC3Nza");
+    }
+
+    #[test]
+    fn test_authorized_key_comment() {
+        let key_bytes = b"no-pty ssh-ed25519 AAAAC3Nza alice laptop";
+        assert_eq!(
+            authorized_key_comment(key_bytes).as_deref(),
+            Some("alice laptop")
+        );
+        assert_eq!(authorized_key_comment(b"ssh-ed25519 AAAAC3Nza"), None);
+    }
+
+    #[test]
+    fn test_find_key_option() {
+        let options = "no-pty,command=\"a,b\",expiry-time=\"20250101\"";
+        assert_eq!(
+            find_key_option(options, "expiry-time").as_deref(),
+            Some("20250101")
+        );
+        assert_eq!(find_key_option(options, "command").as_deref(), Some("a,b"));
+        assert_eq!(find_key_option(options, "from"), None);
+    }
 }